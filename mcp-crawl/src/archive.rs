@@ -0,0 +1,727 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use chrono::{DateTime, SecondsFormat, Utc};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, Request, Response, ResponseBuilderExt, StatusCode};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use url::{Position, Url};
+
+use crate::config::CrawlerConfig;
+use crate::fetch;
+
+/// WARC header naming the crawl job an exchange belongs to.
+const JOB_FIELD: &str = "X-Crawl-Job-ID";
+
+/// Subdirectory of the archive directory exports are written to.
+const EXPORTS_DIR: &str = "exports";
+
+/// Whether sessions record what they fetch or replay it.
+#[derive(Clone)]
+pub enum ArchiveMode {
+    /// Fetch from the network and record every exchange.
+    Record(Arc<Archive>),
+    /// Answer every request from an archive, never touching the network.
+    Replay(Arc<Replay>),
+}
+
+impl ArchiveMode {
+    /// The mode asked for by `--archive-dir` or `--replay`, if any.
+    pub fn from_config(config: &CrawlerConfig) -> Result<Option<Self>> {
+        match (&config.archive_dir, &config.replay) {
+            (Some(_), Some(_)) => {
+                anyhow::bail!("An archive cannot be recorded while replaying one")
+            }
+            (Some(dir), None) => {
+                std::fs::create_dir_all(dir)
+                    .with_context(|| format!("Failed to create {}", dir.display()))?;
+                Ok(Some(ArchiveMode::Record(Arc::new(
+                    Archive::new(dir.clone(), &config.user_agent)
+                        .with_max_body_bytes(config.max_body_bytes),
+                ))))
+            }
+            (None, Some(path)) => Ok(Some(ArchiveMode::Replay(Arc::new(Replay::open(path)?)))),
+            (None, None) => Ok(None),
+        }
+    }
+
+    pub fn is_replay(&self) -> bool {
+        matches!(self, ArchiveMode::Replay(_))
+    }
+
+    /// Send `request` with `client` and record the exchange, or answer it
+    /// from the replayed archive. `client` must not follow redirects: they
+    /// are followed here when `follow_redirects` is set, so that every hop
+    /// is recorded, and replayed, as an exchange of its own.
+    pub(crate) async fn execute(
+        &self,
+        client: &Client,
+        mut request: Request,
+        follow_redirects: bool,
+        job_id: Option<&str>,
+    ) -> Result<Response> {
+        let mut redirects = 0;
+        loop {
+            let next = if follow_redirects {
+                request.try_clone()
+            } else {
+                None
+            };
+            let response = match self {
+                ArchiveMode::Record(archive) => archive.execute(client, request, job_id).await?,
+                ArchiveMode::Replay(replay) => replay.response(&request)?,
+            };
+            let Some(next) = next.and_then(|next| fetch::redirect_request(next, &response)) else {
+                return Ok(response);
+            };
+            redirects += 1;
+            if redirects > fetch::MAX_REDIRECTS {
+                anyhow::bail!("Too many redirects from {}", response.url());
+            }
+            request = next;
+        }
+    }
+}
+
+/// A request together with the response it got.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exchange {
+    pub job_id: Option<String>,
+    pub date: DateTime<Utc>,
+    pub method: String,
+    /// URL the request was sent to.
+    pub url: String,
+    pub request_headers: Vec<(String, String)>,
+    pub request_body: Vec<u8>,
+    /// URL the response came from. The same as `url` for exchanges
+    /// recorded here, since each redirect is an exchange of its own.
+    pub response_url: String,
+    pub status: u16,
+    pub response_headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    pub ip_address: Option<String>,
+    request_id: String,
+    response_id: String,
+}
+
+impl Exchange {
+    /// The request and response records of the exchange.
+    fn to_warc(&self, warcinfo_id: &str) -> Vec<u8> {
+        let date = warc_date(&self.date);
+        let mut common = vec![
+            ("WARC-Date", date),
+            ("WARC-Warcinfo-ID", warcinfo_id.to_string()),
+        ];
+        if let Some(job_id) = &self.job_id {
+            common.push((JOB_FIELD, job_id.clone()));
+        }
+
+        let mut request = vec![
+            ("WARC-Type", "request".to_string()),
+            ("WARC-Record-ID", self.request_id.clone()),
+            ("WARC-Target-URI", self.url.clone()),
+            ("WARC-Concurrent-To", self.response_id.clone()),
+        ];
+        request.extend(common.iter().cloned());
+        let mut response = vec![
+            ("WARC-Type", "response".to_string()),
+            ("WARC-Record-ID", self.response_id.clone()),
+            ("WARC-Target-URI", self.response_url.clone()),
+        ];
+        if let Some(ip_address) = &self.ip_address {
+            response.push(("WARC-IP-Address", ip_address.clone()));
+        }
+        response.extend(common);
+
+        let mut records = warc_record(
+            &request,
+            "application/http; msgtype=request",
+            &self.request_block(),
+        );
+        records.extend(warc_record(
+            &response,
+            "application/http; msgtype=response",
+            &self.response_block(),
+        ));
+        records
+    }
+
+    fn request_block(&self) -> Vec<u8> {
+        let target = Url::parse(&self.url)
+            .map(|url| url[Position::BeforePath..Position::AfterQuery].to_string())
+            .unwrap_or_else(|_| self.url.clone());
+        let mut block = format!("{} {} HTTP/1.1\r\n", self.method, target).into_bytes();
+        write_headers(&mut block, &self.request_headers);
+        block.extend_from_slice(&self.request_body);
+        block
+    }
+
+    fn response_block(&self) -> Vec<u8> {
+        let reason = StatusCode::from_u16(self.status)
+            .ok()
+            .and_then(|s| s.canonical_reason())
+            .unwrap_or_default();
+        let mut block = format!("HTTP/1.1 {} {}\r\n", self.status, reason).into_bytes();
+        write_headers(&mut block, &self.response_headers);
+        block.extend_from_slice(&self.body);
+        block
+    }
+
+    /// One line of a JSONL export. Bodies that are not UTF-8 are base64
+    /// encoded.
+    fn to_json(&self) -> serde_json::Value {
+        let (body, encoding) = match std::str::from_utf8(&self.body) {
+            Ok(text) => (text.to_string(), "text"),
+            Err(_) => (
+                base64::engine::general_purpose::STANDARD.encode(&self.body),
+                "base64",
+            ),
+        };
+        json!({
+            "job_id": self.job_id,
+            "date": warc_date(&self.date),
+            "method": self.method,
+            "url": self.url,
+            "request_headers": joined_headers(&self.request_headers),
+            "status": self.status,
+            "response_url": self.response_url,
+            "response_headers": joined_headers(&self.response_headers),
+            "ip_address": self.ip_address,
+            "body": body,
+            "body_encoding": encoding,
+        })
+    }
+
+    /// The recorded response, as if it had just been received.
+    fn to_response(&self) -> Result<Response> {
+        let url = Url::parse(&self.response_url)?;
+        let mut builder = http::Response::builder().status(self.status).url(url);
+        for (name, value) in &self.response_headers {
+            builder = builder.header(
+                HeaderName::from_bytes(name.as_bytes())?,
+                HeaderValue::from_bytes(value.as_bytes())?,
+            );
+        }
+        Ok(builder.body(self.body.clone())?.into())
+    }
+}
+
+/// Format of an archive export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Warc,
+    /// One JSON object per exchange and line.
+    Jsonl,
+}
+
+impl ExportFormat {
+    pub fn parse(format: &str) -> Result<Self> {
+        match format {
+            "warc" => Ok(ExportFormat::Warc),
+            "jsonl" => Ok(ExportFormat::Jsonl),
+            other => anyhow::bail!("Unknown format: {} (expected warc or jsonl)", other),
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Warc => "warc",
+            ExportFormat::Jsonl => "jsonl",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportReport {
+    pub path: PathBuf,
+    pub format: ExportFormat,
+    pub exchanges: usize,
+    pub bytes: usize,
+}
+
+/// A directory of WARC files, one per server run, recording every request
+/// sent and the response it got.
+///
+/// Request records hold the method, URL, the headers set by the session
+/// along with `Host` and `User-Agent`, and the body; headers the HTTP
+/// client adds itself, such as cookies, are missing. Response bodies are
+/// kept as received, but without `Transfer-Encoding` since they are no
+/// longer chunked. A redirected request is recorded hop by hop, each
+/// response along with the request it answers. Responses too large to be
+/// read are not recorded.
+pub struct Archive {
+    dir: PathBuf,
+    user_agent: String,
+    max_body_bytes: usize,
+    /// The WARC file of this run, created with its first record.
+    file: Mutex<Option<WarcFile>>,
+}
+
+struct WarcFile {
+    file: File,
+    warcinfo_id: String,
+}
+
+impl Archive {
+    pub fn new(dir: PathBuf, user_agent: &str) -> Self {
+        Self {
+            dir,
+            user_agent: user_agent.to_string(),
+            max_body_bytes: CrawlerConfig::default().max_body_bytes,
+            file: Mutex::new(None),
+        }
+    }
+
+    /// Fail requests whose response body is larger than `max_body_bytes`,
+    /// as sessions do.
+    pub fn with_max_body_bytes(mut self, max_body_bytes: usize) -> Self {
+        self.max_body_bytes = max_body_bytes;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Send `request` with `client` and record the exchange, without
+    /// following redirects.
+    pub(crate) async fn execute(
+        &self,
+        client: &Client,
+        request: Request,
+        job_id: Option<&str>,
+    ) -> Result<Response> {
+        let method = request.method().to_string();
+        let url = request.url().clone();
+        let mut request_headers = header_pairs(request.headers());
+        if !request.headers().contains_key("host") {
+            request_headers.insert(
+                0,
+                (
+                    "host".to_string(),
+                    url[Position::BeforeHost..Position::AfterPort].to_string(),
+                ),
+            );
+        }
+        if !request.headers().contains_key("user-agent") {
+            request_headers.push(("user-agent".to_string(), self.user_agent.clone()));
+        }
+        let request_body = request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .map(|body| body.to_vec())
+            .unwrap_or_default();
+        let date = Utc::now();
+
+        let response = client.execute(request).await?;
+        let response_url = response.url().to_string();
+        let status = response.status().as_u16();
+        let ip_address = response.remote_addr().map(|addr| addr.ip().to_string());
+        let mut headers = response.headers().clone();
+        headers.remove("transfer-encoding");
+        let body = fetch::read_body(response, self.max_body_bytes).await?;
+
+        let exchange = Exchange {
+            job_id: job_id.map(|id| id.to_string()),
+            date,
+            method,
+            url: url.to_string(),
+            request_headers,
+            request_body,
+            response_url,
+            status,
+            response_headers: header_pairs(&headers),
+            body,
+            ip_address,
+            request_id: record_id(),
+            response_id: record_id(),
+        };
+        self.record(&exchange)?;
+        exchange.to_response()
+    }
+
+    fn record(&self, exchange: &Exchange) -> Result<()> {
+        let mut file = self.file.lock().unwrap();
+        if file.is_none() {
+            *file = Some(self.create_file()?);
+        }
+        let warc = file.as_mut().expect("file was created above");
+        warc.file
+            .write_all(&exchange.to_warc(&warc.warcinfo_id))
+            .and_then(|_| warc.file.flush())
+            .context("Failed to write to the archive")
+    }
+
+    fn create_file(&self) -> Result<WarcFile> {
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        let now = Utc::now();
+        let name = format!(
+            "mcp-crawl-{}-{}.warc",
+            now.format("%Y%m%d%H%M%S"),
+            &uuid::Uuid::new_v4().simple().to_string()[..8]
+        );
+        let path = self.dir.join(&name);
+        let mut file = OpenOptions::new()
+            .create_new(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        let warcinfo_id = record_id();
+        file.write_all(&warcinfo(&warcinfo_id, &name, &now))?;
+        Ok(WarcFile { file, warcinfo_id })
+    }
+
+    /// Every exchange recorded so far, or those of the crawl job `job_id`,
+    /// oldest first.
+    pub fn exchanges(&self, job_id: Option<&str>) -> Result<Vec<Exchange>> {
+        // Keep the file from being written to while it is read
+        let _file = self.file.lock().unwrap();
+        let exchanges = read_dir(&self.dir)?;
+        Ok(exchanges
+            .into_iter()
+            .filter(|e| job_id.is_none() || e.job_id.as_deref() == job_id)
+            .collect())
+    }
+
+    /// Write the exchanges of `job_id`, or all of them, to a new file in
+    /// the `exports` subdirectory.
+    pub fn export(&self, job_id: Option<&str>, format: ExportFormat) -> Result<ExportReport> {
+        let exchanges = self.exchanges(job_id)?;
+        if exchanges.is_empty() {
+            match job_id {
+                Some(job_id) => anyhow::bail!("Nothing was recorded for crawl job {}", job_id),
+                None => anyhow::bail!("Nothing was recorded yet"),
+            }
+        }
+
+        let now = Utc::now();
+        let label: String = job_id
+            .unwrap_or("all")
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let name = format!(
+            "{}-{}.{}",
+            label,
+            now.format("%Y%m%d%H%M%S"),
+            format.extension()
+        );
+        let dir = self.dir.join(EXPORTS_DIR);
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+        let path = dir.join(&name);
+
+        let data = match format {
+            ExportFormat::Warc => {
+                let warcinfo_id = record_id();
+                let mut data = warcinfo(&warcinfo_id, &name, &now);
+                for exchange in &exchanges {
+                    data.extend(exchange.to_warc(&warcinfo_id));
+                }
+                data
+            }
+            ExportFormat::Jsonl => exchanges
+                .iter()
+                .map(|exchange| format!("{}\n", exchange.to_json()))
+                .collect::<String>()
+                .into_bytes(),
+        };
+        std::fs::write(&path, &data)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+
+        Ok(ExportReport {
+            path,
+            format,
+            exchanges: exchanges.len(),
+            bytes: data.len(),
+        })
+    }
+}
+
+/// Responses read from WARC files, looked up by method and URL. A URL
+/// fetched more than once is answered with its latest full response:
+/// `304 Not Modified` answers to revalidations have no body to replay.
+pub struct Replay {
+    exchanges: HashMap<(String, String), Exchange>,
+}
+
+impl Replay {
+    /// Read a WARC file, or every `.warc` file of a directory.
+    pub fn open(path: &Path) -> Result<Self> {
+        let exchanges = if path.is_dir() {
+            read_dir(path)?
+        } else {
+            read_file(path)?
+        };
+        let mut index = HashMap::new();
+        for exchange in exchanges {
+            if exchange.status == StatusCode::NOT_MODIFIED.as_u16() {
+                continue;
+            }
+            index
+                .entry((exchange.method.clone(), exchange.response_url.clone()))
+                .or_insert_with(|| exchange.clone());
+            index.insert((exchange.method.clone(), exchange.url.clone()), exchange);
+        }
+        Ok(Self { exchanges: index })
+    }
+
+    /// Number of distinct requests that can be answered.
+    pub fn len(&self) -> usize {
+        self.exchanges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exchanges.is_empty()
+    }
+
+    /// The archived response to `request`.
+    pub(crate) fn response(&self, request: &Request) -> Result<Response> {
+        let key = (request.method().to_string(), request.url().to_string());
+        match self.exchanges.get(&key) {
+            Some(exchange) => exchange.to_response(),
+            None => anyhow::bail!("{} {} is not in the replayed archive", key.0, key.1),
+        }
+    }
+}
+
+/// A parsed WARC record.
+struct WarcRecord {
+    fields: Vec<(String, String)>,
+    block: Vec<u8>,
+}
+
+impl WarcRecord {
+    fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Exchanges of the `.warc` files of `dir`, in file name order.
+fn read_dir(dir: &Path) -> Result<Vec<Exchange>> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|e| e == "warc"))
+        .collect();
+    paths.sort();
+    let mut exchanges = Vec::new();
+    for path in paths {
+        exchanges.extend(read_file(&path)?);
+    }
+    Ok(exchanges)
+}
+
+fn read_file(path: &Path) -> Result<Vec<Exchange>> {
+    let data = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let records =
+        parse_warc(&data).with_context(|| format!("Invalid WARC file {}", path.display()))?;
+    Ok(exchanges(records))
+}
+
+fn parse_warc(mut data: &[u8]) -> Result<Vec<WarcRecord>> {
+    let mut records = Vec::new();
+    loop {
+        while let Some(rest) = data.strip_prefix(b"\r\n") {
+            data = rest;
+        }
+        if data.is_empty() {
+            return Ok(records);
+        }
+        let head_end = find(data, b"\r\n\r\n").context("Truncated record header")?;
+        let head = String::from_utf8_lossy(&data[..head_end]);
+        let mut lines = head.split("\r\n");
+        if !lines
+            .next()
+            .is_some_and(|version| version.starts_with("WARC/"))
+        {
+            anyhow::bail!("Expected a WARC record");
+        }
+        let fields: Vec<(String, String)> = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+            .collect();
+        let length: usize = fields
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("Content-Length"))
+            .and_then(|(_, value)| value.parse().ok())
+            .context("Record without Content-Length")?;
+        let start = head_end + 4;
+        let end = start
+            .checked_add(length)
+            .filter(|end| *end <= data.len())
+            .context("Truncated record")?;
+        records.push(WarcRecord {
+            fields,
+            block: data[start..end].to_vec(),
+        });
+        data = &data[end..];
+    }
+}
+
+/// Pair response records with the request records sent for them.
+fn exchanges(records: Vec<WarcRecord>) -> Vec<Exchange> {
+    let requests: HashMap<&str, &WarcRecord> = records
+        .iter()
+        .filter(|r| r.field("WARC-Type") == Some("request"))
+        .filter_map(|r| Some((r.field("WARC-Concurrent-To")?, r)))
+        .collect();
+
+    records
+        .iter()
+        .filter(|r| r.field("WARC-Type") == Some("response"))
+        .filter_map(|response| {
+            let response_id = response.field("WARC-Record-ID")?;
+            let response_url = response.field("WARC-Target-URI")?.to_string();
+            let (status_line, response_headers, body) = parse_http(&response.block);
+            let status = status_line.split_whitespace().nth(1)?.parse().ok()?;
+            let request = requests.get(response_id);
+            let (method, request_headers, request_body) = match request {
+                Some(request) => {
+                    let (line, headers, body) = parse_http(&request.block);
+                    let method = line.split_whitespace().next().unwrap_or("GET").to_string();
+                    (method, headers, body)
+                }
+                None => ("GET".to_string(), vec![], vec![]),
+            };
+            Some(Exchange {
+                job_id: response.field(JOB_FIELD).map(|id| id.to_string()),
+                date: response
+                    .field("WARC-Date")
+                    .and_then(|d| DateTime::parse_from_rfc3339(d).ok())
+                    .map(|d| d.with_timezone(&Utc))
+                    .unwrap_or_default(),
+                method,
+                url: request
+                    .and_then(|r| r.field("WARC-Target-URI"))
+                    .unwrap_or(&response_url)
+                    .to_string(),
+                request_headers,
+                request_body,
+                response_url,
+                status,
+                response_headers,
+                body,
+                ip_address: response.field("WARC-IP-Address").map(|ip| ip.to_string()),
+                request_id: request
+                    .and_then(|r| r.field("WARC-Record-ID"))
+                    .map(|id| id.to_string())
+                    .unwrap_or_else(record_id),
+                response_id: response_id.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Start line, headers and body of an HTTP message.
+fn parse_http(block: &[u8]) -> (String, Vec<(String, String)>, Vec<u8>) {
+    let (head, body) = match find(block, b"\r\n\r\n") {
+        Some(end) => (&block[..end], block[end + 4..].to_vec()),
+        None => (block, vec![]),
+    };
+    let head = String::from_utf8_lossy(head);
+    let mut lines = head.split("\r\n");
+    let start = lines.next().unwrap_or_default().to_string();
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+    (start, headers, body)
+}
+
+fn find(data: &[u8], needle: &[u8]) -> Option<usize> {
+    data.windows(needle.len()).position(|w| w == needle)
+}
+
+fn warc_record(fields: &[(&str, String)], content_type: &str, block: &[u8]) -> Vec<u8> {
+    let mut record = b"WARC/1.1\r\n".to_vec();
+    for (name, value) in fields {
+        record.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+    }
+    record.extend_from_slice(
+        format!(
+            "Content-Type: {}\r\nContent-Length: {}\r\n\r\n",
+            content_type,
+            block.len()
+        )
+        .as_bytes(),
+    );
+    record.extend_from_slice(block);
+    record.extend_from_slice(b"\r\n\r\n");
+    record
+}
+
+/// The `warcinfo` record starting a WARC file.
+fn warcinfo(id: &str, filename: &str, date: &DateTime<Utc>) -> Vec<u8> {
+    let info = format!(
+        "software: mcp-crawl/{}\r\nformat: WARC File Format 1.1\r\n",
+        env!("CARGO_PKG_VERSION")
+    );
+    warc_record(
+        &[
+            ("WARC-Type", "warcinfo".to_string()),
+            ("WARC-Record-ID", id.to_string()),
+            ("WARC-Date", warc_date(date)),
+            ("WARC-Filename", filename.to_string()),
+        ],
+        "application/warc-fields",
+        info.as_bytes(),
+    )
+}
+
+fn write_headers(block: &mut Vec<u8>, headers: &[(String, String)]) {
+    for (name, value) in headers {
+        block.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+    }
+    block.extend_from_slice(b"\r\n");
+}
+
+fn header_pairs(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.as_str().to_string(),
+                String::from_utf8_lossy(value.as_bytes()).into_owned(),
+            )
+        })
+        .collect()
+}
+
+/// Headers as an object, repeated ones joined with `, `.
+fn joined_headers(headers: &[(String, String)]) -> BTreeMap<String, String> {
+    let mut map: BTreeMap<String, String> = BTreeMap::new();
+    for (name, value) in headers {
+        map.entry(name.clone())
+            .and_modify(|existing| {
+                existing.push_str(", ");
+                existing.push_str(value);
+            })
+            .or_insert_with(|| value.clone());
+    }
+    map
+}
+
+fn record_id() -> String {
+    format!("<urn:uuid:{}>", uuid::Uuid::new_v4())
+}
+
+fn warc_date(date: &DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
@@ -0,0 +1,191 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::fetch::ResponseInfo;
+
+/// Limits of the page cache.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheOptions {
+    /// How long an entry is served without asking the origin again.
+    pub ttl: Duration,
+    /// Maximum number of cached pages.
+    pub max_entries: usize,
+    /// Maximum total size of cached bodies in bytes. Larger pages are
+    /// never cached.
+    pub max_bytes: usize,
+}
+
+impl Default for CacheOptions {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(300),
+            max_entries: 256,
+            max_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+/// Where the body returned by a fetch came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheStatus {
+    /// Served from the cache without contacting the origin.
+    Hit,
+    /// The entry had expired but the origin answered 304 Not Modified.
+    Revalidated,
+    /// Fetched from the origin.
+    Miss,
+    /// Fetched from the origin by a session without a cache.
+    Bypass,
+}
+
+impl CacheStatus {
+    /// Whether the body came out of the cache.
+    pub fn is_cached(&self) -> bool {
+        matches!(self, CacheStatus::Hit | CacheStatus::Revalidated)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub body: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// The response the body came from.
+    pub response: Option<ResponseInfo>,
+    fetched_at: Instant,
+    last_used: Instant,
+}
+
+impl CacheEntry {
+    pub fn new(body: String, etag: Option<String>, last_modified: Option<String>) -> Self {
+        let now = Instant::now();
+        Self {
+            body,
+            etag,
+            last_modified,
+            response: None,
+            fetched_at: now,
+            last_used: now,
+        }
+    }
+
+    pub fn with_response(mut self, response: ResponseInfo) -> Self {
+        self.response = Some(response);
+        self
+    }
+
+    /// Whether the origin can answer a conditional request for this entry.
+    pub fn can_revalidate(&self) -> bool {
+        self.etag.is_some() || self.last_modified.is_some()
+    }
+}
+
+/// Result of a cache lookup.
+pub enum Lookup {
+    /// Fresh entry, usable as is.
+    Fresh(CacheEntry),
+    /// Expired entry that still carries validators.
+    Stale(CacheEntry),
+    Missing,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub bytes: usize,
+}
+
+/// Server-wide cache of fetched pages keyed by URL, shared by all tools.
+///
+/// Entries are evicted least recently used first once either limit is
+/// reached. Expired entries without an ETag or Last-Modified header are
+/// dropped on lookup.
+pub struct PageCache {
+    options: CacheOptions,
+    entries: Mutex<HashMap<String, CacheEntry>>,
+}
+
+impl PageCache {
+    pub fn new(options: CacheOptions) -> Self {
+        Self {
+            options,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn lookup(&self, url: &str) -> Lookup {
+        let mut entries = self.entries.lock().unwrap();
+        let Some(entry) = entries.get_mut(url) else {
+            return Lookup::Missing;
+        };
+        entry.last_used = Instant::now();
+        if entry.fetched_at.elapsed() < self.options.ttl {
+            return Lookup::Fresh(entry.clone());
+        }
+        if entry.can_revalidate() {
+            return Lookup::Stale(entry.clone());
+        }
+        entries.remove(url);
+        Lookup::Missing
+    }
+
+    pub fn insert(&self, url: &str, entry: CacheEntry) {
+        if self.options.max_entries == 0 || entry.body.len() > self.options.max_bytes {
+            self.remove(url);
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        entries.remove(url);
+
+        let mut bytes: usize = entries.values().map(|e| e.body.len()).sum();
+        while !entries.is_empty()
+            && (entries.len() >= self.options.max_entries
+                || bytes + entry.body.len() > self.options.max_bytes)
+        {
+            let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(url, _)| url.clone())
+            else {
+                break;
+            };
+            if let Some(evicted) = entries.remove(&oldest) {
+                bytes -= evicted.body.len();
+            }
+        }
+        entries.insert(url.to_string(), entry);
+    }
+
+    /// Mark a stale entry as fresh again after a 304 response.
+    pub fn refresh(&self, url: &str) -> Option<CacheEntry> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.get_mut(url)?;
+        let now = Instant::now();
+        entry.fetched_at = now;
+        entry.last_used = now;
+        Some(entry.clone())
+    }
+
+    pub fn remove(&self, url: &str) -> bool {
+        self.entries.lock().unwrap().remove(url).is_some()
+    }
+
+    /// Evict every entry and return how many there were.
+    pub fn clear(&self) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        let count = entries.len();
+        entries.clear();
+        count
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let entries = self.entries.lock().unwrap();
+        CacheStats {
+            entries: entries.len(),
+            bytes: entries.values().map(|e| e.body.len()).sum(),
+        }
+    }
+}
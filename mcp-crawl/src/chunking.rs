@@ -0,0 +1,433 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// Approximate token count of `text`, at four characters per token.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Where chunks may be cut. Coarser boundaries are preferred; a piece that
+/// is still over budget is cut at the next finer boundary, down to words.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkStrategy {
+    /// At Markdown headings.
+    #[default]
+    Heading,
+    /// At blank lines.
+    Paragraph,
+    /// At sentence ends.
+    Sentence,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkOptions {
+    pub strategy: ChunkStrategy,
+    /// Budget of every chunk, in approximate tokens.
+    pub max_tokens: usize,
+    /// How much of the end of a chunk is repeated at the start of the
+    /// next one. Capped at half of `max_tokens`.
+    pub overlap_tokens: usize,
+}
+
+impl Default for ChunkOptions {
+    fn default() -> Self {
+        Self {
+            strategy: ChunkStrategy::Heading,
+            max_tokens: 1000,
+            overlap_tokens: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Chunk {
+    pub index: usize,
+    /// Heading of the section the chunk starts in.
+    pub heading: Option<String>,
+    pub text: String,
+    /// Approximate number of tokens, see [`estimate_tokens`].
+    pub tokens: usize,
+}
+
+/// Split text or Markdown into chunks within a token budget.
+///
+/// The text is cut at the boundaries of `options.strategy` and the pieces
+/// are packed back together while they fit. Code fences are never cut at a
+/// heading or blank line inside them.
+pub fn chunk_text(text: &str, options: &ChunkOptions) -> Vec<Chunk> {
+    let max_tokens = options.max_tokens.max(1);
+    let overlap_tokens = options.overlap_tokens.min(max_tokens / 2);
+    let start = match options.strategy {
+        ChunkStrategy::Heading => Level::Section,
+        ChunkStrategy::Paragraph => Level::Paragraph,
+        ChunkStrategy::Sentence => Level::Sentence,
+    };
+
+    let mut units = Vec::new();
+    for (heading, section) in sections(text) {
+        let splitter = Splitter {
+            start,
+            max_tokens,
+            heading,
+        };
+        splitter.split(&section, Level::Section, "\n\n", &mut units);
+    }
+    pack(&units, max_tokens, overlap_tokens)
+}
+
+/// A piece of text that is never cut further.
+struct Unit {
+    text: String,
+    /// Whitespace put before the unit when it follows another one.
+    separator: &'static str,
+    heading: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Level {
+    Section,
+    Paragraph,
+    Sentence,
+    Word,
+}
+
+struct Splitter {
+    start: Level,
+    max_tokens: usize,
+    heading: Option<String>,
+}
+
+impl Splitter {
+    fn split(&self, text: &str, level: Level, separator: &'static str, units: &mut Vec<Unit>) {
+        if level == Level::Word || (level >= self.start && estimate_tokens(text) <= self.max_tokens)
+        {
+            units.push(Unit {
+                text: text.to_string(),
+                separator,
+                heading: self.heading.clone(),
+            });
+            return;
+        }
+
+        let parts = match level {
+            Level::Section => paragraphs(text).into_iter().map(|p| (p, "\n\n")).collect(),
+            // Code is cut between lines rather than at full stops
+            Level::Paragraph if text.starts_with("```") => {
+                text.lines().map(|line| (line.to_string(), "\n")).collect()
+            }
+            Level::Paragraph => sentences(text),
+            Level::Sentence => words(text, self.max_tokens)
+                .into_iter()
+                .map(|w| (w, " "))
+                .collect::<Vec<_>>(),
+            Level::Word => unreachable!(),
+        };
+        let next = match level {
+            Level::Section => Level::Paragraph,
+            Level::Paragraph => Level::Sentence,
+            _ => Level::Word,
+        };
+        for (i, (part, part_separator)) in parts.into_iter().enumerate() {
+            let separator = if i == 0 { separator } else { part_separator };
+            self.split(&part, next, separator, units);
+        }
+    }
+}
+
+/// Pack units into chunks, repeating up to `overlap_tokens` of trailing
+/// units at the start of the next chunk.
+fn pack(units: &[Unit], max_tokens: usize, overlap_tokens: usize) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    // Units of the current chunk; the first `overlap` ones repeat the previous chunk
+    let mut members: Vec<usize> = Vec::new();
+    let mut overlap = 0;
+
+    for i in 0..units.len() {
+        let mut candidate = members.clone();
+        candidate.push(i);
+        if members.len() > overlap && estimate_tokens(&join(units, &candidate)) > max_tokens {
+            // A heading moves on with the section it introduces
+            let mut carried = Vec::new();
+            if let [.., heading] = members[overlap..] {
+                if members.len() > overlap + 1
+                    && heading_text(&units[heading].text).is_some()
+                    && estimate_tokens(&join(units, &[heading, i])) <= max_tokens
+                {
+                    carried.push(heading);
+                    members.pop();
+                }
+            }
+            chunks.push(finish_chunk(chunks.len(), units, &members, overlap));
+
+            let mut tail: Vec<usize> = Vec::new();
+            for &member in members.iter().rev() {
+                let mut with_member = vec![member];
+                with_member.extend(&tail);
+                if estimate_tokens(&join(units, &with_member)) > overlap_tokens {
+                    break;
+                }
+                tail = with_member;
+            }
+            // The overlap gives way when the next unit would not fit otherwise
+            while !tail.is_empty() {
+                let mut with_unit = tail.clone();
+                with_unit.extend(&carried);
+                with_unit.push(i);
+                if estimate_tokens(&join(units, &with_unit)) <= max_tokens {
+                    break;
+                }
+                tail.remove(0);
+            }
+            overlap = tail.len();
+            members = tail;
+            members.extend(carried);
+        }
+        members.push(i);
+    }
+    if members.len() > overlap {
+        chunks.push(finish_chunk(chunks.len(), units, &members, overlap));
+    }
+    chunks
+}
+
+fn finish_chunk(index: usize, units: &[Unit], members: &[usize], overlap: usize) -> Chunk {
+    let text = join(units, members).trim().to_string();
+    Chunk {
+        index,
+        heading: units[members[overlap]].heading.clone(),
+        tokens: estimate_tokens(&text),
+        text,
+    }
+}
+
+fn join(units: &[Unit], members: &[usize]) -> String {
+    let mut text = String::new();
+    for (i, &member) in members.iter().enumerate() {
+        if i > 0 {
+            text.push_str(units[member].separator);
+        }
+        text.push_str(&units[member].text);
+    }
+    text
+}
+
+/// Split at every heading outside code fences, with the heading text of
+/// each section.
+fn sections(text: &str) -> Vec<(Option<String>, String)> {
+    let mut sections: Vec<(Option<String>, String)> = vec![(None, String::new())];
+    let mut fence: Option<String> = None;
+    for line in text.lines() {
+        match &fence {
+            Some(open) if line.trim_start().starts_with(open.as_str()) => fence = None,
+            Some(_) => {}
+            None if line.trim_start().starts_with("```") => {
+                let marker = line.trim_start();
+                fence = Some(marker[..marker.len() - marker.trim_start_matches('`').len()].into());
+            }
+            None => {
+                if let Some(heading) = heading_text(line) {
+                    sections.push((Some(heading), String::new()));
+                }
+            }
+        }
+        let section = &mut sections.last_mut().unwrap().1;
+        section.push_str(line);
+        section.push('\n');
+    }
+    sections
+        .into_iter()
+        .map(|(heading, s)| (heading, s.trim().to_string()))
+        .filter(|(_, s)| !s.is_empty())
+        .collect()
+}
+
+/// Text of an ATX heading line such as `## Install`.
+fn heading_text(line: &str) -> Option<String> {
+    let hashes = line.len() - line.trim_start_matches('#').len();
+    let rest = &line[hashes..];
+    if (1..=6).contains(&hashes) && (rest.is_empty() || rest.starts_with(' ')) {
+        Some(rest.trim().to_string())
+    } else {
+        None
+    }
+}
+
+/// Blank-line separated blocks, keeping code fences whole.
+fn paragraphs(text: &str) -> Vec<String> {
+    let mut paragraphs = Vec::new();
+    let mut current = String::new();
+    let mut in_fence = false;
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        if line.trim().is_empty() && !in_fence {
+            if !current.is_empty() {
+                paragraphs.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        if !current.is_empty() {
+            current.push('\n');
+        }
+        current.push_str(line.trim_end());
+    }
+    if !current.is_empty() {
+        paragraphs.push(current);
+    }
+    paragraphs
+}
+
+/// Sentences of a paragraph, each with the whitespace that preceded it:
+/// a line break where the paragraph had one, a space otherwise.
+fn sentences(paragraph: &str) -> Vec<(String, &'static str)> {
+    let mut sentences = Vec::new();
+    let mut current = String::new();
+    let mut separator = " ";
+    let chars: Vec<char> = paragraph.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() && ends_sentence(&current, &chars[i..]) {
+            let mut j = i;
+            let mut line_break = false;
+            while j < chars.len() && chars[j].is_whitespace() {
+                line_break |= chars[j] == '\n';
+                j += 1;
+            }
+            sentences.push((std::mem::take(&mut current), separator));
+            separator = if line_break { "\n" } else { " " };
+            i = j;
+            continue;
+        }
+        current.push(c);
+        i += 1;
+    }
+    if !current.trim().is_empty() {
+        sentences.push((current, separator));
+    }
+    sentences
+}
+
+/// Whether `sentence` is complete, given the text after it: it ends with
+/// `.`, `!` or `?` (maybe followed by closing quotes or brackets) and the
+/// next word does not start in lower case.
+fn ends_sentence(sentence: &str, rest: &[char]) -> bool {
+    let trimmed = sentence.trim_end_matches(['"', '\'', ')', ']', '”', '’', '*', '_']);
+    if !trimmed.ends_with(['.', '!', '?']) {
+        return false;
+    }
+    rest.iter()
+        .find(|c| !c.is_whitespace())
+        .is_some_and(|c| !c.is_lowercase())
+}
+
+/// Cut text between words into pieces of at most `max_tokens`. Words longer
+/// than that are cut themselves.
+fn words(text: &str, max_tokens: usize) -> Vec<String> {
+    let max_chars = max_tokens * 4;
+    let mut pieces = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        let word: Vec<char> = word.chars().collect();
+        for part in word.chunks(max_chars) {
+            let part: String = part.iter().collect();
+            if !current.is_empty() && current.chars().count() + 1 + part.chars().count() > max_chars
+            {
+                pieces.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(&part);
+        }
+    }
+    if !current.is_empty() {
+        pieces.push(current);
+    }
+    pieces
+}
+
+/// One chunk of a stored document with the cursor of the next one.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChunkPage {
+    pub chunk: Chunk,
+    pub total_chunks: usize,
+    /// Pass to `get_chunk` for the next chunk; `None` after the last one.
+    pub next_cursor: Option<String>,
+}
+
+/// Chunked documents kept for `get_chunk`, oldest dropped first.
+pub struct ChunkStore {
+    capacity: usize,
+    documents: Mutex<Documents>,
+}
+
+#[derive(Default)]
+struct Documents {
+    /// Ids, oldest first.
+    order: VecDeque<String>,
+    chunks: HashMap<String, Vec<Chunk>>,
+}
+
+impl Default for ChunkStore {
+    fn default() -> Self {
+        Self::new(64)
+    }
+}
+
+impl ChunkStore {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            documents: Mutex::new(Documents::default()),
+        }
+    }
+
+    /// Keep `chunks` and return the first one, or `None` when there are none.
+    pub fn store(&self, chunks: Vec<Chunk>) -> Option<ChunkPage> {
+        if chunks.is_empty() {
+            return None;
+        }
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        let page = page(&id, &chunks, 0);
+        let mut documents = self.documents.lock().unwrap();
+        while documents.order.len() >= self.capacity {
+            if let Some(oldest) = documents.order.pop_front() {
+                documents.chunks.remove(&oldest);
+            }
+        }
+        documents.order.push_back(id.clone());
+        documents.chunks.insert(id, chunks);
+        page
+    }
+
+    /// The chunk a cursor points at.
+    pub fn get(&self, cursor: &str) -> Result<ChunkPage> {
+        let (id, index) = cursor
+            .rsplit_once(':')
+            .and_then(|(id, index)| Some((id, index.parse::<usize>().ok()?)))
+            .ok_or(anyhow::anyhow!("Invalid cursor: {}", cursor))?;
+        let documents = self.documents.lock().unwrap();
+        let chunks = documents.chunks.get(id).ok_or(anyhow::anyhow!(
+            "Unknown or expired cursor: {}; fetch the page again",
+            cursor
+        ))?;
+        page(id, chunks, index).ok_or(anyhow::anyhow!(
+            "Cursor {} is past the last chunk ({} chunks)",
+            cursor,
+            chunks.len()
+        ))
+    }
+}
+
+fn page(id: &str, chunks: &[Chunk], index: usize) -> Option<ChunkPage> {
+    Some(ChunkPage {
+        chunk: chunks.get(index)?.clone(),
+        total_chunks: chunks.len(),
+        next_cursor: (index + 1 < chunks.len()).then(|| format!("{}:{}", id, index + 1)),
+    })
+}
@@ -0,0 +1,70 @@
+use anyhow::Result;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::cache::CacheOptions;
+
+/// Fetching behaviour shared by every tool, set from the command line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrawlerConfig {
+    /// User-Agent header sent with every request.
+    pub user_agent: String,
+    /// Minimum time between two requests to the same host.
+    pub delay: Duration,
+    /// Default link depth for multi-page crawls.
+    pub max_depth: usize,
+    /// Whether multi-page crawls may visit subdomains by default.
+    pub subdomains: bool,
+    /// Whether robots.txt rules, including Crawl-delay, are honoured.
+    pub respect_robots: bool,
+    /// Maximum number of requests in flight to the same host.
+    pub per_host_concurrency: usize,
+    /// How many times a 429 or 5xx response is retried.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for every further one.
+    /// `Retry-After` takes precedence.
+    pub retry_backoff: Duration,
+    /// Largest response body read. Bigger responses fail instead of
+    /// filling memory.
+    pub max_body_bytes: usize,
+    /// Limits of the page cache shared by all tools.
+    pub cache: CacheOptions,
+    /// Where `monitor` keeps its snapshots. Monitors are forgotten when the
+    /// server stops without it.
+    pub monitor_dir: Option<PathBuf>,
+    /// Directory where every request and response is recorded as WARC.
+    pub archive_dir: Option<PathBuf>,
+    /// WARC file, or directory of them, answering requests instead of the
+    /// network.
+    pub replay: Option<PathBuf>,
+}
+
+impl Default for CrawlerConfig {
+    fn default() -> Self {
+        Self {
+            user_agent: concat!("mcp-crawl/", env!("CARGO_PKG_VERSION")).to_string(),
+            delay: Duration::from_secs(1),
+            max_depth: 2,
+            subdomains: false,
+            respect_robots: true,
+            per_host_concurrency: 2,
+            max_retries: 3,
+            retry_backoff: Duration::from_millis(500),
+            max_body_bytes: 32 * 1024 * 1024,
+            cache: CacheOptions::default(),
+            monitor_dir: None,
+            archive_dir: None,
+            replay: None,
+        }
+    }
+}
+
+impl CrawlerConfig {
+    /// Set the delay from a number of seconds, as given on the command line.
+    /// Negative delays count as none; infinite ones are an error.
+    pub fn with_delay_secs(mut self, secs: f64) -> Result<Self> {
+        self.delay = Duration::try_from_secs_f64(secs.max(0.0))
+            .map_err(|e| anyhow::anyhow!("Invalid delay {}: {}", secs, e))?;
+        Ok(self)
+    }
+}
@@ -0,0 +1,294 @@
+use anyhow::Result;
+use readability::extractor;
+use regex::Regex;
+use scraper::{Html, Selector};
+use serde::Serialize;
+use std::collections::{HashSet, VecDeque};
+use url::Url;
+
+use crate::scraper_tools::{document_base_url, ScrapingSession};
+
+/// Which hosts a crawl may visit, relative to the seed URL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrawlScope {
+    /// Only the seed's host.
+    SameDomain,
+    /// The seed's host and any of its subdomains.
+    Subdomains,
+    /// Any host.
+    Any,
+}
+
+impl CrawlScope {
+    pub fn allows(&self, seed: &Url, url: &Url) -> bool {
+        let (Some(seed_host), Some(host)) = (seed.host_str(), url.host_str()) else {
+            return false;
+        };
+        let seed_host = seed_host.trim_start_matches("www.");
+        let host = host.trim_start_matches("www.");
+        match self {
+            CrawlScope::SameDomain => host == seed_host,
+            CrawlScope::Subdomains => {
+                host == seed_host || host.ends_with(&format!(".{}", seed_host))
+            }
+            CrawlScope::Any => true,
+        }
+    }
+}
+
+/// Limits and filters for a multi-page crawl.
+#[derive(Debug, Clone)]
+pub struct CrawlOptions {
+    pub max_pages: usize,
+    pub max_depth: usize,
+    pub scope: CrawlScope,
+    /// URLs must match at least one of these, when any are given.
+    pub include_patterns: Vec<Regex>,
+    /// URLs matching any of these are skipped.
+    pub exclude_patterns: Vec<Regex>,
+    /// Run readability on every page to get its main text.
+    pub extract_content: bool,
+    pub excerpt_length: usize,
+}
+
+impl Default for CrawlOptions {
+    fn default() -> Self {
+        Self {
+            max_pages: 10,
+            max_depth: 2,
+            scope: CrawlScope::SameDomain,
+            include_patterns: vec![],
+            exclude_patterns: vec![],
+            extract_content: true,
+            excerpt_length: 300,
+        }
+    }
+}
+
+impl CrawlOptions {
+    /// Whether `url` passes the scope and include/exclude filters.
+    /// The seed itself is always allowed.
+    pub fn should_visit(&self, seed: &Url, url: &Url) -> bool {
+        if url == seed {
+            return true;
+        }
+        if !matches!(url.scheme(), "http" | "https") || !self.scope.allows(seed, url) {
+            return false;
+        }
+        if self
+            .exclude_patterns
+            .iter()
+            .any(|p| p.is_match(url.as_str()))
+        {
+            return false;
+        }
+        self.include_patterns.is_empty()
+            || self
+                .include_patterns
+                .iter()
+                .any(|p| p.is_match(url.as_str()))
+    }
+}
+
+/// One entry of the site map returned by a crawl.
+#[derive(Debug, Clone, Serialize)]
+pub struct CrawledPage {
+    pub url: String,
+    pub depth: usize,
+    pub status: Option<u16>,
+    pub title: Option<String>,
+    pub excerpt: Option<String>,
+    pub links_found: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl CrawledPage {
+    /// Whether the page could not be fetched or answered with an error.
+    pub fn failed(&self) -> bool {
+        self.error.is_some() || self.status.is_none_or(|s| s >= 400)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CrawlReport {
+    pub seed: String,
+    pub pages: Vec<CrawledPage>,
+    pub pages_crawled: usize,
+    pub pages_failed: usize,
+    /// URLs discovered but not visited because a limit was reached.
+    pub pages_remaining: usize,
+}
+
+/// Breadth-first crawler following links from a seed URL.
+pub struct Crawler {
+    options: CrawlOptions,
+}
+
+impl Crawler {
+    pub fn new(options: CrawlOptions) -> Self {
+        Self { options }
+    }
+
+    pub async fn run(&self, session: &mut ScrapingSession, seed: &str) -> Result<CrawlReport> {
+        self.run_with_progress(session, seed, |_, _| {}).await
+    }
+
+    /// Like [`Crawler::run`], calling `progress` with every page visited
+    /// and the number of URLs then waiting in the queue.
+    pub async fn run_with_progress(
+        &self,
+        session: &mut ScrapingSession,
+        seed: &str,
+        mut progress: impl FnMut(&CrawledPage, usize),
+    ) -> Result<CrawlReport> {
+        let seed = normalize_url(&Url::parse(seed)?);
+        let mut queue = VecDeque::from([(seed.clone(), 0usize)]);
+        let mut seen = HashSet::from([seed.to_string()]);
+        let mut pages = Vec::new();
+
+        while let Some((url, depth)) = queue.pop_front() {
+            if pages.len() >= self.options.max_pages {
+                queue.push_front((url, depth));
+                break;
+            }
+
+            let (page, links) = self.visit(session, &url, depth).await;
+            if depth < self.options.max_depth {
+                for link in links {
+                    if self.options.should_visit(&seed, &link) && seen.insert(link.to_string()) {
+                        queue.push_back((link, depth + 1));
+                    }
+                }
+            }
+            progress(&page, queue.len());
+            pages.push(page);
+        }
+
+        let pages_failed = pages.iter().filter(|p| p.failed()).count();
+        Ok(CrawlReport {
+            seed: seed.to_string(),
+            pages_crawled: pages.len(),
+            pages_failed,
+            pages_remaining: queue.len(),
+            pages,
+        })
+    }
+
+    async fn visit(
+        &self,
+        session: &mut ScrapingSession,
+        url: &Url,
+        depth: usize,
+    ) -> (CrawledPage, Vec<Url>) {
+        let mut page = CrawledPage {
+            url: url.to_string(),
+            depth,
+            status: None,
+            title: None,
+            excerpt: None,
+            links_found: 0,
+            error: None,
+        };
+
+        let response = match session.get(url.clone()).await {
+            Ok(response) => response,
+            Err(e) => {
+                page.error = Some(e.to_string());
+                return (page, vec![]);
+            }
+        };
+        page.status = Some(response.status().as_u16());
+        let final_url = response.url().clone();
+        let is_html = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_none_or(|ct| ct.contains("html"));
+
+        let body = match session.read_text(response).await {
+            Ok(body) => body,
+            Err(e) => {
+                page.error = Some(e.to_string());
+                return (page, vec![]);
+            }
+        };
+        if !is_html {
+            return (page, vec![]);
+        }
+
+        let links = extract_page_links(&body, &final_url);
+        page.links_found = links.len();
+        let (title, excerpt) = self.summarize(&body, &final_url);
+        page.title = title;
+        page.excerpt = excerpt;
+
+        (page, links)
+    }
+
+    fn summarize(&self, html: &str, url: &Url) -> (Option<String>, Option<String>) {
+        let title = Selector::parse("title").ok().and_then(|selector| {
+            Html::parse_document(html)
+                .select(&selector)
+                .next()
+                .map(|t| t.text().collect::<String>().trim().to_string())
+                .filter(|t| !t.is_empty())
+        });
+        if !self.options.extract_content {
+            return (title, None);
+        }
+
+        match extractor::extract(&mut html.as_bytes(), url) {
+            Ok(article) => {
+                let text = article
+                    .text
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let excerpt = truncate_chars(&text, self.options.excerpt_length);
+                let title = title.or(Some(article.title).filter(|t| !t.is_empty()));
+                (title, Some(excerpt))
+            }
+            Err(_) => (title, None),
+        }
+    }
+}
+
+/// Absolute, fragment-free URLs of every `a[href]` on the page at `page_url`,
+/// honouring `<base href>`.
+pub fn extract_page_links(html: &str, page_url: &Url) -> Vec<Url> {
+    let Ok(selector) = Selector::parse("a[href]") else {
+        return vec![];
+    };
+    let document = Html::parse_document(html);
+    let Some(base) = document_base_url(&document, Some(page_url)) else {
+        return vec![];
+    };
+    let mut links = Vec::new();
+    let mut seen = HashSet::new();
+    for element in document.select(&selector) {
+        let Some(href) = element.value().attr("href") else {
+            continue;
+        };
+        if let Ok(url) = base.join(href.trim()) {
+            let url = normalize_url(&url);
+            if seen.insert(url.to_string()) {
+                links.push(url);
+            }
+        }
+    }
+    links
+}
+
+fn normalize_url(url: &Url) -> Url {
+    let mut url = url.clone();
+    url.set_fragment(None);
+    url
+}
+
+fn truncate_chars(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((index, _)) => format!("{}...", &text[..index]),
+        None => text.to_string(),
+    }
+}
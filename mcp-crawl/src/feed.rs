@@ -0,0 +1,170 @@
+use anyhow::Result;
+use scraper::Html;
+use serde::Serialize;
+use sxd_document::dom::{ChildOfElement, ChildOfRoot, Element};
+use url::Url;
+
+/// An RSS 2.0, RSS 1.0 (RDF) or Atom feed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Feed {
+    /// `rss`, `rdf` or `atom`.
+    pub format: &'static str,
+    pub title: Option<String>,
+    pub link: Option<String>,
+    pub description: Option<String>,
+    pub items: Vec<FeedItem>,
+}
+
+/// An RSS item or Atom entry.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FeedItem {
+    pub title: Option<String>,
+    pub link: Option<String>,
+    pub id: Option<String>,
+    pub published: Option<String>,
+    pub updated: Option<String>,
+    pub author: Option<String>,
+    /// Description or summary with markup removed.
+    pub summary: Option<String>,
+}
+
+/// Parse a feed document. Relative links resolve against `base_url`.
+pub fn parse(xml: &str, base_url: Option<&Url>) -> Result<Feed> {
+    let package = sxd_document::parser::parse(xml.trim_start_matches('\u{feff}'))
+        .map_err(|e| anyhow::anyhow!("Invalid feed XML: {}", e))?;
+    let document = package.as_document();
+    let root = document
+        .root()
+        .children()
+        .into_iter()
+        .find_map(|child| match child {
+            ChildOfRoot::Element(element) => Some(element),
+            _ => None,
+        })
+        .ok_or(anyhow::anyhow!("Feed has no root element"))?;
+    let link = |element: Element| {
+        child_text(element, "link")
+            .or_else(|| atom_link(element))
+            .map(|href| resolve(&href, base_url))
+    };
+
+    let (format, channel, items) = match root.name().local_part() {
+        "rss" => {
+            let channel =
+                child(root, "channel").ok_or(anyhow::anyhow!("RSS feed has no channel"))?;
+            ("rss", channel, children(channel, "item"))
+        }
+        // RSS 1.0 keeps items next to the channel rather than inside it
+        "RDF" => {
+            let channel = child(root, "channel").unwrap_or(root);
+            ("rdf", channel, children(root, "item"))
+        }
+        "feed" => ("atom", root, children(root, "entry")),
+        other => anyhow::bail!("Not an RSS or Atom feed: root element is <{}>", other),
+    };
+
+    let items = items
+        .into_iter()
+        .map(|item| FeedItem {
+            title: child_text(item, "title"),
+            link: link(item),
+            id: child_text(item, "guid").or_else(|| child_text(item, "id")),
+            published: child_text(item, "pubDate")
+                .or_else(|| child_text(item, "published"))
+                .or_else(|| child_text(item, "date")),
+            updated: child_text(item, "updated"),
+            author: child(item, "author")
+                .and_then(|author| child_text(author, "name").or_else(|| text(author)))
+                .or_else(|| child_text(item, "creator")),
+            summary: child_text(item, "description")
+                .or_else(|| child_text(item, "summary"))
+                .or_else(|| child_text(item, "content"))
+                .map(|html| strip_markup(&html)),
+        })
+        .collect();
+
+    Ok(Feed {
+        format,
+        title: child_text(channel, "title"),
+        link: link(channel),
+        description: child_text(channel, "description")
+            .or_else(|| child_text(channel, "subtitle"))
+            .map(|html| strip_markup(&html)),
+        items,
+    })
+}
+
+/// Whether the start of `body` looks like a feed document.
+pub(crate) fn looks_like_feed(body: &[u8]) -> bool {
+    let head = String::from_utf8_lossy(&body[..body.len().min(1024)]);
+    head.contains("<rss") || head.contains("<feed") || head.contains("<rdf:RDF")
+}
+
+pub(crate) fn children<'d>(element: Element<'d>, name: &str) -> Vec<Element<'d>> {
+    element
+        .children()
+        .into_iter()
+        .filter_map(|child| match child {
+            ChildOfElement::Element(e) if e.name().local_part() == name => Some(e),
+            _ => None,
+        })
+        .collect()
+}
+
+fn child<'d>(element: Element<'d>, name: &str) -> Option<Element<'d>> {
+    children(element, name).into_iter().next()
+}
+
+/// Trimmed text of the first `name` child that has any.
+pub(crate) fn child_text(element: Element, name: &str) -> Option<String> {
+    children(element, name).into_iter().find_map(text)
+}
+
+/// Trimmed text content of `element`, or `None` when empty.
+fn text(element: Element) -> Option<String> {
+    fn collect(element: Element, text: &mut String) {
+        for child in element.children() {
+            match child {
+                ChildOfElement::Text(t) => text.push_str(t.text()),
+                ChildOfElement::Element(e) => collect(e, text),
+                _ => {}
+            }
+        }
+    }
+    let mut content = String::new();
+    collect(element, &mut content);
+    let content = content.trim();
+    (!content.is_empty()).then(|| content.to_string())
+}
+
+/// Atom `<link href>`, preferring `rel="alternate"`.
+fn atom_link(element: Element) -> Option<String> {
+    let links = children(element, "link");
+    links
+        .iter()
+        .find(|link| {
+            link.attribute_value("rel")
+                .is_none_or(|rel| rel == "alternate")
+        })
+        .or(links.first())
+        .and_then(|link| link.attribute_value("href"))
+        .map(|href| href.to_string())
+}
+
+fn resolve(href: &str, base_url: Option<&Url>) -> String {
+    base_url
+        .and_then(|base| base.join(href).ok())
+        .map(|url| url.to_string())
+        .unwrap_or_else(|| href.to_string())
+}
+
+/// Plain text of an HTML snippet, with whitespace collapsed.
+fn strip_markup(html: &str) -> String {
+    Html::parse_fragment(html)
+        .root_element()
+        .text()
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
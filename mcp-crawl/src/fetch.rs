@@ -0,0 +1,320 @@
+use anyhow::Result;
+use encoding_rs::{Encoding, UTF_8};
+use regex::Regex;
+use reqwest::header::{
+    self, HeaderMap, AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, COOKIE, LOCATION,
+    PROXY_AUTHORIZATION, TRANSFER_ENCODING, WWW_AUTHENTICATE,
+};
+use reqwest::{Method, Request, Response, StatusCode};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::sync::LazyLock;
+use std::time::{Duration, SystemTime};
+
+use crate::feed;
+
+/// Longest wait before a retry. Responses asking for more are returned as is.
+pub(crate) const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Redirects followed for a request, as many as the HTTP client follows.
+pub(crate) const MAX_REDIRECTS: usize = 10;
+
+/// Gzipped bodies are not inflated beyond this, the size limit of a sitemap.
+const MAX_GUNZIPPED_BYTES: u64 = 50 * 1024 * 1024;
+
+/// A response whose body is larger than the server accepts.
+#[derive(Debug, thiserror::Error)]
+#[error("Response body of {url} is larger than {limit} bytes")]
+pub struct BodyTooLarge {
+    pub url: String,
+    pub limit: usize,
+}
+
+/// A fetch answered with a non-2xx status.
+#[derive(Debug, thiserror::Error)]
+#[error("HTTP {status} {reason} for {url}")]
+pub struct HttpError {
+    pub status: u16,
+    pub reason: String,
+    pub url: String,
+}
+
+impl HttpError {
+    pub fn new(status: StatusCode, url: &str) -> Self {
+        Self {
+            status: status.as_u16(),
+            reason: status.canonical_reason().unwrap_or_default().to_string(),
+            url: url.to_string(),
+        }
+    }
+}
+
+/// What kind of document a response holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentKind {
+    Html,
+    Json,
+    /// RSS or Atom feed.
+    Feed,
+    Pdf,
+    /// Any other text, such as plain text, CSV or XML.
+    Text,
+    /// Images and other binary data, which have no text body.
+    Binary,
+}
+
+impl ContentKind {
+    /// Classify a response from its media type, sniffing the first bytes of
+    /// `body` when the type is missing or generic.
+    pub fn detect(content_type: Option<&str>, body: &[u8]) -> Self {
+        let is_xml = |t: &str| t == "text/xml" || t == "application/xml" || t.ends_with("+xml");
+        match content_type {
+            Some("text/html" | "application/xhtml+xml") => ContentKind::Html,
+            Some("application/rss+xml" | "application/atom+xml") => ContentKind::Feed,
+            Some(t) if is_xml(t) && feed::looks_like_feed(body) => ContentKind::Feed,
+            Some(t) if t == "application/json" || t.ends_with("+json") => ContentKind::Json,
+            Some("application/pdf") => ContentKind::Pdf,
+            Some(t) if t.starts_with("text/") || is_xml(t) => ContentKind::Text,
+            Some("application/octet-stream") => Self::sniff(body).unwrap_or(ContentKind::Binary),
+            Some(_) => ContentKind::Binary,
+            None => Self::sniff(body).unwrap_or(ContentKind::Html),
+        }
+    }
+
+    fn sniff(body: &[u8]) -> Option<Self> {
+        let start = body.trim_ascii_start();
+        if start.starts_with(b"%PDF-") {
+            Some(ContentKind::Pdf)
+        } else if start.starts_with(b"<?xml") {
+            Some(Self::detect(Some("application/xml"), body))
+        } else if start.starts_with(b"<") {
+            Some(ContentKind::Html)
+        } else {
+            None
+        }
+    }
+}
+
+/// Details of the response a page came from, without its body.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResponseInfo {
+    /// Final URL after redirects.
+    pub url: String,
+    pub status: u16,
+    /// Response headers with lower-cased names; repeated headers are joined
+    /// with `, `.
+    pub headers: BTreeMap<String, String>,
+    /// Media type without parameters, e.g. `text/html`.
+    pub content_type: Option<String>,
+    pub kind: ContentKind,
+    /// Charset the body was decoded from, unless it was not text.
+    pub charset: Option<String>,
+    /// Time from sending the request to receiving the whole body.
+    pub elapsed_ms: u64,
+    /// Size of the body as received, in bytes.
+    pub size: usize,
+}
+
+impl ResponseInfo {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+/// Collect `headers` into a map of lower-cased names to values.
+pub(crate) fn header_map(headers: &HeaderMap) -> BTreeMap<String, String> {
+    let mut map: BTreeMap<String, String> = BTreeMap::new();
+    for (name, value) in headers {
+        let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+        map.entry(name.as_str().to_string())
+            .and_modify(|existing| {
+                existing.push_str(", ");
+                existing.push_str(&value);
+            })
+            .or_insert(value);
+    }
+    map
+}
+
+/// Split a Content-Type header into its lower-cased media type and charset
+/// parameter.
+pub(crate) fn parse_content_type(value: &str) -> (Option<String>, Option<String>) {
+    let mut parts = value.split(';');
+    let media_type = parts
+        .next()
+        .map(|t| t.trim().to_ascii_lowercase())
+        .filter(|t| !t.is_empty());
+    let charset = parts.find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| value.trim().trim_matches('"').to_string())
+    });
+    (media_type, charset)
+}
+
+/// Decode a `kind` document. A byte order mark wins, then the Content-Type
+/// `charset`, then a `<meta charset>` or XML declaration in the document,
+/// falling back to UTF-8. Returns the text and the name of the encoding used.
+pub(crate) fn decode_body(
+    body: &[u8],
+    charset: Option<&str>,
+    kind: ContentKind,
+) -> (String, &'static str) {
+    let encoding = charset
+        .and_then(|label| Encoding::for_label(label.as_bytes()))
+        .or_else(|| declared_encoding(body, kind))
+        .unwrap_or(UTF_8);
+    // decode() sniffs the BOM itself and lets it override `encoding`
+    let (text, encoding, _) = encoding.decode(body);
+    (text.into_owned(), encoding.name())
+}
+
+/// `<meta charset="x">` and `<meta http-equiv content="...; charset=x">`.
+static META_CHARSET: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)<meta[^>]+charset\s*=\s*["']?\s*([\w.:-]+)"#).unwrap());
+
+/// `<?xml ... encoding="x"?>`.
+static XML_ENCODING: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"^\s*<\?xml[^>]+encoding\s*=\s*["']([\w.:-]+)"#).unwrap());
+
+/// Encoding declared inside the first KiB of an HTML or XML document.
+fn declared_encoding(body: &[u8], kind: ContentKind) -> Option<&'static Encoding> {
+    let pattern = match kind {
+        ContentKind::Html => &*META_CHARSET,
+        ContentKind::Feed | ContentKind::Text => &*XML_ENCODING,
+        _ => return None,
+    };
+    let head = String::from_utf8_lossy(&body[..body.len().min(1024)]);
+    let label = pattern.captures(&head)?.get(1)?.as_str().to_string();
+    Encoding::for_label(label.as_bytes())
+}
+
+/// Read the whole body of `response`, failing with [`BodyTooLarge`] past
+/// `limit` bytes.
+pub(crate) async fn read_body(response: Response, limit: usize) -> Result<Vec<u8>> {
+    let url = response.url().to_string();
+    match read_body_prefix(response, limit).await? {
+        (body, false) => Ok(body),
+        (_, true) => Err(BodyTooLarge { url, limit }.into()),
+    }
+}
+
+/// Read the body of `response` chunk by chunk, up to `limit` bytes, along
+/// with whether it was cut short there.
+pub(crate) async fn read_body_prefix(
+    mut response: Response,
+    limit: usize,
+) -> Result<(Vec<u8>, bool)> {
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        let room = limit - body.len();
+        if chunk.len() > room {
+            body.extend_from_slice(&chunk[..room]);
+            return Ok((body, true));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok((body, false))
+}
+
+/// The request to send after `response` to follow its redirect, made from
+/// `request` the way the HTTP client would, if `response` is one.
+pub(crate) fn redirect_request(mut request: Request, response: &Response) -> Option<Request> {
+    let status = response.status();
+    let location = response.headers().get(LOCATION)?.to_str().ok()?;
+    let url = response.url().join(location).ok()?;
+    match status {
+        StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND | StatusCode::SEE_OTHER => {
+            // Forms posted are followed by a GET without the body
+            *request.body_mut() = None;
+            for name in [TRANSFER_ENCODING, CONTENT_TYPE, CONTENT_LENGTH] {
+                request.headers_mut().remove(name);
+            }
+            if !matches!(*request.method(), Method::GET | Method::HEAD) {
+                *request.method_mut() = Method::GET;
+            }
+        }
+        StatusCode::TEMPORARY_REDIRECT | StatusCode::PERMANENT_REDIRECT => {}
+        _ => return None,
+    }
+    // Credentials are not sent to another host
+    if url.host_str() != request.url().host_str()
+        || url.port_or_known_default() != request.url().port_or_known_default()
+    {
+        for name in [AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION, WWW_AUTHENTICATE] {
+            request.headers_mut().remove(name);
+        }
+    }
+    *request.url_mut() = url;
+    Some(request)
+}
+
+/// Whether a body is a gzip file, such as a `.xml.gz` sitemap, rather than
+/// a response sent with `Content-Encoding: gzip`.
+pub(crate) fn is_gzip(content_type: Option<&str>, body: &[u8]) -> bool {
+    matches!(
+        content_type,
+        Some("application/gzip" | "application/x-gzip" | "application/octet-stream") | None
+    ) && body.starts_with(&[0x1f, 0x8b])
+}
+
+pub(crate) fn gunzip(body: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    flate2::read::GzDecoder::new(body)
+        .take(MAX_GUNZIPPED_BYTES + 1)
+        .read_to_end(&mut out)
+        .map_err(|e| anyhow::anyhow!("Failed to decompress gzip body: {}", e))?;
+    if out.len() as u64 > MAX_GUNZIPPED_BYTES {
+        anyhow::bail!("Gzip body inflates beyond {} bytes", MAX_GUNZIPPED_BYTES);
+    }
+    Ok(out)
+}
+
+/// Text of a PDF document.
+pub(crate) fn pdf_text(body: &[u8]) -> Result<String> {
+    // pdf-extract panics on some malformed documents
+    std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem(body))
+        .map_err(|_| anyhow::anyhow!("Failed to extract text from PDF"))?
+        .map_err(|e| anyhow::anyhow!("Failed to extract text from PDF: {}", e))
+}
+
+/// How long to wait before retrying a response with `status`, or `None`
+/// when it should not be retried. Only 429 and 5xx responses are retried;
+/// `Retry-After` wins over exponential backoff from `backoff`.
+pub(crate) fn retry_delay(
+    status: StatusCode,
+    headers: &HeaderMap,
+    attempt: u32,
+    backoff: Duration,
+) -> Option<Duration> {
+    if status != StatusCode::TOO_MANY_REQUESTS && !status.is_server_error() {
+        return None;
+    }
+    let delay = match headers
+        .get(header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_retry_after)
+    {
+        Some(delay) => delay,
+        None => backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(MAX_RETRY_DELAY),
+    };
+    (delay <= MAX_RETRY_DELAY).then_some(delay)
+}
+
+/// Parse a `Retry-After` value given in seconds or as an HTTP date.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
@@ -0,0 +1,291 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, Notify};
+use url::Url;
+
+use crate::crawler::{CrawlOptions, CrawledPage, Crawler};
+use crate::sessions::SharedSession;
+
+/// Finished jobs kept for `crawl_status` and `crawl_results`; the oldest
+/// are forgotten first.
+const MAX_FINISHED_JOBS: usize = 100;
+
+/// Sends a notification to the client, given its method and params.
+pub type Notifier = Arc<dyn Fn(String, Value) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// Reports the progress of a request as `notifications/progress` messages,
+/// delivered in order.
+#[derive(Clone)]
+pub struct ProgressReporter {
+    token: Value,
+    sender: mpsc::UnboundedSender<Value>,
+}
+
+impl ProgressReporter {
+    /// Report progress under the client's `token`. Must be called within a
+    /// Tokio runtime.
+    pub fn new(token: Value, notifier: Notifier) -> Self {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Some(params) = receiver.recv().await {
+                notifier("notifications/progress".to_string(), params).await;
+            }
+        });
+        Self { token, sender }
+    }
+
+    pub fn report(&self, progress: usize, total: Option<usize>, message: &str) {
+        let mut params = json!({
+            "progressToken": self.token,
+            "progress": progress,
+            "message": message,
+        });
+        if let Some(total) = total {
+            params["total"] = json!(total);
+        }
+        // The client is gone when nobody receives
+        let _ = self.sender.send(params);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Running,
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+/// Where a crawl job stands.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobStatus {
+    pub job_id: String,
+    pub seed: String,
+    pub state: JobState,
+    pub started: DateTime<Utc>,
+    pub finished: Option<DateTime<Utc>>,
+    /// Pages visited so far, failed ones included.
+    pub pages_done: usize,
+    pub pages_failed: usize,
+    /// URLs found but not visited yet. Not all of them will be when
+    /// `max_pages` is reached first.
+    pub pages_queued: usize,
+    pub max_pages: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A slice of the pages a job has visited, in the order it visited them.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobResults {
+    #[serde(flatten)]
+    pub status: JobStatus,
+    pub offset: usize,
+    pub pages: Vec<CrawledPage>,
+    /// Offset of the next slice, when there are more pages already.
+    pub next_offset: Option<usize>,
+}
+
+struct CrawlJob {
+    progress: Mutex<JobProgress>,
+    cancel: Notify,
+    /// Dropped when the job ends.
+    reporter: Mutex<Option<ProgressReporter>>,
+}
+
+struct JobProgress {
+    status: JobStatus,
+    pages: Vec<CrawledPage>,
+}
+
+impl CrawlJob {
+    fn status(&self) -> JobStatus {
+        self.progress.lock().unwrap().status.clone()
+    }
+
+    fn record(&self, page: &CrawledPage, queued: usize) {
+        let (done, total) = {
+            let mut progress = self.progress.lock().unwrap();
+            if progress.status.state != JobState::Running {
+                return;
+            }
+            let status = &mut progress.status;
+            status.pages_done += 1;
+            status.pages_failed += page.failed() as usize;
+            status.pages_queued = queued;
+            let total = (status.pages_done + queued).min(status.max_pages);
+            let done = status.pages_done;
+            progress.pages.push(page.clone());
+            (done, total)
+        };
+        if let Some(reporter) = self.reporter.lock().unwrap().as_ref() {
+            reporter.report(done, Some(total), &format!("Crawled {}", page.url));
+        }
+    }
+
+    /// Move a running job to `state`. Jobs that already ended stay as they
+    /// are.
+    fn finish(&self, state: JobState, error: Option<String>) -> bool {
+        let (done, message) = {
+            let mut progress = self.progress.lock().unwrap();
+            let status = &mut progress.status;
+            if status.state != JobState::Running {
+                return false;
+            }
+            status.state = state;
+            status.finished = Some(Utc::now());
+            status.error = error;
+            if state == JobState::Completed {
+                status.pages_queued = 0;
+            }
+            let message = match (state, &status.error) {
+                (_, Some(error)) => format!("Crawl failed: {}", error),
+                (JobState::Cancelled, _) => "Crawl cancelled".to_string(),
+                _ => format!("Crawl completed, {} pages", status.pages_done),
+            };
+            (status.pages_done, message)
+        };
+        if let Some(reporter) = self.reporter.lock().unwrap().take() {
+            reporter.report(done, Some(done), &message);
+        }
+        true
+    }
+}
+
+/// Crawls running in the background, polled with `crawl_status` and
+/// `crawl_results` instead of blocking a request for minutes.
+#[derive(Default)]
+pub struct CrawlJobs {
+    jobs: Mutex<HashMap<String, Arc<CrawlJob>>>,
+}
+
+impl CrawlJobs {
+    /// Start crawling from `seed` with `session` and return at once. The
+    /// job id also tags what the crawl records in the archive.
+    ///
+    /// The session is held until the crawl ends, so other calls using it
+    /// wait. Must be called within a Tokio runtime.
+    pub fn start(
+        &self,
+        session: SharedSession,
+        seed: Url,
+        options: CrawlOptions,
+        reporter: Option<ProgressReporter>,
+    ) -> JobStatus {
+        let id = uuid::Uuid::new_v4().to_string();
+        let status = JobStatus {
+            job_id: id.clone(),
+            seed: seed.to_string(),
+            state: JobState::Running,
+            started: Utc::now(),
+            finished: None,
+            pages_done: 0,
+            pages_failed: 0,
+            pages_queued: 1,
+            max_pages: options.max_pages,
+            error: None,
+        };
+        let job = Arc::new(CrawlJob {
+            progress: Mutex::new(JobProgress {
+                status: status.clone(),
+                pages: vec![],
+            }),
+            cancel: Notify::new(),
+            reporter: Mutex::new(reporter),
+        });
+        {
+            let mut jobs = self.jobs.lock().unwrap();
+            forget_finished(&mut jobs);
+            jobs.insert(id.clone(), job.clone());
+        }
+
+        tokio::spawn(async move {
+            let mut session = tokio::select! {
+                session = session.lock() => session,
+                _ = job.cancel.notified() => return,
+            };
+            session.set_job_id(Some(id));
+            let crawler = Crawler::new(options);
+            let crawl = crawler.run_with_progress(&mut session, seed.as_str(), |page, queued| {
+                job.record(page, queued)
+            });
+            let result = tokio::select! {
+                result = crawl => Some(result),
+                _ = job.cancel.notified() => None,
+            };
+            session.set_job_id(None);
+            match result {
+                Some(Ok(_)) => job.finish(JobState::Completed, None),
+                Some(Err(e)) => job.finish(JobState::Failed, Some(e.to_string())),
+                None => false,
+            };
+        });
+
+        status
+    }
+
+    pub fn status(&self, id: &str) -> Result<JobStatus> {
+        Ok(self.get(id)?.status())
+    }
+
+    /// Up to `limit` pages of job `id` from `offset` on. Pages can be read
+    /// while the job runs.
+    pub fn results(&self, id: &str, offset: usize, limit: usize) -> Result<JobResults> {
+        let job = self.get(id)?;
+        let progress = job.progress.lock().unwrap();
+        let pages: Vec<CrawledPage> = progress
+            .pages
+            .iter()
+            .skip(offset)
+            .take(limit)
+            .cloned()
+            .collect();
+        let end = offset + pages.len();
+        Ok(JobResults {
+            status: progress.status.clone(),
+            offset,
+            next_offset: (end < progress.pages.len()).then_some(end),
+            pages,
+        })
+    }
+
+    /// Stop job `id`, keeping the pages visited so far. Jobs that already
+    /// ended are left as they are.
+    pub fn cancel(&self, id: &str) -> Result<JobStatus> {
+        let job = self.get(id)?;
+        if job.finish(JobState::Cancelled, None) {
+            job.cancel.notify_one();
+        }
+        Ok(job.status())
+    }
+
+    fn get(&self, id: &str) -> Result<Arc<CrawlJob>> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .get(id)
+            .cloned()
+            .ok_or(anyhow::anyhow!("Unknown crawl job: {}", id))
+    }
+}
+
+/// Drop the oldest finished jobs beyond [`MAX_FINISHED_JOBS`].
+fn forget_finished(jobs: &mut HashMap<String, Arc<CrawlJob>>) {
+    let mut finished: Vec<(DateTime<Utc>, String)> = jobs
+        .iter()
+        .filter_map(|(id, job)| Some((job.status().finished?, id.clone())))
+        .collect();
+    if finished.len() <= MAX_FINISHED_JOBS {
+        return;
+    }
+    finished.sort();
+    for (_, id) in &finished[..finished.len() - MAX_FINISHED_JOBS] {
+        jobs.remove(id);
+    }
+}
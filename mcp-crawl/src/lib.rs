@@ -1,8 +1,58 @@
+mod archive;
+mod cache;
+mod chunking;
+mod config;
+mod crawler;
+mod feed;
+mod fetch;
+mod jobs;
+mod link_checker;
+mod markdown;
+mod metadata;
+mod monitor;
+mod pagination;
+mod politeness;
+mod schema;
 mod server;
 mod scraper_tools;
+mod sessions;
+mod sitemap;
+mod state;
+mod structured_data;
+mod tables;
+mod xpath;
 
+pub use archive::{Archive, ArchiveMode, Exchange, ExportFormat, ExportReport, Replay};
+pub use cache::{CacheOptions, CacheStatus, PageCache};
+pub use chunking::{
+    chunk_text, estimate_tokens, Chunk, ChunkOptions, ChunkPage, ChunkStore, ChunkStrategy,
+};
+pub use config::CrawlerConfig;
+pub use crawler::{CrawlOptions, CrawlReport, CrawlScope, CrawledPage, Crawler};
+pub use feed::{Feed, FeedItem};
+pub use fetch::{BodyTooLarge, ContentKind, HttpError, ResponseInfo};
+pub use jobs::{CrawlJobs, JobResults, JobState, JobStatus, Notifier, ProgressReporter};
+pub use link_checker::{
+    CheckedLink, LinkCheckOptions, LinkCheckReport, LinkChecker, LinkStatus, LinkSummary,
+    PageLinks, Redirect,
+};
+pub use markdown::MarkdownOptions;
+pub use metadata::{Alternate, FeedLink, Icon, PageMetadata};
+pub use monitor::{CheckStatus, Monitor, MonitorCheck, MonitorOptions, MonitorStore, NoiseFilter};
+pub use pagination::{
+    NextPage, PageExtraction, PaginatedPage, PaginatedRecord, PaginationOptions, PaginationReport,
+    Paginator, StopReason,
+};
+pub use politeness::{Politeness, RobotsBlocked, RobotsTxt};
+pub use schema::ExtractionSchema;
 pub use server::build;
-pub use scraper_tools::{ElementExtractor, ScrapingSession, FormSubmitter, XPathAlternative};
+pub use scraper_tools::{
+    parse_srcset, ElementExtractor, FetchedPage, FormSubmitter, LinkKind, ScrapingSession,
+    XPathAlternative,
+};
+pub use sitemap::{Sitemap, SitemapOptions, SitemapReader, SitemapReport, SitemapUrl};
+pub use tables::{ColumnType, Table, TableFormat, TableOptions};
+pub use xpath::XPathResult;
 
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,491 @@
+use anyhow::Result;
+use futures::stream::{self, StreamExt};
+use percent_encoding::percent_decode_str;
+use reqwest::{header, Method};
+use scraper::{Html, Selector};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+use url::Url;
+
+use crate::cache::CacheStatus;
+use crate::crawler::CrawlOptions;
+use crate::fetch::ContentKind;
+use crate::scraper_tools::{document_base_url, FetchedPage, ScrapingSession};
+
+/// Redirects followed before a link is reported as an error.
+const MAX_REDIRECTS: usize = 10;
+
+/// What to check and how hard to go at it.
+#[derive(Debug, Clone)]
+pub struct LinkCheckOptions {
+    /// Pages whose links are checked: the start page, and the pages a crawl
+    /// reaches from it when `max_pages` is above 1. `exclude_patterns` also
+    /// keeps links from being checked.
+    pub crawl: CrawlOptions,
+    /// Check links leaving the crawl scope.
+    pub check_external: bool,
+    /// Check that the fragment of a link names an element of its target.
+    pub check_anchors: bool,
+    /// Requests in flight at once, within the per-host limits.
+    pub concurrency: usize,
+    /// Time allowed for each request.
+    pub timeout: Duration,
+    /// Report working links too, not only those with a problem.
+    pub include_ok: bool,
+}
+
+impl Default for LinkCheckOptions {
+    fn default() -> Self {
+        Self {
+            crawl: CrawlOptions {
+                max_pages: 1,
+                extract_content: false,
+                ..Default::default()
+            },
+            check_external: true,
+            check_anchors: true,
+            concurrency: 8,
+            timeout: Duration::from_secs(10),
+            include_ok: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkStatus {
+    /// A 2xx response.
+    Ok,
+    /// A 2xx response at the end of redirects.
+    Redirected,
+    /// A 4xx or 5xx response, possibly after redirects.
+    Broken,
+    /// The target is fine but has no element with the link's fragment as
+    /// its `id` or `name`.
+    MissingAnchor,
+    /// No response within the timeout.
+    Timeout,
+    /// The request failed: no connection, a redirect loop, robots.txt...
+    Error,
+}
+
+/// A response that sent the request elsewhere.
+#[derive(Debug, Clone, Serialize)]
+pub struct Redirect {
+    pub url: String,
+    pub status: u16,
+}
+
+/// A link of a page and where it leads.
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckedLink {
+    /// Absolute URL of the link, fragment included.
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    pub status: LinkStatus,
+    /// Status of the last response.
+    pub http_status: Option<u16>,
+    /// Every hop before the last response, in order.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub redirects: Vec<Redirect>,
+    /// Where the redirects led.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub final_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The links found on one page.
+#[derive(Debug, Clone, Serialize)]
+pub struct PageLinks {
+    pub url: String,
+    pub http_status: Option<u16>,
+    pub links_found: usize,
+    /// Links with a problem, or every link with `include_ok`.
+    pub links: Vec<CheckedLink>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Number of links of each status, over all pages.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LinkSummary {
+    pub ok: usize,
+    pub redirected: usize,
+    pub broken: usize,
+    pub missing_anchor: usize,
+    pub timeout: usize,
+    pub error: usize,
+}
+
+impl LinkSummary {
+    fn add(&mut self, status: LinkStatus) {
+        *match status {
+            LinkStatus::Ok => &mut self.ok,
+            LinkStatus::Redirected => &mut self.redirected,
+            LinkStatus::Broken => &mut self.broken,
+            LinkStatus::MissingAnchor => &mut self.missing_anchor,
+            LinkStatus::Timeout => &mut self.timeout,
+            LinkStatus::Error => &mut self.error,
+        } += 1;
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkCheckReport {
+    pub start_url: String,
+    pub pages: Vec<PageLinks>,
+    pub pages_checked: usize,
+    /// Distinct URLs requested, fragments aside.
+    pub urls_checked: usize,
+    pub summary: LinkSummary,
+}
+
+/// A link as found on a page.
+struct Link {
+    url: Url,
+    text: Option<String>,
+}
+
+/// Where a URL, fragment aside, led.
+struct Target {
+    status: Option<u16>,
+    redirects: Vec<Redirect>,
+    final_url: Url,
+    error: Option<(LinkStatus, String)>,
+    /// `id`s and anchor names of the target, when it is an HTML page that
+    /// was downloaded.
+    anchors: Option<HashSet<String>>,
+}
+
+/// Finds the broken links of a page, or of the pages a crawl reaches.
+pub struct LinkChecker {
+    options: LinkCheckOptions,
+}
+
+impl LinkChecker {
+    pub fn new(options: LinkCheckOptions) -> Self {
+        Self { options }
+    }
+
+    /// Crawl from `start`, then check every link of the pages crawled.
+    /// Links are requested with HEAD, or GET when their anchor is checked or
+    /// the server does not handle HEAD.
+    pub async fn run(&self, session: &ScrapingSession, start: &Url) -> Result<LinkCheckReport> {
+        let start = without_fragment(start);
+        let crawl = &self.options.crawl;
+        let concurrency = self.options.concurrency.max(1);
+        let mut targets: HashMap<Url, Target> = HashMap::new();
+        let mut pages: Vec<(Url, Vec<Link>)> = Vec::new();
+
+        // Crawl breadth-first, a level at a time
+        let mut seen = HashSet::from([start.clone()]);
+        let mut level = vec![start.clone()];
+        let mut depth = 0;
+        while !level.is_empty() && pages.len() < crawl.max_pages {
+            level.truncate(crawl.max_pages - pages.len());
+            let fetched: Vec<(Url, Target, Option<FetchedPage>)> = stream::iter(level)
+                .map(|url| async move {
+                    let (target, page) = self.check(session, url.clone(), Method::GET).await;
+                    (url, target, page)
+                })
+                .buffered(concurrency)
+                .collect()
+                .await;
+
+            let mut next = Vec::new();
+            for (url, target, page) in fetched {
+                let links = page
+                    .filter(|page| page.response.kind == ContentKind::Html)
+                    .map(|page| page_links(&page.html, &page.url))
+                    .unwrap_or_default();
+                if depth < crawl.max_depth {
+                    for link in &links {
+                        let link = without_fragment(&link.url);
+                        if crawl.should_visit(&start, &link) && seen.insert(link.clone()) {
+                            next.push(link);
+                        }
+                    }
+                }
+                targets.insert(url.clone(), target);
+                pages.push((url, links));
+            }
+            level = next;
+            depth += 1;
+        }
+
+        // Then check the links that were not crawled
+        let mut pending: Vec<(Url, bool)> = Vec::new();
+        let mut index: HashMap<Url, usize> = HashMap::new();
+        for link in pages.iter().flat_map(|(_, links)| links) {
+            let url = without_fragment(&link.url);
+            if targets.contains_key(&url) || !self.should_check(&start, &url) {
+                continue;
+            }
+            let anchor = self.options.check_anchors && checked_fragment(&link.url).is_some();
+            match index.get(&url) {
+                Some(&i) => pending[i].1 |= anchor,
+                None => {
+                    index.insert(url.clone(), pending.len());
+                    pending.push((url, anchor));
+                }
+            }
+        }
+        let checked: Vec<(Url, Target)> = stream::iter(pending)
+            .map(|(url, anchor)| async move {
+                let method = if anchor { Method::GET } else { Method::HEAD };
+                let (target, _) = self.check(session, url.clone(), method).await;
+                (url, target)
+            })
+            .buffer_unordered(concurrency)
+            .collect()
+            .await;
+        targets.extend(checked);
+
+        let mut summary = LinkSummary::default();
+        let pages: Vec<PageLinks> = pages
+            .into_iter()
+            .map(|(url, links)| {
+                let page = &targets[&url];
+                let checked = links
+                    .iter()
+                    .filter_map(|link| {
+                        let target = targets.get(&without_fragment(&link.url))?;
+                        let checked = self.checked_link(link, target);
+                        summary.add(checked.status);
+                        (self.options.include_ok || checked.status != LinkStatus::Ok)
+                            .then_some(checked)
+                    })
+                    .collect();
+                PageLinks {
+                    url: url.to_string(),
+                    http_status: page.status,
+                    links_found: links.len(),
+                    links: checked,
+                    error: page.error.as_ref().map(|(_, e)| e.clone()),
+                }
+            })
+            .collect();
+
+        Ok(LinkCheckReport {
+            start_url: start.to_string(),
+            pages_checked: pages.len(),
+            urls_checked: targets.len(),
+            pages,
+            summary,
+        })
+    }
+
+    fn should_check(&self, start: &Url, url: &Url) -> bool {
+        let crawl = &self.options.crawl;
+        if crawl
+            .exclude_patterns
+            .iter()
+            .any(|p| p.is_match(url.as_str()))
+        {
+            return false;
+        }
+        self.options.check_external || crawl.scope.allows(start, url)
+    }
+
+    /// Request `url`, falling back to GET when a HEAD request fails.
+    async fn check(
+        &self,
+        session: &ScrapingSession,
+        url: Url,
+        method: Method,
+    ) -> (Target, Option<FetchedPage>) {
+        let (target, page) = self.follow(session, url.clone(), method.clone()).await;
+        if method == Method::HEAD && target.status.is_some_and(|s| s >= 400) {
+            return self.follow(session, url, Method::GET).await;
+        }
+        (target, page)
+    }
+
+    /// Request `url` and follow its redirects one by one. GET responses are
+    /// read, and kept along with the anchors of HTML pages.
+    async fn follow(
+        &self,
+        session: &ScrapingSession,
+        mut url: Url,
+        method: Method,
+    ) -> (Target, Option<FetchedPage>) {
+        let mut target = Target {
+            status: None,
+            redirects: vec![],
+            final_url: url.clone(),
+            error: None,
+            anchors: None,
+        };
+        loop {
+            let started = Instant::now();
+            let response = match session
+                .probe(method.clone(), url.clone(), self.options.timeout)
+                .await
+            {
+                Ok(response) => response,
+                Err(e) => {
+                    target.error = Some(self.failure(&e));
+                    return (target, None);
+                }
+            };
+            let status = response.status();
+            target.status = Some(status.as_u16());
+
+            let location = response
+                .headers()
+                .get(header::LOCATION)
+                .filter(|_| status.is_redirection())
+                .and_then(|v| v.to_str().ok())
+                .and_then(|location| url.join(location).ok());
+            if let Some(location) = location {
+                target.redirects.push(Redirect {
+                    url: url.to_string(),
+                    status: status.as_u16(),
+                });
+                let error = if target.redirects.iter().any(|r| r.url == location.as_str()) {
+                    Some(format!("Redirect loop at {}", location))
+                } else if target.redirects.len() > MAX_REDIRECTS {
+                    Some(format!("More than {} redirects", MAX_REDIRECTS))
+                } else {
+                    None
+                };
+                target.final_url = location.clone();
+                if let Some(error) = error {
+                    target.error = Some((LinkStatus::Error, error));
+                    return (target, None);
+                }
+                url = location;
+                continue;
+            }
+
+            if method != Method::GET {
+                return (target, None);
+            }
+            let max_bytes = session.max_body_bytes();
+            let page = FetchedPage::read(response, started, CacheStatus::Bypass, max_bytes).await;
+            return match page {
+                Ok(page) => {
+                    if page.response.kind == ContentKind::Html {
+                        target.anchors = Some(page_anchors(&page.html));
+                    }
+                    (target, Some(page))
+                }
+                Err(e) => {
+                    target.error = Some(self.failure(&e));
+                    (target, None)
+                }
+            };
+        }
+    }
+
+    fn failure(&self, error: &anyhow::Error) -> (LinkStatus, String) {
+        let timed_out = error
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(|e| e.is_timeout());
+        if timed_out {
+            let message = format!("No response within {:?}", self.options.timeout);
+            (LinkStatus::Timeout, message)
+        } else {
+            (LinkStatus::Error, error.to_string())
+        }
+    }
+
+    fn checked_link(&self, link: &Link, target: &Target) -> CheckedLink {
+        let missing_anchor = || {
+            let fragment = checked_fragment(&link.url)?;
+            let anchors = target.anchors.as_ref()?;
+            let decoded = percent_decode_str(fragment).decode_utf8_lossy();
+            Some(!anchors.contains(fragment) && !anchors.contains(decoded.as_ref()))
+        };
+        let (status, error) = match (&target.error, target.status) {
+            (Some((status, error)), _) => (*status, Some(error.clone())),
+            (None, Some(s)) if !(200..300).contains(&s) => (LinkStatus::Broken, None),
+            _ if self.options.check_anchors && missing_anchor() == Some(true) => (
+                LinkStatus::MissingAnchor,
+                Some(format!(
+                    "No element with id {:?}",
+                    link.url.fragment().unwrap_or_default()
+                )),
+            ),
+            _ if !target.redirects.is_empty() => (LinkStatus::Redirected, None),
+            _ => (LinkStatus::Ok, None),
+        };
+        CheckedLink {
+            url: link.url.to_string(),
+            text: link.text.clone(),
+            status,
+            http_status: target.status,
+            redirects: target.redirects.clone(),
+            final_url: (!target.redirects.is_empty()).then(|| target.final_url.to_string()),
+            error,
+        }
+    }
+}
+
+/// The fragment of `url` that should name an element of its target: not
+/// empty, not `#top`, and not a client-side route like `#/path` or `#!path`.
+fn checked_fragment(url: &Url) -> Option<&str> {
+    url.fragment().filter(|fragment| {
+        !fragment.is_empty()
+            && !fragment.eq_ignore_ascii_case("top")
+            && !fragment.starts_with(['/', '!'])
+    })
+}
+
+/// HTTP(S) links of every `a[href]` of the page at `page_url`, honouring
+/// `<base href>`, with their fragment and text.
+fn page_links(html: &str, page_url: &Url) -> Vec<Link> {
+    let Ok(selector) = Selector::parse("a[href]") else {
+        return vec![];
+    };
+    let document = Html::parse_document(html);
+    let Some(base) = document_base_url(&document, Some(page_url)) else {
+        return vec![];
+    };
+    let mut links = Vec::new();
+    let mut seen = HashSet::new();
+    for element in document.select(&selector) {
+        let Some(href) = element.value().attr("href") else {
+            continue;
+        };
+        let Ok(url) = base.join(href.trim()) else {
+            continue;
+        };
+        if !matches!(url.scheme(), "http" | "https") || !seen.insert(url.to_string()) {
+            continue;
+        }
+        let text = element
+            .text()
+            .collect::<String>()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        links.push(Link {
+            url,
+            text: Some(text).filter(|t| !t.is_empty()),
+        });
+    }
+    links
+}
+
+/// What a fragment can point to: `id`s, and the names of `a` elements.
+fn page_anchors(html: &str) -> HashSet<String> {
+    let Ok(selector) = Selector::parse("[id], a[name]") else {
+        return HashSet::new();
+    };
+    Html::parse_document(html)
+        .select(&selector)
+        .flat_map(|e| [e.value().attr("id"), e.value().attr("name")])
+        .flatten()
+        .map(|anchor| anchor.to_string())
+        .collect()
+}
+
+fn without_fragment(url: &Url) -> Url {
+    let mut url = url.clone();
+    url.set_fragment(None);
+    url
+}
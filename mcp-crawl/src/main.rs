@@ -1,11 +1,14 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
 use anyhow::Result;
 use async_mcp::transport::ServerStdioTransport;
 use clap::Parser;
-use mcp_crawl::build;
+use mcp_crawl::{build, CacheOptions, CrawlerConfig};
 
 #[derive(Parser)]
 #[command(name = "mcp-crawl")]
-#[command(about = "MCP server for web crawling and scraping using spider-rs")]
+#[command(about = "MCP server for web crawling and scraping ")]
 #[command(version)]
 struct Cli {
     /// Enable debug logging
@@ -16,21 +19,89 @@ struct Cli {
     #[arg(long, default_value = "mcp-crawl/0.1.0")]
     user_agent: String,
 
-    /// Default delay between requests in seconds
+    /// Minimum delay between requests to the same host in seconds
     #[arg(long, default_value = "1.0")]
     delay: f64,
 
     /// Maximum crawl depth
     #[arg(long, default_value = "2")]
-    max_depth: u16,
+    max_depth: usize,
 
     /// Enable subdomain crawling
     #[arg(long)]
     subdomains: bool,
 
-    /// Respect robots.txt
-    #[arg(long, default_value = "true")]
+    /// Respect robots.txt (pass `--respect-robots false` to ignore it)
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
     respect_robots: bool,
+
+    /// Maximum number of concurrent requests to the same host
+    #[arg(long, default_value = "2")]
+    per_host_concurrency: usize,
+
+    /// How many times a 429 or 5xx response is retried
+    #[arg(long, default_value = "3")]
+    max_retries: u32,
+
+    /// Seconds before the first retry, doubled for every further one
+    #[arg(long, default_value = "0.5")]
+    retry_backoff: f64,
+
+    /// Largest response body read, in bytes
+    #[arg(long, default_value = "33554432")]
+    max_body_bytes: usize,
+
+    /// Seconds a cached page is served before it is revalidated
+    #[arg(long, default_value = "300")]
+    cache_ttl: u64,
+
+    /// Maximum number of pages in the cache (0 disables caching)
+    #[arg(long, default_value = "256")]
+    cache_max_entries: usize,
+
+    /// Maximum total size of cached pages in bytes
+    #[arg(long, default_value = "67108864")]
+    cache_max_bytes: usize,
+
+    /// Directory where watched pages are kept between runs
+    #[arg(long)]
+    monitor_dir: Option<PathBuf>,
+
+    /// Directory where every request and response is recorded as WARC
+    #[arg(long)]
+    archive_dir: Option<PathBuf>,
+
+    /// Answer requests from a WARC file, or a directory of them, instead of
+    /// the network
+    #[arg(long, conflicts_with = "archive_dir")]
+    replay: Option<PathBuf>,
+}
+
+impl Cli {
+    fn crawler_config(&self) -> Result<CrawlerConfig> {
+        let retry_backoff = Duration::try_from_secs_f64(self.retry_backoff.max(0.0))
+            .map_err(|e| anyhow::anyhow!("Invalid retry backoff {}: {}", self.retry_backoff, e))?;
+        CrawlerConfig {
+            user_agent: self.user_agent.clone(),
+            max_depth: self.max_depth,
+            subdomains: self.subdomains,
+            respect_robots: self.respect_robots,
+            per_host_concurrency: self.per_host_concurrency,
+            max_retries: self.max_retries,
+            retry_backoff,
+            max_body_bytes: self.max_body_bytes,
+            cache: CacheOptions {
+                ttl: Duration::from_secs(self.cache_ttl),
+                max_entries: self.cache_max_entries,
+                max_bytes: self.cache_max_bytes,
+            },
+            monitor_dir: self.monitor_dir.clone(),
+            archive_dir: self.archive_dir.clone(),
+            replay: self.replay.clone(),
+            ..Default::default()
+        }
+        .with_delay_secs(self.delay)
+    }
 }
 
 #[tokio::main]
@@ -50,15 +121,34 @@ async fn main() -> Result<()> {
         .with_writer(std::io::stderr)
         .init();
 
-    tracing::info!("Starting MCP Spider server");
+    let config = cli.crawler_config()?;
+
+    tracing::info!("Starting MCP Crawl server");
     tracing::info!("Configuration:");
-    tracing::info!("  User Agent: {}", cli.user_agent);
-    tracing::info!("  Default Delay: {}s", cli.delay);
-    tracing::info!("  Max Depth: {}", cli.max_depth);
-    tracing::info!("  Subdomains: {}", cli.subdomains);
-    tracing::info!("  Respect Robots: {}", cli.respect_robots);
+    tracing::info!("  User Agent: {}", config.user_agent);
+    tracing::info!("  Default Delay: {:?}", config.delay);
+    tracing::info!("  Max Depth: {}", config.max_depth);
+    tracing::info!("  Subdomains: {}", config.subdomains);
+    tracing::info!("  Respect Robots: {}", config.respect_robots);
+    tracing::info!("  Per-host Concurrency: {}", config.per_host_concurrency);
+    tracing::info!(
+        "  Retries: {} (backoff {:?})",
+        config.max_retries,
+        config.retry_backoff
+    );
+    tracing::info!("  Max Body Size: {} bytes", config.max_body_bytes);
+    tracing::info!("  Cache: {:?}", config.cache);
+    if let Some(dir) = &config.monitor_dir {
+        tracing::info!("  Monitor Directory: {}", dir.display());
+    }
+    if let Some(dir) = &config.archive_dir {
+        tracing::info!("  Archive Directory: {}", dir.display());
+    }
+    if let Some(path) = &config.replay {
+        tracing::info!("  Replaying: {}", path.display());
+    }
 
-    let server = build(ServerStdioTransport)?;
+    let server = build(ServerStdioTransport, config)?;
     let server_handle = tokio::spawn(async move { server.listen().await });
 
     server_handle
@@ -0,0 +1,474 @@
+use ego_tree::NodeRef;
+use scraper::{ElementRef, Html, Node};
+use url::Url;
+
+/// How HTML is rendered as Markdown.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MarkdownOptions {
+    /// Keep images as `![alt](src)`; they are dropped otherwise.
+    pub include_images: bool,
+}
+
+/// Render a parsed document or fragment as Markdown.
+///
+/// Headings, paragraphs, lists, block quotes, code blocks, tables, links and
+/// emphasis are kept. Relative link and image URLs are resolved against
+/// `base_url`. Scripts, styles and form controls are dropped.
+pub fn to_markdown(document: &Html, base_url: Option<&Url>, options: &MarkdownOptions) -> String {
+    let renderer = Renderer { base_url, options };
+    renderer.blocks(document.tree.root(), "\n\n")
+}
+
+const SKIPPED: &[&str] = &[
+    "head", "script", "style", "noscript", "template", "svg", "canvas", "iframe", "object",
+    "embed", "button", "input", "select", "textarea", "option",
+];
+
+const BLOCKS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "body",
+    "dd",
+    "details",
+    "dialog",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hgroup",
+    "hr",
+    "html",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "summary",
+    "table",
+    "ul",
+];
+
+struct Renderer<'a> {
+    base_url: Option<&'a Url>,
+    options: &'a MarkdownOptions,
+}
+
+impl Renderer<'_> {
+    /// Render the children of `node` as blocks joined by `separator`. Runs
+    /// of inline content between blocks become paragraphs.
+    fn blocks(&self, node: NodeRef<'_, Node>, separator: &str) -> String {
+        let mut blocks: Vec<String> = Vec::new();
+        let mut inline = String::new();
+        for child in node.children() {
+            let block = match ElementRef::wrap(child) {
+                Some(element) if is_block(element) => self.block(element),
+                _ => {
+                    inline.push_str(&self.inline(child));
+                    continue;
+                }
+            };
+            push_block(&mut blocks, paragraph(&std::mem::take(&mut inline)));
+            push_block(&mut blocks, block);
+        }
+        push_block(&mut blocks, paragraph(&inline));
+        blocks.join(separator)
+    }
+
+    fn block(&self, element: ElementRef<'_>) -> String {
+        let name = element.value().name();
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let level = name[1..].parse().unwrap_or(1);
+                let text = finish_inline(&self.inline_children(*element)).replace("  \n", " ");
+                if text.is_empty() {
+                    String::new()
+                } else {
+                    format!("{} {}", "#".repeat(level), text)
+                }
+            }
+            "p" | "dd" | "figcaption" | "summary" => paragraph(&self.inline_children(*element)),
+            "dt" => {
+                let text = finish_inline(&self.inline_children(*element));
+                if text.is_empty() {
+                    text
+                } else {
+                    format!("**{}**", text)
+                }
+            }
+            "hr" => "---".to_string(),
+            "pre" => code_block(element),
+            "blockquote" => {
+                let inner = self.blocks(*element, "\n\n");
+                inner
+                    .lines()
+                    .map(|line| {
+                        if line.is_empty() {
+                            ">".to_string()
+                        } else {
+                            format!("> {}", line)
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            "ul" | "ol" => self.list(element),
+            "table" => self.table(element),
+            "li" => self.list_item(element, "- "),
+            _ => self.blocks(*element, "\n\n"),
+        }
+    }
+
+    fn list(&self, element: ElementRef<'_>) -> String {
+        let ordered = element.value().name() == "ol";
+        let mut number: usize = element
+            .value()
+            .attr("start")
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(1);
+        let mut items = Vec::new();
+        for child in element.children().filter_map(ElementRef::wrap) {
+            let marker = if ordered {
+                format!("{}. ", number)
+            } else {
+                "- ".to_string()
+            };
+            let item = match child.value().name() {
+                "li" => self.list_item(child, &marker),
+                // Lists nested directly in a list belong to the previous item
+                "ul" | "ol" => indent(&self.list(child), "  "),
+                _ => continue,
+            };
+            if child.value().name() == "li" {
+                number += 1;
+            }
+            if !item.is_empty() {
+                items.push(item);
+            }
+        }
+        items.join("\n")
+    }
+
+    fn list_item(&self, element: ElementRef<'_>, marker: &str) -> String {
+        let content = self.blocks(*element, "\n");
+        let padding = " ".repeat(marker.len());
+        let mut lines = content.lines();
+        let first = lines.next().unwrap_or_default();
+        let mut item = format!("{}{}", marker, first).trim_end().to_string();
+        for line in lines {
+            item.push('\n');
+            if !line.is_empty() {
+                item.push_str(&padding);
+                item.push_str(line);
+            }
+        }
+        item
+    }
+
+    fn table(&self, element: ElementRef<'_>) -> String {
+        let mut caption = None;
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        for child in element.children().filter_map(ElementRef::wrap) {
+            match child.value().name() {
+                "caption" => caption = Some(finish_inline(&self.inline_children(*child))),
+                "thead" | "tbody" | "tfoot" => {
+                    for row in child.children().filter_map(ElementRef::wrap) {
+                        if row.value().name() == "tr" {
+                            rows.push(self.table_row(row));
+                        }
+                    }
+                }
+                "tr" => rows.push(self.table_row(child)),
+                _ => {}
+            }
+        }
+        rows.retain(|row| !row.is_empty());
+        if rows.is_empty() {
+            return caption.unwrap_or_default();
+        }
+
+        // Markdown tables have exactly one header row: the first one
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let line = |row: &[String]| {
+            let cells: Vec<&str> = (0..columns)
+                .map(|i| row.get(i).map(String::as_str).unwrap_or(""))
+                .collect();
+            format!("| {} |", cells.join(" | "))
+        };
+
+        let mut lines = Vec::new();
+        if let Some(caption) = caption.filter(|c| !c.is_empty()) {
+            lines.push(format!("**{}**", caption));
+            lines.push(String::new());
+        }
+        lines.push(line(&rows[0]));
+        lines.push(format!("|{}", " --- |".repeat(columns)));
+        for row in &rows[1..] {
+            lines.push(line(row));
+        }
+        lines.join("\n")
+    }
+
+    fn table_row(&self, row: ElementRef<'_>) -> Vec<String> {
+        let mut cells = Vec::new();
+        for cell in row.children().filter_map(ElementRef::wrap) {
+            if !matches!(cell.value().name(), "td" | "th") {
+                continue;
+            }
+            let text = finish_inline(&self.inline_children(*cell))
+                .replace('\n', " ")
+                .replace('|', "\\|");
+            cells.push(text);
+            let span: usize = cell
+                .value()
+                .attr("colspan")
+                .and_then(|s| s.trim().parse().ok())
+                .unwrap_or(1);
+            cells.extend((1..span.min(1000)).map(|_| String::new()));
+        }
+        cells
+    }
+
+    fn inline_children(&self, node: NodeRef<'_, Node>) -> String {
+        node.children().map(|child| self.inline(child)).collect()
+    }
+
+    /// Render a node as inline Markdown. Whitespace is collapsed to single
+    /// spaces; `<br>` becomes a hard line break.
+    fn inline(&self, node: NodeRef<'_, Node>) -> String {
+        let element = match node.value() {
+            Node::Text(text) => return escape(&collapse_whitespace(text)),
+            Node::Element(element) => element,
+            _ => return String::new(),
+        };
+        let name = element.name();
+        if SKIPPED.contains(&name) {
+            return String::new();
+        }
+        match name {
+            "br" => "  \n".to_string(),
+            "img" => self.image(element),
+            "a" => {
+                let text = self.inline_children(node);
+                let href = element
+                    .attr("href")
+                    .map(str::trim)
+                    .filter(|href| !href.is_empty() && !href.starts_with("javascript:"));
+                match href {
+                    Some(href) => {
+                        let url = self.resolve(href);
+                        if text.trim().is_empty() {
+                            format!("<{}>", url)
+                        } else {
+                            wrap_inline(&text, "[", &format!("]({})", url))
+                        }
+                    }
+                    None => text,
+                }
+            }
+            "strong" | "b" => wrap_inline(&self.inline_children(node), "**", "**"),
+            "em" | "i" | "cite" => wrap_inline(&self.inline_children(node), "*", "*"),
+            "del" | "s" | "strike" => wrap_inline(&self.inline_children(node), "~~", "~~"),
+            "code" | "kbd" | "samp" | "tt" => {
+                let text: String = ElementRef::wrap(node)
+                    .map(|e| e.text().collect())
+                    .unwrap_or_default();
+                inline_code(&collapse_whitespace(&text))
+            }
+            // Blocks nested in inline content are rendered as text on their own line
+            _ if BLOCKS.contains(&name) => {
+                format!("  \n{}  \n", self.inline_children(node))
+            }
+            _ => self.inline_children(node),
+        }
+    }
+
+    fn image(&self, element: &scraper::node::Element) -> String {
+        if !self.options.include_images {
+            return String::new();
+        }
+        let src = element
+            .attr("src")
+            .map(str::trim)
+            .filter(|src| !src.is_empty() && !src.starts_with("data:"));
+        match src {
+            Some(src) => {
+                let alt = escape(&collapse_whitespace(element.attr("alt").unwrap_or("")));
+                format!("![{}]({})", alt.trim(), self.resolve(src))
+            }
+            None => String::new(),
+        }
+    }
+
+    fn resolve(&self, href: &str) -> String {
+        let url = match self.base_url {
+            Some(base) => base.join(href).map(String::from),
+            None => Url::parse(href).map(String::from),
+        }
+        .unwrap_or_else(|_| href.to_string());
+        // Keep the destination on one line and balanced for Markdown
+        url.replace(' ', "%20")
+            .replace('(', "%28")
+            .replace(')', "%29")
+    }
+}
+
+fn is_block(element: ElementRef<'_>) -> bool {
+    let name = element.value().name();
+    BLOCKS.contains(&name) || matches!(name, "thead" | "tbody" | "tfoot" | "tr" | "td" | "th")
+}
+
+fn push_block(blocks: &mut Vec<String>, block: String) {
+    if !block.trim().is_empty() {
+        blocks.push(block);
+    }
+}
+
+/// Tidy a run of inline Markdown: trim every line and drop empty ones,
+/// keeping hard breaks between the remaining lines.
+fn finish_inline(inline: &str) -> String {
+    inline
+        .split("  \n")
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("  \n")
+}
+
+/// A paragraph of inline Markdown, escaped so it cannot read as a heading.
+fn paragraph(inline: &str) -> String {
+    let text = finish_inline(inline);
+    if text.starts_with('#') {
+        format!("\\{}", text)
+    } else {
+        text
+    }
+}
+
+/// Put `open`/`close` around the text, keeping surrounding spaces outside
+/// so `**bold** text` does not become `** bold**text`.
+fn wrap_inline(text: &str, open: &str, close: &str) -> String {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return text.to_string();
+    }
+    let leading = if text.starts_with(char::is_whitespace) {
+        " "
+    } else {
+        ""
+    };
+    let trailing = if text.ends_with(char::is_whitespace) {
+        " "
+    } else {
+        ""
+    };
+    format!("{}{}{}{}{}", leading, open, trimmed, close, trailing)
+}
+
+fn inline_code(text: &str) -> String {
+    if text.trim().is_empty() {
+        return text.to_string();
+    }
+    let ticks = longest_run(text, '`') + 1;
+    let fence = "`".repeat(ticks);
+    let pad = if text.starts_with('`') || text.ends_with('`') {
+        " "
+    } else {
+        ""
+    };
+    format!("{}{}{}{}{}", fence, pad, text, pad, fence)
+}
+
+fn code_block(element: ElementRef<'_>) -> String {
+    let code = element.text().collect::<String>();
+    let code = code.strip_prefix('\n').unwrap_or(&code).trim_end();
+    let language = std::iter::once(element)
+        .chain(
+            element
+                .children()
+                .filter_map(ElementRef::wrap)
+                .filter(|child| child.value().name() == "code"),
+        )
+        .filter_map(|e| e.value().attr("class"))
+        .flat_map(str::split_whitespace)
+        .find_map(|class| {
+            class
+                .strip_prefix("language-")
+                .or_else(|| class.strip_prefix("lang-"))
+        })
+        .unwrap_or("");
+    let fence = "`".repeat(longest_run(code, '`').max(2) + 1);
+    format!("{}{}\n{}\n{}", fence, language, code, fence)
+}
+
+fn longest_run(text: &str, c: char) -> usize {
+    let mut longest = 0;
+    let mut current = 0;
+    for ch in text.chars() {
+        if ch == c {
+            current += 1;
+            longest = longest.max(current);
+        } else {
+            current = 0;
+        }
+    }
+    longest
+}
+
+/// Collapse whitespace runs to one space, keeping a leading or trailing
+/// space so adjacent inline nodes stay separated.
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut in_space = false;
+    for ch in text.chars() {
+        if ch.is_whitespace() {
+            if !in_space {
+                collapsed.push(' ');
+            }
+            in_space = true;
+        } else {
+            collapsed.push(ch);
+            in_space = false;
+        }
+    }
+    collapsed
+}
+
+/// Escape characters that would otherwise start Markdown formatting.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        if matches!(ch, '\\' | '*' | '_' | '`' | '[' | ']') {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+fn indent(text: &str, padding: &str) -> String {
+    text.lines()
+        .map(|line| {
+            if line.is_empty() {
+                String::new()
+            } else {
+                format!("{}{}", padding, line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
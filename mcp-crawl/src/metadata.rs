@@ -0,0 +1,211 @@
+use scraper::{Html, Selector};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Metadata declared in a page's `<head>`. Every field is always present,
+/// as `null` or empty when the page does not declare it, and URLs are
+/// resolved against the page.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PageMetadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub keywords: Option<String>,
+    pub author: Option<String>,
+    pub published: Option<String>,
+    pub modified: Option<String>,
+    /// `<html lang>`, or the `Content-Language` meta tag.
+    pub language: Option<String>,
+    pub robots: Option<String>,
+    pub viewport: Option<String>,
+    pub canonical: Option<String>,
+    /// The `icon` or `shortcut icon` link.
+    pub favicon: Option<String>,
+    /// Every icon link, favicons and Apple touch icons alike.
+    pub icons: Vec<Icon>,
+    /// Translations of the page, from `<link rel="alternate" hreflang>`.
+    pub alternates: Vec<Alternate>,
+    /// RSS, Atom and JSON feeds, from `<link rel="alternate" type>`.
+    pub feeds: Vec<FeedLink>,
+    /// `og:*` properties without the prefix, e.g. `title` or `image:width`.
+    pub open_graph: BTreeMap<String, String>,
+    /// `twitter:*` properties without the prefix, e.g. `card` or `site`.
+    pub twitter: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Icon {
+    /// e.g. `icon` or `apple-touch-icon`.
+    pub rel: String,
+    pub href: String,
+    pub sizes: Option<String>,
+    #[serde(rename = "type")]
+    pub media_type: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Alternate {
+    pub hreflang: String,
+    pub href: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FeedLink {
+    #[serde(rename = "type")]
+    pub media_type: String,
+    pub title: Option<String>,
+    pub href: String,
+}
+
+const FEED_TYPES: &[&str] = &[
+    "application/rss+xml",
+    "application/atom+xml",
+    "application/feed+json",
+    "application/json",
+];
+
+/// Read the metadata of `document`, resolving URLs with `resolve`.
+pub fn extract(document: &Html, resolve: &dyn Fn(&str) -> String) -> PageMetadata {
+    let meta = meta_tags(document);
+    let first = |names: &[&str]| names.iter().find_map(|name| meta.get(*name).cloned());
+    let properties = |prefix: &str| -> BTreeMap<String, String> {
+        meta.iter()
+            .filter_map(|(name, content)| {
+                let property = name.strip_prefix(prefix)?;
+                let content = if is_url_property(property) {
+                    resolve(content)
+                } else {
+                    content.clone()
+                };
+                Some((property.to_string(), content))
+            })
+            .collect()
+    };
+
+    let mut metadata = PageMetadata {
+        title: select_first(document, "title")
+            .map(|title| title.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|title| !title.is_empty()),
+        description: first(&["description"]),
+        keywords: first(&["keywords"]),
+        author: first(&["author", "article:author", "dc.creator"]),
+        published: first(&[
+            "article:published_time",
+            "datepublished",
+            "date",
+            "pubdate",
+            "dc.date",
+            "dcterms.created",
+        ]),
+        modified: first(&[
+            "article:modified_time",
+            "og:updated_time",
+            "datemodified",
+            "last-modified",
+            "dcterms.modified",
+        ]),
+        language: Selector::parse("html[lang]")
+            .ok()
+            .and_then(|s| document.select(&s).next())
+            .and_then(|html| html.value().attr("lang"))
+            .map(|lang| lang.trim().to_string())
+            .filter(|lang| !lang.is_empty())
+            .or_else(|| first(&["content-language"])),
+        robots: first(&["robots"]),
+        viewport: first(&["viewport"]),
+        open_graph: properties("og:"),
+        twitter: properties("twitter:"),
+        ..Default::default()
+    };
+
+    let Ok(links) = Selector::parse("link[rel][href]") else {
+        return metadata;
+    };
+    for link in document.select(&links) {
+        let attr = |name: &str| {
+            link.value()
+                .attr(name)
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        let rel = attr("rel").unwrap_or_default().to_ascii_lowercase();
+        let rels: Vec<&str> = rel.split_whitespace().collect();
+        let href = resolve(link.value().attr("href").unwrap_or_default());
+
+        if rels.contains(&"canonical") && metadata.canonical.is_none() {
+            metadata.canonical = Some(href.clone());
+        }
+        if rels
+            .iter()
+            .any(|rel| rel.ends_with("icon") || rel.starts_with("apple-touch-icon"))
+        {
+            if rels.contains(&"icon") && metadata.favicon.is_none() {
+                metadata.favicon = Some(href.clone());
+            }
+            metadata.icons.push(Icon {
+                rel: rel.clone(),
+                href: href.clone(),
+                sizes: attr("sizes"),
+                media_type: attr("type"),
+            });
+        }
+        if rels.contains(&"alternate") {
+            let media_type = attr("type").map(|t| t.to_ascii_lowercase());
+            if let Some(hreflang) = attr("hreflang") {
+                metadata.alternates.push(Alternate {
+                    hreflang,
+                    href: href.clone(),
+                });
+            } else if let Some(media_type) = media_type.filter(|t| FEED_TYPES.contains(&t.as_str()))
+            {
+                metadata.feeds.push(FeedLink {
+                    media_type,
+                    title: attr("title"),
+                    href,
+                });
+            }
+        }
+    }
+
+    metadata
+}
+
+/// Content of every `<meta>` tag keyed by its lower-cased `name`,
+/// `property`, `itemprop` or `http-equiv`. The first tag of a name wins.
+fn meta_tags(document: &Html) -> BTreeMap<String, String> {
+    let mut tags = BTreeMap::new();
+    let Ok(selector) = Selector::parse("meta[content]") else {
+        return tags;
+    };
+    for meta in document.select(&selector) {
+        let element = meta.value();
+        let content = element.attr("content").unwrap_or_default().trim();
+        if content.is_empty() {
+            continue;
+        }
+        for key in ["name", "property", "itemprop", "http-equiv"] {
+            if let Some(name) = element.attr(key) {
+                tags.entry(name.trim().to_ascii_lowercase())
+                    .or_insert_with(|| content.to_string());
+            }
+        }
+    }
+    tags
+}
+
+fn select_first(document: &Html, selector: &str) -> Option<String> {
+    let selector = Selector::parse(selector).ok()?;
+    document
+        .select(&selector)
+        .next()
+        .map(|element| element.text().collect())
+}
+
+/// Whether an Open Graph or Twitter property, without its prefix, holds a
+/// URL.
+fn is_url_property(property: &str) -> bool {
+    matches!(
+        property,
+        "url" | "image" | "image:src" | "video" | "audio" | "player" | "player:stream"
+    ) || property.ends_with(":url")
+        || property.ends_with(":secure_url")
+}
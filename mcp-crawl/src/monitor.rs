@@ -0,0 +1,537 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use ego_tree::NodeId;
+use regex::Regex;
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use url::Url;
+
+use crate::fetch::ContentKind;
+use crate::markdown::{self, MarkdownOptions};
+use crate::scraper_tools::{document_base_url, ScrapingSession};
+
+/// Extension of the files monitors are kept in, one per monitor, inside
+/// the monitor directory.
+const MONITOR_EXTENSION: &str = "json";
+
+/// Elements readers never see.
+const HIDDEN: &str = "script, style, noscript, template";
+
+/// Ad slots of the common ad networks and the usual names sites give them.
+const ADS: &str = "ins.adsbygoogle, [id^='google_ads'], [id^='div-gpt-ad'], [id^='ad-'], \
+    [id^='ad_'], [class~='ad'], [class~='ads'], [class*='advert'], [class*='ad-slot'], \
+    [class*='ad-container'], [class*='ad-banner'], [class*='sponsored'], [data-ad-slot], \
+    [data-ad-client], [data-ad], iframe[src*='doubleclick']";
+
+/// Elements compared one by one to tell what was added or removed. Only
+/// the innermost of nested ones count.
+const BLOCKS: &[&str] = &[
+    "article",
+    "blockquote",
+    "caption",
+    "dd",
+    "div",
+    "dt",
+    "figcaption",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "li",
+    "p",
+    "pre",
+    "section",
+    "tr",
+];
+
+/// Dates, times and relative timestamps, masked by the noise filter. Day,
+/// month, hour and minute numbers must be in range, and dotted dates need a
+/// four-digit year, so version numbers like `2.10.15` are not masked.
+const TIMESTAMPS: &[&str] = &[
+    r"(?i)\b\d{4}-\d{2}-\d{2}(?:[T ]\d{1,2}:\d{2}(?::\d{2}(?:\.\d+)?)?(?:Z|[+-]\d{2}:?\d{2})?)?\b",
+    r"\b(?:0?[1-9]|[12]\d|3[01])\.(?:0?[1-9]|1[0-2])\.\d{4}\b",
+    r"\b(?:0?[1-9]|[12]\d|3[01])/(?:0?[1-9]|[12]\d|3[01])/(?:\d{4}|\d{2})\b",
+    r"(?i)\b(?:jan|feb|mar|apr|may|jun|jul|aug|sep|oct|nov|dec)[a-z]*\.? \d{1,2}(?:st|nd|rd|th)?,? \d{4}\b",
+    r"(?i)\b\d{1,2}(?:st|nd|rd|th)? (?:jan|feb|mar|apr|may|jun|jul|aug|sep|oct|nov|dec)[a-z]*\.?,? \d{4}\b",
+    r"(?i)\b(?:[01]?\d|2[0-3]):[0-5]\d(?::[0-5]\d)?(?:\s?[ap]\.?m\b\.?|\b)",
+    r"(?i)\b(?:\d+|an?) (?:second|minute|hour|day|week|month|year)s? ago\b",
+    r"(?i)\bjust now\b",
+];
+
+/// Replaces what [`TIMESTAMPS`] matches.
+const TIMESTAMP_MASK: &str = "[timestamp]";
+
+/// What is left out when two snapshots are compared.
+#[derive(Debug, Clone)]
+pub struct NoiseFilter {
+    /// Mask dates, times and relative timestamps such as "5 minutes ago".
+    pub ignore_timestamps: bool,
+    /// Drop ad slots.
+    pub ignore_ads: bool,
+    /// Further elements to drop, as CSS selectors.
+    pub ignore_selectors: Vec<String>,
+    /// Text to remove, e.g. a visitor counter.
+    pub ignore_patterns: Vec<Regex>,
+}
+
+impl Default for NoiseFilter {
+    fn default() -> Self {
+        Self {
+            ignore_timestamps: true,
+            ignore_ads: true,
+            ignore_selectors: vec![],
+            ignore_patterns: vec![],
+        }
+    }
+}
+
+impl NoiseFilter {
+    /// Remove hidden elements, and ads and ignored elements as configured.
+    fn strip(&self, fragment: &mut Html) -> Result<()> {
+        let mut selectors = vec![HIDDEN];
+        if self.ignore_ads {
+            selectors.push(ADS);
+        }
+        selectors.extend(self.ignore_selectors.iter().map(String::as_str));
+
+        let mut ids: Vec<NodeId> = Vec::new();
+        for selector in selectors {
+            let selector = Selector::parse(selector)
+                .map_err(|e| anyhow::anyhow!("Invalid CSS selector: {}", e))?;
+            ids.extend(fragment.select(&selector).map(|e| e.id()));
+        }
+        for id in ids {
+            if let Some(mut node) = fragment.tree.get_mut(id) {
+                node.detach();
+            }
+        }
+        Ok(())
+    }
+
+    /// `text` with timestamps masked and ignored patterns removed.
+    fn clean(&self, text: &str, timestamps: &[Regex]) -> String {
+        let mut text = text.to_string();
+        for pattern in timestamps {
+            text = pattern.replace_all(&text, TIMESTAMP_MASK).into_owned();
+        }
+        for pattern in &self.ignore_patterns {
+            text = pattern.replace_all(&text, "").into_owned();
+        }
+        text
+    }
+
+    fn timestamp_patterns(&self) -> Vec<Regex> {
+        if !self.ignore_timestamps {
+            return vec![];
+        }
+        TIMESTAMPS
+            .iter()
+            .filter_map(|pattern| Regex::new(pattern).ok())
+            .collect()
+    }
+}
+
+/// What to watch on a page.
+#[derive(Debug, Clone, Default)]
+pub struct MonitorOptions {
+    /// Only watch the elements matching this CSS selector; the whole body
+    /// otherwise.
+    pub selector: Option<String>,
+    pub filter: NoiseFilter,
+}
+
+/// A watched page. Monitors are identified by their URL and selector.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Monitor {
+    pub id: String,
+    pub url: String,
+    pub selector: Option<String>,
+    pub created: DateTime<Utc>,
+    pub last_checked: DateTime<Utc>,
+    /// When a check last found a change, `None` until one does.
+    pub last_changed: Option<DateTime<Utc>>,
+    /// Checks after the first snapshot.
+    pub checks: u64,
+    /// Checks that found a change.
+    pub changes: u64,
+}
+
+/// The watched part of a page as it was at a check.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Snapshot {
+    base_url: Option<Url>,
+    /// Outer HTML of every watched element. The noise filter is applied
+    /// when comparing, so changing it does not read as a page change.
+    html: Vec<String>,
+}
+
+/// A monitor together with its last snapshot, as stored on disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredMonitor {
+    #[serde(flatten)]
+    monitor: Monitor,
+    snapshot: Snapshot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    /// The page was not watched yet; its first snapshot was taken.
+    Created,
+    Unchanged,
+    Changed,
+}
+
+/// Outcome of checking a monitor.
+#[derive(Debug, Clone, Serialize)]
+pub struct MonitorCheck {
+    pub status: CheckStatus,
+    #[serde(flatten)]
+    pub monitor: Monitor,
+    /// When the snapshot compared against was taken.
+    pub previous_check: Option<DateTime<Utc>>,
+    /// Unified diff of the page text, as Markdown, since the previous
+    /// snapshot. Empty unless the page changed.
+    pub diff: String,
+    /// Text of the elements that appeared since the previous snapshot.
+    pub added: Vec<String>,
+    /// Text of the elements that disappeared since the previous snapshot.
+    pub removed: Vec<String>,
+}
+
+/// The comparable form of a snapshot.
+struct View {
+    text: String,
+    elements: Vec<String>,
+}
+
+impl Snapshot {
+    fn take(html: &str, page_url: &Url, selector: Option<&str>) -> Result<Self> {
+        let document = Html::parse_document(html);
+        let scope = Selector::parse(selector.unwrap_or("body"))
+            .map_err(|e| anyhow::anyhow!("Invalid CSS selector: {}", e))?;
+        Ok(Self {
+            base_url: document_base_url(&document, Some(page_url)),
+            html: document.select(&scope).map(|e| e.html()).collect(),
+        })
+    }
+
+    fn view(&self, filter: &NoiseFilter) -> Result<View> {
+        let timestamps = filter.timestamp_patterns();
+        let mut text = Vec::new();
+        let mut elements = Vec::new();
+        for html in &self.html {
+            let mut fragment = Html::parse_fragment(html);
+            filter.strip(&mut fragment)?;
+            let rendered = markdown::to_markdown(
+                &fragment,
+                self.base_url.as_ref(),
+                &MarkdownOptions::default(),
+            );
+            text.extend(
+                rendered
+                    .lines()
+                    .map(|line| filter.clean(line, &timestamps).trim_end().to_string()),
+            );
+            text.push(String::new());
+
+            let root = fragment.root_element();
+            let mut blocks = Vec::new();
+            innermost_blocks(root, &mut blocks);
+            if blocks.is_empty() {
+                blocks.push(root);
+            }
+            elements.extend(
+                blocks
+                    .into_iter()
+                    .map(|block| collapse(&filter.clean(&block_text(block), &timestamps)))
+                    .filter(|text| !text.is_empty()),
+            );
+        }
+        Ok(View {
+            text: text.join("\n"),
+            elements,
+        })
+    }
+}
+
+/// The innermost [`BLOCKS`] below `element`.
+fn innermost_blocks<'a>(element: ElementRef<'a>, found: &mut Vec<ElementRef<'a>>) -> bool {
+    let mut has_blocks = false;
+    for child in element.children().filter_map(ElementRef::wrap) {
+        has_blocks |= innermost_blocks(child, found);
+    }
+    if !has_blocks && BLOCKS.contains(&element.value().name()) {
+        found.push(element);
+        return true;
+    }
+    has_blocks
+}
+
+/// Text of a block, with table cells kept apart.
+fn block_text(element: ElementRef) -> String {
+    if element.value().name() != "tr" {
+        return element.text().collect();
+    }
+    element
+        .children()
+        .filter_map(ElementRef::wrap)
+        .map(|cell| collapse(&cell.text().collect::<String>()))
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+fn collapse(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Elements of `new` missing from `old`, counting repeats.
+fn difference(old: &[String], new: &[String]) -> Vec<String> {
+    let mut remaining: HashMap<&str, usize> = HashMap::new();
+    for element in old {
+        *remaining.entry(element).or_default() += 1;
+    }
+    new.iter()
+        .filter(|element| match remaining.get_mut(element.as_str()) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                false
+            }
+            _ => true,
+        })
+        .cloned()
+        .collect()
+}
+
+/// Watched pages with their last snapshot, kept in a directory so they
+/// outlive the server, or in memory without one.
+pub struct MonitorStore {
+    dir: Option<PathBuf>,
+    /// Loaded from `dir` on first use.
+    monitors: Mutex<Option<BTreeMap<String, StoredMonitor>>>,
+}
+
+impl MonitorStore {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        Self {
+            dir,
+            monitors: Mutex::new(None),
+        }
+    }
+
+    /// Snapshot `url` and compare it with the previous snapshot of the same
+    /// URL and selector, or start watching it when there is none.
+    pub async fn check(
+        &self,
+        session: &mut ScrapingSession,
+        url: &Url,
+        options: &MonitorOptions,
+    ) -> Result<MonitorCheck> {
+        let page = session.fetch(url.as_str()).await?.error_for_status()?;
+        if page.response.kind != ContentKind::Html {
+            anyhow::bail!(
+                "Not an HTML page: {} content",
+                page.response.content_type.as_deref().unwrap_or("unknown")
+            );
+        }
+        let snapshot = Snapshot::take(&page.html, &page.url, options.selector.as_deref())?;
+        let current = snapshot.view(&options.filter)?;
+        let now = Utc::now();
+
+        self.update(|monitors| {
+            let existing = monitors.values().find(|stored| {
+                stored.monitor.url == url.as_str() && stored.monitor.selector == options.selector
+            });
+            let Some(stored) = existing else {
+                if snapshot.html.is_empty() {
+                    anyhow::bail!(
+                        "No element matches {} on {}",
+                        options.selector.as_deref().unwrap_or("body"),
+                        url
+                    );
+                }
+                let monitor = Monitor {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    url: url.to_string(),
+                    selector: options.selector.clone(),
+                    created: now,
+                    last_checked: now,
+                    last_changed: None,
+                    checks: 0,
+                    changes: 0,
+                };
+                let stored = StoredMonitor {
+                    monitor: monitor.clone(),
+                    snapshot,
+                };
+                self.save(&stored)?;
+                monitors.insert(monitor.id.clone(), stored);
+                return Ok(MonitorCheck {
+                    status: CheckStatus::Created,
+                    monitor,
+                    previous_check: None,
+                    diff: String::new(),
+                    added: vec![],
+                    removed: vec![],
+                });
+            };
+
+            let previous = stored.snapshot.view(&options.filter)?;
+            let added = difference(&previous.elements, &current.elements);
+            let removed = difference(&current.elements, &previous.elements);
+            let changed = previous.text != current.text || !added.is_empty() || !removed.is_empty();
+            let diff = if changed {
+                TextDiff::from_lines(&previous.text, &current.text)
+                    .unified_diff()
+                    .header("previous", "current")
+                    .to_string()
+            } else {
+                String::new()
+            };
+
+            let mut monitor = stored.monitor.clone();
+            let previous_check = monitor.last_checked;
+            monitor.last_checked = now;
+            monitor.checks += 1;
+            if changed {
+                monitor.last_changed = Some(now);
+                monitor.changes += 1;
+            }
+            let stored = StoredMonitor {
+                monitor: monitor.clone(),
+                snapshot,
+            };
+            self.save(&stored)?;
+            monitors.insert(monitor.id.clone(), stored);
+
+            Ok(MonitorCheck {
+                status: if changed {
+                    CheckStatus::Changed
+                } else {
+                    CheckStatus::Unchanged
+                },
+                monitor,
+                previous_check: Some(previous_check),
+                diff,
+                added,
+                removed,
+            })
+        })
+    }
+
+    /// Every monitor, ordered by URL and selector.
+    pub fn list(&self) -> Result<Vec<Monitor>> {
+        let mut monitors = self.read(|monitors| {
+            monitors
+                .values()
+                .map(|stored| stored.monitor.clone())
+                .collect::<Vec<_>>()
+        })?;
+        monitors.sort_by(|a, b| (&a.url, &a.selector).cmp(&(&b.url, &b.selector)));
+        Ok(monitors)
+    }
+
+    /// Stop watching the monitor `id`.
+    pub fn remove(&self, id: &str) -> Result<bool> {
+        self.update(|monitors| {
+            if !monitors.contains_key(id) {
+                return Ok(false);
+            }
+            self.delete(id)?;
+            monitors.remove(id);
+            Ok(true)
+        })
+    }
+
+    fn read<R>(&self, f: impl FnOnce(&BTreeMap<String, StoredMonitor>) -> R) -> Result<R> {
+        let mut monitors = self.monitors.lock().unwrap();
+        Ok(f(self.loaded(&mut monitors)?))
+    }
+
+    /// Run `f` on the monitors. `f` writes the monitor it changes to disk
+    /// before changing it in memory, so a failed write leaves both as they
+    /// were.
+    fn update<R>(
+        &self,
+        f: impl FnOnce(&mut BTreeMap<String, StoredMonitor>) -> Result<R>,
+    ) -> Result<R> {
+        let mut monitors = self.monitors.lock().unwrap();
+        f(self.loaded(&mut monitors)?)
+    }
+
+    fn loaded<'a>(
+        &self,
+        monitors: &'a mut Option<BTreeMap<String, StoredMonitor>>,
+    ) -> Result<&'a mut BTreeMap<String, StoredMonitor>> {
+        if monitors.is_none() {
+            *monitors = Some(self.load()?);
+        }
+        Ok(monitors.get_or_insert_with(BTreeMap::new))
+    }
+
+    fn load(&self) -> Result<BTreeMap<String, StoredMonitor>> {
+        let mut monitors = BTreeMap::new();
+        let Some(dir) = &self.dir else {
+            return Ok(monitors);
+        };
+        if !dir.exists() {
+            return Ok(monitors);
+        }
+        let entries =
+            std::fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(MONITOR_EXTENSION) {
+                continue;
+            }
+            let data = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let stored: StoredMonitor = serde_json::from_str(&data)
+                .with_context(|| format!("Invalid monitor file {}", path.display()))?;
+            monitors.insert(stored.monitor.id.clone(), stored);
+        }
+        Ok(monitors)
+    }
+
+    /// File the monitor `id` is kept in, if the store has a directory.
+    fn path(&self, id: &str) -> Option<PathBuf> {
+        let dir = self.dir.as_ref()?;
+        Some(dir.join(format!("{}.{}", id, MONITOR_EXTENSION)))
+    }
+
+    /// Write one monitor to a temporary file first so an interrupted save
+    /// leaves its previous file intact.
+    fn save(&self, stored: &StoredMonitor) -> Result<()> {
+        let Some(path) = self.path(&stored.monitor.id) else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let temp = path.with_extension("json.tmp");
+        std::fs::write(&temp, serde_json::to_vec_pretty(stored)?)
+            .with_context(|| format!("Failed to write {}", temp.display()))?;
+        std::fs::rename(&temp, &path)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    fn delete(&self, id: &str) -> Result<()> {
+        let Some(path) = self.path(id) else {
+            return Ok(());
+        };
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("Failed to delete {}", path.display()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashSet;
+use url::Url;
+
+use crate::fetch::ContentKind;
+use crate::schema::ExtractionSchema;
+use crate::scraper_tools::{ElementExtractor, FetchedPage, ScrapingSession};
+
+/// Placeholder for the page number in a URL template.
+const PAGE_PLACEHOLDER: &str = "{page}";
+
+/// How to get from one page of a listing to the next.
+#[derive(Debug, Clone)]
+pub enum NextPage {
+    /// The `href` of the first element matching this CSS selector.
+    Selector(String),
+    /// A `<link rel="next">` or `<a rel="next">`.
+    RelNext,
+    /// A URL with `{page}` replaced by consecutive page numbers from
+    /// `start`. The walk ends at the first page without new records, as
+    /// sites often repeat their last page past the end.
+    Template { template: String, start: u64 },
+}
+
+/// What to extract from every page.
+#[derive(Debug, Clone)]
+pub enum PageExtraction {
+    /// Elements matching a CSS selector, as `select_elements` returns them.
+    Selector(String),
+    Schema(ExtractionSchema),
+}
+
+impl PageExtraction {
+    fn extract(&self, extractor: &ElementExtractor) -> Result<Vec<Value>> {
+        match self {
+            PageExtraction::Selector(selector) => extractor.select_elements(selector),
+            PageExtraction::Schema(schema) => Ok(extractor.extract_schema(schema)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PaginationOptions {
+    pub next: NextPage,
+    pub extract: PageExtraction,
+    pub max_pages: usize,
+    /// Record field that identifies a record, e.g. `url`. Records without
+    /// it, or without this option, are compared whole.
+    pub dedupe_by: Option<String>,
+}
+
+/// One page visited while following a listing.
+#[derive(Debug, Clone, Serialize)]
+pub struct PaginatedPage {
+    /// 1-based position of the page in the walk.
+    pub page: usize,
+    pub url: String,
+    pub status: Option<u16>,
+    /// Records extracted from the page, duplicates included.
+    pub records: usize,
+    /// Records already seen on an earlier page.
+    pub duplicates: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A record together with the page it was first seen on.
+#[derive(Debug, Clone, Serialize)]
+pub struct PaginatedRecord {
+    pub page: usize,
+    pub url: String,
+    pub data: Value,
+}
+
+/// Why a walk ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    /// The last page had no next-page link.
+    NoNextPage,
+    /// A templated page had no records, or only records already seen.
+    EmptyPage,
+    /// The next-page link pointed to a page already visited.
+    Cycle,
+    MaxPages,
+    /// A page could not be fetched or was not HTML.
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PaginationReport {
+    pub pages: Vec<PaginatedPage>,
+    pub records: Vec<PaginatedRecord>,
+    pub total_records: usize,
+    pub duplicates: usize,
+    pub stopped: StopReason,
+}
+
+/// Walks the pages of a listing, extracting records from each.
+pub struct Paginator {
+    options: PaginationOptions,
+}
+
+impl Paginator {
+    pub fn new(options: PaginationOptions) -> Self {
+        Self { options }
+    }
+
+    /// URL of the first page: `url`, or the template's first page.
+    pub fn first_url(&self, url: Option<&Url>) -> Result<Url> {
+        match (&self.options.next, url) {
+            (NextPage::Template { template, start }, _) => page_url(template, *start),
+            (_, Some(url)) => Ok(url.clone()),
+            (_, None) => anyhow::bail!("url is required unless a url_template is given"),
+        }
+    }
+
+    pub async fn run(&self, session: &mut ScrapingSession, start: Url) -> Result<PaginationReport> {
+        let mut url = start;
+        let mut visited = HashSet::new();
+        let mut seen = HashSet::new();
+        let mut pages: Vec<PaginatedPage> = Vec::new();
+        let mut records = Vec::new();
+        let mut duplicates = 0;
+
+        let stopped = loop {
+            if pages.len() >= self.options.max_pages {
+                break StopReason::MaxPages;
+            }
+            visited.insert(url.to_string());
+            let number = pages.len() + 1;
+            let mut page = PaginatedPage {
+                page: number,
+                url: url.to_string(),
+                status: None,
+                records: 0,
+                duplicates: 0,
+                error: None,
+            };
+
+            let fetched = session.fetch(url.as_str()).await;
+            if let Ok(fetched) = &fetched {
+                page.status = Some(fetched.response.status);
+                page.url = fetched.url.to_string();
+                visited.insert(page.url.clone());
+            }
+            let (found, next) = match fetched.and_then(|f| self.read(f, number)) {
+                Ok(read) => read,
+                Err(e) => {
+                    page.error = Some(e.to_string());
+                    pages.push(page);
+                    break StopReason::Error;
+                }
+            };
+
+            page.records = found.len();
+            for data in found {
+                if seen.insert(self.dedupe_key(&data)) {
+                    records.push(PaginatedRecord {
+                        page: number,
+                        url: page.url.clone(),
+                        data,
+                    });
+                } else {
+                    page.duplicates += 1;
+                }
+            }
+            duplicates += page.duplicates;
+            let empty = page.records == page.duplicates;
+            pages.push(page);
+
+            match next {
+                _ if empty && matches!(self.options.next, NextPage::Template { .. }) => {
+                    break StopReason::EmptyPage
+                }
+                None => break StopReason::NoNextPage,
+                Some(next) if visited.contains(next.as_str()) => break StopReason::Cycle,
+                Some(next) => url = next,
+            }
+        };
+
+        Ok(PaginationReport {
+            pages,
+            total_records: records.len(),
+            records,
+            duplicates,
+            stopped,
+        })
+    }
+
+    /// Records of the `number`th page and the URL of the page after it.
+    fn read(&self, page: FetchedPage, number: usize) -> Result<(Vec<Value>, Option<Url>)> {
+        let page = page.error_for_status()?;
+        if page.response.kind != ContentKind::Html {
+            anyhow::bail!(
+                "Not an HTML page: {} content",
+                page.response.content_type.as_deref().unwrap_or("unknown")
+            );
+        }
+        let extractor = ElementExtractor::new(&page.html).with_page_url(Some(page.url.clone()));
+        let records = self.options.extract.extract(&extractor)?;
+        let href = match &self.options.next {
+            NextPage::Template { template, start } => {
+                return Ok((records, Some(page_url(template, start + number as u64)?)));
+            }
+            NextPage::Selector(selector) => extractor.extract_attributes(selector, "href")?,
+            NextPage::RelNext => {
+                extractor.extract_attributes("link[rel~=next], a[rel~=next]", "href")?
+            }
+        };
+        let next = href.first().and_then(|href| {
+            let base = extractor.base_url().unwrap_or(&page.url);
+            let mut url = base.join(href.trim()).ok()?;
+            url.set_fragment(None);
+            Some(url)
+        });
+        Ok((records, next))
+    }
+
+    fn dedupe_key(&self, record: &Value) -> String {
+        match &self.options.dedupe_by {
+            Some(field) => match record.get(field) {
+                Some(key) if !key.is_null() => key.to_string(),
+                _ => record.to_string(),
+            },
+            None => record.to_string(),
+        }
+    }
+}
+
+/// `template` with its `{page}` placeholder set to `number`.
+pub fn page_url(template: &str, number: u64) -> Result<Url> {
+    if !template.contains(PAGE_PLACEHOLDER) {
+        anyhow::bail!("url_template must contain {}", PAGE_PLACEHOLDER);
+    }
+    let url = template.replace(PAGE_PLACEHOLDER, &number.to_string());
+    Url::parse(&url).map_err(|e| anyhow::anyhow!("Invalid url_template {}: {}", template, e))
+}
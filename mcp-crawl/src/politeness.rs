@@ -0,0 +1,347 @@
+use anyhow::Result;
+use reqwest::Client;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::debug;
+use url::Url;
+
+use crate::archive::ArchiveMode;
+use crate::config::CrawlerConfig;
+use crate::fetch;
+
+/// How long a host's robots.txt is trusted before it is fetched again.
+const ROBOTS_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Robots.txt files larger than this are truncated, as RFC 9309 allows.
+const ROBOTS_MAX_BYTES: usize = 500 * 1024;
+
+/// Time allowed for fetching a robots.txt, so an unresponsive host does not
+/// hold up every request to it.
+const ROBOTS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Longest `Crawl-delay` honoured, so a hostile robots.txt cannot hold up
+/// every request to its host for hours.
+const MAX_CRAWL_DELAY: Duration = Duration::from_secs(30);
+
+/// A fetch refused because robots.txt disallows it.
+#[derive(Debug, thiserror::Error)]
+#[error("Blocked by robots.txt: {url} is disallowed for user agent {user_agent}")]
+pub struct RobotsBlocked {
+    pub url: String,
+    pub user_agent: String,
+}
+
+/// Parsed robots.txt rules of a host.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RobotsTxt {
+    groups: Vec<Group>,
+    sitemaps: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Group {
+    /// Lower-cased user-agent tokens the group applies to.
+    agents: Vec<String>,
+    rules: Vec<Rule>,
+    crawl_delay: Option<Duration>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    allow: bool,
+    pattern: String,
+}
+
+impl RobotsTxt {
+    pub fn parse(text: &str) -> Self {
+        let mut robots = RobotsTxt::default();
+        // Whether the last line was a user-agent line, so the next one extends the group
+        let mut in_agents = false;
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "user-agent" => {
+                    if !in_agents {
+                        robots.groups.push(Group::default());
+                    }
+                    if let Some(group) = robots.groups.last_mut() {
+                        group.agents.push(value.to_ascii_lowercase());
+                    }
+                    in_agents = true;
+                }
+                key @ ("allow" | "disallow") => {
+                    in_agents = false;
+                    // An empty Disallow allows everything, which is the default anyway
+                    if let (Some(group), false) = (robots.groups.last_mut(), value.is_empty()) {
+                        group.rules.push(Rule {
+                            allow: key == "allow",
+                            pattern: value.to_string(),
+                        });
+                    }
+                }
+                "crawl-delay" => {
+                    in_agents = false;
+                    let delay = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|d| d.is_finite() && *d >= 0.0);
+                    if let (Some(group), Some(delay)) = (robots.groups.last_mut(), delay) {
+                        group.crawl_delay = Some(Duration::from_secs_f64(delay));
+                    }
+                }
+                "sitemap" => {
+                    if !value.is_empty() {
+                        robots.sitemaps.push(value.to_string());
+                    }
+                }
+                _ => in_agents = false,
+            }
+        }
+        robots
+    }
+
+    /// Rules that disallow every path, used when robots.txt is unavailable
+    /// because of a server error.
+    pub fn disallow_all() -> Self {
+        Self::parse("User-agent: *\nDisallow: /")
+    }
+
+    /// Whether `user_agent` may fetch `url`. The longest matching pattern
+    /// wins; on a tie Allow wins.
+    pub fn is_allowed(&self, user_agent: &str, url: &Url) -> bool {
+        let mut path = url.path().to_string();
+        if let Some(query) = url.query() {
+            path.push('?');
+            path.push_str(query);
+        }
+        self.groups_for(user_agent)
+            .flat_map(|group| &group.rules)
+            .filter(|rule| pattern_matches(&rule.pattern, &path))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+            .is_none_or(|rule| rule.allow)
+    }
+
+    /// Crawl-delay asked of `user_agent`, if any.
+    pub fn crawl_delay(&self, user_agent: &str) -> Option<Duration> {
+        self.groups_for(user_agent)
+            .filter_map(|group| group.crawl_delay)
+            .max()
+    }
+
+    /// Sitemap URLs listed in the file.
+    pub fn sitemaps(&self) -> &[String] {
+        &self.sitemaps
+    }
+
+    /// Groups naming our product token, or the `*` groups when none does.
+    fn groups_for<'a>(&'a self, user_agent: &str) -> impl Iterator<Item = &'a Group> + 'a {
+        let token = product_token(user_agent);
+        let named = self
+            .groups
+            .iter()
+            .any(|group| group.agents.contains(&token));
+        self.groups.iter().filter(move |group| {
+            group
+                .agents
+                .iter()
+                .any(|agent| if named { *agent == token } else { agent == "*" })
+        })
+    }
+}
+
+/// `mcp-crawl` for `mcp-crawl/0.1.0 (+https://...)`, lower-cased.
+fn product_token(user_agent: &str) -> String {
+    user_agent
+        .split(['/', ' '])
+        .next()
+        .unwrap_or("")
+        .to_ascii_lowercase()
+}
+
+/// Match a robots.txt path pattern, where `*` matches any run of
+/// characters and a trailing `$` anchors the end of the path.
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    if !path.starts_with(first) {
+        return false;
+    }
+    let mut rest = &path[first.len()..];
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return !anchored || rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    if anchored {
+        rest.ends_with(last)
+    } else {
+        rest.contains(last)
+    }
+}
+
+/// Politeness rules shared by every fetch of a server: robots.txt,
+/// Crawl-delay, a minimum delay between requests to the same host and a
+/// cap on concurrent requests per host.
+pub struct Politeness {
+    user_agent: String,
+    respect_robots: bool,
+    delay: Duration,
+    per_host_concurrency: usize,
+    /// Where robots.txt fetches are recorded, or replayed from.
+    archive: Option<ArchiveMode>,
+    robots: Mutex<HashMap<String, (Instant, Arc<RobotsTxt>)>>,
+    hosts: Mutex<HashMap<String, Host>>,
+}
+
+struct Host {
+    permits: Arc<Semaphore>,
+    /// Earliest time the next request may start.
+    next_request: Instant,
+}
+
+/// Held while a request to a host is in flight.
+pub struct HostPermit {
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Politeness {
+    pub fn new(config: &CrawlerConfig) -> Self {
+        Self {
+            user_agent: config.user_agent.clone(),
+            respect_robots: config.respect_robots,
+            delay: config.delay,
+            per_host_concurrency: config.per_host_concurrency.max(1),
+            archive: None,
+            robots: Mutex::new(HashMap::new()),
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_archive(mut self, archive: ArchiveMode) -> Self {
+        self.archive = Some(archive);
+        self
+    }
+
+    /// Wait until `url` may be fetched and return a permit to hold while
+    /// the request runs. Fails with [`RobotsBlocked`] when robots.txt
+    /// disallows the URL.
+    pub async fn acquire(&self, client: &Client, url: &Url) -> Result<HostPermit> {
+        let mut delay = self.delay;
+        if self.respect_robots {
+            let robots = self.robots(client, url).await;
+            if !robots.is_allowed(&self.user_agent, url) {
+                return Err(RobotsBlocked {
+                    url: url.to_string(),
+                    user_agent: self.user_agent.clone(),
+                }
+                .into());
+            }
+            if let Some(crawl_delay) = robots.crawl_delay(&self.user_agent) {
+                delay = delay.max(crawl_delay.min(MAX_CRAWL_DELAY));
+            }
+        }
+
+        let key = url.origin().ascii_serialization();
+        let permits = self
+            .hosts
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_insert_with(|| Host {
+                permits: Arc::new(Semaphore::new(self.per_host_concurrency)),
+                next_request: Instant::now(),
+            })
+            .permits
+            .clone();
+        let permit = permits.acquire_owned().await?;
+
+        // Reserve the next slot before sleeping so concurrent requests queue up
+        let wait = {
+            let mut hosts = self.hosts.lock().unwrap();
+            let host = hosts.get_mut(&key).expect("host was registered above");
+            let now = Instant::now();
+            let start = host.next_request.max(now);
+            host.next_request = start + delay;
+            start - now
+        };
+        tokio::time::sleep(wait).await;
+
+        Ok(HostPermit { _permit: permit })
+    }
+
+    /// Robots.txt rules of the host of `url`, fetched with `client` unless
+    /// cached. When archiving, `client` must not follow redirects since the
+    /// archive follows them itself. A missing file allows everything; a server error or no
+    /// response at all disallows everything, as RFC 9309 requires, and is
+    /// not cached, so the next fetch asks again.
+    pub async fn robots(&self, client: &Client, url: &Url) -> Arc<RobotsTxt> {
+        let key = url.origin().ascii_serialization();
+        if let Some((fetched_at, robots)) = self.robots.lock().unwrap().get(&key) {
+            if fetched_at.elapsed() < ROBOTS_TTL {
+                return robots.clone();
+            }
+        }
+
+        let (robots, cacheable) = match url.join("/robots.txt") {
+            Ok(robots_url) => fetch_robots(client, robots_url, self.archive.as_ref()).await,
+            Err(_) => (RobotsTxt::default(), true),
+        };
+        let robots = Arc::new(robots);
+        if cacheable {
+            self.robots
+                .lock()
+                .unwrap()
+                .insert(key, (Instant::now(), robots.clone()));
+        }
+        robots
+    }
+}
+
+/// Fetch and parse a robots.txt, along with whether the result may be cached.
+async fn fetch_robots(
+    client: &Client,
+    url: Url,
+    archive: Option<&ArchiveMode>,
+) -> (RobotsTxt, bool) {
+    let request = client.get(url.clone()).timeout(ROBOTS_TIMEOUT);
+    let response = match archive {
+        Some(archive) => match request.build() {
+            Ok(request) => archive.execute(client, request, true, None).await,
+            Err(e) => Err(e.into()),
+        },
+        None => request.send().await.map_err(Into::into),
+    };
+    let response = match response {
+        Ok(response) => response,
+        Err(e) => {
+            debug!("Failed to fetch {}: {}", url, e);
+            return (RobotsTxt::disallow_all(), false);
+        }
+    };
+    let status = response.status();
+    if status.is_server_error() {
+        return (RobotsTxt::disallow_all(), false);
+    }
+    if !status.is_success() {
+        return (RobotsTxt::default(), true);
+    }
+    match fetch::read_body_prefix(response, ROBOTS_MAX_BYTES).await {
+        Ok((body, _)) => (RobotsTxt::parse(&String::from_utf8_lossy(&body)), true),
+        Err(_) => (RobotsTxt::disallow_all(), false),
+    }
+}
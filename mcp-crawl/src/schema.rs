@@ -0,0 +1,336 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use regex::Regex;
+use scraper::{ElementRef, Selector};
+use serde_json::{json, Map, Value};
+
+use crate::tables;
+
+/// A declarative description of records to extract from a page.
+///
+/// ```json
+/// {
+///   "items": "div.product",
+///   "fields": {
+///     "name": "h2",
+///     "price": {"selector": ".price", "attr": "data-value", "type": "number"},
+///     "url": {"selector": "a", "attr": "href", "resolve": true},
+///     "variants": {"items": "li.variant", "fields": {"sku": {"attr": "data-sku"}}}
+///   }
+/// }
+/// ```
+///
+/// Without `items` the whole page is a single record.
+#[derive(Debug, Clone)]
+pub struct ExtractionSchema {
+    items: Option<Selector>,
+    fields: Vec<Field>,
+}
+
+#[derive(Debug, Clone)]
+struct Field {
+    name: String,
+    /// Elements the value is read from, relative to the record; the record
+    /// element itself when missing.
+    selector: Option<Selector>,
+    extract: Extract,
+}
+
+#[derive(Debug, Clone)]
+enum Extract {
+    Value(ValueSpec),
+    /// A nested record of the first match, or a list of records with `items`.
+    Nested(Box<ExtractionSchema>),
+}
+
+#[derive(Debug, Clone)]
+struct ValueSpec {
+    /// Read this attribute instead of the text.
+    attr: Option<String>,
+    /// Collapse whitespace and trim the value.
+    trim: bool,
+    /// Keep the first capture group, or the whole match without groups.
+    regex: Option<Regex>,
+    /// Resolve the value as a URL against the page's base URL.
+    resolve: bool,
+    value_type: ValueType,
+    /// Return every match as a list instead of the first one.
+    all: bool,
+    /// Used when nothing matches or the value cannot be converted.
+    default: Option<Value>,
+}
+
+#[derive(Debug, Clone)]
+enum ValueType {
+    String,
+    /// Digits, sign and decimal point are kept; commas are taken as
+    /// thousands separators.
+    Number,
+    Integer,
+    /// Whether a value was found, unless it reads false, no, 0 or off.
+    Boolean,
+    /// Normalised to `YYYY-MM-DD`, or RFC 3339 with a time, read with the
+    /// given chrono format or common formats.
+    Date(Option<String>),
+}
+
+impl ExtractionSchema {
+    pub fn from_json(spec: &Value) -> Result<Self> {
+        Self::parse(spec, "schema")
+    }
+
+    fn parse(spec: &Value, path: &str) -> Result<Self> {
+        let spec = spec
+            .as_object()
+            .with_context(|| format!("{}: expected an object with fields", path))?;
+        let items = spec
+            .get("items")
+            .map(|items| parse_selector(items, &format!("{}.items", path)))
+            .transpose()?;
+        let fields = spec
+            .get("fields")
+            .and_then(|f| f.as_object())
+            .with_context(|| format!("{}.fields: expected an object", path))?
+            .iter()
+            .map(|(name, field)| Field::parse(name, field, &format!("{}.fields.{}", path, name)))
+            .collect::<Result<_>>()?;
+        Ok(Self { items, fields })
+    }
+
+    /// Extract the records below `scope`: one per `items` match, or a
+    /// single one for `scope` itself.
+    pub(crate) fn extract(
+        &self,
+        scope: ElementRef,
+        resolve: &dyn Fn(&str) -> String,
+    ) -> Vec<Value> {
+        match &self.items {
+            Some(items) => scope
+                .select(items)
+                .map(|item| self.record(item, resolve))
+                .collect(),
+            None => vec![self.record(scope, resolve)],
+        }
+    }
+
+    fn record(&self, element: ElementRef, resolve: &dyn Fn(&str) -> String) -> Value {
+        let record: Map<String, Value> = self
+            .fields
+            .iter()
+            .map(|field| (field.name.clone(), field.extract(element, resolve)))
+            .collect();
+        Value::Object(record)
+    }
+}
+
+impl Field {
+    fn parse(name: &str, spec: &Value, path: &str) -> Result<Self> {
+        // "name": "h2" is short for {"selector": "h2"}
+        if spec.is_string() {
+            return Ok(Self {
+                name: name.to_string(),
+                selector: Some(parse_selector(spec, path)?),
+                extract: Extract::Value(ValueSpec::default()),
+            });
+        }
+        let object = spec
+            .as_object()
+            .with_context(|| format!("{}: expected a selector or an object", path))?;
+        let selector = object
+            .get("selector")
+            .map(|s| parse_selector(s, &format!("{}.selector", path)))
+            .transpose()?;
+        if object.contains_key("fields") {
+            return Ok(Self {
+                name: name.to_string(),
+                selector,
+                extract: Extract::Nested(Box::new(ExtractionSchema::parse(spec, path)?)),
+            });
+        }
+
+        let string = |key: &str| -> Result<Option<String>> {
+            object
+                .get(key)
+                .map(|v| {
+                    v.as_str()
+                        .map(|s| s.to_string())
+                        .with_context(|| format!("{}.{}: expected a string", path, key))
+                })
+                .transpose()
+        };
+        let flag =
+            |key: &str, default: bool| object.get(key).and_then(|v| v.as_bool()).unwrap_or(default);
+        let value_type = match string("type")?.as_deref().unwrap_or("string") {
+            "string" => ValueType::String,
+            "number" => ValueType::Number,
+            "integer" => ValueType::Integer,
+            "boolean" => ValueType::Boolean,
+            "date" => ValueType::Date(string("format")?),
+            other => anyhow::bail!(
+                "{}.type: unknown type {} (expected string, number, integer, boolean or date)",
+                path,
+                other
+            ),
+        };
+        let regex = string("regex")?
+            .map(|r| Regex::new(&r).with_context(|| format!("{}.regex: invalid regex {}", path, r)))
+            .transpose()?;
+
+        Ok(Self {
+            name: name.to_string(),
+            selector,
+            extract: Extract::Value(ValueSpec {
+                attr: string("attr")?,
+                trim: flag("trim", true),
+                regex,
+                resolve: flag("resolve", false),
+                value_type,
+                all: flag("all", false),
+                default: object.get("default").cloned(),
+            }),
+        })
+    }
+
+    fn extract(&self, element: ElementRef, resolve: &dyn Fn(&str) -> String) -> Value {
+        let matches: Vec<ElementRef> = match &self.selector {
+            Some(selector) => element.select(selector).collect(),
+            None => vec![element],
+        };
+        match &self.extract {
+            Extract::Nested(schema) if schema.items.is_some() => Value::Array(
+                matches
+                    .first()
+                    .map(|m| schema.extract(*m, resolve))
+                    .unwrap_or_default(),
+            ),
+            Extract::Nested(schema) => matches
+                .first()
+                .map(|m| schema.record(*m, resolve))
+                .unwrap_or(Value::Null),
+            Extract::Value(spec) if spec.all => Value::Array(
+                matches
+                    .iter()
+                    .map(|m| spec.value(Some(*m), resolve))
+                    .filter(|v| !v.is_null())
+                    .collect(),
+            ),
+            Extract::Value(spec) => spec.value(matches.first().copied(), resolve),
+        }
+    }
+}
+
+impl Default for ValueSpec {
+    fn default() -> Self {
+        Self {
+            attr: None,
+            trim: true,
+            regex: None,
+            resolve: false,
+            value_type: ValueType::String,
+            all: false,
+            default: None,
+        }
+    }
+}
+
+impl ValueSpec {
+    /// Value of `element`, or of a missing match.
+    fn value(&self, element: Option<ElementRef>, resolve: &dyn Fn(&str) -> String) -> Value {
+        let raw = element.and_then(|element| match &self.attr {
+            Some(attr) => element.value().attr(attr).map(|v| v.to_string()),
+            None => Some(element.text().collect::<String>()),
+        });
+        let value = raw
+            .and_then(|raw| self.post_process(raw, resolve))
+            .and_then(|text| self.convert(&text));
+        match (value, &self.default, &self.value_type) {
+            (Some(value), _, _) => value,
+            (None, Some(default), _) => default.clone(),
+            (None, None, ValueType::Boolean) => json!(false),
+            (None, None, _) => Value::Null,
+        }
+    }
+
+    fn post_process(&self, raw: String, resolve: &dyn Fn(&str) -> String) -> Option<String> {
+        let mut text = if self.trim {
+            raw.split_whitespace().collect::<Vec<_>>().join(" ")
+        } else {
+            raw
+        };
+        if let Some(regex) = &self.regex {
+            let captures = regex.captures(&text)?;
+            text = captures.get(1).or(captures.get(0))?.as_str().to_string();
+        }
+        if self.resolve {
+            text = resolve(&text);
+        }
+        Some(text)
+    }
+
+    fn convert(&self, text: &str) -> Option<Value> {
+        match &self.value_type {
+            ValueType::String => Some(json!(text)),
+            ValueType::Number => parse_number(text).map(|n| json!(n)),
+            ValueType::Integer => parse_number(text)
+                .filter(|n| n.fract() == 0.0)
+                .map(|n| json!(n as i64)),
+            ValueType::Boolean => Some(json!(!matches!(
+                text.to_ascii_lowercase().as_str(),
+                "false" | "no" | "0" | "off"
+            ))),
+            ValueType::Date(format) => parse_date(text, format.as_deref()).map(|d| json!(d)),
+        }
+    }
+}
+
+fn parse_selector(value: &Value, path: &str) -> Result<Selector> {
+    let selector = value
+        .as_str()
+        .with_context(|| format!("{}: expected a CSS selector", path))?;
+    Selector::parse(selector)
+        .map_err(|e| anyhow::anyhow!("{}: invalid CSS selector {}: {:?}", path, selector, e))
+}
+
+/// The first number in `text`, read like a table cell: `"$1,299.99"` ->
+/// `1299.99`, `"10-20"` -> `10`. Numbers with ambiguous separators, such
+/// as `"1.234,56"`, give `None`.
+fn parse_number(text: &str) -> Option<f64> {
+    let digits = text.find(|c: char| c.is_ascii_digit())?;
+    let end = text[digits..]
+        .find(|c: char| !(c.is_ascii_digit() || c == ',' || c == '.'))
+        .map_or(text.len(), |len| digits + len);
+    // A leading `.` and a sign belong to the number, but not a hyphen
+    // joining it to a word
+    let head = &text[..digits];
+    let head = head.strip_suffix('.').unwrap_or(head);
+    let signed = head
+        .strip_suffix(['-', '+', '\u{2212}'])
+        .filter(|before| !before.ends_with(char::is_alphanumeric));
+    let start = signed.map_or(head.len(), str::len);
+    tables::parse_number(text[start..end].trim_end_matches([',', '.']))
+}
+
+/// `text` as `YYYY-MM-DD`, or RFC 3339 when it has a time, read with
+/// `format` or common formats.
+pub(crate) fn parse_date(text: &str, format: Option<&str>) -> Option<String> {
+    let date = |date: NaiveDate| date.format("%Y-%m-%d").to_string();
+    if let Some(format) = format {
+        return NaiveDateTime::parse_from_str(text, format)
+            .map(|dt| dt.and_utc().to_rfc3339())
+            .or_else(|_| NaiveDate::parse_from_str(text, format).map(date))
+            .ok();
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc3339(text) {
+        return Some(dt.to_rfc3339());
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc2822(text) {
+        return Some(dt.to_rfc3339());
+    }
+    if let Ok(dt) = NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S") {
+        return Some(dt.and_utc().to_rfc3339());
+    }
+    ["%Y-%m-%d", "%B %d, %Y", "%b %d, %Y", "%d %B %Y", "%d %b %Y"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(text, format).ok())
+        .map(date)
+}
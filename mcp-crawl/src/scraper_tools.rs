@@ -1,59 +1,555 @@
 use anyhow::Result;
 use regex::Regex;
-use reqwest::Client;
+use reqwest::cookie::Jar;
+use reqwest::header::{self, HeaderMap, HeaderName, HeaderValue};
+use reqwest::redirect;
+use reqwest::{Client, IntoUrl, Method, Request, RequestBuilder, Response, StatusCode};
 use scraper::{ElementRef, Html, Selector};
-use serde_json::{json, Value};
-use std::collections::HashMap;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
 use url::Url;
 use readability::extractor;
-use html_escape;
+
+use crate::archive::ArchiveMode;
+use crate::cache::{CacheEntry, CacheStatus, Lookup, PageCache};
+use crate::config::CrawlerConfig;
+use crate::fetch::{self, ContentKind, HttpError, ResponseInfo};
+use crate::markdown::{self, MarkdownOptions};
+use crate::metadata;
+use crate::politeness::{HostPermit, Politeness, RobotsTxt};
+use crate::schema::ExtractionSchema;
+use crate::structured_data;
+use crate::tables::{Table, TableOptions};
+use crate::xpath::XPathResult;
 
 pub struct ScrapingSession {
     client: Client,
+    /// Same as `client`, cookies included, but does not follow redirects.
+    direct_client: Client,
     base_url: Option<Url>,
+    politeness: Arc<Politeness>,
+    cache: Option<Arc<PageCache>>,
+    archive: Option<ArchiveMode>,
+    /// Crawl job the session's exchanges are recorded under.
+    job_id: Option<String>,
+    headers: HeaderMap,
+    max_retries: u32,
+    retry_backoff: Duration,
+    max_body_bytes: usize,
+}
+
+/// A fetched page body along with where it came from.
+#[derive(Debug, Clone)]
+pub struct FetchedPage {
+    /// Final URL after redirects.
+    pub url: Url,
+    /// Body decoded from its charset, or the text of a PDF. Empty for
+    /// binary content.
+    pub html: String,
+    pub response: ResponseInfo,
+    pub cache_status: CacheStatus,
+}
+
+impl FetchedPage {
+    /// Turn a non-2xx response into an [`HttpError`].
+    pub fn error_for_status(self) -> Result<Self> {
+        if self.response.is_success() {
+            Ok(self)
+        } else {
+            let status = StatusCode::from_u16(self.response.status)?;
+            Err(HttpError::new(status, self.url.as_str()).into())
+        }
+    }
+
+    /// Read the body of `response`, sent at `started`. Fails with
+    /// [`BodyTooLarge`] past `max_bytes`.
+    pub(crate) async fn read(
+        response: Response,
+        started: Instant,
+        cache_status: CacheStatus,
+        max_bytes: usize,
+    ) -> Result<Self> {
+        let url = response.url().clone();
+        let status = response.status().as_u16();
+        let headers = fetch::header_map(response.headers());
+        let (content_type, charset) = headers
+            .get("content-type")
+            .map(|v| fetch::parse_content_type(v))
+            .unwrap_or_default();
+        let mut body = fetch::read_body(response, max_bytes).await?;
+        let size = body.len();
+        let mut kind = ContentKind::detect(content_type.as_deref(), &body);
+        if fetch::is_gzip(content_type.as_deref(), &body) {
+            body = fetch::gunzip(&body)?;
+            kind = ContentKind::detect(None, &body);
+        }
+        let (html, charset) = match kind {
+            ContentKind::Pdf => {
+                // Parsing is CPU-bound, and a broken PDF is still a page that
+                // was fetched
+                let text = tokio::task::spawn_blocking(move || fetch::pdf_text(&body))
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|text| text)
+                    .unwrap_or_else(|e| {
+                        warn!("No text extracted from {}: {}", url, e);
+                        String::new()
+                    });
+                (text, None)
+            }
+            ContentKind::Binary => (String::new(), None),
+            _ => {
+                let (text, encoding) = fetch::decode_body(&body, charset.as_deref(), kind);
+                (text, Some(encoding.to_string()))
+            }
+        };
+
+        Ok(Self {
+            response: ResponseInfo {
+                url: url.to_string(),
+                status,
+                headers,
+                content_type,
+                kind,
+                charset,
+                elapsed_ms: started.elapsed().as_millis() as u64,
+                size,
+            },
+            url,
+            html,
+            cache_status,
+        })
+    }
+
+    fn from_cache(url: Url, entry: CacheEntry, cache_status: CacheStatus) -> Self {
+        let response = entry.response.unwrap_or_else(|| ResponseInfo {
+            url: url.to_string(),
+            status: 200,
+            headers: Default::default(),
+            content_type: None,
+            kind: ContentKind::Html,
+            charset: Some("UTF-8".to_string()),
+            elapsed_ms: 0,
+            size: entry.body.len(),
+        });
+        Self {
+            url: Url::parse(&response.url).unwrap_or(url),
+            html: entry.body,
+            response,
+            cache_status,
+        }
+    }
 }
 
 impl ScrapingSession {
-    pub fn new() -> Result<Self> {
+    pub fn new(config: &CrawlerConfig) -> Result<Self> {
+        let jar = Arc::new(Jar::default());
         let client = Client::builder()
-            .cookie_store(true)
-            .user_agent("mcp-crawl/1.0")
+            .cookie_provider(jar.clone())
+            .user_agent(config.user_agent.as_str())
+            .build()
+            .map_err(|e| anyhow::anyhow!("Failed to build HTTP client: {}", e))?;
+        let direct_client = Client::builder()
+            .cookie_provider(jar)
+            .user_agent(config.user_agent.as_str())
+            .redirect(redirect::Policy::none())
             .build()
             .map_err(|e| anyhow::anyhow!("Failed to build HTTP client: {}", e))?;
 
         Ok(Self {
             client,
+            direct_client,
             base_url: None,
+            politeness: Arc::new(Politeness::new(config)),
+            cache: None,
+            archive: None,
+            job_id: None,
+            headers: HeaderMap::new(),
+            max_retries: config.max_retries,
+            retry_backoff: config.retry_backoff,
+            max_body_bytes: config.max_body_bytes,
         })
     }
 
+    /// Resolve relative URLs passed to this session against `base_url`.
+    pub fn with_base_url(mut self, base_url: Url) -> Self {
+        self.base_url = Some(base_url);
+        self
+    }
+
+    /// Send `headers` with every request of this session.
+    pub fn with_headers(mut self, headers: &HashMap<String, String>) -> Result<Self> {
+        for (name, value) in headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| anyhow::anyhow!("Invalid header name {}: {}", name, e))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|e| anyhow::anyhow!("Invalid value for header {}: {}", name, e))?;
+            self.headers.insert(name, value);
+        }
+        Ok(self)
+    }
+
+    /// URL of the last page fetched, or the base URL the session was opened
+    /// with.
+    pub fn base_url(&self) -> Option<&Url> {
+        self.base_url.as_ref()
+    }
+
+    /// Parse `url`, resolving it against the session's base URL if relative.
+    pub fn resolve(&self, url: &str) -> Result<Url> {
+        match (Url::parse(url), &self.base_url) {
+            (Ok(url), _) => Ok(url),
+            (Err(url::ParseError::RelativeUrlWithoutBase), Some(base_url)) => {
+                Ok(base_url.join(url)?)
+            }
+            (Err(e), _) => Err(anyhow::anyhow!("Invalid URL {}: {}", url, e)),
+        }
+    }
+
+    /// Share robots.txt rules and per-host limits with other sessions.
+    pub fn with_politeness(mut self, politeness: Arc<Politeness>) -> Self {
+        self.politeness = politeness;
+        self
+    }
+
+    /// Serve pages from `cache` and store fetched pages in it.
+    pub fn with_cache(mut self, cache: Arc<PageCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Record every exchange in an archive, or answer requests from one.
+    pub fn with_archive(mut self, archive: ArchiveMode) -> Self {
+        self.archive = Some(archive);
+        self
+    }
+
+    /// Record the exchanges that follow under the crawl job `job_id`.
+    pub fn set_job_id(&mut self, job_id: Option<String>) {
+        self.job_id = job_id;
+    }
+
+    /// Fetch a page and return its body. Non-2xx responses are errors.
     pub async fn fetch_page(&mut self, url: &str) -> Result<String> {
-        let response = self.client.get(url).send().await?;
-        let html = response.text().await?;
+        Ok(self.fetch(url).await?.error_for_status()?.html)
+    }
+
+    /// Fetch a page, going through the cache when the session has one.
+    ///
+    /// Expired entries with an ETag or Last-Modified header are revalidated
+    /// with a conditional request. Only successful responses are cached, but
+    /// error responses are returned as well; see
+    /// [`FetchedPage::error_for_status`].
+    pub async fn fetch(&mut self, url: &str) -> Result<FetchedPage> {
+        let url = self.resolve(url)?;
+        self.base_url = Some(url.clone());
+        let cache = self.cache.clone();
+
+        let stale = match cache.as_ref().map(|cache| cache.lookup(url.as_str())) {
+            Some(Lookup::Fresh(entry)) => {
+                let page = FetchedPage::from_cache(url, entry, CacheStatus::Hit);
+                self.base_url = Some(page.url.clone());
+                return Ok(page);
+            }
+            Some(Lookup::Stale(entry)) => Some(entry),
+            _ => None,
+        };
+
+        let started = Instant::now();
+        let mut request = self.request(Method::GET, url.clone());
+        if let Some(entry) = &stale {
+            if let Some(etag) = &entry.etag {
+                request = request.header(header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &entry.last_modified {
+                request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let response = self.send(request).await?;
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            if let (Some(cache), Some(entry)) = (&cache, stale) {
+                let entry = cache.refresh(url.as_str()).unwrap_or(entry);
+                let page = FetchedPage::from_cache(url, entry, CacheStatus::Revalidated);
+                self.base_url = Some(page.url.clone());
+                return Ok(page);
+            }
+        }
 
-        if let Ok(parsed_url) = Url::parse(url) {
-            self.base_url = Some(parsed_url);
+        let header_value = |name: header::HeaderName| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_string())
+        };
+        let cacheable = response.status().is_success()
+            && !header_value(header::CACHE_CONTROL).is_some_and(|v| v.contains("no-store"));
+        let etag = header_value(header::ETAG);
+        let last_modified = header_value(header::LAST_MODIFIED);
+
+        let cache_status = match cache {
+            Some(_) => CacheStatus::Miss,
+            None => CacheStatus::Bypass,
+        };
+        let page = FetchedPage::read(response, started, cache_status, self.max_body_bytes).await?;
+        self.base_url = Some(page.url.clone());
+
+        if let Some(cache) = cache {
+            if cacheable {
+                cache.insert(
+                    url.as_str(),
+                    CacheEntry::new(page.html.clone(), etag, last_modified)
+                        .with_response(page.response.clone()),
+                );
+            } else {
+                cache.remove(url.as_str());
+            }
         }
 
-        Ok(html)
+        Ok(page)
     }
 
     pub fn parse_html(&self, html: &str) -> Html {
         Html::parse_document(html)
     }
+
+    /// Send a GET request, bypassing the cache.
+    pub(crate) async fn get<U: IntoUrl>(&mut self, url: U) -> Result<Response> {
+        self.send(self.request(Method::GET, url)).await
+    }
+
+    /// Submit form `data` to `action`, resolved against the base URL. GET
+    /// forms send the data in the query string. The page the form leads to
+    /// becomes the new base URL.
+    pub async fn submit_form(
+        &mut self,
+        action: &str,
+        method: &str,
+        data: &[(String, String)],
+    ) -> Result<Response> {
+        let action = self.resolve(action)?;
+        let response = match method.to_uppercase().as_str() {
+            "POST" => {
+                let request = self.request(Method::POST, action).form(data);
+                self.send(request).await?
+            }
+            _ => {
+                let mut url = action;
+                url.query_pairs_mut().extend_pairs(data);
+                self.send(self.request(Method::GET, url)).await?
+            }
+        };
+        self.base_url = Some(response.url().clone());
+        Ok(response)
+    }
+
+    /// Wait until robots.txt and the per-host limits allow fetching `url`.
+    /// The permit should be held until the response has arrived.
+    pub(crate) async fn acquire(&self, url: &Url) -> Result<HostPermit> {
+        self.politeness.acquire(self.robots_client(), url).await
+    }
+
+    /// Send a single `method` request to `url` without following redirects,
+    /// giving up after `timeout`. Retried like any other request.
+    pub(crate) async fn probe(
+        &self,
+        method: Method,
+        url: Url,
+        timeout: Duration,
+    ) -> Result<Response> {
+        let request = self
+            .direct_client
+            .request(method, url)
+            .headers(self.headers.clone())
+            .timeout(timeout);
+        self.send_with(request, false).await
+    }
+
+    /// Send `request` once robots.txt and the host limits allow it, then
+    /// each redirect hop it leads to the same way.
+    async fn send(&self, request: RequestBuilder) -> Result<Response> {
+        self.send_with(request, true).await
+    }
+
+    async fn send_with(&self, request: RequestBuilder, follow_redirects: bool) -> Result<Response> {
+        let mut request = request.build()?;
+        let mut redirects = 0;
+        loop {
+            let next = if follow_redirects {
+                request.try_clone()
+            } else {
+                None
+            };
+            let response = self.send_hop(request).await?;
+            let Some(next) = next.and_then(|next| fetch::redirect_request(next, &response)) else {
+                return Ok(response);
+            };
+            redirects += 1;
+            if redirects > fetch::MAX_REDIRECTS {
+                anyhow::bail!("Too many redirects from {}", response.url());
+            }
+            request = next;
+        }
+    }
+
+    /// Send `request` without following redirects. 429 and 5xx responses
+    /// are retried up to `max_retries` times, after the delay asked by
+    /// `Retry-After` or an exponential backoff. Requests that are not
+    /// idempotent, such as form posts, are sent once since sending them
+    /// twice may have side effects.
+    async fn send_hop(&self, mut request: Request) -> Result<Response> {
+        if self.is_replay() {
+            return self.execute(request).await;
+        }
+        let max_retries = if request.method().is_idempotent() {
+            self.max_retries
+        } else {
+            0
+        };
+        let mut attempt = 0;
+        loop {
+            let retry = if attempt < max_retries {
+                request.try_clone()
+            } else {
+                None
+            };
+            let response = {
+                let _permit = self.acquire(request.url()).await?;
+                self.execute(request).await?
+            };
+
+            let Some(next) = retry else {
+                return Ok(response);
+            };
+            let status = response.status();
+            let Some(delay) =
+                fetch::retry_delay(status, response.headers(), attempt, self.retry_backoff)
+            else {
+                return Ok(response);
+            };
+            debug!(
+                "Retrying {} in {:?} after HTTP {}",
+                next.url(),
+                delay,
+                status
+            );
+            tokio::time::sleep(delay).await;
+            request = next;
+            attempt += 1;
+        }
+    }
+
+    /// Send `request` without following redirects, recording the exchange
+    /// when archiving, or answer it from the archive when replaying.
+    async fn execute(&self, request: Request) -> Result<Response> {
+        match &self.archive {
+            Some(archive) => {
+                archive
+                    .execute(&self.direct_client, request, false, self.job_id.as_deref())
+                    .await
+            }
+            None => Ok(self.direct_client.execute(request).await?),
+        }
+    }
+
+    /// Largest response body read.
+    pub(crate) fn max_body_bytes(&self) -> usize {
+        self.max_body_bytes
+    }
+
+    /// Read the body of `response` as text, decoded from its charset.
+    /// Fails with [`BodyTooLarge`](fetch::BodyTooLarge) past the session's
+    /// limit.
+    pub(crate) async fn read_text(&self, response: Response) -> Result<String> {
+        let (content_type, charset) = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(fetch::parse_content_type)
+            .unwrap_or_default();
+        let body = fetch::read_body(response, self.max_body_bytes).await?;
+        let kind = ContentKind::detect(content_type.as_deref(), &body);
+        Ok(fetch::decode_body(&body, charset.as_deref(), kind).0)
+    }
+
+    /// Client robots.txt is fetched with, which must not follow redirects
+    /// when archiving.
+    fn robots_client(&self) -> &Client {
+        match self.archive {
+            Some(_) => &self.direct_client,
+            None => &self.client,
+        }
+    }
+
+    fn is_replay(&self) -> bool {
+        self.archive.as_ref().is_some_and(|a| a.is_replay())
+    }
+
+    /// Robots.txt rules of the host of `url`, fetched even when the server
+    /// does not respect them.
+    pub(crate) async fn robots(&self, url: &Url) -> Arc<RobotsTxt> {
+        self.politeness.robots(self.robots_client(), url).await
+    }
+
+    fn request<U: IntoUrl>(&self, method: Method, url: U) -> RequestBuilder {
+        self.client
+            .request(method, url)
+            .headers(self.headers.clone())
+    }
 }
 
 pub struct ElementExtractor {
     document: Html,
+    /// URL the document was fetched from.
+    page_url: Option<Url>,
+    /// URL relative links resolve against: `<base href>` or the page URL.
+    base_url: Option<Url>,
+}
+
+/// What a link points to, relative to the page it is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    /// Another location on the same site.
+    Internal,
+    /// A different site.
+    External,
+    /// A fragment of the page itself.
+    Anchor,
+    Mailto,
+    /// Any other scheme, e.g. `tel:` or `javascript:`.
+    Other,
 }
 
 impl ElementExtractor {
     pub fn new(html: &str) -> Self {
+        let document = Html::parse_document(html);
+        let base_url = document_base_url(&document, None);
         Self {
-            document: Html::parse_document(html),
+            document,
+            page_url: None,
+            base_url,
         }
     }
 
+    /// Set the URL the document was fetched from, used together with any
+    /// `<base href>` to resolve relative links.
+    pub fn with_page_url(mut self, page_url: Option<Url>) -> Self {
+        self.base_url = document_base_url(&self.document, page_url.as_ref());
+        self.page_url = page_url;
+        self
+    }
+
+    /// The URL relative links on the page resolve against.
+    pub fn base_url(&self) -> Option<&Url> {
+        self.base_url.as_ref()
+    }
+
     /// Extract elements using CSS selectors
     pub fn select_elements(&self, selector: &str) -> Result<Vec<Value>> {
         let css_selector = Selector::parse(selector)
@@ -68,6 +564,11 @@ impl ElementExtractor {
         Ok(elements)
     }
 
+    /// Evaluate an XPath 1.0 expression against the document
+    pub fn evaluate_xpath(&self, expression: &str) -> Result<XPathResult> {
+        crate::xpath::evaluate(&self.document, expression)
+    }
+
     /// Extract text content from elements matching a selector
     pub fn extract_text(&self, selector: &str) -> Result<Vec<String>> {
         let css_selector = Selector::parse(selector)
@@ -113,7 +614,8 @@ impl ElementExtractor {
                         return Some(json!({
                             "href": href,
                             "text": text,
-                            "absolute_url": self.resolve_url(href)
+                            "absolute_url": self.resolve_url(href),
+                            "kind": self.classify_link(href)
                         }));
                     }
                 }
@@ -133,17 +635,28 @@ impl ElementExtractor {
             .document
             .select(&selector)
             .filter_map(|element| {
-                if let Some(src) = element.value().attr("src") {
-                    if !src.trim().is_empty() {
-                        return Some(json!({
-                            "src": src,
-                            "alt": element.value().attr("alt").unwrap_or(""),
-                            "title": element.value().attr("title").unwrap_or(""),
-                            "absolute_url": self.resolve_url(src)
-                        }));
-                    }
+                let src = element.value().attr("src").unwrap_or("");
+                let srcset: Vec<Value> = parse_srcset(element.value().attr("srcset").unwrap_or(""))
+                    .into_iter()
+                    .map(|(url, descriptor)| {
+                        json!({
+                            "url": self.resolve_url(&url),
+                            "descriptor": descriptor
+                        })
+                    })
+                    .collect();
+                if src.trim().is_empty() && srcset.is_empty() {
+                    return None;
                 }
-                None
+                let absolute_url = (!src.trim().is_empty()).then(|| self.resolve_url(src));
+                Some(json!({
+                    "src": src,
+                    "alt": element.value().attr("alt").unwrap_or(""),
+                    "title": element.value().attr("title").unwrap_or(""),
+                    "absolute_url": absolute_url,
+                    "srcset": srcset,
+                    "sizes": element.value().attr("sizes")
+                }))
             })
             .collect();
 
@@ -156,6 +669,8 @@ impl ElementExtractor {
             .map_err(|e| anyhow::anyhow!("Failed to parse form selector: {}", e))?;
         let input_selector = Selector::parse("input, select, textarea")
             .map_err(|e| anyhow::anyhow!("Failed to parse input selector: {}", e))?;
+        let option_selector = Selector::parse("option")
+            .map_err(|e| anyhow::anyhow!("Failed to parse option selector: {}", e))?;
 
         let forms: Vec<Value> = self
             .document
@@ -167,12 +682,29 @@ impl ElementExtractor {
                 let fields: Vec<Value> = form
                     .select(&input_selector)
                     .map(|field| {
+                        // The value a browser would submit without user input
+                        let value = match field.value().name() {
+                            "textarea" => Some(field.text().collect::<String>()),
+                            "select" => {
+                                let mut options = field.select(&option_selector);
+                                options
+                                    .clone()
+                                    .find(|o| o.value().attr("selected").is_some())
+                                    .or_else(|| options.next())
+                                    .map(|o| match o.value().attr("value") {
+                                        Some(value) => value.to_string(),
+                                        None => self.clean_text(&o.text().collect::<String>()),
+                                    })
+                            }
+                            _ => field.value().attr("value").map(|v| v.to_string()),
+                        };
                         json!({
                             "name": field.value().attr("name"),
                             "type": field.value().attr("type"),
-                            "value": field.value().attr("value"),
+                            "value": value,
                             "placeholder": field.value().attr("placeholder"),
                             "required": field.value().attr("required").is_some(),
+                            "checked": field.value().attr("checked").is_some(),
                             "tag": field.value().name()
                         })
                     })
@@ -191,126 +723,29 @@ impl ElementExtractor {
 
     /// Extract tables with headers and data
     pub fn extract_tables(&self) -> Result<Vec<Value>> {
+        self.extract_tables_with(&TableOptions::default())
+    }
+
+    /// Extract tables with spans expanded, in the given format
+    pub fn extract_tables_with(&self, options: &TableOptions) -> Result<Vec<Value>> {
         let table_selector = Selector::parse("table")
             .map_err(|e| anyhow::anyhow!("Failed to parse table selector: {}", e))?;
-        let header_selector = Selector::parse("thead tr th, tr:first-child th")
-            .map_err(|e| anyhow::anyhow!("Failed to parse header selector: {}", e))?;
-        let row_selector = Selector::parse("tbody tr, tr")
-            .map_err(|e| anyhow::anyhow!("Failed to parse row selector: {}", e))?;
-        let cell_selector = Selector::parse("td, th")
-            .map_err(|e| anyhow::anyhow!("Failed to parse cell selector: {}", e))?;
 
         let tables: Vec<Value> = self
             .document
             .select(&table_selector)
-            .filter_map(|table| {
-                // Extract headers - try multiple strategies
-                let mut headers: Vec<String> = table
-                    .select(&header_selector)
-                    .map(|th| self.clean_text(&th.text().collect::<String>()))
-                    .filter(|h| !h.is_empty())
-                    .collect();
-
-                // If no headers found in thead, try first row
-                if headers.is_empty() {
-                    if let Some(first_row) = table.select(&row_selector).next() {
-                        headers = first_row
-                            .select(&cell_selector)
-                            .map(|cell| self.clean_text(&cell.text().collect::<String>()))
-                            .filter(|h| !h.is_empty())
-                            .collect();
-                    }
-                }
-
-                // Extract data rows - skip header row if we found headers
-                let rows: Vec<Vec<String>> = table
-                    .select(&row_selector)
-                    .skip(if !headers.is_empty() { 1 } else { 0 })
-                    .map(|row| {
-                        row.select(&cell_selector)
-                            .map(|cell| {
-                                // Get both inner HTML and text content for better cleaning
-                                let text_content = cell.text().collect::<String>();
-                                let inner_html = cell.inner_html();
-                                
-                                // If cell has significant HTML content, use readability
-                                if inner_html.len() > text_content.len() + 20 {
-                                    self.clean_text(&inner_html)
-                                } else {
-                                    self.clean_text(&text_content)
-                                }
-                            })
-                            .collect()
-                    })
-                    .filter(|row: &Vec<String>| !row.is_empty() && row.iter().any(|cell| !cell.is_empty()))
-                    .collect();
-
-                // Only return tables with meaningful data
-                if !rows.is_empty() || !headers.is_empty() {
-                    Some(json!({
-                        "headers": headers,
-                        "rows": rows
-                    }))
-                } else {
-                    None
-                }
-            })
+            .filter_map(Table::parse)
+            .map(|table| table.to_json(options))
             .collect();
 
         Ok(tables)
     }
 
-    /// Extract metadata from the page
+    /// Extract metadata from the page: title, description, Open Graph and
+    /// Twitter properties, canonical and alternate links, feeds and icons.
     pub fn extract_metadata(&self) -> Value {
-        let title = self
-            .extract_text("title")
-            .unwrap_or_default()
-            .first()
-            .cloned()
-            .unwrap_or_default();
-        let description = self
-            .extract_attributes("meta[name='description']", "content")
-            .unwrap_or_default()
-            .first()
-            .cloned()
-            .unwrap_or_default();
-        let keywords = self
-            .extract_attributes("meta[name='keywords']", "content")
-            .unwrap_or_default()
-            .first()
-            .cloned()
-            .unwrap_or_default();
-
-        // Extract Open Graph metadata
-        let og_title = self
-            .extract_attributes("meta[property='og:title']", "content")
-            .unwrap_or_default()
-            .first()
-            .cloned()
-            .unwrap_or_default();
-        let og_description = self
-            .extract_attributes("meta[property='og:description']", "content")
-            .unwrap_or_default()
-            .first()
-            .cloned()
-            .unwrap_or_default();
-        let og_image = self
-            .extract_attributes("meta[property='og:image']", "content")
-            .unwrap_or_default()
-            .first()
-            .cloned()
-            .unwrap_or_default();
-
-        json!({
-            "title": title,
-            "description": description,
-            "keywords": keywords,
-            "open_graph": {
-                "title": og_title,
-                "description": og_description,
-                "image": og_image
-            }
-        })
+        let metadata = metadata::extract(&self.document, &|href| self.resolve_url(href));
+        serde_json::to_value(metadata).unwrap_or_default()
     }
 
     /// Search for text patterns using regex
@@ -326,31 +761,25 @@ impl ElementExtractor {
         Ok(matches)
     }
 
-    /// Extract structured data (JSON-LD, microdata)
-    pub fn extract_structured_data(&self) -> Result<Vec<Value>> {
-        let mut structured_data = Vec::new();
-
-        // Extract JSON-LD
-        let json_ld_texts = self.extract_text("script[type='application/ld+json']")?;
-        for text in json_ld_texts {
-            if let Ok(data) = serde_json::from_str::<Value>(&text) {
-                structured_data.push(json!({
-                    "type": "json-ld",
-                    "data": data
-                }));
-            }
-        }
+    /// Apply a declarative extraction schema and return its records.
+    pub fn extract_schema(&self, schema: &ExtractionSchema) -> Vec<Value> {
+        schema.extract(self.document.root_element(), &|href| self.resolve_url(href))
+    }
 
-        // Extract microdata
-        let microdata_elements = self.select_elements("[itemscope]")?;
-        for element in microdata_elements {
-            structured_data.push(json!({
-                "type": "microdata",
-                "data": element
-            }));
-        }
+    /// Extract structured data (JSON-LD, microdata, RDFa Lite) as
+    /// schema.org-style items
+    pub fn extract_structured_data(&self) -> Result<Vec<Value>> {
+        self.extract_structured_data_of_types(&[])
+    }
 
-        Ok(structured_data)
+    /// Extract structured data items of the given schema.org types, such as
+    /// `Product` or `Article`; every item when `types` is empty
+    pub fn extract_structured_data_of_types(&self, types: &[String]) -> Result<Vec<Value>> {
+        Ok(structured_data::extract(
+            &self.document,
+            &|href| self.resolve_url(href),
+            types,
+        ))
     }
 
     fn element_to_json(&self, element: ElementRef) -> Value {
@@ -375,14 +804,53 @@ impl ElementExtractor {
         })
     }
 
-    fn resolve_url(&self, relative_url: &str) -> String {
-        if relative_url.starts_with("http") {
-            return relative_url.to_string();
+    /// Resolve `href` against the base URL. Without one, only absolute URLs
+    /// can be normalized and anything else is returned unchanged.
+    fn resolve_url(&self, href: &str) -> String {
+        let href = href.trim();
+        let resolved = match &self.base_url {
+            Some(base_url) => base_url.join(href),
+            None => Url::parse(href),
+        };
+        resolved
+            .map(|url| url.to_string())
+            .unwrap_or_else(|_| href.to_string())
+    }
+
+    fn classify_link(&self, href: &str) -> LinkKind {
+        let href = href.trim();
+        if href.starts_with('#') {
+            return LinkKind::Anchor;
         }
+        let resolved = match &self.base_url {
+            Some(base_url) => base_url.join(href),
+            None => Url::parse(href),
+        };
+        let Ok(url) = resolved else {
+            // Relative link on a page with an unknown URL
+            return LinkKind::Internal;
+        };
 
-        // This is a simplified URL resolution
-        // In a real implementation, you'd want more robust URL handling
-        relative_url.to_string()
+        match url.scheme() {
+            "mailto" => LinkKind::Mailto,
+            "http" | "https" => {
+                let Some(page_url) = self.page_url.as_ref().or(self.base_url.as_ref()) else {
+                    return LinkKind::External;
+                };
+                let mut without_fragment = url.clone();
+                without_fragment.set_fragment(None);
+                let mut page = page_url.clone();
+                page.set_fragment(None);
+                if url.fragment().is_some() && without_fragment == page {
+                    LinkKind::Anchor
+                } else if same_site(&url, page_url) {
+                    LinkKind::Internal
+                } else {
+                    LinkKind::External
+                }
+            }
+            _ => LinkKind::Other,
+        }
     }
 
     /// Clean text by removing extra whitespace and normalizing using readability
@@ -415,6 +883,44 @@ impl ElementExtractor {
         normalized.trim().to_string()
     }
 
+    /// Render the main content of the page as Markdown. With a page URL the
+    /// content is picked by readability, otherwise the whole document is
+    /// rendered.
+    pub fn extract_markdown(&self, options: &MarkdownOptions) -> Result<String> {
+        let Some(page_url) = &self.page_url else {
+            return Ok(markdown::to_markdown(
+                &self.document,
+                self.base_url.as_ref(),
+                options,
+            ));
+        };
+        let article = extractor::extract(&mut self.document.html().as_bytes(), page_url)
+            .map_err(|e| anyhow::anyhow!("Failed to extract readable content: {}", e))?;
+        Ok(markdown::to_markdown(
+            &Html::parse_fragment(&article.content),
+            self.base_url.as_ref(),
+            options,
+        ))
+    }
+
+    /// The main text of the page, picked by readability when the page URL
+    /// is known and the whole body otherwise.
+    pub fn extract_main_text(&self) -> Result<String> {
+        let Some(page_url) = &self.page_url else {
+            let body = Selector::parse("body")
+                .map_err(|e| anyhow::anyhow!("Invalid CSS selector: {}", e))?;
+            return Ok(self
+                .document
+                .select(&body)
+                .next()
+                .map(|body| body.text().collect::<String>().trim().to_string())
+                .unwrap_or_default());
+        };
+        let article = extractor::extract(&mut self.document.html().as_bytes(), page_url)
+            .map_err(|e| anyhow::anyhow!("Failed to extract readable content: {}", e))?;
+        Ok(article.text)
+    }
+
     /// Extract clean readable content using readability
     pub fn extract_readable_content(&self, base_url: &str) -> Result<Value> {
         let html_content = self.document.html();
@@ -438,6 +944,75 @@ impl ElementExtractor {
     }
 }
 
+/// Base URL of `document`: its `<base href>` resolved against the page URL,
+/// falling back to the page URL itself.
+pub(crate) fn document_base_url(document: &Html, page_url: Option<&Url>) -> Option<Url> {
+    let href = Selector::parse("base[href]").ok().and_then(|selector| {
+        document
+            .select(&selector)
+            .next()
+            .and_then(|base| base.value().attr("href"))
+            .map(|href| href.trim().to_string())
+    });
+    match (href, page_url) {
+        (Some(href), Some(page_url)) => page_url.join(&href).ok().or(Some(page_url.clone())),
+        (Some(href), None) => Url::parse(&href).ok(),
+        (None, page_url) => page_url.cloned(),
+    }
+}
+
+/// Whether two URLs are on the same host, ignoring a leading `www.`.
+fn same_site(a: &Url, b: &Url) -> bool {
+    match (a.host_str(), b.host_str()) {
+        (Some(a), Some(b)) => a.trim_start_matches("www.") == b.trim_start_matches("www."),
+        _ => false,
+    }
+}
+
+/// Parse an `srcset` attribute into image URLs and their width (`480w`) or
+/// pixel density (`2x`) descriptors.
+pub fn parse_srcset(srcset: &str) -> Vec<(String, Option<String>)> {
+    let mut candidates = Vec::new();
+    let mut rest = srcset;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        if rest.is_empty() {
+            break;
+        }
+
+        let url_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let (url, after) = rest.split_at(url_end);
+        // A URL directly followed by a comma has no descriptor
+        if url.ends_with(',') {
+            candidates.push((url.trim_end_matches(',').to_string(), None));
+            rest = after;
+            continue;
+        }
+
+        // Descriptors run to the next comma outside of parentheses
+        let mut depth = 0usize;
+        let mut descriptor_end = after.len();
+        for (i, c) in after.char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth = depth.saturating_sub(1),
+                ',' if depth == 0 => {
+                    descriptor_end = i;
+                    break;
+                }
+                _ => {}
+            }
+        }
+        let descriptor = after[..descriptor_end].trim();
+        candidates.push((
+            url.to_string(),
+            (!descriptor.is_empty()).then(|| descriptor.to_string()),
+        ));
+        rest = &after[descriptor_end..];
+    }
+    candidates
+}
+
 pub struct FormSubmitter {
     session: ScrapingSession,
 }
@@ -454,22 +1029,66 @@ impl FormSubmitter {
         method: &str,
         data: HashMap<String, String>,
     ) -> Result<String> {
-        let response = match method.to_uppercase().as_str() {
-            "POST" => {
-                self.session
-                    .client
-                    .post(form_action)
-                    .form(&data)
-                    .send()
-                    .await?
+        let data: Vec<(String, String)> = data.into_iter().collect();
+        let response = self.session.submit_form(form_action, method, &data).await?;
+
+        self.session.read_text(response).await
+    }
+
+    /// Name/value pairs a browser would submit for a form from
+    /// `ElementExtractor::extract_forms`, with `values` filling in or
+    /// overriding fields by name. Array values submit the name repeatedly.
+    pub fn form_data(form: &Value, values: &Map<String, Value>) -> Vec<(String, String)> {
+        let mut data = Vec::new();
+        let mut supplied = HashSet::new();
+        let fields = form["fields"].as_array().cloned().unwrap_or_default();
+        for field in &fields {
+            let Some(name) = field["name"].as_str().filter(|n| !n.is_empty()) else {
+                continue;
+            };
+            if let Some(value) = values.get(name) {
+                // Radio groups and repeated names are submitted once
+                if supplied.insert(name.to_string()) {
+                    data.extend(
+                        form_values(value)
+                            .into_iter()
+                            .map(|v| (name.to_string(), v)),
+                    );
+                }
+                continue;
             }
-            _ => {
-                let url = Url::parse_with_params(form_action, data.iter())?;
-                self.session.client.get(url).send().await?
+
+            let field_type = field["type"].as_str().unwrap_or("text").to_lowercase();
+            let value = field["value"].as_str();
+            match field_type.as_str() {
+                "submit" | "button" | "reset" | "image" | "file" => {}
+                "checkbox" | "radio" => {
+                    if field["checked"].as_bool().unwrap_or(false) {
+                        data.push((name.to_string(), value.unwrap_or("on").to_string()));
+                    }
+                }
+                _ => data.push((name.to_string(), value.unwrap_or_default().to_string())),
             }
-        };
+        }
 
-        Ok(response.text().await?)
+        // Values for names the form does not declare are sent as well
+        for (name, value) in values {
+            let declared = fields.iter().any(|f| f["name"].as_str() == Some(name));
+            if !declared {
+                data.extend(form_values(value).into_iter().map(|v| (name.clone(), v)));
+            }
+        }
+        data
+    }
+}
+
+/// Strings submitted for a form value; arrays submit every item.
+fn form_values(value: &Value) -> Vec<String> {
+    match value {
+        Value::Array(items) => items.iter().flat_map(form_values).collect(),
+        Value::Null => vec![],
+        Value::String(s) => vec![s.clone()],
+        other => vec![other.to_string()],
     }
 }
 
@@ -478,29 +1097,11 @@ impl FormSubmitter {
 pub struct XPathAlternative;
 
 impl XPathAlternative {
-    /// Convert common XPath expressions to CSS selectors
+    /// Convert an XPath expression to an equivalent CSS selector. Fails for
+    /// expressions CSS cannot express exactly; evaluate those with
+    /// [`ElementExtractor::evaluate_xpath`] instead.
     pub fn xpath_to_css(xpath: &str) -> Result<String> {
-        let mut css = xpath.to_string();
-
-        // First handle // to remove it
-        if css.starts_with("//") {
-            css = css.replace("//", "");
-        } else if css.starts_with("/") {
-            css = css.trim_start_matches('/').replace("/", " > ");
-        }
-
-        // Handle attribute selection [@attr] -> [attr]
-        if css.contains("[@") {
-            css = css.replace("[@", "[");
-        }
-
-        // Handle position selectors [1] -> :nth-child(1)
-        if css.contains("[") && !css.contains("=") {
-            let re = Regex::new(r"\[(\d+)\]")?;
-            css = re.replace_all(&css, ":nth-child($1)").to_string();
-        }
-
-        Ok(css)
+        crate::xpath::to_css(xpath)
     }
 
     /// Get XPath alternatives for common use cases
@@ -516,8 +1117,8 @@ impl XPathAlternative {
             "span[class*='highlight']",
         );
         patterns.insert("//div[@id='content']", "div#content");
-        patterns.insert("//p[1]", "p:first-child");
-        patterns.insert("//li[last()]", "li:last-child");
+        patterns.insert("//p[1]", "p:first-of-type");
+        patterns.insert("//li[last()]", "li:last-of-type");
         patterns.insert("//table//tr", "table tr");
         patterns.insert("//form//input", "form input");
 
@@ -1,18 +1,41 @@
-use crate::scraper_tools::{ElementExtractor, ScrapingSession, XPathAlternative};
+use crate::archive::{ArchiveMode, ExportFormat};
+use crate::cache::CacheStatus;
+use crate::chunking::{chunk_text, estimate_tokens, ChunkOptions};
+use crate::config::CrawlerConfig;
+use crate::crawler::{CrawlOptions, CrawlScope, Crawler};
+use crate::feed;
+use crate::fetch::{ContentKind, HttpError, ResponseInfo};
+use crate::jobs::ProgressReporter;
+use crate::link_checker::{LinkCheckOptions, LinkChecker};
+use crate::markdown::{self, MarkdownOptions};
+use crate::monitor::{MonitorOptions, NoiseFilter};
+use crate::pagination::{self, NextPage, PageExtraction, PaginationOptions, Paginator};
+use crate::schema::ExtractionSchema;
+use crate::scraper_tools::{
+    document_base_url, ElementExtractor, FetchedPage, FormSubmitter, XPathAlternative,
+};
+use crate::sitemap::{self, SitemapOptions, SitemapReader};
+use crate::state::CrawlerState;
+use crate::tables::{TableFormat, TableOptions};
 use anyhow::{Context, Result};
 use async_mcp::server::{Server, ServerBuilder};
-use async_mcp::transport::Transport;
+use async_mcp::transport::{JsonRpcMessage, JsonRpcNotification, Transport};
 use async_mcp::types::{
     CallToolRequest, CallToolResponse, ListRequest, PromptsListResponse, Resource,
     ResourcesListResponse, ServerCapabilities, Tool, ToolResponseContent,
 };
 use readability::extractor::extract;
+use regex::Regex;
+use scraper::Html;
 use serde_json::json;
-use spider::website::Website;
-use tracing::info;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info};
 use url::Url;
 
-pub fn build<T: Transport>(t: T) -> Result<Server<T>> {
+pub fn build<T: Transport + Clone>(t: T, config: CrawlerConfig) -> Result<Server<T>> {
+    let transport = t.clone();
     let mut server = Server::builder(t)
         .capabilities(ServerCapabilities {
             tools: Some(json!({})),
@@ -31,7 +54,24 @@ pub fn build<T: Transport>(t: T) -> Result<Server<T>> {
             })
         });
 
-    register_tools(&mut server)?;
+    let mut state = CrawlerState::new(config);
+    if let Some(archive) = ArchiveMode::from_config(&state.config)? {
+        state = state.with_archive(archive);
+    }
+    let state = state.with_notifier(Arc::new(move |method, params| {
+        let transport = transport.clone();
+        Box::pin(async move {
+            let notification = JsonRpcMessage::Notification(JsonRpcNotification {
+                jsonrpc: "2.0".to_string(),
+                method,
+                params: Some(params),
+            });
+            if let Err(e) = transport.send(&notification).await {
+                debug!("Failed to send a notification: {}", e);
+            }
+        })
+    }));
+    register_tools(&mut server, Arc::new(state))?;
 
     let server = server.build();
 
@@ -63,29 +103,57 @@ fn list_resources() -> ResourcesListResponse {
     }
 }
 
-fn register_tools<T: Transport>(server: &mut ServerBuilder<T>) -> Result<()> {
-    register_scrape_tool(server)?;
-    register_select_elements_tool(server)?;
-    register_extract_text_tool(server)?;
-    register_extract_attributes_tool(server)?;
-    register_extract_links_tool(server)?;
-    register_extract_images_tool(server)?;
-    register_extract_forms_tool(server)?;
-    register_extract_tables_tool(server)?;
-    register_extract_metadata_tool(server)?;
-    register_search_patterns_tool(server)?;
-    register_extract_structured_data_tool(server)?;
+fn register_tools<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
+    register_scrape_tool(server, state.clone())?;
+    register_crawl_tool(server, state.clone())?;
+    register_start_crawl_tool(server, state.clone())?;
+    register_crawl_status_tool(server, state.clone())?;
+    register_crawl_results_tool(server, state.clone())?;
+    register_cancel_crawl_tool(server, state.clone())?;
+    register_get_sitemap_tool(server, state.clone())?;
+    register_scrape_paginated_tool(server, state.clone())?;
+    register_export_crawl_tool(server, state.clone())?;
+    register_monitor_tool(server, state.clone())?;
+    register_list_monitors_tool(server, state.clone())?;
+    register_remove_monitor_tool(server, state.clone())?;
+    register_check_links_tool(server, state.clone())?;
+    register_select_elements_tool(server, state.clone())?;
+    register_extract_text_tool(server, state.clone())?;
+    register_extract_attributes_tool(server, state.clone())?;
+    register_extract_links_tool(server, state.clone())?;
+    register_extract_images_tool(server, state.clone())?;
+    register_extract_forms_tool(server, state.clone())?;
+    register_extract_tables_tool(server, state.clone())?;
+    register_extract_metadata_tool(server, state.clone())?;
+    register_search_patterns_tool(server, state.clone())?;
+    register_extract_structured_data_tool(server, state.clone())?;
+    register_extract_schema_tool(server, state.clone())?;
     register_xpath_to_css_tool(server)?;
-    register_advanced_scrape_tool(server)?;
+    register_xpath_query_tool(server, state.clone())?;
+    register_advanced_scrape_tool(server, state.clone())?;
+    register_clear_cache_tool(server, state.clone())?;
+    register_open_session_tool(server, state.clone())?;
+    register_close_session_tool(server, state.clone())?;
+    register_submit_form_tool(server, state.clone())?;
+    register_get_chunk_tool(server, state.clone())?;
 
     Ok(())
 }
 
-fn register_scrape_tool<T: Transport>(server: &mut ServerBuilder<T>) -> Result<()> {
+fn register_scrape_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
     let scrape_tool = Tool {
         name: "scrape".to_string(),
         description: Some(
-            "Scrape a single webpage and extract basic content using readability".to_string(),
+            "Scrape a single webpage and extract its main content using readability, as HTML \
+             and text or as Markdown. JSON is returned as is, RSS/Atom feeds as items and \
+             plain text and PDFs as text"
+                .to_string(),
         ),
         input_schema: json!({
             "type": "object",
@@ -94,7 +162,12 @@ fn register_scrape_tool<T: Transport>(server: &mut ServerBuilder<T>) -> Result<(
                     "type": "string",
                     "description": "The URL to scrape",
                     "format": "uri"
-                }
+                },
+                "format": format_schema(),
+                "markdown_images": markdown_images_schema(),
+                "max_tokens": max_tokens_schema(),
+                "chunk_by": chunk_by_schema(),
+                "overlap_tokens": overlap_tokens_schema()
             },
             "required": ["url"],
             "additionalProperties": false
@@ -103,13 +176,22 @@ fn register_scrape_tool<T: Transport>(server: &mut ServerBuilder<T>) -> Result<(
             "type": "object",
             "properties": {
                 "content": {"type": "string"},
-                "text": {"type": "string"}
+                "text": {"type": "string"},
+                "title": {"type": "string"},
+                "markdown": {"type": "string"},
+                "json": {},
+                "feed": {"type": "object"},
+                "tokens": {"type": "integer"},
+                "total_chunks": {"type": "integer"},
+                "chunk": {"type": ["object", "null"]},
+                "next_cursor": {"type": ["string", "null"]}
             },
             "additionalProperties": false
         })),
     };
 
-    server.register_tool(scrape_tool, |req: CallToolRequest| {
+    server.register_tool(scrape_tool, move |req: CallToolRequest| {
+        let state = state.clone();
         Box::pin(async move {
             let args = req.arguments.unwrap_or_default();
             let result: Result<CallToolResponse, anyhow::Error> = async {
@@ -117,52 +199,34 @@ fn register_scrape_tool<T: Transport>(server: &mut ServerBuilder<T>) -> Result<(
                     .get("url")
                     .and_then(|v| v.as_str())
                     .context("url is missing")?;
-
-                let mut website = Website::new(url)
-                    .with_user_agent(Some("DistriBot"))
-                    .with_subdomains(false)
-                    .with_limit(1)
-                    .with_tld(false)
-                    .with_redirect_limit(3)
-                    .with_respect_robots_txt(false)
-                    .build()?;
-
-                website.scrape().await;
-
-                let page = website
-                    .get_pages()
-                    .map(|pages| pages.iter().next())
-                    .flatten();
-
-                let mut is_error = None;
-                let content = if let Some(page) = page {
-                    if let Some(input) = page.get_bytes() {
-                        let url = Url::parse(url)?;
-                        let mut reader = std::io::Cursor::new(input);
-                        let product = extract(&mut reader, &url)?;
-                        json!({
-                            "content": product.content,
-                            "text": product.text,
-                        })
-                    } else {
-                        is_error = Some(true);
-                        json!({
-                            "error": "No page content available",
-                        })
+                let content_args = content_args(&args)?;
+
+                let page = state.session()?.fetch(url).await?.error_for_status()?;
+                let content = match page.response.kind {
+                    ContentKind::Html => scrape_html(&state, &page, &content_args)?,
+                    ContentKind::Json => json!({
+                        "json": serde_json::from_str::<serde_json::Value>(&page.html)
+                            .context("Invalid JSON body")?,
+                    }),
+                    ContentKind::Feed => json!({
+                        "feed": feed::parse(&page.html, Some(&page.url))?,
+                    }),
+                    ContentKind::Pdf | ContentKind::Text => {
+                        content_args.output(&state, "text", page.html.clone())
                     }
-                } else {
-                    is_error = Some(true);
-                    json!({
-                        "error": "No page found",
-                    })
+                    ContentKind::Binary => anyhow::bail!(
+                        "Cannot scrape {}: {} content has no text",
+                        page.url,
+                        page.response.content_type.as_deref().unwrap_or("binary")
+                    ),
                 };
 
                 Ok(CallToolResponse {
                     content: vec![ToolResponseContent::Text {
                         text: serde_json::to_string_pretty(&content)?,
                     }],
-                    is_error: is_error,
-                    meta: None,
+                    is_error: None,
+                    meta: Some(page_meta(page.cache_status, &page.response)),
                 })
             }
             .await;
@@ -174,33 +238,123 @@ fn register_scrape_tool<T: Transport>(server: &mut ServerBuilder<T>) -> Result<(
     Ok(())
 }
 
-fn register_select_elements_tool<T: Transport>(server: &mut ServerBuilder<T>) -> Result<()> {
+/// Main content of an HTML page with readability, as HTML and text or as
+/// Markdown.
+fn scrape_html(
+    state: &CrawlerState,
+    page: &FetchedPage,
+    content_args: &ContentArgs,
+) -> Result<serde_json::Value> {
+    let product = extract(&mut page.html.as_bytes(), &page.url)?;
+    let mut content = match &content_args.markdown {
+        Some(options) => {
+            let document = Html::parse_document(&page.html);
+            let base_url = document_base_url(&document, Some(&page.url));
+            let markdown = markdown::to_markdown(
+                &Html::parse_fragment(&product.content),
+                base_url.as_ref(),
+                options,
+            );
+            content_args.output(state, "markdown", markdown)
+        }
+        None if content_args.chunking.is_some() => content_args.output(state, "text", product.text),
+        None => json!({
+            "content": product.content,
+            "text": product.text,
+        }),
+    };
+    if content_args.markdown.is_some() || content_args.chunking.is_some() {
+        content["title"] = json!(product.title);
+    }
+    Ok(content)
+}
+
+fn register_crawl_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
     let tool = Tool {
-        name: "select_elements".to_string(),
-        description: Some("Select elements from HTML using CSS selectors".to_string()),
+        name: "crawl".to_string(),
+        description: Some(
+            "Crawl a website by following links from a seed URL and return a site map of the \
+             visited pages with titles, status codes and text excerpts. With --archive-dir, \
+             the job_id of the crawl can be passed to export_crawl"
+                .to_string(),
+        ),
         input_schema: json!({
             "type": "object",
             "properties": {
                 "url": {
                     "type": "string",
-                    "description": "The URL to scrape",
+                    "description": "The seed URL to start crawling from",
                     "format": "uri"
                 },
-                "selector": {
+                "session_id": {
                     "type": "string",
-                    "description": "CSS selector to match elements"
+                    "description": "Fetch within a session opened with open_session"
+                },
+                "max_pages": {
+                    "type": "integer",
+                    "description": "Maximum number of pages to visit",
+                    "default": 10,
+                    "minimum": 1,
+                    "maximum": 500
+                },
+                "max_depth": {
+                    "type": "integer",
+                    "description": "Maximum number of links to follow from the seed URL \
+                        (defaults to the server's --max-depth)",
+                    "minimum": 0
+                },
+                "include_patterns": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "Regular expressions; only URLs matching one of them are visited"
+                },
+                "exclude_patterns": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "Regular expressions; URLs matching any of them are skipped"
+                },
+                "same_domain": {
+                    "type": "boolean",
+                    "description": "Stay on the seed URL's domain",
+                    "default": true
+                },
+                "include_subdomains": {
+                    "type": "boolean",
+                    "description": "Also visit subdomains of the seed URL's domain \
+                        (defaults to the server's --subdomains)"
+                },
+                "extract_content": {
+                    "type": "boolean",
+                    "description": "Extract the main text of every page with readability",
+                    "default": true
+                },
+                "excerpt_length": {
+                    "type": "integer",
+                    "description": "Maximum number of characters in each page excerpt",
+                    "default": 300
                 }
             },
-            "required": ["url", "selector"],
+            "required": ["url"],
             "additionalProperties": false
         }),
         output_schema: Some(json!({
-            "type": "array",
-            "items": {"type": "object"}
+            "type": "object",
+            "properties": {
+                "seed": {"type": "string"},
+                "pages": {"type": "array", "items": {"type": "object"}},
+                "pages_crawled": {"type": "integer"},
+                "pages_failed": {"type": "integer"},
+                "pages_remaining": {"type": "integer"},
+                "job_id": {"type": "string"}
+            }
         })),
     };
 
-    server.register_tool(tool, |req: CallToolRequest| {
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
         Box::pin(async move {
             let args = req.arguments.unwrap_or_default();
             let result: Result<CallToolResponse, anyhow::Error> = async {
@@ -208,19 +362,29 @@ fn register_select_elements_tool<T: Transport>(server: &mut ServerBuilder<T>) ->
                     .get("url")
                     .and_then(|v| v.as_str())
                     .context("url is missing")?;
-                let selector = args
-                    .get("selector")
-                    .and_then(|v| v.as_str())
-                    .context("selector is missing")?;
+                let options = crawl_options_from_args(&args, &state.config)?;
 
-                let mut session = ScrapingSession::new()?;
-                let html = session.fetch_page(url).await?;
-                let extractor = ElementExtractor::new(&html);
-                let elements = extractor.select_elements(selector)?;
+                let session = state.session_for(session_id_arg(&args))?;
+                let mut session = session.lock().await;
+                let url = session.resolve(url)?;
+
+                // Tag what the crawl records so it can be exported on its own
+                let job_id = match &state.archive {
+                    Some(ArchiveMode::Record(_)) => Some(uuid::Uuid::new_v4().to_string()),
+                    _ => None,
+                };
+                session.set_job_id(job_id.clone());
+                let report = Crawler::new(options).run(&mut session, url.as_str()).await;
+                session.set_job_id(None);
+
+                let mut output = serde_json::to_value(report?)?;
+                if let Some(job_id) = job_id {
+                    output["job_id"] = json!(job_id);
+                }
 
                 Ok(CallToolResponse {
                     content: vec![ToolResponseContent::Text {
-                        text: serde_json::to_string_pretty(&elements)?,
+                        text: serde_json::to_string_pretty(&output)?,
                     }],
                     is_error: None,
                     meta: None,
@@ -235,53 +399,169 @@ fn register_select_elements_tool<T: Transport>(server: &mut ServerBuilder<T>) ->
     Ok(())
 }
 
-fn register_extract_text_tool<T: Transport>(server: &mut ServerBuilder<T>) -> Result<()> {
+/// Crawl options from tool arguments, falling back to the server config
+/// for depth and subdomains.
+pub(crate) fn crawl_options_from_args(
+    args: &HashMap<String, serde_json::Value>,
+    config: &CrawlerConfig,
+) -> Result<CrawlOptions> {
+    let defaults = CrawlOptions::default();
+    let same_domain = args
+        .get("same_domain")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true);
+    let include_subdomains = args
+        .get("include_subdomains")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(config.subdomains);
+    let scope = match (same_domain, include_subdomains) {
+        (false, _) => CrawlScope::Any,
+        (true, true) => CrawlScope::Subdomains,
+        (true, false) => CrawlScope::SameDomain,
+    };
+
+    Ok(CrawlOptions {
+        max_pages: args
+            .get("max_pages")
+            .and_then(|v| v.as_u64())
+            .map(|v| v.clamp(1, 500) as usize)
+            .unwrap_or(defaults.max_pages),
+        max_depth: args
+            .get("max_depth")
+            .and_then(|v| v.as_u64())
+            .map(|v| v as usize)
+            .unwrap_or(config.max_depth),
+        scope,
+        include_patterns: patterns_arg(args, "include_patterns")?,
+        exclude_patterns: patterns_arg(args, "exclude_patterns")?,
+        extract_content: args
+            .get("extract_content")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(defaults.extract_content),
+        excerpt_length: args
+            .get("excerpt_length")
+            .and_then(|v| v.as_u64())
+            .map(|v| v as usize)
+            .unwrap_or(defaults.excerpt_length),
+    })
+}
+
+/// Regular expressions given as an array of strings under `key`.
+fn patterns_arg(args: &HashMap<String, serde_json::Value>, key: &str) -> Result<Vec<Regex>> {
+    args.get(key)
+        .and_then(|v| v.as_array())
+        .map(|values| {
+            values
+                .iter()
+                .filter_map(|v| v.as_str())
+                .map(|p| Regex::new(p).with_context(|| format!("Invalid {} regex: {}", key, p)))
+                .collect()
+        })
+        .unwrap_or_else(|| Ok(vec![]))
+}
+
+fn register_start_crawl_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
     let tool = Tool {
-        name: "extract_text".to_string(),
-        description: Some("Extract text content from elements matching a CSS selector".to_string()),
+        name: "start_crawl".to_string(),
+        description: Some(
+            "Start crawling a website in the background, like crawl, and return a job_id at \
+             once. Follow the crawl with crawl_status, read the pages with crawl_results and \
+             stop it with cancel_crawl. Progress is sent as notifications/progress when the \
+             request has a progress token"
+                .to_string(),
+        ),
         input_schema: json!({
             "type": "object",
             "properties": {
                 "url": {
                     "type": "string",
-                    "description": "The URL to scrape",
+                    "description": "The seed URL to start crawling from",
                     "format": "uri"
                 },
-                "selector": {
+                "session_id": {
                     "type": "string",
-                    "description": "CSS selector to match elements"
+                    "description": "Fetch within a session opened with open_session; other \
+                        calls using the session wait until the crawl ends"
+                },
+                "max_pages": {
+                    "type": "integer",
+                    "description": "Maximum number of pages to visit",
+                    "default": 10,
+                    "minimum": 1,
+                    "maximum": 500
+                },
+                "max_depth": {
+                    "type": "integer",
+                    "description": "Maximum number of links to follow from the seed URL \
+                        (defaults to the server's --max-depth)",
+                    "minimum": 0
+                },
+                "include_patterns": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "Regular expressions; only URLs matching one of them are visited"
+                },
+                "exclude_patterns": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "Regular expressions; URLs matching any of them are skipped"
+                },
+                "same_domain": {
+                    "type": "boolean",
+                    "description": "Stay on the seed URL's domain",
+                    "default": true
+                },
+                "include_subdomains": {
+                    "type": "boolean",
+                    "description": "Also visit subdomains of the seed URL's domain \
+                        (defaults to the server's --subdomains)"
+                },
+                "extract_content": {
+                    "type": "boolean",
+                    "description": "Extract the main text of every page with readability",
+                    "default": true
+                },
+                "excerpt_length": {
+                    "type": "integer",
+                    "description": "Maximum number of characters in each page excerpt",
+                    "default": 300
                 }
             },
-            "required": ["url", "selector"],
+            "required": ["url"],
             "additionalProperties": false
         }),
-        output_schema: Some(json!({
-            "type": "array",
-            "items": {"type": "string"}
-        })),
+        output_schema: Some(job_status_schema()),
     };
 
-    server.register_tool(tool, |req: CallToolRequest| {
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
         Box::pin(async move {
+            let progress_token = req
+                .meta
+                .as_ref()
+                .and_then(|meta| meta.get("progressToken"))
+                .cloned();
             let args = req.arguments.unwrap_or_default();
             let result: Result<CallToolResponse, anyhow::Error> = async {
                 let url = args
                     .get("url")
                     .and_then(|v| v.as_str())
                     .context("url is missing")?;
-                let selector = args
-                    .get("selector")
-                    .and_then(|v| v.as_str())
-                    .context("selector is missing")?;
+                let options = crawl_options_from_args(&args, &state.config)?;
 
-                let mut session = ScrapingSession::new()?;
-                let html = session.fetch_page(url).await?;
-                let extractor = ElementExtractor::new(&html);
-                let texts = extractor.extract_text(selector)?;
+                let session = state.session_for(session_id_arg(&args))?;
+                let url = session.lock().await.resolve(url)?;
+                let reporter = progress_token
+                    .zip(state.notifier.clone())
+                    .map(|(token, notifier)| ProgressReporter::new(token, notifier));
+                let status = state.jobs.start(session, url, options, reporter);
 
                 Ok(CallToolResponse {
                     content: vec![ToolResponseContent::Text {
-                        text: serde_json::to_string_pretty(&texts)?,
+                        text: serde_json::to_string_pretty(&status)?,
                     }],
                     is_error: None,
                     meta: None,
@@ -296,63 +576,45 @@ fn register_extract_text_tool<T: Transport>(server: &mut ServerBuilder<T>) -> Re
     Ok(())
 }
 
-fn register_extract_attributes_tool<T: Transport>(server: &mut ServerBuilder<T>) -> Result<()> {
+fn register_crawl_status_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
     let tool = Tool {
-        name: "extract_attributes".to_string(),
+        name: "crawl_status".to_string(),
         description: Some(
-            "Extract specific attribute values from elements matching a CSS selector".to_string(),
+            "Report how far a crawl started with start_crawl has got: its state and the \
+             number of pages done, failed and queued"
+                .to_string(),
         ),
         input_schema: json!({
             "type": "object",
             "properties": {
-                "url": {
-                    "type": "string",
-                    "description": "The URL to scrape",
-                    "format": "uri"
-                },
-                "selector": {
-                    "type": "string",
-                    "description": "CSS selector to match elements"
-                },
-                "attribute": {
+                "job_id": {
                     "type": "string",
-                    "description": "Attribute name to extract"
+                    "description": "The job_id returned by start_crawl"
                 }
             },
-            "required": ["url", "selector", "attribute"],
+            "required": ["job_id"],
             "additionalProperties": false
         }),
-        output_schema: Some(json!({
-            "type": "array",
-            "items": {"type": "string"}
-        })),
+        output_schema: Some(job_status_schema()),
     };
 
-    server.register_tool(tool, |req: CallToolRequest| {
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
         Box::pin(async move {
             let args = req.arguments.unwrap_or_default();
             let result: Result<CallToolResponse, anyhow::Error> = async {
-                let url = args
-                    .get("url")
-                    .and_then(|v| v.as_str())
-                    .context("url is missing")?;
-                let selector = args
-                    .get("selector")
-                    .and_then(|v| v.as_str())
-                    .context("selector is missing")?;
-                let attribute = args
-                    .get("attribute")
+                let job_id = args
+                    .get("job_id")
                     .and_then(|v| v.as_str())
-                    .context("attribute is missing")?;
-
-                let mut session = ScrapingSession::new()?;
-                let html = session.fetch_page(url).await?;
-                let extractor = ElementExtractor::new(&html);
-                let attributes = extractor.extract_attributes(selector, attribute)?;
+                    .context("job_id is missing")?;
+                let status = state.jobs.status(job_id)?;
 
                 Ok(CallToolResponse {
                     content: vec![ToolResponseContent::Text {
-                        text: serde_json::to_string_pretty(&attributes)?,
+                        text: serde_json::to_string_pretty(&status)?,
                     }],
                     is_error: None,
                     meta: None,
@@ -367,45 +629,74 @@ fn register_extract_attributes_tool<T: Transport>(server: &mut ServerBuilder<T>)
     Ok(())
 }
 
-fn register_extract_links_tool<T: Transport>(server: &mut ServerBuilder<T>) -> Result<()> {
+fn register_crawl_results_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
     let tool = Tool {
-        name: "extract_links".to_string(),
-        description: Some("Extract all links from a webpage".to_string()),
+        name: "crawl_results".to_string(),
+        description: Some(
+            "Read the pages a crawl started with start_crawl has visited, a slice at a time, \
+             while it runs or after it ended"
+                .to_string(),
+        ),
         input_schema: json!({
             "type": "object",
             "properties": {
-                "url": {
+                "job_id": {
                     "type": "string",
-                    "description": "The URL to scrape",
-                    "format": "uri"
+                    "description": "The job_id returned by start_crawl"
+                },
+                "offset": {
+                    "type": "integer",
+                    "description": "Number of pages to skip, as given by next_offset",
+                    "default": 0,
+                    "minimum": 0
+                },
+                "limit": {
+                    "type": "integer",
+                    "description": "Maximum number of pages to return",
+                    "default": 20,
+                    "minimum": 1,
+                    "maximum": 100
                 }
             },
-            "required": ["url"],
+            "required": ["job_id"],
             "additionalProperties": false
         }),
         output_schema: Some(json!({
-            "type": "array",
-            "items": {"type": "object"}
+            "type": "object",
+            "properties": {
+                "job_id": {"type": "string"},
+                "state": {"type": "string", "enum": ["running", "completed", "cancelled", "failed"]},
+                "pages_done": {"type": "integer"},
+                "offset": {"type": "integer"},
+                "pages": {"type": "array", "items": {"type": "object"}},
+                "next_offset": {"type": ["integer", "null"]}
+            }
         })),
     };
 
-    server.register_tool(tool, |req: CallToolRequest| {
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
         Box::pin(async move {
             let args = req.arguments.unwrap_or_default();
             let result: Result<CallToolResponse, anyhow::Error> = async {
-                let url = args
-                    .get("url")
+                let job_id = args
+                    .get("job_id")
                     .and_then(|v| v.as_str())
-                    .context("url is missing")?;
-
-                let mut session = ScrapingSession::new()?;
-                let html = session.fetch_page(url).await?;
-                let extractor = ElementExtractor::new(&html);
-                let links = extractor.extract_links()?;
+                    .context("job_id is missing")?;
+                let offset = args.get("offset").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+                let limit = args
+                    .get("limit")
+                    .and_then(|v| v.as_u64())
+                    .map(|v| v.clamp(1, 100) as usize)
+                    .unwrap_or(20);
+                let results = state.jobs.results(job_id, offset, limit)?;
 
                 Ok(CallToolResponse {
                     content: vec![ToolResponseContent::Text {
-                        text: serde_json::to_string_pretty(&links)?,
+                        text: serde_json::to_string_pretty(&results)?,
                     }],
                     is_error: None,
                     meta: None,
@@ -420,45 +711,45 @@ fn register_extract_links_tool<T: Transport>(server: &mut ServerBuilder<T>) -> R
     Ok(())
 }
 
-fn register_extract_images_tool<T: Transport>(server: &mut ServerBuilder<T>) -> Result<()> {
+fn register_cancel_crawl_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
     let tool = Tool {
-        name: "extract_images".to_string(),
-        description: Some("Extract all images from a webpage".to_string()),
+        name: "cancel_crawl".to_string(),
+        description: Some(
+            "Stop a crawl started with start_crawl. The pages visited so far can still be \
+             read with crawl_results"
+                .to_string(),
+        ),
         input_schema: json!({
             "type": "object",
             "properties": {
-                "url": {
+                "job_id": {
                     "type": "string",
-                    "description": "The URL to scrape",
-                    "format": "uri"
+                    "description": "The job_id returned by start_crawl"
                 }
             },
-            "required": ["url"],
+            "required": ["job_id"],
             "additionalProperties": false
         }),
-        output_schema: Some(json!({
-            "type": "array",
-            "items": {"type": "object"}
-        })),
+        output_schema: Some(job_status_schema()),
     };
 
-    server.register_tool(tool, |req: CallToolRequest| {
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
         Box::pin(async move {
             let args = req.arguments.unwrap_or_default();
             let result: Result<CallToolResponse, anyhow::Error> = async {
-                let url = args
-                    .get("url")
+                let job_id = args
+                    .get("job_id")
                     .and_then(|v| v.as_str())
-                    .context("url is missing")?;
-
-                let mut session = ScrapingSession::new()?;
-                let html = session.fetch_page(url).await?;
-                let extractor = ElementExtractor::new(&html);
-                let images = extractor.extract_images()?;
+                    .context("job_id is missing")?;
+                let status = state.jobs.cancel(job_id)?;
 
                 Ok(CallToolResponse {
                     content: vec![ToolResponseContent::Text {
-                        text: serde_json::to_string_pretty(&images)?,
+                        text: serde_json::to_string_pretty(&status)?,
                     }],
                     is_error: None,
                     meta: None,
@@ -473,29 +764,78 @@ fn register_extract_images_tool<T: Transport>(server: &mut ServerBuilder<T>) ->
     Ok(())
 }
 
-fn register_extract_forms_tool<T: Transport>(server: &mut ServerBuilder<T>) -> Result<()> {
+fn register_get_sitemap_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
     let tool = Tool {
-        name: "extract_forms".to_string(),
-        description: Some("Extract all forms and their fields from a webpage".to_string()),
+        name: "get_sitemap".to_string(),
+        description: Some(
+            "List the URLs a site declares in its sitemaps, found through robots.txt or \
+             /sitemap.xml, with lastmod, changefreq and priority. Sitemap indexes and gzipped \
+             sitemaps are followed"
+                .to_string(),
+        ),
         input_schema: json!({
             "type": "object",
             "properties": {
                 "url": {
                     "type": "string",
-                    "description": "The URL to scrape",
+                    "description": "A page of the site, or an XML sitemap URL (.xml or .xml.gz) \
+                        to read directly",
                     "format": "uri"
+                },
+                "session_id": {
+                    "type": "string",
+                    "description": "Fetch within a session opened with open_session"
+                },
+                "include_patterns": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "Regular expressions; only URLs matching one of them are returned"
+                },
+                "exclude_patterns": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "Regular expressions; URLs matching any of them are left out"
+                },
+                "modified_since": {
+                    "type": "string",
+                    "description": "Only URLs with a lastmod at or after this date \
+                        (YYYY-MM-DD or RFC 3339); URLs without lastmod are left out"
+                },
+                "max_urls": {
+                    "type": "integer",
+                    "description": "Maximum number of URLs to return",
+                    "default": 1000,
+                    "minimum": 1,
+                    "maximum": 50000
+                },
+                "max_sitemaps": {
+                    "type": "integer",
+                    "description": "Maximum number of sitemap files to fetch, indexes included",
+                    "default": 50,
+                    "minimum": 1,
+                    "maximum": 500
                 }
             },
             "required": ["url"],
             "additionalProperties": false
         }),
         output_schema: Some(json!({
-            "type": "array",
-            "items": {"type": "object"}
+            "type": "object",
+            "properties": {
+                "sitemaps": {"type": "array", "items": {"type": "string"}},
+                "urls": {"type": "array", "items": {"type": "object"}},
+                "total_urls": {"type": "integer"},
+                "truncated": {"type": "boolean"},
+                "errors": {"type": "array", "items": {"type": "object"}}
+            }
         })),
     };
 
-    server.register_tool(tool, |req: CallToolRequest| {
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
         Box::pin(async move {
             let args = req.arguments.unwrap_or_default();
             let result: Result<CallToolResponse, anyhow::Error> = async {
@@ -503,15 +843,16 @@ fn register_extract_forms_tool<T: Transport>(server: &mut ServerBuilder<T>) -> R
                     .get("url")
                     .and_then(|v| v.as_str())
                     .context("url is missing")?;
+                let options = sitemap_options_from_args(&args)?;
 
-                let mut session = ScrapingSession::new()?;
-                let html = session.fetch_page(url).await?;
-                let extractor = ElementExtractor::new(&html);
-                let forms = extractor.extract_forms()?;
+                let session = state.session_for(session_id_arg(&args))?;
+                let mut session = session.lock().await;
+                let url = session.resolve(url)?;
+                let report = SitemapReader::new(options).run(&mut session, &url).await?;
 
                 Ok(CallToolResponse {
                     content: vec![ToolResponseContent::Text {
-                        text: serde_json::to_string_pretty(&forms)?,
+                        text: serde_json::to_string_pretty(&report)?,
                     }],
                     is_error: None,
                     meta: None,
@@ -526,45 +867,133 @@ fn register_extract_forms_tool<T: Transport>(server: &mut ServerBuilder<T>) -> R
     Ok(())
 }
 
-fn register_extract_tables_tool<T: Transport>(server: &mut ServerBuilder<T>) -> Result<()> {
+pub(crate) fn sitemap_options_from_args(
+    args: &HashMap<String, serde_json::Value>,
+) -> Result<SitemapOptions> {
+    let defaults = SitemapOptions::default();
+    let modified_since = match args.get("modified_since").and_then(|v| v.as_str()) {
+        Some(value) => Some(
+            sitemap::parse_lastmod(value)
+                .with_context(|| format!("Invalid modified_since date: {}", value))?,
+        ),
+        None => None,
+    };
+
+    Ok(SitemapOptions {
+        include_patterns: patterns_arg(args, "include_patterns")?,
+        exclude_patterns: patterns_arg(args, "exclude_patterns")?,
+        modified_since,
+        max_urls: args
+            .get("max_urls")
+            .and_then(|v| v.as_u64())
+            .map(|v| v.clamp(1, 50000) as usize)
+            .unwrap_or(defaults.max_urls),
+        max_sitemaps: args
+            .get("max_sitemaps")
+            .and_then(|v| v.as_u64())
+            .map(|v| v.clamp(1, 500) as usize)
+            .unwrap_or(defaults.max_sitemaps),
+    })
+}
+
+fn register_scrape_paginated_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
     let tool = Tool {
-        name: "extract_tables".to_string(),
-        description: Some("Extract all tables with headers and data from a webpage".to_string()),
+        name: "scrape_paginated".to_string(),
+        description: Some(
+            "Extract records from every page of a paginated listing, such as search results, \
+             by following next-page links or a URL template. Records are merged, deduplicated \
+             and tagged with the page they came from"
+                .to_string(),
+        ),
         input_schema: json!({
             "type": "object",
             "properties": {
                 "url": {
                     "type": "string",
-                    "description": "The URL to scrape",
+                    "description": "The first page of the listing",
                     "format": "uri"
+                },
+                "session_id": {
+                    "type": "string",
+                    "description": "Fetch within a session opened with open_session"
+                },
+                "selector": {
+                    "type": "string",
+                    "description": "CSS selector of the elements to extract from every page"
+                },
+                "schema": {
+                    "type": "object",
+                    "description": "Extraction schema applied to every page, as for extract_schema"
+                },
+                "next_selector": {
+                    "type": "string",
+                    "description": "CSS selector of the next-page link; its href is followed. \
+                        Without it and url_template, rel=\"next\" links are followed"
+                },
+                "url_template": {
+                    "type": "string",
+                    "description": "URL with a {page} placeholder, e.g. \
+                        https://example.com/search?q=x&page={page}. Pages are read in turn \
+                        until one has no new records"
+                },
+                "start_page": {
+                    "type": "integer",
+                    "description": "First page number of url_template",
+                    "default": 1,
+                    "minimum": 0
+                },
+                "max_pages": {
+                    "type": "integer",
+                    "description": "Maximum number of pages to read",
+                    "default": 10,
+                    "minimum": 1,
+                    "maximum": 100
+                },
+                "dedupe_by": {
+                    "type": "string",
+                    "description": "Record field identifying a record, e.g. href or url; \
+                        whole records are compared without it"
                 }
             },
-            "required": ["url"],
+            "oneOf": [{"required": ["selector"]}, {"required": ["schema"]}],
+            "anyOf": [{"required": ["url"]}, {"required": ["url_template"]}],
             "additionalProperties": false
         }),
         output_schema: Some(json!({
-            "type": "array",
-            "items": {"type": "object"}
+            "type": "object",
+            "properties": {
+                "pages": {"type": "array", "items": {"type": "object"}},
+                "records": {"type": "array", "items": {"type": "object"}},
+                "total_records": {"type": "integer"},
+                "duplicates": {"type": "integer"},
+                "stopped": {"type": "string"}
+            }
         })),
     };
 
-    server.register_tool(tool, |req: CallToolRequest| {
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
         Box::pin(async move {
             let args = req.arguments.unwrap_or_default();
             let result: Result<CallToolResponse, anyhow::Error> = async {
+                let paginator = Paginator::new(pagination_options_from_args(&args)?);
+
+                let session = state.session_for(session_id_arg(&args))?;
+                let mut session = session.lock().await;
                 let url = args
                     .get("url")
                     .and_then(|v| v.as_str())
-                    .context("url is missing")?;
-
-                let mut session = ScrapingSession::new()?;
-                let html = session.fetch_page(url).await?;
-                let extractor = ElementExtractor::new(&html);
-                let tables = extractor.extract_tables()?;
+                    .map(|url| session.resolve(url))
+                    .transpose()?;
+                let start = paginator.first_url(url.as_ref())?;
+                let report = paginator.run(&mut session, start).await?;
 
                 Ok(CallToolResponse {
                     content: vec![ToolResponseContent::Text {
-                        text: serde_json::to_string_pretty(&tables)?,
+                        text: serde_json::to_string_pretty(&report)?,
                     }],
                     is_error: None,
                     meta: None,
@@ -579,46 +1008,111 @@ fn register_extract_tables_tool<T: Transport>(server: &mut ServerBuilder<T>) ->
     Ok(())
 }
 
-fn register_extract_metadata_tool<T: Transport>(server: &mut ServerBuilder<T>) -> Result<()> {
-    let tool = Tool {
-        name: "extract_metadata".to_string(),
+pub(crate) fn pagination_options_from_args(
+    args: &HashMap<String, serde_json::Value>,
+) -> Result<PaginationOptions> {
+    let extract = match (args.get("selector"), args.get("schema")) {
+        (Some(_), Some(_)) => anyhow::bail!("Pass either selector or schema, not both"),
+        (Some(selector), None) => PageExtraction::Selector(
+            selector
+                .as_str()
+                .context("selector must be a string")?
+                .to_string(),
+        ),
+        (None, Some(schema)) => PageExtraction::Schema(ExtractionSchema::from_json(schema)?),
+        (None, None) => anyhow::bail!("selector or schema is required"),
+    };
+    let next = match (
+        args.get("url_template").and_then(|v| v.as_str()),
+        args.get("next_selector").and_then(|v| v.as_str()),
+    ) {
+        (Some(_), Some(_)) => anyhow::bail!("Pass either url_template or next_selector, not both"),
+        (Some(template), None) => {
+            let start = args.get("start_page").and_then(|v| v.as_u64()).unwrap_or(1);
+            pagination::page_url(template, start)?;
+            NextPage::Template {
+                template: template.to_string(),
+                start,
+            }
+        }
+        (None, Some(selector)) => NextPage::Selector(selector.to_string()),
+        (None, None) => NextPage::RelNext,
+    };
+
+    Ok(PaginationOptions {
+        next,
+        extract,
+        max_pages: args
+            .get("max_pages")
+            .and_then(|v| v.as_u64())
+            .map(|v| v.clamp(1, 100) as usize)
+            .unwrap_or(10),
+        dedupe_by: args
+            .get("dedupe_by")
+            .and_then(|v| v.as_str())
+            .map(|v| v.to_string()),
+    })
+}
+
+fn register_export_crawl_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
+    let tool = Tool {
+        name: "export_crawl".to_string(),
         description: Some(
-            "Extract page metadata including title, description, and Open Graph data".to_string(),
+            "Export what the server recorded in its archive, every request and response as \
+             fetched, for a crawl job or as a whole. Needs --archive-dir"
+                .to_string(),
         ),
         input_schema: json!({
             "type": "object",
             "properties": {
-                "url": {
+                "job_id": {
                     "type": "string",
-                    "description": "The URL to scrape",
-                    "format": "uri"
+                    "description": "The job_id returned by crawl or start_crawl; everything \
+                        recorded is exported without it"
+                },
+                "format": {
+                    "type": "string",
+                    "enum": ["warc", "jsonl"],
+                    "description": "WARC records, or one JSON object per request and \
+                        response",
+                    "default": "warc"
                 }
             },
-            "required": ["url"],
             "additionalProperties": false
         }),
         output_schema: Some(json!({
-            "type": "object"
+            "type": "object",
+            "properties": {
+                "path": {"type": "string"},
+                "format": {"type": "string"},
+                "exchanges": {"type": "integer"},
+                "bytes": {"type": "integer"}
+            }
         })),
     };
 
-    server.register_tool(tool, |req: CallToolRequest| {
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
         Box::pin(async move {
             let args = req.arguments.unwrap_or_default();
             let result: Result<CallToolResponse, anyhow::Error> = async {
-                let url = args
-                    .get("url")
-                    .and_then(|v| v.as_str())
-                    .context("url is missing")?;
-
-                let mut session = ScrapingSession::new()?;
-                let html = session.fetch_page(url).await?;
-                let extractor = ElementExtractor::new(&html);
-                let metadata = extractor.extract_metadata();
+                let Some(ArchiveMode::Record(archive)) = &state.archive else {
+                    anyhow::bail!("Nothing is recorded: start the server with --archive-dir");
+                };
+                let format = ExportFormat::parse(
+                    args.get("format")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("warc"),
+                )?;
+                let job_id = args.get("job_id").and_then(|v| v.as_str());
+                let report = archive.export(job_id, format)?;
 
                 Ok(CallToolResponse {
                     content: vec![ToolResponseContent::Text {
-                        text: serde_json::to_string_pretty(&metadata)?,
+                        text: serde_json::to_string_pretty(&report)?,
                     }],
                     is_error: None,
                     meta: None,
@@ -633,33 +1127,83 @@ fn register_extract_metadata_tool<T: Transport>(server: &mut ServerBuilder<T>) -
     Ok(())
 }
 
-fn register_search_patterns_tool<T: Transport>(server: &mut ServerBuilder<T>) -> Result<()> {
+fn register_monitor_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
     let tool = Tool {
-        name: "search_patterns".to_string(),
-        description: Some("Search for text patterns using regular expressions".to_string()),
+        name: "monitor".to_string(),
+        description: Some(
+            "Watch a page, or the elements matching a selector, for changes. The first call \
+             takes a snapshot; later calls with the same URL and selector compare the page \
+             with the last snapshot and return a text diff along with the elements added and \
+             removed. Dates, times and ads are ignored by default"
+                .to_string(),
+        ),
         input_schema: json!({
             "type": "object",
             "properties": {
                 "url": {
                     "type": "string",
-                    "description": "The URL to scrape",
+                    "description": "The page to watch",
                     "format": "uri"
                 },
-                "pattern": {
+                "session_id": {
                     "type": "string",
-                    "description": "Regular expression pattern to search for"
+                    "description": "Fetch within a session opened with open_session"
+                },
+                "selector": {
+                    "type": "string",
+                    "description": "CSS selector of the elements to watch, defaults to the \
+                        whole page"
+                },
+                "ignore_timestamps": {
+                    "type": "boolean",
+                    "description": "Ignore dates, times and texts like \"5 minutes ago\"",
+                    "default": true
+                },
+                "ignore_ads": {
+                    "type": "boolean",
+                    "description": "Ignore ad slots",
+                    "default": true
+                },
+                "ignore_selectors": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "CSS selectors of further elements to ignore"
+                },
+                "ignore_patterns": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "Regular expressions of text to ignore, e.g. a visitor \
+                        counter"
                 }
             },
-            "required": ["url", "pattern"],
+            "required": ["url"],
             "additionalProperties": false
         }),
         output_schema: Some(json!({
-            "type": "array",
-            "items": {"type": "string"}
+            "type": "object",
+            "properties": {
+                "status": {"type": "string", "enum": ["created", "unchanged", "changed"]},
+                "id": {"type": "string"},
+                "url": {"type": "string"},
+                "selector": {"type": ["string", "null"]},
+                "created": {"type": "string"},
+                "last_checked": {"type": "string"},
+                "last_changed": {"type": ["string", "null"]},
+                "checks": {"type": "integer"},
+                "changes": {"type": "integer"},
+                "previous_check": {"type": ["string", "null"]},
+                "diff": {"type": "string"},
+                "added": {"type": "array", "items": {"type": "string"}},
+                "removed": {"type": "array", "items": {"type": "string"}}
+            }
         })),
     };
 
-    server.register_tool(tool, |req: CallToolRequest| {
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
         Box::pin(async move {
             let args = req.arguments.unwrap_or_default();
             let result: Result<CallToolResponse, anyhow::Error> = async {
@@ -667,19 +1211,20 @@ fn register_search_patterns_tool<T: Transport>(server: &mut ServerBuilder<T>) ->
                     .get("url")
                     .and_then(|v| v.as_str())
                     .context("url is missing")?;
-                let pattern = args
-                    .get("pattern")
-                    .and_then(|v| v.as_str())
-                    .context("pattern is missing")?;
+                let options = monitor_options_from_args(&args)?;
 
-                let mut session = ScrapingSession::new()?;
-                let html = session.fetch_page(url).await?;
-                let extractor = ElementExtractor::new(&html);
-                let matches = extractor.search_patterns(pattern)?;
+                // Pages are always fetched anew, a cached copy would hide changes
+                let session = match session_id_arg(&args) {
+                    Some(id) => state.sessions.get(id)?,
+                    None => Arc::new(state.uncached_session()?.into()),
+                };
+                let mut session = session.lock().await;
+                let url = session.resolve(url)?;
+                let check = state.monitors.check(&mut session, &url, &options).await?;
 
                 Ok(CallToolResponse {
                     content: vec![ToolResponseContent::Text {
-                        text: serde_json::to_string_pretty(&matches)?,
+                        text: serde_json::to_string_pretty(&check)?,
                     }],
                     is_error: None,
                     meta: None,
@@ -694,49 +1239,67 @@ fn register_search_patterns_tool<T: Transport>(server: &mut ServerBuilder<T>) ->
     Ok(())
 }
 
-fn register_extract_structured_data_tool<T: Transport>(
+pub(crate) fn monitor_options_from_args(
+    args: &HashMap<String, serde_json::Value>,
+) -> Result<MonitorOptions> {
+    let flag = |key: &str| args.get(key).and_then(|v| v.as_bool()).unwrap_or(true);
+    Ok(MonitorOptions {
+        selector: args
+            .get("selector")
+            .and_then(|v| v.as_str())
+            .map(|v| v.to_string()),
+        filter: NoiseFilter {
+            ignore_timestamps: flag("ignore_timestamps"),
+            ignore_ads: flag("ignore_ads"),
+            ignore_selectors: args
+                .get("ignore_selectors")
+                .and_then(|v| v.as_array())
+                .map(|values| {
+                    values
+                        .iter()
+                        .filter_map(|v| v.as_str())
+                        .map(|v| v.to_string())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            ignore_patterns: patterns_arg(args, "ignore_patterns")?,
+        },
+    })
+}
+
+fn register_list_monitors_tool<T: Transport>(
     server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
 ) -> Result<()> {
     let tool = Tool {
-        name: "extract_structured_data".to_string(),
+        name: "list_monitors".to_string(),
         description: Some(
-            "Extract structured data (JSON-LD, microdata) from a webpage".to_string(),
+            "List the pages watched with monitor, with when they were last checked and last \
+             changed"
+                .to_string(),
         ),
         input_schema: json!({
             "type": "object",
-            "properties": {
-                "url": {
-                    "type": "string",
-                    "description": "The URL to scrape",
-                    "format": "uri"
-                }
-            },
-            "required": ["url"],
+            "properties": {},
             "additionalProperties": false
         }),
         output_schema: Some(json!({
-            "type": "array",
-            "items": {"type": "object"}
+            "type": "object",
+            "properties": {
+                "monitors": {"type": "array", "items": {"type": "object"}}
+            }
         })),
     };
 
-    server.register_tool(tool, |req: CallToolRequest| {
+    server.register_tool(tool, move |_req: CallToolRequest| {
+        let state = state.clone();
         Box::pin(async move {
-            let args = req.arguments.unwrap_or_default();
             let result: Result<CallToolResponse, anyhow::Error> = async {
-                let url = args
-                    .get("url")
-                    .and_then(|v| v.as_str())
-                    .context("url is missing")?;
-
-                let mut session = ScrapingSession::new()?;
-                let html = session.fetch_page(url).await?;
-                let extractor = ElementExtractor::new(&html);
-                let structured_data = extractor.extract_structured_data()?;
-
                 Ok(CallToolResponse {
                     content: vec![ToolResponseContent::Text {
-                        text: serde_json::to_string_pretty(&structured_data)?,
+                        text: serde_json::to_string_pretty(&json!({
+                            "monitors": state.monitors.list()?,
+                        }))?,
                     }],
                     is_error: None,
                     meta: None,
@@ -751,60 +1314,48 @@ fn register_extract_structured_data_tool<T: Transport>(
     Ok(())
 }
 
-fn register_xpath_to_css_tool<T: Transport>(server: &mut ServerBuilder<T>) -> Result<()> {
+fn register_remove_monitor_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
     let tool = Tool {
-        name: "xpath_to_css".to_string(),
-        description: Some(
-            "Convert XPath expressions to CSS selectors and show common XPath alternatives"
-                .to_string(),
-        ),
+        name: "remove_monitor".to_string(),
+        description: Some("Stop watching a page watched with monitor".to_string()),
         input_schema: json!({
             "type": "object",
             "properties": {
-                "xpath": {
+                "id": {
                     "type": "string",
-                    "description": "XPath expression to convert"
-                },
-                "show_common_patterns": {
-                    "type": "boolean",
-                    "description": "Whether to show common XPath to CSS pattern mappings",
-                    "default": false
+                    "description": "The monitor id, as listed by list_monitors"
                 }
             },
-            "required": ["xpath"],
+            "required": ["id"],
             "additionalProperties": false
         }),
         output_schema: Some(json!({
-            "type": "object"
+            "type": "object",
+            "properties": {
+                "removed": {"type": "boolean"}
+            }
         })),
     };
 
-    server.register_tool(tool, |req: CallToolRequest| {
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
         Box::pin(async move {
             let args = req.arguments.unwrap_or_default();
             let result: Result<CallToolResponse, anyhow::Error> = async {
-                let xpath = args
-                    .get("xpath")
+                let id = args
+                    .get("id")
                     .and_then(|v| v.as_str())
-                    .context("xpath is missing")?;
-                let show_common_patterns = args
-                    .get("show_common_patterns")
-                    .and_then(|v| v.as_bool())
-                    .unwrap_or(false);
-
-                let css = XPathAlternative::xpath_to_css(xpath)?;
-                let mut response = json!({
-                    "xpath": xpath,
-                    "css": css
-                });
-
-                if show_common_patterns {
-                    response["common_patterns"] = json!(XPathAlternative::common_patterns());
+                    .context("id is missing")?;
+                if !state.monitors.remove(id)? {
+                    anyhow::bail!("Unknown monitor: {}", id);
                 }
 
                 Ok(CallToolResponse {
                     content: vec![ToolResponseContent::Text {
-                        text: serde_json::to_string_pretty(&response)?,
+                        text: serde_json::to_string_pretty(&json!({"removed": true}))?,
                     }],
                     is_error: None,
                     meta: None,
@@ -819,60 +1370,114 @@ fn register_xpath_to_css_tool<T: Transport>(server: &mut ServerBuilder<T>) -> Re
     Ok(())
 }
 
-fn register_advanced_scrape_tool<T: Transport>(server: &mut ServerBuilder<T>) -> Result<()> {
+fn register_check_links_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
     let tool = Tool {
-        name: "advanced_scrape".to_string(),
+        name: "check_links".to_string(),
         description: Some(
-            "Perform comprehensive scraping of a webpage extracting all available data".to_string(),
+            "Find broken links on a page, or on the pages of a bounded crawl. Every link is \
+             requested and reported with its status code, redirect chain, timeout or error, \
+             and whether the element its #fragment points to exists, grouped by the page it \
+             was found on"
+                .to_string(),
         ),
         input_schema: json!({
             "type": "object",
             "properties": {
                 "url": {
                     "type": "string",
-                    "description": "The URL to scrape",
+                    "description": "The page whose links are checked, and where the crawl starts",
                     "format": "uri"
                 },
-                "include_links": {
-                    "type": "boolean",
-                    "description": "Include links extraction",
-                    "default": true
+                "session_id": {
+                    "type": "string",
+                    "description": "Fetch within a session opened with open_session"
                 },
-                "include_images": {
+                "max_pages": {
+                    "type": "integer",
+                    "description": "Number of pages whose links are checked; above 1, further \
+                        pages are found by crawling from url",
+                    "default": 1,
+                    "minimum": 1,
+                    "maximum": 500
+                },
+                "max_depth": {
+                    "type": "integer",
+                    "description": "Maximum number of links to follow from url when crawling \
+                        (defaults to the server's --max-depth)",
+                    "minimum": 0
+                },
+                "include_patterns": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "Regular expressions; only pages matching one of them are \
+                        crawled"
+                },
+                "exclude_patterns": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "Regular expressions; URLs matching any of them are neither \
+                        crawled nor checked"
+                },
+                "same_domain": {
                     "type": "boolean",
-                    "description": "Include images extraction",
+                    "description": "Only crawl pages on the domain of url",
                     "default": true
                 },
-                "include_forms": {
+                "include_subdomains": {
                     "type": "boolean",
-                    "description": "Include forms extraction",
-                    "default": true
+                    "description": "Also crawl subdomains of the domain of url \
+                        (defaults to the server's --subdomains)"
                 },
-                "include_tables": {
+                "check_external": {
                     "type": "boolean",
-                    "description": "Include tables extraction",
+                    "description": "Check links to pages outside the crawl's domain",
                     "default": true
                 },
-                "include_metadata": {
+                "check_anchors": {
                     "type": "boolean",
-                    "description": "Include metadata extraction",
+                    "description": "Check that the target of a link with a #fragment has an \
+                        element with that id",
                     "default": true
                 },
-                "include_structured_data": {
+                "concurrency": {
+                    "type": "integer",
+                    "description": "Maximum number of requests in flight, within the server's \
+                        per-host limits",
+                    "default": 8,
+                    "minimum": 1,
+                    "maximum": 32
+                },
+                "timeout_seconds": {
+                    "type": "number",
+                    "description": "Time allowed for each request",
+                    "default": 10
+                },
+                "include_ok": {
                     "type": "boolean",
-                    "description": "Include structured data extraction",
-                    "default": true
+                    "description": "List working links too, not only those with a problem",
+                    "default": false
                 }
             },
             "required": ["url"],
             "additionalProperties": false
         }),
         output_schema: Some(json!({
-            "type": "object"
+            "type": "object",
+            "properties": {
+                "start_url": {"type": "string"},
+                "pages": {"type": "array", "items": {"type": "object"}},
+                "pages_checked": {"type": "integer"},
+                "urls_checked": {"type": "integer"},
+                "summary": {"type": "object"}
+            }
         })),
     };
 
-    server.register_tool(tool, |req: CallToolRequest| {
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
         Box::pin(async move {
             let args = req.arguments.unwrap_or_default();
             let result: Result<CallToolResponse, anyhow::Error> = async {
@@ -880,66 +1485,17 @@ fn register_advanced_scrape_tool<T: Transport>(server: &mut ServerBuilder<T>) ->
                     .get("url")
                     .and_then(|v| v.as_str())
                     .context("url is missing")?;
-                let include_links = args
-                    .get("include_links")
-                    .and_then(|v| v.as_bool())
-                    .unwrap_or(true);
-                let include_images = args
-                    .get("include_images")
-                    .and_then(|v| v.as_bool())
-                    .unwrap_or(true);
-                let include_forms = args
-                    .get("include_forms")
-                    .and_then(|v| v.as_bool())
-                    .unwrap_or(true);
-                let include_tables = args
-                    .get("include_tables")
-                    .and_then(|v| v.as_bool())
-                    .unwrap_or(true);
-                let include_metadata = args
-                    .get("include_metadata")
-                    .and_then(|v| v.as_bool())
-                    .unwrap_or(true);
-                let include_structured_data = args
-                    .get("include_structured_data")
-                    .and_then(|v| v.as_bool())
-                    .unwrap_or(true);
-
-                let mut session = ScrapingSession::new()?;
-                let html = session.fetch_page(url).await?;
-                let extractor = ElementExtractor::new(&html);
-
-                let mut response = json!({
-                    "url": url
-                });
-
-                if include_metadata {
-                    response["metadata"] = extractor.extract_metadata();
-                }
-
-                if include_links {
-                    response["links"] = json!(extractor.extract_links()?);
-                }
-
-                if include_images {
-                    response["images"] = json!(extractor.extract_images()?);
-                }
-
-                if include_forms {
-                    response["forms"] = json!(extractor.extract_forms()?);
-                }
-
-                if include_tables {
-                    response["tables"] = json!(extractor.extract_tables()?);
-                }
+                let options = link_check_options_from_args(&args, &state.config)?;
 
-                if include_structured_data {
-                    response["structured_data"] = json!(extractor.extract_structured_data()?);
-                }
+                // Links are requested without the page cache, whatever the session
+                let session = state.session_for(session_id_arg(&args))?;
+                let session = session.lock().await;
+                let url = session.resolve(url)?;
+                let report = LinkChecker::new(options).run(&session, &url).await?;
 
                 Ok(CallToolResponse {
                     content: vec![ToolResponseContent::Text {
-                        text: serde_json::to_string_pretty(&response)?,
+                        text: serde_json::to_string_pretty(&report)?,
                     }],
                     is_error: None,
                     meta: None,
@@ -954,11 +1510,1784 @@ fn register_advanced_scrape_tool<T: Transport>(server: &mut ServerBuilder<T>) ->
     Ok(())
 }
 
-fn handle_tool_result(
-    result: Result<CallToolResponse, anyhow::Error>,
-) -> Result<CallToolResponse, anyhow::Error> {
-    match result {
-        Ok(response) => Ok(response),
+/// Link check options from tool arguments; the crawl defaults to the start
+/// page alone.
+pub(crate) fn link_check_options_from_args(
+    args: &HashMap<String, serde_json::Value>,
+    config: &CrawlerConfig,
+) -> Result<LinkCheckOptions> {
+    let defaults = LinkCheckOptions::default();
+    let flag =
+        |key: &str, default: bool| args.get(key).and_then(|v| v.as_bool()).unwrap_or(default);
+    let mut crawl = crawl_options_from_args(args, config)?;
+    if !args.contains_key("max_pages") {
+        crawl.max_pages = defaults.crawl.max_pages;
+    }
+    crawl.extract_content = false;
+
+    Ok(LinkCheckOptions {
+        crawl,
+        check_external: flag("check_external", defaults.check_external),
+        check_anchors: flag("check_anchors", defaults.check_anchors),
+        concurrency: args
+            .get("concurrency")
+            .and_then(|v| v.as_u64())
+            .map(|v| v.clamp(1, 32) as usize)
+            .unwrap_or(defaults.concurrency),
+        timeout: args
+            .get("timeout_seconds")
+            .and_then(|v| v.as_f64())
+            .filter(|v| *v > 0.0)
+            .map(Duration::from_secs_f64)
+            .unwrap_or(defaults.timeout),
+        include_ok: flag("include_ok", defaults.include_ok),
+    })
+}
+
+fn register_select_elements_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
+    let tool = Tool {
+        name: "select_elements".to_string(),
+        description: Some("Select elements from HTML using CSS selectors".to_string()),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "url": {
+                    "type": "string",
+                    "description": "The URL to scrape",
+                    "format": "uri"
+                },
+                "session_id": {
+                    "type": "string",
+                    "description": "Fetch within a session opened with open_session"
+                },
+                "html": {
+                    "type": "string",
+                    "description": "HTML to extract from instead of fetching a URL"
+                },
+                "base_url": {
+                    "type": "string",
+                    "description": "URL used to resolve relative links, defaults to `url`",
+                    "format": "uri"
+                },
+                "selector": {
+                    "type": "string",
+                    "description": "CSS selector to match elements"
+                }
+            },
+            "required": ["selector"],
+            "oneOf": [{"required": ["url"]}, {"required": ["html"]}],
+            "additionalProperties": false
+        }),
+        output_schema: Some(json!({
+            "type": "array",
+            "items": {"type": "object"}
+        })),
+    };
+
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
+        Box::pin(async move {
+            let args = req.arguments.unwrap_or_default();
+            let result: Result<CallToolResponse, anyhow::Error> = async {
+                let selector = args
+                    .get("selector")
+                    .and_then(|v| v.as_str())
+                    .context("selector is missing")?;
+
+                let page = page_from_args(&state, &args).await?;
+                let extractor = page.extractor();
+                let elements = extractor.select_elements(selector)?;
+
+                Ok(CallToolResponse {
+                    content: vec![ToolResponseContent::Text {
+                        text: serde_json::to_string_pretty(&elements)?,
+                    }],
+                    is_error: None,
+                    meta: page.meta(),
+                })
+            }
+            .await;
+
+            handle_tool_result(result)
+        })
+    });
+
+    Ok(())
+}
+
+fn register_extract_text_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
+    let tool = Tool {
+        name: "extract_text".to_string(),
+        description: Some("Extract text content from elements matching a CSS selector".to_string()),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "url": {
+                    "type": "string",
+                    "description": "The URL to scrape",
+                    "format": "uri"
+                },
+                "session_id": {
+                    "type": "string",
+                    "description": "Fetch within a session opened with open_session"
+                },
+                "html": {
+                    "type": "string",
+                    "description": "HTML to extract from instead of fetching a URL"
+                },
+                "base_url": {
+                    "type": "string",
+                    "description": "URL used to resolve relative links, defaults to `url`",
+                    "format": "uri"
+                },
+                "selector": {
+                    "type": "string",
+                    "description": "CSS selector to match elements"
+                }
+            },
+            "required": ["selector"],
+            "oneOf": [{"required": ["url"]}, {"required": ["html"]}],
+            "additionalProperties": false
+        }),
+        output_schema: Some(json!({
+            "type": "array",
+            "items": {"type": "string"}
+        })),
+    };
+
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
+        Box::pin(async move {
+            let args = req.arguments.unwrap_or_default();
+            let result: Result<CallToolResponse, anyhow::Error> = async {
+                let selector = args
+                    .get("selector")
+                    .and_then(|v| v.as_str())
+                    .context("selector is missing")?;
+
+                let page = page_from_args(&state, &args).await?;
+                let extractor = page.extractor();
+                let texts = extractor.extract_text(selector)?;
+
+                Ok(CallToolResponse {
+                    content: vec![ToolResponseContent::Text {
+                        text: serde_json::to_string_pretty(&texts)?,
+                    }],
+                    is_error: None,
+                    meta: page.meta(),
+                })
+            }
+            .await;
+
+            handle_tool_result(result)
+        })
+    });
+
+    Ok(())
+}
+
+fn register_extract_attributes_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
+    let tool = Tool {
+        name: "extract_attributes".to_string(),
+        description: Some(
+            "Extract specific attribute values from elements matching a CSS selector".to_string(),
+        ),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "url": {
+                    "type": "string",
+                    "description": "The URL to scrape",
+                    "format": "uri"
+                },
+                "session_id": {
+                    "type": "string",
+                    "description": "Fetch within a session opened with open_session"
+                },
+                "html": {
+                    "type": "string",
+                    "description": "HTML to extract from instead of fetching a URL"
+                },
+                "base_url": {
+                    "type": "string",
+                    "description": "URL used to resolve relative links, defaults to `url`",
+                    "format": "uri"
+                },
+                "selector": {
+                    "type": "string",
+                    "description": "CSS selector to match elements"
+                },
+                "attribute": {
+                    "type": "string",
+                    "description": "Attribute name to extract"
+                }
+            },
+            "required": ["selector", "attribute"],
+            "oneOf": [{"required": ["url"]}, {"required": ["html"]}],
+            "additionalProperties": false
+        }),
+        output_schema: Some(json!({
+            "type": "array",
+            "items": {"type": "string"}
+        })),
+    };
+
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
+        Box::pin(async move {
+            let args = req.arguments.unwrap_or_default();
+            let result: Result<CallToolResponse, anyhow::Error> = async {
+                let selector = args
+                    .get("selector")
+                    .and_then(|v| v.as_str())
+                    .context("selector is missing")?;
+                let attribute = args
+                    .get("attribute")
+                    .and_then(|v| v.as_str())
+                    .context("attribute is missing")?;
+
+                let page = page_from_args(&state, &args).await?;
+                let extractor = page.extractor();
+                let attributes = extractor.extract_attributes(selector, attribute)?;
+
+                Ok(CallToolResponse {
+                    content: vec![ToolResponseContent::Text {
+                        text: serde_json::to_string_pretty(&attributes)?,
+                    }],
+                    is_error: None,
+                    meta: page.meta(),
+                })
+            }
+            .await;
+
+            handle_tool_result(result)
+        })
+    });
+
+    Ok(())
+}
+
+fn register_extract_links_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
+    let tool = Tool {
+        name: "extract_links".to_string(),
+        description: Some("Extract all links from a webpage".to_string()),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "url": {
+                    "type": "string",
+                    "description": "The URL to scrape",
+                    "format": "uri"
+                },
+                "session_id": {
+                    "type": "string",
+                    "description": "Fetch within a session opened with open_session"
+                },
+                "html": {
+                    "type": "string",
+                    "description": "HTML to extract from instead of fetching a URL"
+                },
+                "base_url": {
+                    "type": "string",
+                    "description": "URL used to resolve relative links, defaults to `url`",
+                    "format": "uri"
+                }
+            },
+            "oneOf": [{"required": ["url"]}, {"required": ["html"]}],
+            "additionalProperties": false
+        }),
+        output_schema: Some(json!({
+            "type": "array",
+            "items": {"type": "object"}
+        })),
+    };
+
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
+        Box::pin(async move {
+            let args = req.arguments.unwrap_or_default();
+            let result: Result<CallToolResponse, anyhow::Error> = async {
+                let page = page_from_args(&state, &args).await?;
+                let extractor = page.extractor();
+                let links = extractor.extract_links()?;
+
+                Ok(CallToolResponse {
+                    content: vec![ToolResponseContent::Text {
+                        text: serde_json::to_string_pretty(&links)?,
+                    }],
+                    is_error: None,
+                    meta: page.meta(),
+                })
+            }
+            .await;
+
+            handle_tool_result(result)
+        })
+    });
+
+    Ok(())
+}
+
+fn register_extract_images_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
+    let tool = Tool {
+        name: "extract_images".to_string(),
+        description: Some("Extract all images from a webpage".to_string()),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "url": {
+                    "type": "string",
+                    "description": "The URL to scrape",
+                    "format": "uri"
+                },
+                "session_id": {
+                    "type": "string",
+                    "description": "Fetch within a session opened with open_session"
+                },
+                "html": {
+                    "type": "string",
+                    "description": "HTML to extract from instead of fetching a URL"
+                },
+                "base_url": {
+                    "type": "string",
+                    "description": "URL used to resolve relative links, defaults to `url`",
+                    "format": "uri"
+                }
+            },
+            "oneOf": [{"required": ["url"]}, {"required": ["html"]}],
+            "additionalProperties": false
+        }),
+        output_schema: Some(json!({
+            "type": "array",
+            "items": {"type": "object"}
+        })),
+    };
+
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
+        Box::pin(async move {
+            let args = req.arguments.unwrap_or_default();
+            let result: Result<CallToolResponse, anyhow::Error> = async {
+                let page = page_from_args(&state, &args).await?;
+                let extractor = page.extractor();
+                let images = extractor.extract_images()?;
+
+                Ok(CallToolResponse {
+                    content: vec![ToolResponseContent::Text {
+                        text: serde_json::to_string_pretty(&images)?,
+                    }],
+                    is_error: None,
+                    meta: page.meta(),
+                })
+            }
+            .await;
+
+            handle_tool_result(result)
+        })
+    });
+
+    Ok(())
+}
+
+fn register_extract_forms_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
+    let tool = Tool {
+        name: "extract_forms".to_string(),
+        description: Some("Extract all forms and their fields from a webpage".to_string()),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "url": {
+                    "type": "string",
+                    "description": "The URL to scrape",
+                    "format": "uri"
+                },
+                "session_id": {
+                    "type": "string",
+                    "description": "Fetch within a session opened with open_session"
+                },
+                "html": {
+                    "type": "string",
+                    "description": "HTML to extract from instead of fetching a URL"
+                },
+                "base_url": {
+                    "type": "string",
+                    "description": "URL used to resolve relative links, defaults to `url`",
+                    "format": "uri"
+                }
+            },
+            "oneOf": [{"required": ["url"]}, {"required": ["html"]}],
+            "additionalProperties": false
+        }),
+        output_schema: Some(json!({
+            "type": "array",
+            "items": {"type": "object"}
+        })),
+    };
+
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
+        Box::pin(async move {
+            let args = req.arguments.unwrap_or_default();
+            let result: Result<CallToolResponse, anyhow::Error> = async {
+                let page = page_from_args(&state, &args).await?;
+                let extractor = page.extractor();
+                let forms = extractor.extract_forms()?;
+
+                Ok(CallToolResponse {
+                    content: vec![ToolResponseContent::Text {
+                        text: serde_json::to_string_pretty(&forms)?,
+                    }],
+                    is_error: None,
+                    meta: page.meta(),
+                })
+            }
+            .await;
+
+            handle_tool_result(result)
+        })
+    });
+
+    Ok(())
+}
+
+fn register_extract_tables_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
+    let tool = Tool {
+        name: "extract_tables".to_string(),
+        description: Some(
+            "Extract all tables from a webpage with captions, headers and data, expanding \
+             colspan and rowspan, as rows, records, CSV or Markdown"
+                .to_string(),
+        ),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "url": {
+                    "type": "string",
+                    "description": "The URL to scrape",
+                    "format": "uri"
+                },
+                "session_id": {
+                    "type": "string",
+                    "description": "Fetch within a session opened with open_session"
+                },
+                "html": {
+                    "type": "string",
+                    "description": "HTML to extract from instead of fetching a URL"
+                },
+                "base_url": {
+                    "type": "string",
+                    "description": "URL used to resolve relative links, defaults to `url`",
+                    "format": "uri"
+                },
+                "format": {
+                    "type": "string",
+                    "enum": ["rows", "records", "csv", "markdown"],
+                    "description": "rows: arrays of cells; records: objects keyed by header; \
+                        csv or markdown: the table as text",
+                    "default": "rows"
+                },
+                "infer_types": {
+                    "type": "boolean",
+                    "description": "Convert numeric, percentage, currency and date columns and \
+                        report column_types",
+                    "default": false
+                }
+            },
+            "oneOf": [{"required": ["url"]}, {"required": ["html"]}],
+            "additionalProperties": false
+        }),
+        output_schema: Some(json!({
+            "type": "array",
+            "items": {"type": "object"}
+        })),
+    };
+
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
+        Box::pin(async move {
+            let args = req.arguments.unwrap_or_default();
+            let result: Result<CallToolResponse, anyhow::Error> = async {
+                let page = page_from_args(&state, &args).await?;
+                let extractor = page.extractor();
+                let options = TableOptions {
+                    format: match args.get("format").and_then(|v| v.as_str()) {
+                        Some(format) => TableFormat::parse(format)?,
+                        None => TableFormat::default(),
+                    },
+                    infer_types: args
+                        .get("infer_types")
+                        .and_then(|v| v.as_bool())
+                        .unwrap_or(false),
+                };
+                let tables = extractor.extract_tables_with(&options)?;
+
+                Ok(CallToolResponse {
+                    content: vec![ToolResponseContent::Text {
+                        text: serde_json::to_string_pretty(&tables)?,
+                    }],
+                    is_error: None,
+                    meta: page.meta(),
+                })
+            }
+            .await;
+
+            handle_tool_result(result)
+        })
+    });
+
+    Ok(())
+}
+
+fn register_extract_metadata_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
+    let tool = Tool {
+        name: "extract_metadata".to_string(),
+        description: Some(
+            "Extract page metadata: title, description, author, dates, language, robots, \
+             canonical URL, hreflang alternates, feeds, icons, and Open Graph and Twitter card \
+             properties"
+                .to_string(),
+        ),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "url": {
+                    "type": "string",
+                    "description": "The URL to scrape",
+                    "format": "uri"
+                },
+                "session_id": {
+                    "type": "string",
+                    "description": "Fetch within a session opened with open_session"
+                },
+                "html": {
+                    "type": "string",
+                    "description": "HTML to extract from instead of fetching a URL"
+                },
+                "base_url": {
+                    "type": "string",
+                    "description": "URL used to resolve relative links, defaults to `url`",
+                    "format": "uri"
+                }
+            },
+            "oneOf": [{"required": ["url"]}, {"required": ["html"]}],
+            "additionalProperties": false
+        }),
+        output_schema: Some(json!({
+            "type": "object"
+        })),
+    };
+
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
+        Box::pin(async move {
+            let args = req.arguments.unwrap_or_default();
+            let result: Result<CallToolResponse, anyhow::Error> = async {
+                let page = page_from_args(&state, &args).await?;
+                let extractor = page.extractor();
+                let metadata = extractor.extract_metadata();
+
+                Ok(CallToolResponse {
+                    content: vec![ToolResponseContent::Text {
+                        text: serde_json::to_string_pretty(&metadata)?,
+                    }],
+                    is_error: None,
+                    meta: page.meta(),
+                })
+            }
+            .await;
+
+            handle_tool_result(result)
+        })
+    });
+
+    Ok(())
+}
+
+fn register_search_patterns_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
+    let tool = Tool {
+        name: "search_patterns".to_string(),
+        description: Some("Search for text patterns using regular expressions".to_string()),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "url": {
+                    "type": "string",
+                    "description": "The URL to scrape",
+                    "format": "uri"
+                },
+                "session_id": {
+                    "type": "string",
+                    "description": "Fetch within a session opened with open_session"
+                },
+                "html": {
+                    "type": "string",
+                    "description": "HTML to extract from instead of fetching a URL"
+                },
+                "base_url": {
+                    "type": "string",
+                    "description": "URL used to resolve relative links, defaults to `url`",
+                    "format": "uri"
+                },
+                "pattern": {
+                    "type": "string",
+                    "description": "Regular expression pattern to search for"
+                }
+            },
+            "required": ["pattern"],
+            "oneOf": [{"required": ["url"]}, {"required": ["html"]}],
+            "additionalProperties": false
+        }),
+        output_schema: Some(json!({
+            "type": "array",
+            "items": {"type": "string"}
+        })),
+    };
+
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
+        Box::pin(async move {
+            let args = req.arguments.unwrap_or_default();
+            let result: Result<CallToolResponse, anyhow::Error> = async {
+                let pattern = args
+                    .get("pattern")
+                    .and_then(|v| v.as_str())
+                    .context("pattern is missing")?;
+
+                let page = page_from_args(&state, &args).await?;
+                let extractor = page.extractor();
+                let matches = extractor.search_patterns(pattern)?;
+
+                Ok(CallToolResponse {
+                    content: vec![ToolResponseContent::Text {
+                        text: serde_json::to_string_pretty(&matches)?,
+                    }],
+                    is_error: None,
+                    meta: page.meta(),
+                })
+            }
+            .await;
+
+            handle_tool_result(result)
+        })
+    });
+
+    Ok(())
+}
+
+fn register_extract_structured_data_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
+    let tool = Tool {
+        name: "extract_structured_data".to_string(),
+        description: Some(
+            "Extract structured data (JSON-LD, microdata, RDFa Lite) from a webpage as \
+             schema.org-style items, optionally only those of given types"
+                .to_string(),
+        ),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "url": {
+                    "type": "string",
+                    "description": "The URL to scrape",
+                    "format": "uri"
+                },
+                "session_id": {
+                    "type": "string",
+                    "description": "Fetch within a session opened with open_session"
+                },
+                "html": {
+                    "type": "string",
+                    "description": "HTML to extract from instead of fetching a URL"
+                },
+                "base_url": {
+                    "type": "string",
+                    "description": "URL used to resolve relative links, defaults to `url`",
+                    "format": "uri"
+                },
+                "types": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "Only items of these schema.org types, e.g. Product or \
+                        Article, including items nested in others"
+                }
+            },
+            "oneOf": [{"required": ["url"]}, {"required": ["html"]}],
+            "additionalProperties": false
+        }),
+        output_schema: Some(json!({
+            "type": "array",
+            "items": {"type": "object"}
+        })),
+    };
+
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
+        Box::pin(async move {
+            let args = req.arguments.unwrap_or_default();
+            let result: Result<CallToolResponse, anyhow::Error> = async {
+                let page = page_from_args(&state, &args).await?;
+                let extractor = page.extractor();
+                let types: Vec<String> = args
+                    .get("types")
+                    .and_then(|v| v.as_array())
+                    .map(|types| {
+                        types
+                            .iter()
+                            .filter_map(|t| t.as_str())
+                            .map(|t| t.to_string())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                let structured_data = extractor.extract_structured_data_of_types(&types)?;
+
+                Ok(CallToolResponse {
+                    content: vec![ToolResponseContent::Text {
+                        text: serde_json::to_string_pretty(&structured_data)?,
+                    }],
+                    is_error: None,
+                    meta: page.meta(),
+                })
+            }
+            .await;
+
+            handle_tool_result(result)
+        })
+    });
+
+    Ok(())
+}
+
+fn register_extract_schema_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
+    let tool = Tool {
+        name: "extract_schema".to_string(),
+        description: Some(
+            "Extract typed JSON records from a webpage with a declarative schema of CSS \
+             selectors, instead of one selector call per field"
+                .to_string(),
+        ),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "url": {
+                    "type": "string",
+                    "description": "The URL to scrape",
+                    "format": "uri"
+                },
+                "session_id": {
+                    "type": "string",
+                    "description": "Fetch within a session opened with open_session"
+                },
+                "html": {
+                    "type": "string",
+                    "description": "HTML to extract from instead of fetching a URL"
+                },
+                "base_url": {
+                    "type": "string",
+                    "description": "URL used to resolve relative links, defaults to `url`",
+                    "format": "uri"
+                },
+                "schema": {
+                    "type": "object",
+                    "description": "`items`: CSS selector of the record elements (the whole page \
+                        is one record without it). `fields`: name -> CSS selector, or an object \
+                        with `selector`, `attr`, `type` (string, number, integer, boolean, date), \
+                        `format` (chrono date format), `regex` (first capture group is kept), \
+                        `trim` (default true), `resolve` (resolve as URL), `all` (list of every \
+                        match) and `default`. A field with its own `fields` is a nested record, \
+                        or a list of records when it has `items`",
+                    "properties": {
+                        "items": {"type": "string"},
+                        "fields": {"type": "object"}
+                    },
+                    "required": ["fields"]
+                }
+            },
+            "required": ["schema"],
+            "oneOf": [{"required": ["url"]}, {"required": ["html"]}],
+            "additionalProperties": false
+        }),
+        output_schema: Some(json!({
+            "type": "object",
+            "properties": {
+                "records": {"type": "array", "items": {"type": "object"}},
+                "count": {"type": "integer"}
+            }
+        })),
+    };
+
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
+        Box::pin(async move {
+            let args = req.arguments.unwrap_or_default();
+            let result: Result<CallToolResponse, anyhow::Error> = async {
+                let schema =
+                    ExtractionSchema::from_json(args.get("schema").context("schema is missing")?)?;
+                let page = page_from_args(&state, &args).await?;
+                let records = page.extractor().extract_schema(&schema);
+
+                Ok(CallToolResponse {
+                    content: vec![ToolResponseContent::Text {
+                        text: serde_json::to_string_pretty(&json!({
+                            "count": records.len(),
+                            "records": records,
+                        }))?,
+                    }],
+                    is_error: None,
+                    meta: page.meta(),
+                })
+            }
+            .await;
+
+            handle_tool_result(result)
+        })
+    });
+
+    Ok(())
+}
+
+fn register_xpath_to_css_tool<T: Transport>(server: &mut ServerBuilder<T>) -> Result<()> {
+    let tool = Tool {
+        name: "xpath_to_css".to_string(),
+        description: Some(
+            "Convert an XPath expression to an equivalent CSS selector. Fails when CSS cannot express it exactly; use xpath_query for those"
+                .to_string(),
+        ),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "xpath": {
+                    "type": "string",
+                    "description": "XPath expression to convert"
+                },
+                "show_common_patterns": {
+                    "type": "boolean",
+                    "description": "Whether to show common XPath to CSS pattern mappings",
+                    "default": false
+                }
+            },
+            "required": ["xpath"],
+            "additionalProperties": false
+        }),
+        output_schema: Some(json!({
+            "type": "object"
+        })),
+    };
+
+    server.register_tool(tool, |req: CallToolRequest| {
+        Box::pin(async move {
+            let args = req.arguments.unwrap_or_default();
+            let result: Result<CallToolResponse, anyhow::Error> = async {
+                let xpath = args
+                    .get("xpath")
+                    .and_then(|v| v.as_str())
+                    .context("xpath is missing")?;
+                let show_common_patterns = args
+                    .get("show_common_patterns")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+
+                let css = XPathAlternative::xpath_to_css(xpath)?;
+                let mut response = json!({
+                    "xpath": xpath,
+                    "css": css
+                });
+
+                if show_common_patterns {
+                    response["common_patterns"] = json!(XPathAlternative::common_patterns());
+                }
+
+                Ok(CallToolResponse {
+                    content: vec![ToolResponseContent::Text {
+                        text: serde_json::to_string_pretty(&response)?,
+                    }],
+                    is_error: None,
+                    meta: None,
+                })
+            }
+            .await;
+
+            handle_tool_result(result)
+        })
+    });
+
+    Ok(())
+}
+
+fn register_xpath_query_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
+    let tool = Tool {
+        name: "xpath_query".to_string(),
+        description: Some(
+            "Evaluate an XPath 1.0 expression against a page and return the matched nodes, or the string, number or boolean it computes"
+                .to_string(),
+        ),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "url": {
+                    "type": "string",
+                    "description": "The URL to scrape",
+                    "format": "uri"
+                },
+                "session_id": {
+                    "type": "string",
+                    "description": "Fetch within a session opened with open_session"
+                },
+                "html": {
+                    "type": "string",
+                    "description": "HTML to query instead of fetching a URL"
+                },
+                "base_url": {
+                    "type": "string",
+                    "description": "URL used to resolve relative links, defaults to `url`",
+                    "format": "uri"
+                },
+                "xpath": {
+                    "type": "string",
+                    "description": "XPath 1.0 expression, e.g. //a/@href or count(//li)"
+                },
+                "limit": {
+                    "type": "integer",
+                    "description": "Maximum number of nodes to return",
+                    "minimum": 1
+                }
+            },
+            "required": ["xpath"],
+            "oneOf": [{"required": ["url"]}, {"required": ["html"]}],
+            "additionalProperties": false
+        }),
+        output_schema: Some(json!({
+            "type": "object",
+            "properties": {
+                "type": {"enum": ["nodes", "string", "number", "boolean"]},
+                "count": {"type": "integer"},
+                "nodes": {"type": "array"},
+                "value": {}
+            }
+        })),
+    };
+
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
+        Box::pin(async move {
+            let args = req.arguments.unwrap_or_default();
+            let result: Result<CallToolResponse, anyhow::Error> = async {
+                let xpath = args
+                    .get("xpath")
+                    .and_then(|v| v.as_str())
+                    .context("xpath is missing")?;
+                let limit = args
+                    .get("limit")
+                    .and_then(|v| v.as_u64())
+                    .map(|v| v as usize);
+
+                let page = page_from_args(&state, &args).await?;
+                let mut response = page.extractor().evaluate_xpath(xpath)?.to_json();
+                // `count` keeps the total number of matches when truncated
+                if let (Some(limit), Some(nodes)) = (limit, response["nodes"].as_array_mut()) {
+                    nodes.truncate(limit);
+                }
+
+                Ok(CallToolResponse {
+                    content: vec![ToolResponseContent::Text {
+                        text: serde_json::to_string_pretty(&response)?,
+                    }],
+                    is_error: None,
+                    meta: page.meta(),
+                })
+            }
+            .await;
+
+            handle_tool_result(result)
+        })
+    });
+
+    Ok(())
+}
+
+fn register_advanced_scrape_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
+    let tool = Tool {
+        name: "advanced_scrape".to_string(),
+        description: Some(
+            "Perform comprehensive scraping of a webpage extracting all available data".to_string(),
+        ),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "url": {
+                    "type": "string",
+                    "description": "The URL to scrape",
+                    "format": "uri"
+                },
+                "session_id": {
+                    "type": "string",
+                    "description": "Fetch within a session opened with open_session"
+                },
+                "include_links": {
+                    "type": "boolean",
+                    "description": "Include links extraction",
+                    "default": true
+                },
+                "include_images": {
+                    "type": "boolean",
+                    "description": "Include images extraction",
+                    "default": true
+                },
+                "include_forms": {
+                    "type": "boolean",
+                    "description": "Include forms extraction",
+                    "default": true
+                },
+                "include_tables": {
+                    "type": "boolean",
+                    "description": "Include tables extraction",
+                    "default": true
+                },
+                "include_metadata": {
+                    "type": "boolean",
+                    "description": "Include metadata extraction",
+                    "default": true
+                },
+                "include_structured_data": {
+                    "type": "boolean",
+                    "description": "Include structured data extraction",
+                    "default": true
+                },
+                "format": format_schema(),
+                "markdown_images": markdown_images_schema(),
+                "max_tokens": max_tokens_schema(),
+                "chunk_by": chunk_by_schema(),
+                "overlap_tokens": overlap_tokens_schema()
+            },
+            "required": ["url"],
+            "additionalProperties": false
+        }),
+        output_schema: Some(json!({
+            "type": "object"
+        })),
+    };
+
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
+        Box::pin(async move {
+            let args = req.arguments.unwrap_or_default();
+            let result: Result<CallToolResponse, anyhow::Error> = async {
+                let url = args
+                    .get("url")
+                    .and_then(|v| v.as_str())
+                    .context("url is missing")?;
+                let include_links = args
+                    .get("include_links")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(true);
+                let include_images = args
+                    .get("include_images")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(true);
+                let include_forms = args
+                    .get("include_forms")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(true);
+                let include_tables = args
+                    .get("include_tables")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(true);
+                let include_metadata = args
+                    .get("include_metadata")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(true);
+                let include_structured_data = args
+                    .get("include_structured_data")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(true);
+                let content_args = content_args(&args)?;
+
+                let page = page_from_args(&state, &args).await?;
+                let extractor = page.extractor();
+
+                let mut response = json!({
+                    "url": url
+                });
+
+                if include_metadata {
+                    response["metadata"] = extractor.extract_metadata();
+                }
+
+                if include_links {
+                    response["links"] = json!(extractor.extract_links()?);
+                }
+
+                if include_images {
+                    response["images"] = json!(extractor.extract_images()?);
+                }
+
+                if include_forms {
+                    response["forms"] = json!(extractor.extract_forms()?);
+                }
+
+                if include_tables {
+                    response["tables"] = json!(extractor.extract_tables()?);
+                }
+
+                if include_structured_data {
+                    response["structured_data"] = json!(extractor.extract_structured_data()?);
+                }
+
+                if let Some(options) = &content_args.markdown {
+                    let markdown = extractor.extract_markdown(options)?;
+                    response["content"] = content_args.output(&state, "markdown", markdown);
+                } else if content_args.chunking.is_some() {
+                    let text = extractor.extract_main_text()?;
+                    response["content"] = content_args.output(&state, "text", text);
+                }
+
+                Ok(CallToolResponse {
+                    content: vec![ToolResponseContent::Text {
+                        text: serde_json::to_string_pretty(&response)?,
+                    }],
+                    is_error: None,
+                    meta: page.meta(),
+                })
+            }
+            .await;
+
+            handle_tool_result(result)
+        })
+    });
+
+    Ok(())
+}
+
+fn register_clear_cache_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
+    let tool = Tool {
+        name: "clear_cache".to_string(),
+        description: Some(
+            "Evict pages from the server-wide page cache, either a single URL or everything"
+                .to_string(),
+        ),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "url": {
+                    "type": "string",
+                    "description": "Only evict this URL; evicts every page when omitted",
+                    "format": "uri"
+                }
+            },
+            "additionalProperties": false
+        }),
+        output_schema: Some(json!({
+            "type": "object",
+            "properties": {
+                "evicted": {"type": "integer"},
+                "entries": {"type": "integer"},
+                "bytes": {"type": "integer"}
+            }
+        })),
+    };
+
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
+        Box::pin(async move {
+            let args = req.arguments.unwrap_or_default();
+            let result: Result<CallToolResponse, anyhow::Error> = async {
+                let evicted = match args.get("url").and_then(|v| v.as_str()) {
+                    Some(url) => usize::from(state.cache.remove(url)),
+                    None => state.cache.clear(),
+                };
+                let stats = state.cache.stats();
+
+                Ok(CallToolResponse {
+                    content: vec![ToolResponseContent::Text {
+                        text: serde_json::to_string_pretty(&json!({
+                            "evicted": evicted,
+                            "entries": stats.entries,
+                            "bytes": stats.bytes,
+                        }))?,
+                    }],
+                    is_error: None,
+                    meta: None,
+                })
+            }
+            .await;
+
+            handle_tool_result(result)
+        })
+    });
+
+    Ok(())
+}
+
+fn register_open_session_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
+    let tool = Tool {
+        name: "open_session".to_string(),
+        description: Some(
+            "Open a browsing session that keeps cookies, headers and the current URL across \
+             tool calls. Pass the returned session_id to fetch, extract and submit_form tools. \
+             Sessions unused for an hour are closed"
+                .to_string(),
+        ),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "session_id": {
+                    "type": "string",
+                    "description": "Name for the session; generated when omitted"
+                },
+                "base_url": {
+                    "type": "string",
+                    "description": "URL that relative URLs in later calls resolve against",
+                    "format": "uri"
+                },
+                "headers": {
+                    "type": "object",
+                    "additionalProperties": {"type": "string"},
+                    "description": "Headers sent with every request of the session"
+                }
+            },
+            "additionalProperties": false
+        }),
+        output_schema: Some(json!({
+            "type": "object",
+            "properties": {
+                "session_id": {"type": "string"},
+                "base_url": {"type": ["string", "null"]}
+            }
+        })),
+    };
+
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
+        Box::pin(async move {
+            let args = req.arguments.unwrap_or_default();
+            let result: Result<CallToolResponse, anyhow::Error> = async {
+                let headers: HashMap<String, String> = args
+                    .get("headers")
+                    .and_then(|v| v.as_object())
+                    .map(|headers| {
+                        headers
+                            .iter()
+                            .filter_map(|(k, v)| v.as_str().map(|v| (k.clone(), v.to_string())))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+
+                let mut session = state.uncached_session()?.with_headers(&headers)?;
+                if let Some(base_url) = args.get("base_url").and_then(|v| v.as_str()) {
+                    session =
+                        session.with_base_url(Url::parse(base_url).context("Invalid base_url")?);
+                }
+                let base_url = session.base_url().map(|u| u.to_string());
+                let session_id = state.sessions.open(session_id_arg(&args), session)?;
+
+                Ok(CallToolResponse {
+                    content: vec![ToolResponseContent::Text {
+                        text: serde_json::to_string_pretty(&json!({
+                            "session_id": session_id,
+                            "base_url": base_url,
+                        }))?,
+                    }],
+                    is_error: None,
+                    meta: None,
+                })
+            }
+            .await;
+
+            handle_tool_result(result)
+        })
+    });
+
+    Ok(())
+}
+
+fn register_close_session_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
+    let tool = Tool {
+        name: "close_session".to_string(),
+        description: Some("Close a session opened with open_session".to_string()),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "session_id": {
+                    "type": "string",
+                    "description": "The session to close"
+                }
+            },
+            "required": ["session_id"],
+            "additionalProperties": false
+        }),
+        output_schema: Some(json!({
+            "type": "object",
+            "properties": {
+                "closed": {"type": "boolean"},
+                "open_sessions": {"type": "array", "items": {"type": "string"}}
+            }
+        })),
+    };
+
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
+        Box::pin(async move {
+            let args = req.arguments.unwrap_or_default();
+            let result: Result<CallToolResponse, anyhow::Error> = async {
+                let session_id = session_id_arg(&args).context("session_id is missing")?;
+                if !state.sessions.close(session_id) {
+                    anyhow::bail!("Unknown session: {}", session_id);
+                }
+
+                Ok(CallToolResponse {
+                    content: vec![ToolResponseContent::Text {
+                        text: serde_json::to_string_pretty(&json!({
+                            "closed": true,
+                            "open_sessions": state.sessions.ids(),
+                        }))?,
+                    }],
+                    is_error: None,
+                    meta: None,
+                })
+            }
+            .await;
+
+            handle_tool_result(result)
+        })
+    });
+
+    Ok(())
+}
+
+fn register_submit_form_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
+    let tool = Tool {
+        name: "submit_form".to_string(),
+        description: Some(
+            "Fill in and submit a form returned by extract_forms. Fields keep their default \
+             values unless overridden in `values`. Use a session_id to keep the resulting \
+             cookies, e.g. to log in"
+                .to_string(),
+        ),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "form": {
+                    "type": "object",
+                    "description": "A form object as returned by extract_forms"
+                },
+                "values": {
+                    "type": "object",
+                    "description": "Field values by name; arrays submit a name several times"
+                },
+                "session_id": {
+                    "type": "string",
+                    "description": "Submit within a session opened with open_session"
+                }
+            },
+            "required": ["form"],
+            "additionalProperties": false
+        }),
+        output_schema: Some(json!({
+            "type": "object",
+            "properties": {
+                "url": {"type": "string"},
+                "status": {"type": "integer"},
+                "title": {"type": "string"},
+                "text": {"type": "string"}
+            }
+        })),
+    };
+
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
+        Box::pin(async move {
+            let args = req.arguments.unwrap_or_default();
+            let result: Result<CallToolResponse, anyhow::Error> = async {
+                let form = args.get("form").context("form is missing")?;
+                let values = args
+                    .get("values")
+                    .and_then(|v| v.as_object())
+                    .cloned()
+                    .unwrap_or_default();
+                let action = form["action"].as_str().unwrap_or_default();
+                let method = form["method"].as_str().unwrap_or("GET");
+                let data = FormSubmitter::form_data(form, &values);
+
+                let session = state.session_for(session_id_arg(&args))?;
+                let mut session = session.lock().await;
+                let response = session.submit_form(action, method, &data).await?;
+                let url = response.url().to_string();
+                let status = response.status();
+                if !status.is_success() {
+                    return Err(HttpError::new(status, &url).into());
+                }
+                let status = status.as_u16();
+                let html = session.read_text(response).await?;
+
+                let extractor = ElementExtractor::new(&html);
+                let title = extractor.extract_text("title")?.into_iter().next();
+                let text = extractor
+                    .extract_text("body")?
+                    .join(" ")
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ");
+
+                Ok(CallToolResponse {
+                    content: vec![ToolResponseContent::Text {
+                        text: serde_json::to_string_pretty(&json!({
+                            "url": url,
+                            "status": status,
+                            "title": title,
+                            "text": text,
+                        }))?,
+                    }],
+                    is_error: None,
+                    meta: None,
+                })
+            }
+            .await;
+
+            handle_tool_result(result)
+        })
+    });
+
+    Ok(())
+}
+
+fn register_get_chunk_tool<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    state: Arc<CrawlerState>,
+) -> Result<()> {
+    let tool = Tool {
+        name: "get_chunk".to_string(),
+        description: Some(
+            "Read the next chunk of content that scrape or advanced_scrape split up with \
+             max_tokens"
+                .to_string(),
+        ),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "cursor": {
+                    "type": "string",
+                    "description": "next_cursor of the previous response"
+                }
+            },
+            "required": ["cursor"],
+            "additionalProperties": false
+        }),
+        output_schema: Some(json!({
+            "type": "object",
+            "properties": {
+                "chunk": {"type": "object"},
+                "total_chunks": {"type": "integer"},
+                "next_cursor": {"type": ["string", "null"]}
+            }
+        })),
+    };
+
+    server.register_tool(tool, move |req: CallToolRequest| {
+        let state = state.clone();
+        Box::pin(async move {
+            let args = req.arguments.unwrap_or_default();
+            let result: Result<CallToolResponse, anyhow::Error> = async {
+                let cursor = args
+                    .get("cursor")
+                    .and_then(|v| v.as_str())
+                    .context("cursor is missing")?;
+                let page = state.chunks.get(cursor)?;
+
+                Ok(CallToolResponse {
+                    content: vec![ToolResponseContent::Text {
+                        text: serde_json::to_string_pretty(&page)?,
+                    }],
+                    is_error: None,
+                    meta: None,
+                })
+            }
+            .await;
+
+            handle_tool_result(result)
+        })
+    });
+
+    Ok(())
+}
+
+/// Output schema of the tools reporting on a crawl job.
+fn job_status_schema() -> serde_json::Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "job_id": {"type": "string"},
+            "seed": {"type": "string"},
+            "state": {"type": "string", "enum": ["running", "completed", "cancelled", "failed"]},
+            "started": {"type": "string"},
+            "finished": {"type": ["string", "null"]},
+            "pages_done": {"type": "integer"},
+            "pages_failed": {"type": "integer"},
+            "pages_queued": {"type": "integer"},
+            "max_pages": {"type": "integer"},
+            "error": {"type": "string"}
+        }
+    })
+}
+
+/// Schema of the `format` argument of tools that can return Markdown.
+fn format_schema() -> serde_json::Value {
+    json!({
+        "type": "string",
+        "enum": ["html", "markdown"],
+        "description": "Return the main content as readability HTML and text, or as Markdown",
+        "default": "html"
+    })
+}
+
+fn markdown_images_schema() -> serde_json::Value {
+    json!({
+        "type": "boolean",
+        "description": "Keep images in the Markdown",
+        "default": false
+    })
+}
+
+fn max_tokens_schema() -> serde_json::Value {
+    json!({
+        "type": "integer",
+        "description": "Split the content into chunks of at most this many tokens and return \
+            the first one with a cursor for get_chunk",
+        "minimum": 1
+    })
+}
+
+fn chunk_by_schema() -> serde_json::Value {
+    json!({
+        "type": "string",
+        "enum": ["heading", "paragraph", "sentence"],
+        "description": "Boundaries chunks are preferably cut at",
+        "default": "heading"
+    })
+}
+
+fn overlap_tokens_schema() -> serde_json::Value {
+    json!({
+        "type": "integer",
+        "description": "Tokens from the end of a chunk repeated at the start of the next",
+        "default": 0,
+        "minimum": 0
+    })
+}
+
+/// How a tool should return page content, from its `format`,
+/// `markdown_images`, `max_tokens`, `chunk_by` and `overlap_tokens`
+/// arguments.
+pub(crate) struct ContentArgs {
+    /// Set for `format: "markdown"`.
+    pub markdown: Option<MarkdownOptions>,
+    /// Set when a `max_tokens` budget was given.
+    pub chunking: Option<ChunkOptions>,
+}
+
+impl ContentArgs {
+    /// `{<key>: text, tokens}`, or with a token budget the first chunk of
+    /// `text` and a cursor for the rest.
+    pub fn output(&self, state: &CrawlerState, key: &str, text: String) -> serde_json::Value {
+        let tokens = estimate_tokens(&text);
+        let Some(options) = &self.chunking else {
+            return json!({
+                key: text,
+                "tokens": tokens,
+            });
+        };
+        let chunks = chunk_text(&text, options);
+        let total_chunks = chunks.len();
+        let page = state.chunks.store(chunks);
+        json!({
+            "tokens": tokens,
+            "total_chunks": total_chunks,
+            "chunk": page.as_ref().map(|p| &p.chunk),
+            "next_cursor": page.as_ref().and_then(|p| p.next_cursor.as_ref()),
+        })
+    }
+}
+
+pub(crate) fn content_args(args: &HashMap<String, serde_json::Value>) -> Result<ContentArgs> {
+    let markdown = match args
+        .get("format")
+        .and_then(|v| v.as_str())
+        .unwrap_or("html")
+    {
+        "html" => None,
+        "markdown" => Some(MarkdownOptions {
+            include_images: args
+                .get("markdown_images")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+        }),
+        other => anyhow::bail!("Unknown format: {} (expected html or markdown)", other),
+    };
+
+    let chunking = match args.get("max_tokens").and_then(|v| v.as_u64()) {
+        Some(max_tokens) => Some(ChunkOptions {
+            strategy: args
+                .get("chunk_by")
+                .map(|v| serde_json::from_value(v.clone()))
+                .transpose()
+                .context("chunk_by must be heading, paragraph or sentence")?
+                .unwrap_or_default(),
+            max_tokens: max_tokens as usize,
+            overlap_tokens: args
+                .get("overlap_tokens")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0) as usize,
+        }),
+        None => None,
+    };
+
+    Ok(ContentArgs { markdown, chunking })
+}
+
+/// The document an extraction tool works on.
+pub(crate) struct ToolInput {
+    pub html: String,
+    pub base_url: Option<Url>,
+    /// Where the page came from, when it was fetched.
+    pub cache_status: Option<CacheStatus>,
+    /// The response the page came from, when it was fetched.
+    pub response: Option<ResponseInfo>,
+}
+
+impl ToolInput {
+    pub fn extractor(&self) -> ElementExtractor {
+        ElementExtractor::new(&self.html).with_page_url(self.base_url.clone())
+    }
+
+    pub fn meta(&self) -> Option<serde_json::Value> {
+        Some(page_meta(self.cache_status?, self.response.as_ref()?))
+    }
+}
+
+/// Take the document from the `html` argument, or fetch it from `url`.
+/// Relative links resolve against `base_url` when given, else `url`.
+pub(crate) async fn page_from_args(
+    state: &CrawlerState,
+    args: &HashMap<String, serde_json::Value>,
+) -> Result<ToolInput> {
+    let base_url = args
+        .get("base_url")
+        .and_then(|v| v.as_str())
+        .map(Url::parse)
+        .transpose()
+        .context("Invalid base_url")?;
+    let url = args.get("url").and_then(|v| v.as_str());
+    let html = args.get("html").and_then(|v| v.as_str());
+
+    match (url, html) {
+        (Some(_), Some(_)) => anyhow::bail!("Pass either url or html, not both"),
+        (None, None) => anyhow::bail!("url or html is required"),
+        (None, Some(html)) => Ok(ToolInput {
+            html: html.to_string(),
+            base_url,
+            cache_status: None,
+            response: None,
+        }),
+        (Some(url), None) => {
+            let session = state.session_for(session_id_arg(args))?;
+            let page = session.lock().await.fetch(url).await?.error_for_status()?;
+            if page.response.kind == ContentKind::Binary {
+                anyhow::bail!(
+                    "Cannot extract from {}: {} content has no text",
+                    page.url,
+                    page.response.content_type.as_deref().unwrap_or("binary")
+                );
+            }
+            Ok(ToolInput {
+                html: page.html,
+                base_url: base_url.or(Some(page.url)),
+                cache_status: Some(page.cache_status),
+                response: Some(page.response),
+            })
+        }
+    }
+}
+
+fn session_id_arg(args: &HashMap<String, serde_json::Value>) -> Option<&str> {
+    args.get("session_id").and_then(|v| v.as_str())
+}
+
+/// Response `meta` telling whether the page came from the cache, along
+/// with the status, headers and timing of the HTTP response.
+fn page_meta(status: CacheStatus, response: &ResponseInfo) -> serde_json::Value {
+    json!({
+        "cached": status.is_cached(),
+        "cache_status": status,
+        "response": response,
+    })
+}
+
+fn handle_tool_result(
+    result: Result<CallToolResponse, anyhow::Error>,
+) -> Result<CallToolResponse, anyhow::Error> {
+    match result {
+        Ok(mut response) => {
+            let tokens: usize = response
+                .content
+                .iter()
+                .map(|content| match content {
+                    ToolResponseContent::Text { text } => estimate_tokens(text),
+                    _ => 0,
+                })
+                .sum();
+            let mut meta = response.meta.take().unwrap_or_else(|| json!({}));
+            meta["tokens"] = json!(tokens);
+            response.meta = Some(meta);
+            Ok(response)
+        }
         Err(e) => {
             info!("Error handling tool request: {:#?}", e);
             Ok(CallToolResponse {
@@ -0,0 +1,107 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex as AsyncMutex;
+
+use crate::scraper_tools::ScrapingSession;
+
+/// Most sessions open at once.
+const MAX_SESSIONS: usize = 100;
+
+/// Sessions unused for this long are closed.
+const SESSION_IDLE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// A session shared between tool calls. Calls using the same session are
+/// serialized so cookies and the base URL change in a predictable order.
+pub type SharedSession = Arc<AsyncMutex<ScrapingSession>>;
+
+struct OpenSession {
+    session: SharedSession,
+    last_used: Instant,
+}
+
+/// Named browsing sessions kept open across tool calls. Sessions idle for
+/// longer than the idle TTL are closed, unless a call is still using them.
+pub struct SessionStore {
+    sessions: Mutex<HashMap<String, OpenSession>>,
+    max_sessions: usize,
+    idle_ttl: Duration,
+}
+
+impl Default for SessionStore {
+    fn default() -> Self {
+        Self::with_limits(MAX_SESSIONS, SESSION_IDLE_TTL)
+    }
+}
+
+impl SessionStore {
+    /// A store holding at most `max_sessions`, each closed after `idle_ttl`
+    /// without use.
+    pub fn with_limits(max_sessions: usize, idle_ttl: Duration) -> Self {
+        Self {
+            sessions: Mutex::new(HashMap::new()),
+            max_sessions,
+            idle_ttl,
+        }
+    }
+
+    /// Keep `session` under `id`, or a generated id when none is given.
+    pub fn open(&self, id: Option<&str>, session: ScrapingSession) -> Result<String> {
+        let id = match id {
+            Some(id) if id.trim().is_empty() => anyhow::bail!("session_id must not be empty"),
+            Some(id) => id.to_string(),
+            None => uuid::Uuid::new_v4().to_string(),
+        };
+        let mut sessions = self.sessions.lock().unwrap();
+        self.expire(&mut sessions);
+        if sessions.contains_key(&id) {
+            anyhow::bail!("Session already open: {}", id);
+        }
+        if sessions.len() >= self.max_sessions {
+            anyhow::bail!(
+                "Too many open sessions ({}); close one with close_session first",
+                self.max_sessions
+            );
+        }
+        let open = OpenSession {
+            session: Arc::new(AsyncMutex::new(session)),
+            last_used: Instant::now(),
+        };
+        sessions.insert(id.clone(), open);
+        Ok(id)
+    }
+
+    pub fn get(&self, id: &str) -> Result<SharedSession> {
+        let mut sessions = self.sessions.lock().unwrap();
+        self.expire(&mut sessions);
+        let open = sessions
+            .get_mut(id)
+            .ok_or(anyhow::anyhow!("Unknown session: {}", id))?;
+        open.last_used = Instant::now();
+        Ok(open.session.clone())
+    }
+
+    pub fn close(&self, id: &str) -> bool {
+        let mut sessions = self.sessions.lock().unwrap();
+        self.expire(&mut sessions);
+        sessions.remove(id).is_some()
+    }
+
+    /// Ids of the open sessions, sorted.
+    pub fn ids(&self) -> Vec<String> {
+        let mut sessions = self.sessions.lock().unwrap();
+        self.expire(&mut sessions);
+        let mut ids: Vec<String> = sessions.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    /// Close sessions idle for longer than the TTL. Sessions still held by
+    /// a call, such as a running crawl job, are kept.
+    fn expire(&self, sessions: &mut HashMap<String, OpenSession>) {
+        sessions.retain(|_, open| {
+            open.last_used.elapsed() <= self.idle_ttl || Arc::strong_count(&open.session) > 1
+        });
+    }
+}
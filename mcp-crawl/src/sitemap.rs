@@ -0,0 +1,281 @@
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, Utc};
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{HashSet, VecDeque};
+use sxd_document::dom::{ChildOfRoot, Element};
+use url::Url;
+
+use crate::feed::{child_text, children};
+use crate::fetch::ContentKind;
+use crate::scraper_tools::ScrapingSession;
+
+/// A page listed in a sitemap.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SitemapUrl {
+    pub loc: String,
+    pub lastmod: Option<String>,
+    pub changefreq: Option<String>,
+    pub priority: Option<f64>,
+    /// Sitemap the page was listed in.
+    pub sitemap: String,
+}
+
+/// Contents of one sitemap file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sitemap {
+    /// A `<urlset>`, or a plain-text list of URLs.
+    Urls(Vec<SitemapUrl>),
+    /// A `<sitemapindex>`: other sitemaps with their lastmod.
+    Index(Vec<(Url, Option<String>)>),
+}
+
+/// Limits and filters for reading a site's sitemaps.
+#[derive(Debug, Clone)]
+pub struct SitemapOptions {
+    /// URLs must match at least one of these, when any are given.
+    pub include_patterns: Vec<Regex>,
+    /// URLs matching any of these are skipped.
+    pub exclude_patterns: Vec<Regex>,
+    /// Only URLs whose lastmod is at or after this time. URLs without a
+    /// lastmod are skipped too.
+    pub modified_since: Option<DateTime<Utc>>,
+    pub max_urls: usize,
+    /// Maximum number of sitemap files fetched, index files included.
+    pub max_sitemaps: usize,
+}
+
+impl Default for SitemapOptions {
+    fn default() -> Self {
+        Self {
+            include_patterns: vec![],
+            exclude_patterns: vec![],
+            modified_since: None,
+            max_urls: 1000,
+            max_sitemaps: 50,
+        }
+    }
+}
+
+impl SitemapOptions {
+    pub fn matches(&self, url: &SitemapUrl) -> bool {
+        if self.exclude_patterns.iter().any(|p| p.is_match(&url.loc)) {
+            return false;
+        }
+        if !self.include_patterns.is_empty()
+            && !self.include_patterns.iter().any(|p| p.is_match(&url.loc))
+        {
+            return false;
+        }
+        match self.modified_since {
+            Some(since) => url
+                .lastmod
+                .as_deref()
+                .and_then(parse_lastmod)
+                .is_some_and(|lastmod| lastmod >= since),
+            None => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SitemapError {
+    pub sitemap: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SitemapReport {
+    /// Sitemap files read, in order.
+    pub sitemaps: Vec<String>,
+    pub urls: Vec<SitemapUrl>,
+    pub total_urls: usize,
+    /// Whether a limit stopped reading before every URL was collected.
+    pub truncated: bool,
+    /// Sitemaps that could not be fetched or parsed.
+    pub errors: Vec<SitemapError>,
+}
+
+/// Reads the sitemaps of a site, following sitemap indexes.
+pub struct SitemapReader {
+    options: SitemapOptions,
+}
+
+impl SitemapReader {
+    pub fn new(options: SitemapOptions) -> Self {
+        Self { options }
+    }
+
+    /// Read the sitemaps of the site of `url`, or `url` itself when it
+    /// points to a sitemap.
+    pub async fn run(&self, session: &mut ScrapingSession, url: &Url) -> Result<SitemapReport> {
+        let mut queue: VecDeque<Url> = if is_sitemap_url(url) {
+            VecDeque::from([url.clone()])
+        } else {
+            discover(session, url).await.into()
+        };
+        let mut seen_sitemaps: HashSet<String> = queue.iter().map(|u| u.to_string()).collect();
+        let mut seen_urls = HashSet::new();
+        let mut report = SitemapReport::default();
+
+        'sitemaps: while let Some(sitemap) = queue.pop_front() {
+            if report.sitemaps.len() >= self.options.max_sitemaps {
+                report.truncated = true;
+                break;
+            }
+            report.sitemaps.push(sitemap.to_string());
+
+            let parsed = match fetch_sitemap(session, &sitemap).await {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    report.errors.push(SitemapError {
+                        sitemap: sitemap.to_string(),
+                        error: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+            match parsed {
+                Sitemap::Index(children) => {
+                    for (child, lastmod) in children {
+                        // A sitemap unchanged since the cutoff cannot list newer URLs
+                        let lastmod = lastmod.as_deref().and_then(parse_lastmod);
+                        if let (Some(since), Some(lastmod)) = (self.options.modified_since, lastmod)
+                        {
+                            if lastmod < since {
+                                continue;
+                            }
+                        }
+                        if seen_sitemaps.insert(child.to_string()) {
+                            queue.push_back(child);
+                        }
+                    }
+                }
+                Sitemap::Urls(urls) => {
+                    for url in urls {
+                        if !self.options.matches(&url) || !seen_urls.insert(url.loc.clone()) {
+                            continue;
+                        }
+                        if report.urls.len() >= self.options.max_urls {
+                            report.truncated = true;
+                            break 'sitemaps;
+                        }
+                        report.urls.push(url);
+                    }
+                }
+            }
+        }
+
+        report.total_urls = report.urls.len();
+        Ok(report)
+    }
+}
+
+/// Sitemaps of the site of `url` listed in its robots.txt, or
+/// `/sitemap.xml` when there are none.
+pub async fn discover(session: &ScrapingSession, url: &Url) -> Vec<Url> {
+    let robots = session.robots(url).await;
+    let listed: Vec<Url> = robots
+        .sitemaps()
+        .iter()
+        .filter_map(|sitemap| url.join(sitemap).ok())
+        .collect();
+    if !listed.is_empty() {
+        return listed;
+    }
+    url.join("/sitemap.xml").into_iter().collect()
+}
+
+async fn fetch_sitemap(session: &mut ScrapingSession, url: &Url) -> Result<Sitemap> {
+    let page = session.fetch(url.as_str()).await?.error_for_status()?;
+    if matches!(page.response.kind, ContentKind::Binary | ContentKind::Pdf) {
+        anyhow::bail!(
+            "Not a sitemap: {} content",
+            page.response.content_type.as_deref().unwrap_or("binary")
+        );
+    }
+    parse(&page.html, &page.url)
+}
+
+/// Parse a sitemap read from `source`: a `<urlset>`, a `<sitemapindex>` or
+/// a plain-text list of URLs.
+pub fn parse(text: &str, source: &Url) -> Result<Sitemap> {
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    if !text.starts_with('<') {
+        let urls = text
+            .lines()
+            .filter_map(|line| Url::parse(line.trim()).ok())
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .map(|url| SitemapUrl {
+                loc: url.to_string(),
+                lastmod: None,
+                changefreq: None,
+                priority: None,
+                sitemap: source.to_string(),
+            })
+            .collect();
+        return Ok(Sitemap::Urls(urls));
+    }
+
+    let package = sxd_document::parser::parse(text)
+        .map_err(|e| anyhow::anyhow!("Invalid sitemap XML: {}", e))?;
+    let document = package.as_document();
+    let root = document
+        .root()
+        .children()
+        .into_iter()
+        .find_map(|child| match child {
+            ChildOfRoot::Element(element) => Some(element),
+            _ => None,
+        })
+        .ok_or(anyhow::anyhow!("Sitemap has no root element"))?;
+    let loc = |element: Element| child_text(element, "loc").and_then(|loc| source.join(&loc).ok());
+
+    match root.name().local_part() {
+        "urlset" => Ok(Sitemap::Urls(
+            children(root, "url")
+                .into_iter()
+                .filter_map(|url| {
+                    Some(SitemapUrl {
+                        loc: loc(url)?.to_string(),
+                        lastmod: child_text(url, "lastmod"),
+                        changefreq: child_text(url, "changefreq"),
+                        priority: child_text(url, "priority").and_then(|p| p.parse().ok()),
+                        sitemap: source.to_string(),
+                    })
+                })
+                .collect(),
+        )),
+        "sitemapindex" => Ok(Sitemap::Index(
+            children(root, "sitemap")
+                .into_iter()
+                .filter_map(|sitemap| Some((loc(sitemap)?, child_text(sitemap, "lastmod"))))
+                .collect(),
+        )),
+        other => anyhow::bail!("Not a sitemap: root element is <{}>", other),
+    }
+}
+
+/// Parse a W3C datetime as used by lastmod: a date, optionally with a
+/// time and offset. Dates without a time are taken as midnight UTC.
+pub fn parse_lastmod(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return Some(datetime.with_timezone(&Utc));
+    }
+    if let Ok(datetime) = DateTime::parse_from_str(value, "%Y-%m-%dT%H:%M%:z") {
+        return Some(datetime.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|datetime| datetime.and_utc())
+}
+
+/// Whether `url` names an XML sitemap rather than a site. Plain-text
+/// sitemaps are only read when robots.txt or an index lists them, since any
+/// `.txt` file, robots.txt included, would otherwise pass for one.
+fn is_sitemap_url(url: &Url) -> bool {
+    let path = url.path().to_ascii_lowercase();
+    path.ends_with(".xml") || path.ends_with(".xml.gz")
+}
@@ -0,0 +1,88 @@
+use anyhow::Result;
+use std::sync::Arc;
+
+use crate::archive::ArchiveMode;
+use crate::cache::PageCache;
+use crate::chunking::ChunkStore;
+use crate::config::CrawlerConfig;
+use crate::jobs::{CrawlJobs, Notifier};
+use crate::monitor::MonitorStore;
+use crate::politeness::Politeness;
+use crate::scraper_tools::ScrapingSession;
+use crate::sessions::{SessionStore, SharedSession};
+
+/// State shared by every tool of a server.
+pub struct CrawlerState {
+    pub config: CrawlerConfig,
+    pub cache: Arc<PageCache>,
+    pub politeness: Arc<Politeness>,
+    pub sessions: SessionStore,
+    /// Chunked content waiting to be read with `get_chunk`.
+    pub chunks: ChunkStore,
+    /// Pages watched with `monitor`.
+    pub monitors: MonitorStore,
+    /// Where sessions record their exchanges, or replay them from.
+    pub archive: Option<ArchiveMode>,
+    /// Crawls started with `start_crawl`.
+    pub jobs: CrawlJobs,
+    /// Sends notifications, such as progress, to the client.
+    pub notifier: Option<Notifier>,
+}
+
+impl CrawlerState {
+    pub fn new(config: CrawlerConfig) -> Self {
+        let cache = Arc::new(PageCache::new(config.cache.clone()));
+        let politeness = Arc::new(Politeness::new(&config));
+        let monitors = MonitorStore::new(config.monitor_dir.clone());
+        Self {
+            config,
+            cache,
+            politeness,
+            sessions: SessionStore::default(),
+            chunks: ChunkStore::default(),
+            monitors,
+            archive: None,
+            jobs: CrawlJobs::default(),
+            notifier: None,
+        }
+    }
+
+    /// Record every exchange of the server's sessions, robots.txt fetches
+    /// included, or answer them from an archive.
+    pub fn with_archive(mut self, archive: ArchiveMode) -> Self {
+        self.politeness = Arc::new(Politeness::new(&self.config).with_archive(archive.clone()));
+        self.archive = Some(archive);
+        self
+    }
+
+    pub fn with_notifier(mut self, notifier: Notifier) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    /// A new session using the server configuration, page cache,
+    /// politeness rules and archive.
+    pub fn session(&self) -> Result<ScrapingSession> {
+        Ok(self.uncached_session()?.with_cache(self.cache.clone()))
+    }
+
+    /// A new session like [`CrawlerState::session`] that always fetches
+    /// pages anew.
+    pub fn uncached_session(&self) -> Result<ScrapingSession> {
+        let session = ScrapingSession::new(&self.config)?.with_politeness(self.politeness.clone());
+        Ok(match &self.archive {
+            Some(archive) => session.with_archive(archive.clone()),
+            None => session,
+        })
+    }
+
+    /// The named session `session_id`, or a new one-off session when none
+    /// is given. Named sessions bypass the page cache since their pages may
+    /// depend on cookies.
+    pub fn session_for(&self, session_id: Option<&str>) -> Result<SharedSession> {
+        match session_id {
+            Some(id) => self.sessions.get(id),
+            None => Ok(Arc::new(self.session()?.into())),
+        }
+    }
+}
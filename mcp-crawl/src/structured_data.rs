@@ -0,0 +1,395 @@
+use ego_tree::NodeId;
+use scraper::{ElementRef, Html, Selector};
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, HashSet};
+
+const SCHEMA_ORG: &str = "https://schema.org";
+
+/// Items described by a page's JSON-LD, microdata and RDFa Lite markup,
+/// each as `{"type": format, "data": item}`.
+///
+/// Items are schema.org-style JSON objects: `@type` holds the type name
+/// (the full URL for vocabularies other than schema.org), `@id` the item's
+/// identifier, and each property its value, or a list when it is given more
+/// than once. JSON-LD `@graph` arrays are flattened into their nodes.
+///
+/// With `types`, only items of one of those types are kept, looking into
+/// nested items too, so `Product` finds the product of a `WebPage`.
+pub fn extract(document: &Html, resolve: &dyn Fn(&str) -> String, types: &[String]) -> Vec<Value> {
+    let items = json_ld(document)
+        .into_iter()
+        .map(|item| ("json-ld", item))
+        .chain(
+            Microdata::new(document, resolve)
+                .items()
+                .into_iter()
+                .map(|item| ("microdata", item)),
+        )
+        .chain(
+            rdfa(document, resolve)
+                .into_iter()
+                .map(|item| ("rdfa", item)),
+        );
+
+    let mut data = Vec::new();
+    for (format, item) in items {
+        let matching = if types.is_empty() {
+            vec![item]
+        } else {
+            let mut matching = Vec::new();
+            find_types(item, types, &mut matching);
+            matching
+        };
+        data.extend(
+            matching
+                .into_iter()
+                .map(|item| json!({"type": format, "data": item})),
+        );
+    }
+    data
+}
+
+/// Nodes of every `application/ld+json` script, with `@graph` flattened.
+fn json_ld(document: &Html) -> Vec<Value> {
+    let Ok(selector) = Selector::parse("script[type='application/ld+json']") else {
+        return vec![];
+    };
+    document
+        .select(&selector)
+        .filter_map(|script| serde_json::from_str(&script.text().collect::<String>()).ok())
+        .flat_map(flatten_graph)
+        .collect()
+}
+
+fn flatten_graph(data: Value) -> Vec<Value> {
+    match data {
+        Value::Array(items) => items.into_iter().flat_map(flatten_graph).collect(),
+        Value::Object(mut object) if object.contains_key("@graph") => {
+            let context = object.remove("@context");
+            let nodes = match object.remove("@graph") {
+                Some(Value::Array(nodes)) => nodes,
+                Some(node) => vec![node],
+                None => vec![],
+            };
+            nodes
+                .into_iter()
+                .map(|mut node| {
+                    if let (Some(node), Some(context)) = (node.as_object_mut(), &context) {
+                        node.entry("@context").or_insert_with(|| context.clone());
+                    }
+                    node
+                })
+                .collect()
+        }
+        data => vec![data],
+    }
+}
+
+/// Keep `item` when it has one of `types`, else look for such items among
+/// its property values.
+fn find_types(item: Value, types: &[String], found: &mut Vec<Value>) {
+    match item {
+        Value::Object(object) => {
+            let matches = match object.get("@type") {
+                Some(Value::String(t)) => has_type(t, types),
+                Some(Value::Array(ts)) => ts
+                    .iter()
+                    .filter_map(|t| t.as_str())
+                    .any(|t| has_type(t, types)),
+                _ => false,
+            };
+            if matches {
+                found.push(Value::Object(object));
+            } else {
+                for (_, value) in object {
+                    find_types(value, types, found);
+                }
+            }
+        }
+        Value::Array(values) => values.into_iter().for_each(|v| find_types(v, types, found)),
+        _ => {}
+    }
+}
+
+fn has_type(item_type: &str, types: &[String]) -> bool {
+    let name = term_name(item_type);
+    types
+        .iter()
+        .any(|t| term_name(t).eq_ignore_ascii_case(name))
+}
+
+/// `Product` of `https://schema.org/Product`, `schema:Product` or `Product`.
+fn term_name(term: &str) -> &str {
+    match term.split_once("://") {
+        Some((_, rest)) => rest.rsplit(['/', '#']).next().unwrap_or(term),
+        None => term.rsplit(':').next().unwrap_or(term),
+    }
+}
+
+/// A schema.org type or property URL as its bare name; other URLs stay
+/// whole.
+fn schema_name(url: &str) -> String {
+    ["https://schema.org/", "http://schema.org/"]
+        .iter()
+        .find_map(|prefix| url.strip_prefix(prefix))
+        .unwrap_or(url)
+        .to_string()
+}
+
+/// Add `value` to `property`, turning repeated properties into lists.
+fn add_property(item: &mut Map<String, Value>, property: String, value: Value) {
+    match item.get_mut(&property) {
+        Some(Value::Array(values)) => values.push(value),
+        Some(existing) => *existing = json!([existing.take(), value]),
+        None => {
+            item.insert(property, value);
+        }
+    }
+}
+
+fn text_content(element: ElementRef) -> String {
+    element
+        .text()
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Microdata items as described by the HTML standard.
+struct Microdata<'a> {
+    document: &'a Html,
+    resolve: &'a dyn Fn(&str) -> String,
+    /// Elements by `id`, for `itemref`.
+    ids: HashMap<&'a str, ElementRef<'a>>,
+}
+
+impl<'a> Microdata<'a> {
+    fn new(document: &'a Html, resolve: &'a dyn Fn(&str) -> String) -> Self {
+        let ids = Selector::parse("[id]")
+            .map(|selector| {
+                document
+                    .select(&selector)
+                    .filter_map(|e| Some((e.value().attr("id")?, e)))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            document,
+            resolve,
+            ids,
+        }
+    }
+
+    /// Top-level items: those that are not a property of another item.
+    fn items(&self) -> Vec<Value> {
+        let Ok(selector) = Selector::parse("[itemscope]:not([itemprop])") else {
+            return vec![];
+        };
+        self.document
+            .select(&selector)
+            .map(|element| {
+                let mut item = self.item(element, &mut vec![]);
+                if let Some(object) = item.as_object_mut() {
+                    object.insert("@context".to_string(), json!(SCHEMA_ORG));
+                }
+                item
+            })
+            .collect()
+    }
+
+    /// The item of an `itemscope` element. `parents` are the items being
+    /// built around it, to stop `itemref` loops.
+    fn item(&self, element: ElementRef<'a>, parents: &mut Vec<NodeId>) -> Value {
+        parents.push(element.id());
+        let attr = |name: &str| {
+            element
+                .value()
+                .attr(name)
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+        };
+        let mut item = Map::new();
+        if let Some(types) = attr("itemtype") {
+            let types: Vec<String> = types.split_whitespace().map(schema_name).collect();
+            item.insert(
+                "@type".to_string(),
+                if types.len() == 1 {
+                    json!(types[0])
+                } else {
+                    json!(types)
+                },
+            );
+        }
+        if let Some(id) = attr("itemid") {
+            item.insert("@id".to_string(), json!((self.resolve)(id)));
+        }
+
+        for property in self.properties(element) {
+            let value = if property.value().attr("itemscope").is_some() {
+                if parents.contains(&property.id()) {
+                    continue;
+                }
+                self.item(property, parents)
+            } else {
+                self.value(property)
+            };
+            let names = property.value().attr("itemprop").unwrap_or_default();
+            for name in names.split_whitespace() {
+                add_property(&mut item, schema_name(name), value.clone());
+            }
+        }
+
+        parents.pop();
+        Value::Object(item)
+    }
+
+    /// Elements with an `itemprop` belonging to the item of `root`: its
+    /// descendants and those of the elements it `itemref`s, without
+    /// looking into nested items.
+    fn properties(&self, root: ElementRef<'a>) -> Vec<ElementRef<'a>> {
+        let mut pending: Vec<ElementRef> = root.children().filter_map(ElementRef::wrap).collect();
+        if let Some(refs) = root.value().attr("itemref") {
+            pending.extend(
+                refs.split_whitespace()
+                    .filter_map(|id| self.ids.get(id).copied()),
+            );
+        }
+        pending.reverse();
+
+        let mut seen = HashSet::new();
+        let mut properties = Vec::new();
+        while let Some(element) = pending.pop() {
+            if element.id() == root.id() || !seen.insert(element.id()) {
+                continue;
+            }
+            if element.value().attr("itemprop").is_some() {
+                properties.push(element);
+            }
+            if element.value().attr("itemscope").is_none() {
+                let children: Vec<ElementRef> =
+                    element.children().filter_map(ElementRef::wrap).collect();
+                pending.extend(children.into_iter().rev());
+            }
+        }
+        properties
+    }
+
+    /// Value of a property element, depending on its tag.
+    fn value(&self, element: ElementRef) -> Value {
+        let e = element.value();
+        let url = |name: &str| json!((self.resolve)(e.attr(name).unwrap_or_default()));
+        match e.name() {
+            "meta" => json!(e.attr("content").unwrap_or_default()),
+            "audio" | "embed" | "iframe" | "img" | "source" | "track" | "video" => url("src"),
+            "a" | "area" | "link" => url("href"),
+            "object" => url("data"),
+            "data" | "meter" => json!(e.attr("value").unwrap_or_default()),
+            "time" => json!(e
+                .attr("datetime")
+                .map(str::to_string)
+                .unwrap_or_else(|| text_content(element))),
+            _ => json!(text_content(element)),
+        }
+    }
+}
+
+/// RDFa Lite items: `vocab`, `typeof`, `property` and `resource`, with
+/// prefixed terms such as `schema:name` taken by their name.
+fn rdfa(document: &Html, resolve: &dyn Fn(&str) -> String) -> Vec<Value> {
+    let Ok(selector) = Selector::parse("[typeof]:not([property])") else {
+        return vec![];
+    };
+    document
+        .select(&selector)
+        .map(|element| {
+            let mut item = rdfa_item(element, resolve);
+            if let Some(object) = item.as_object_mut() {
+                if !object.contains_key("@context") {
+                    let vocab = rdfa_vocab(element);
+                    object.insert("@context".to_string(), json!(vocab.unwrap_or(SCHEMA_ORG)));
+                }
+            }
+            item
+        })
+        .collect()
+}
+
+fn rdfa_item(element: ElementRef, resolve: &dyn Fn(&str) -> String) -> Value {
+    let vocab = rdfa_vocab(element);
+    let term = |term: &str| {
+        if term.contains("://") {
+            return schema_name(term);
+        }
+        match (term.split_once(':'), vocab) {
+            (Some((_, name)), _) => name.to_string(),
+            (None, Some(vocab)) if !is_schema_org(vocab) => format!("{}{}", vocab, term),
+            (None, _) => term.to_string(),
+        }
+    };
+    let mut item = Map::new();
+    if let Some(types) = element
+        .value()
+        .attr("typeof")
+        .filter(|t| !t.trim().is_empty())
+    {
+        let types: Vec<String> = types.split_whitespace().map(term).collect();
+        item.insert(
+            "@type".to_string(),
+            if types.len() == 1 {
+                json!(types[0])
+            } else {
+                json!(types)
+            },
+        );
+    }
+    if let Some(id) = element.value().attr("resource") {
+        item.insert("@id".to_string(), json!(resolve(id)));
+    }
+
+    let mut pending: Vec<ElementRef> = element.children().filter_map(ElementRef::wrap).collect();
+    pending.reverse();
+    while let Some(child) = pending.pop() {
+        if let Some(properties) = child.value().attr("property") {
+            let value = if child.value().attr("typeof").is_some() {
+                rdfa_item(child, resolve)
+            } else {
+                rdfa_value(child, resolve)
+            };
+            for property in properties.split_whitespace() {
+                add_property(&mut item, term(property), value.clone());
+            }
+        }
+        if child.value().attr("typeof").is_none() {
+            let children: Vec<ElementRef> = child.children().filter_map(ElementRef::wrap).collect();
+            pending.extend(children.into_iter().rev());
+        }
+    }
+    Value::Object(item)
+}
+
+fn rdfa_value(element: ElementRef, resolve: &dyn Fn(&str) -> String) -> Value {
+    let e = element.value();
+    if let Some(content) = e.attr("content") {
+        return json!(content);
+    }
+    if let Some(url) = e.attr("resource").or(e.attr("href")).or(e.attr("src")) {
+        return json!(resolve(url));
+    }
+    if let Some(datetime) = e.attr("datetime") {
+        return json!(datetime);
+    }
+    json!(text_content(element))
+}
+
+/// The `vocab` in scope at `element`.
+fn rdfa_vocab(element: ElementRef<'_>) -> Option<&str> {
+    std::iter::successors(Some(element), |e| e.parent().and_then(ElementRef::wrap))
+        .find_map(|e| e.value().attr("vocab"))
+        .map(str::trim)
+        .filter(|vocab| !vocab.is_empty())
+}
+
+fn is_schema_org(vocab: &str) -> bool {
+    vocab.trim_end_matches('/').ends_with("://schema.org")
+}
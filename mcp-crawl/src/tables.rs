@@ -0,0 +1,456 @@
+use anyhow::Result;
+use scraper::{ElementRef, Node};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use crate::schema;
+
+/// How the cells of an extracted table are returned.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TableFormat {
+    /// `rows`: one array of cells per row.
+    #[default]
+    Rows,
+    /// `records`: one object per row, keyed by header.
+    Records,
+    /// `csv`: the header and rows as CSV text.
+    Csv,
+    /// `markdown`: a Markdown table.
+    Markdown,
+}
+
+impl TableFormat {
+    pub fn parse(format: &str) -> Result<Self> {
+        match format {
+            "rows" | "json" => Ok(TableFormat::Rows),
+            "records" => Ok(TableFormat::Records),
+            "csv" => Ok(TableFormat::Csv),
+            "markdown" => Ok(TableFormat::Markdown),
+            other => anyhow::bail!(
+                "Unknown table format {} (expected rows, records, csv or markdown)",
+                other
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TableOptions {
+    pub format: TableFormat,
+    /// Convert the cells of numeric, percentage, currency and date columns,
+    /// and report the type of every column.
+    pub infer_types: bool,
+}
+
+/// What the values of a column are, when every non-empty cell agrees.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    String,
+    Integer,
+    Number,
+    /// `12.5%`, converted to `12.5`.
+    Percentage,
+    /// `$1,299.00` or `20 EUR`, converted to the amount.
+    Currency,
+    /// Converted to `YYYY-MM-DD`, or RFC 3339 with a time.
+    Date,
+}
+
+const CURRENCIES: &[&str] = &[
+    "US$", "$", "€", "£", "¥", "₹", "USD", "EUR", "GBP", "JPY", "CHF", "CAD", "AUD",
+];
+
+/// A table with its row and column spans expanded into a grid: a cell
+/// spanning several rows or columns is repeated in each of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    pub caption: Option<String>,
+    /// One name per column. Columns under several header rows are named
+    /// after each of them, e.g. `Sales / Q1`.
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// A cell of the expanded grid.
+#[derive(Debug, Clone, Default)]
+struct Cell {
+    text: String,
+    header: bool,
+}
+
+impl Table {
+    /// Read a `<table>` element. Rows of nested tables are left out.
+    /// `None` when the table has neither headers nor data.
+    pub fn parse(table: ElementRef) -> Option<Self> {
+        let mut caption = None;
+        let mut head = Vec::new();
+        let mut body = Vec::new();
+        let mut foot = Vec::new();
+        for child in table.children().filter_map(ElementRef::wrap) {
+            match child.value().name() {
+                "caption" => caption = Some(cell_text(child)).filter(|c| !c.is_empty()),
+                "thead" => head.extend(expand(child.children().filter_map(ElementRef::wrap))),
+                "tfoot" => foot.extend(expand(child.children().filter_map(ElementRef::wrap))),
+                "tbody" => body.extend(expand(child.children().filter_map(ElementRef::wrap))),
+                _ => {}
+            }
+        }
+        // Rows directly in the table form an implicit body of their own
+        let direct_rows = expand(table.children().filter_map(ElementRef::wrap));
+        if body.is_empty() {
+            body = direct_rows;
+        } else {
+            body.extend(direct_rows);
+        }
+        body.extend(foot);
+
+        // Without a <thead>, leading rows of <th> are headers, and failing
+        // that the first row is
+        if head.is_empty() {
+            let leading = body
+                .iter()
+                .take_while(|row| !row.is_empty() && row.iter().all(|cell| cell.header))
+                .count();
+            head = body.drain(..leading.max(1).min(body.len())).collect();
+        }
+
+        let width = head.iter().chain(&body).map(Vec::len).max().unwrap_or(0);
+        let headers: Vec<String> = (0..width)
+            .map(|column| {
+                let mut names: Vec<&str> = Vec::new();
+                for row in &head {
+                    match row.get(column).map(|cell| cell.text.as_str()) {
+                        Some(name) if !name.is_empty() && names.last() != Some(&name) => {
+                            names.push(name)
+                        }
+                        _ => {}
+                    }
+                }
+                names.join(" / ")
+            })
+            .collect();
+        let rows: Vec<Vec<String>> = body
+            .into_iter()
+            .filter(|row| row.iter().any(|cell| !cell.text.is_empty()))
+            .map(|row| {
+                let mut texts: Vec<String> = row.into_iter().map(|cell| cell.text).collect();
+                texts.resize(width, String::new());
+                texts
+            })
+            .collect();
+
+        let headers = if headers.iter().all(|h| h.is_empty()) {
+            vec![]
+        } else {
+            headers
+        };
+        if headers.is_empty() && rows.is_empty() {
+            return None;
+        }
+        Some(Self {
+            caption,
+            headers,
+            rows,
+        })
+    }
+
+    fn width(&self) -> usize {
+        self.rows
+            .first()
+            .map(Vec::len)
+            .unwrap_or(self.headers.len())
+    }
+
+    /// The type of every column.
+    pub fn column_types(&self) -> Vec<ColumnType> {
+        (0..self.width())
+            .map(|column| {
+                infer_type(
+                    self.rows
+                        .iter()
+                        .map(|row| row[column].as_str())
+                        .filter(|cell| !cell.is_empty()),
+                )
+            })
+            .collect()
+    }
+
+    pub fn to_json(&self, options: &TableOptions) -> Value {
+        let mut table = json!({
+            "caption": self.caption,
+            "headers": self.headers,
+        });
+        let types = options.infer_types.then(|| self.column_types());
+        if let Some(types) = &types {
+            table["column_types"] = json!(types);
+        }
+        let row_values = |row: &Vec<String>| -> Vec<Value> {
+            row.iter()
+                .enumerate()
+                .map(|(column, cell)| match &types {
+                    Some(types) => convert(cell, types[column]),
+                    None => json!(cell),
+                })
+                .collect()
+        };
+
+        match options.format {
+            TableFormat::Rows => {
+                table["rows"] = json!(self.rows.iter().map(row_values).collect::<Vec<_>>());
+            }
+            TableFormat::Records => {
+                let keys = self.record_keys();
+                let records: Vec<Value> = self
+                    .rows
+                    .iter()
+                    .map(|row| {
+                        let record: Map<String, Value> =
+                            keys.iter().cloned().zip(row_values(row)).collect();
+                        Value::Object(record)
+                    })
+                    .collect();
+                table["records"] = json!(records);
+            }
+            TableFormat::Csv => table["csv"] = json!(self.to_csv()),
+            TableFormat::Markdown => table["markdown"] = json!(self.to_markdown()),
+        }
+        table
+    }
+
+    /// Unique record keys: the headers, with `column_N` for unnamed columns
+    /// and a `_2`, `_3`... suffix on repeated names.
+    fn record_keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = Vec::new();
+        for column in 0..self.width() {
+            let name = match self.headers.get(column) {
+                Some(name) if !name.is_empty() => name.clone(),
+                _ => format!("column_{}", column + 1),
+            };
+            let mut key = name.clone();
+            let mut n = 1;
+            while keys.contains(&key) {
+                n += 1;
+                key = format!("{}_{}", name, n);
+            }
+            keys.push(key);
+        }
+        keys
+    }
+
+    /// The header, when there is one, and the rows as CSV (RFC 4180).
+    pub fn to_csv(&self) -> String {
+        let line = |cells: &[String]| {
+            let fields: Vec<String> = cells
+                .iter()
+                .map(|cell| {
+                    if cell.contains([',', '"', '\n', '\r']) {
+                        format!("\"{}\"", cell.replace('"', "\"\""))
+                    } else {
+                        cell.clone()
+                    }
+                })
+                .collect();
+            format!("{}\r\n", fields.join(","))
+        };
+        let header = (!self.headers.is_empty()).then(|| line(&self.headers));
+        header
+            .into_iter()
+            .chain(self.rows.iter().map(|row| line(row)))
+            .collect()
+    }
+
+    /// A Markdown table, with the caption in bold above it. Markdown
+    /// tables need a header, so the first row is used when there is none.
+    pub fn to_markdown(&self) -> String {
+        let line = |cells: &[String]| {
+            let cells: Vec<String> = cells.iter().map(|c| c.replace('|', "\\|")).collect();
+            format!("| {} |", cells.join(" | "))
+        };
+        let (header, rows) = if self.headers.is_empty() {
+            self.rows
+                .split_first()
+                .map(|(h, r)| (h.as_slice(), r))
+                .unwrap_or_default()
+        } else {
+            (self.headers.as_slice(), self.rows.as_slice())
+        };
+
+        let mut lines = Vec::new();
+        if let Some(caption) = &self.caption {
+            lines.push(format!("**{}**", caption));
+            lines.push(String::new());
+        }
+        lines.push(line(header));
+        lines.push(format!("|{}", " --- |".repeat(header.len())));
+        lines.extend(rows.iter().map(|row| line(row)));
+        lines.join("\n")
+    }
+}
+
+/// Lay out the `<tr>`s among `rows` on a grid, repeating cells across
+/// their `colspan` and `rowspan`. Row spans do not reach beyond `rows`.
+fn expand<'a>(rows: impl Iterator<Item = ElementRef<'a>>) -> Vec<Vec<Cell>> {
+    let rows: Vec<ElementRef> = rows.filter(|row| row.value().name() == "tr").collect();
+    let mut grid: Vec<Vec<Option<Cell>>> = vec![Vec::new(); rows.len()];
+    for (r, row) in rows.iter().enumerate() {
+        let mut column = 0;
+        for cell in row.children().filter_map(ElementRef::wrap) {
+            let name = cell.value().name();
+            if name != "td" && name != "th" {
+                continue;
+            }
+            while grid[r].get(column).is_some_and(|c| c.is_some()) {
+                column += 1;
+            }
+            let span = |attr: &str, default: usize| {
+                cell.value()
+                    .attr(attr)
+                    .and_then(|v| v.trim().parse::<usize>().ok())
+                    .unwrap_or(default)
+            };
+            let colspan = span("colspan", 1).clamp(1, 1000);
+            // rowspan="0" spans the rest of the rows
+            let rowspan = match span("rowspan", 1) {
+                0 => rows.len() - r,
+                n => n.min(rows.len() - r),
+            };
+            let value = Cell {
+                text: cell_text(cell),
+                header: name == "th",
+            };
+            for spanned in &mut grid[r..r + rowspan] {
+                if spanned.len() < column + colspan {
+                    spanned.resize(column + colspan, None);
+                }
+                for slot in &mut spanned[column..column + colspan] {
+                    *slot = Some(value.clone());
+                }
+            }
+            column += colspan;
+        }
+    }
+    grid.into_iter()
+        .map(|row| row.into_iter().map(Option::unwrap_or_default).collect())
+        .collect()
+}
+
+/// Text of a cell with whitespace collapsed, leaving out scripts and
+/// styles.
+fn cell_text(cell: ElementRef) -> String {
+    let mut text = String::new();
+    for node in cell.descendants() {
+        if let Node::Text(t) = node.value() {
+            let hidden = node
+                .ancestors()
+                .filter_map(ElementRef::wrap)
+                .any(|e| matches!(e.value().name(), "script" | "style" | "template"));
+            if !hidden {
+                text.push_str(t);
+                text.push(' ');
+            }
+        } else if let Some(element) = ElementRef::wrap(node) {
+            if element.value().name() == "br" {
+                text.push(' ');
+            }
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn infer_type<'a>(cells: impl Iterator<Item = &'a str>) -> ColumnType {
+    let cells: Vec<&str> = cells.collect();
+    if cells.is_empty() {
+        return ColumnType::String;
+    }
+    [
+        ColumnType::Integer,
+        ColumnType::Number,
+        ColumnType::Percentage,
+        ColumnType::Currency,
+        ColumnType::Date,
+    ]
+    .into_iter()
+    .find(|column_type| {
+        cells
+            .iter()
+            .all(|cell| !convert(cell, *column_type).is_null())
+    })
+    .unwrap_or(ColumnType::String)
+}
+
+/// `cell` as a value of `column_type`, or `null` when it is empty or not of
+/// that type.
+fn convert(cell: &str, column_type: ColumnType) -> Value {
+    let cell = cell.trim();
+    if cell.is_empty() {
+        return match column_type {
+            ColumnType::String => json!(""),
+            _ => Value::Null,
+        };
+    }
+    let value = match column_type {
+        ColumnType::String => Some(json!(cell)),
+        ColumnType::Integer => parse_number(cell)
+            .filter(|_| !cell.contains('.'))
+            .map(|n| json!(n as i64)),
+        ColumnType::Number => parse_number(cell).map(|n| json!(n)),
+        ColumnType::Percentage => cell
+            .strip_suffix('%')
+            .and_then(|n| parse_number(n.trim_end()))
+            .map(|n| json!(n)),
+        ColumnType::Currency => parse_currency(cell).map(|n| json!(n)),
+        ColumnType::Date => schema::parse_date(cell, None).map(|d| json!(d)),
+    };
+    value.unwrap_or(Value::Null)
+}
+
+/// A plain number such as `1,234.5`, `-7` or `+0.97`: commas are only
+/// taken as thousands separators.
+pub(crate) fn parse_number(text: &str) -> Option<f64> {
+    let text = text.trim().replace('\u{2212}', "-");
+    let (sign, digits) = match text.strip_prefix(['+', '-']) {
+        Some(rest) => (&text[..1], rest),
+        None => ("", text.as_str()),
+    };
+    let (int, fraction) = match digits.split_once('.') {
+        Some((int, fraction)) if !fraction.is_empty() => (int, fraction),
+        Some(_) => return None,
+        None => (digits, ""),
+    };
+    if !fraction.bytes().all(|b| b.is_ascii_digit()) || (int.is_empty() && fraction.is_empty()) {
+        return None;
+    }
+    let groups: Vec<&str> = int.split(',').collect();
+    let valid_groups = groups.iter().enumerate().all(|(i, group)| {
+        group.bytes().all(|b| b.is_ascii_digit())
+            && match (i, groups.len()) {
+                (_, 1) => true,
+                (0, _) => (1..=3).contains(&group.len()),
+                _ => group.len() == 3,
+            }
+    });
+    if !valid_groups {
+        return None;
+    }
+    format!("{}{}.{}", sign, groups.concat(), fraction)
+        .trim_end_matches('.')
+        .parse()
+        .ok()
+}
+
+/// An amount with a currency symbol or code before or after it, such as
+/// `$1,299.00`, `-£5` or `20 EUR`.
+fn parse_currency(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let (negative, rest) = match text.strip_prefix(['-', '\u{2212}']) {
+        Some(rest) => (true, rest.trim_start()),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let amount = CURRENCIES.iter().find_map(|currency| {
+        rest.strip_prefix(currency)
+            .or_else(|| rest.strip_suffix(currency))
+            .and_then(|amount| parse_number(amount.trim()))
+    })?;
+    Some(if negative { -amount } else { amount })
+}
@@ -1,12 +1,209 @@
-use crate::scraper_tools::{ElementExtractor, XPathAlternative};
+use crate::archive::{Archive, ArchiveMode, ExportFormat, Replay};
+use crate::cache::{CacheEntry, CacheOptions, CacheStatus, Lookup, PageCache};
+use crate::chunking::{chunk_text, estimate_tokens, ChunkOptions, ChunkStore, ChunkStrategy};
+use crate::config::CrawlerConfig;
+use crate::crawler::{extract_page_links, CrawlOptions, CrawlReport, CrawlScope, Crawler};
+use crate::feed;
+use crate::fetch::{
+    decode_body, gunzip, is_gzip, retry_delay, BodyTooLarge, ContentKind, HttpError,
+};
+use crate::jobs::{CrawlJobs, JobState, Notifier, ProgressReporter};
+use crate::link_checker::{LinkCheckOptions, LinkChecker, LinkStatus};
+use crate::markdown::MarkdownOptions;
+use crate::monitor::{CheckStatus, MonitorOptions, MonitorStore};
+use crate::pagination::{Paginator, StopReason};
+use crate::politeness::{Politeness, RobotsBlocked, RobotsTxt};
+use crate::schema::ExtractionSchema;
+use crate::scraper_tools::{
+    parse_srcset, ElementExtractor, FormSubmitter, ScrapingSession, XPathAlternative,
+};
+use crate::server::{
+    link_check_options_from_args, monitor_options_from_args, page_from_args,
+    pagination_options_from_args,
+};
+use crate::sessions::SessionStore;
+use crate::sitemap::{self, Sitemap, SitemapOptions, SitemapReader};
+use crate::state::CrawlerState;
+use crate::tables::{TableFormat, TableOptions};
+use crate::xpath::XPathResult;
 use async_mcp::{
     protocol::RequestOptions,
     transport::{ClientInMemoryTransport, ServerInMemoryTransport, Transport},
 };
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use regex::Regex;
+use reqwest::header::{self, HeaderMap};
+use reqwest::StatusCode;
 use serde_json::json;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use url::Url;
+
+/// Raw requests received by a test server, in arrival order.
+type RequestLog = Arc<Mutex<Vec<String>>>;
+
+/// Serve `routes` (path -> status, content type, body) over HTTP on a local
+/// port and return the base URL along with a log of the received requests.
+/// Unknown paths get a 404. Every response carries an ETag, and a matching
+/// `If-None-Match` gets a 304. Extra response headers can be appended to the
+/// content type, separated by `\r\n`. Requests for `/robots.txt` are not
+/// logged.
+async fn serve_pages(
+    routes: Vec<(&'static str, u16, &'static str, String)>,
+) -> (String, RequestLog) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let requests = RequestLog::default();
+    let log = requests.clone();
+    let routes: HashMap<&'static str, (u16, &'static str, String)> = routes
+        .into_iter()
+        .map(|(path, status, content_type, body)| (path, (status, content_type, body)))
+        .collect();
+
+    tokio::spawn(async move {
+        loop {
+            let Ok((mut stream, _)) = listener.accept().await else {
+                break;
+            };
+            let request = read_request(&mut stream).await;
+            let target = request.split_whitespace().nth(1).unwrap_or("/");
+            let path = target.split('?').next().unwrap_or("/").to_string();
+            if path != "/robots.txt" {
+                log.lock().unwrap().push(request.clone());
+            }
+            let (mut status, content_type, mut body) = match routes.get(path.as_str()) {
+                Some(route) => route.clone(),
+                None => (404, "text/html", "<h1>Not found</h1>".to_string()),
+            };
+            let mut hasher = DefaultHasher::new();
+            body.hash(&mut hasher);
+            let etag = format!("\"{:x}\"", hasher.finish());
+            let if_none_match = format!("if-none-match: {}", etag);
+            if request
+                .lines()
+                .any(|line| line.eq_ignore_ascii_case(&if_none_match))
+            {
+                status = 304;
+                body.clear();
+            }
+            let response = format!(
+                "HTTP/1.1 {} X\r\nContent-Type: {}\r\nETag: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                content_type,
+                etag,
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+        }
+    });
+
+    (base, requests)
+}
+
+/// Serve `versions` of a page in turn, whatever the path, repeating the
+/// last one. robots.txt is not found.
+async fn serve_versions(versions: Vec<String>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+
+    tokio::spawn(async move {
+        let mut served = 0;
+        loop {
+            let Ok((mut stream, _)) = listener.accept().await else {
+                break;
+            };
+            let request = read_request(&mut stream).await;
+            let (status, body) = if request.starts_with("GET /robots.txt ") {
+                (404, String::new())
+            } else {
+                served += 1;
+                (200, versions[served.min(versions.len()) - 1].clone())
+            };
+            let response = format!(
+                "HTTP/1.1 {} X\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+        }
+    });
+
+    base
+}
+
+/// Serve a host that answers robots.txt but leaves every other request
+/// hanging. Its URL names `localhost`, another host than `serve_pages`.
+async fn serve_hanging() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://localhost:{}", listener.local_addr().unwrap().port());
+
+    tokio::spawn(async move {
+        let mut pending = Vec::new();
+        while let Ok((mut stream, _)) = listener.accept().await {
+            if read_request(&mut stream)
+                .await
+                .starts_with("GET /robots.txt ")
+            {
+                let response = "HTTP/1.1 404 X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+                let _ = stream.write_all(response.as_bytes()).await;
+            } else {
+                pending.push(stream);
+            }
+        }
+    });
+
+    base
+}
+
+/// Read a request head and its body, as announced by Content-Length.
+async fn read_request(stream: &mut tokio::net::TcpStream) -> String {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = stream.read(&mut buf).await.unwrap_or(0);
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+
+        let text = String::from_utf8_lossy(&request);
+        let Some(head_end) = text.find("\r\n\r\n") else {
+            continue;
+        };
+        let content_length = text[..head_end]
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+            .unwrap_or(0);
+        if request.len() >= head_end + 4 + content_length {
+            break;
+        }
+    }
+    String::from_utf8_lossy(&request).to_string()
+}
+
+/// Config for tests that talk to a local server: no delay between requests
+/// or retries.
+fn test_config() -> CrawlerConfig {
+    CrawlerConfig {
+        delay: Duration::ZERO,
+        retry_backoff: Duration::ZERO,
+        ..Default::default()
+    }
+}
 
 async fn async_server(transport: ServerInMemoryTransport) {
-    let server = crate::build(transport.clone()).unwrap();
+    let server = crate::build(transport.clone(), CrawlerConfig::default()).unwrap();
     server.listen().await.unwrap();
 }
 
@@ -328,7 +525,7 @@ fn test_xpath_to_css_conversion() {
     );
     assert_eq!(
         XPathAlternative::xpath_to_css("//div[1]").unwrap(),
-        "div:nth-child(1)"
+        "div:first-of-type"
     );
     assert_eq!(
         XPathAlternative::xpath_to_css("/html/body/div").unwrap(),
@@ -347,8 +544,118 @@ fn test_xpath_common_patterns() {
         Some(&"input[type='text']")
     );
     assert_eq!(patterns.get("//div[@id='content']"), Some(&"div#content"));
-    assert_eq!(patterns.get("//p[1]"), Some(&"p:first-child"));
-    assert_eq!(patterns.get("//li[last()]"), Some(&"li:last-child"));
+    assert_eq!(patterns.get("//p[1]"), Some(&"p:first-of-type"));
+    assert_eq!(patterns.get("//li[last()]"), Some(&"li:last-of-type"));
+
+    for (xpath, css) in patterns {
+        assert_eq!(XPathAlternative::xpath_to_css(xpath).unwrap(), css);
+    }
+}
+
+#[test]
+fn test_xpath_to_css_only_translates_exactly() {
+    assert_eq!(XPathAlternative::xpath_to_css("//div//a").unwrap(), "div a");
+    assert_eq!(
+        XPathAlternative::xpath_to_css("//ul/li[3]").unwrap(),
+        "ul > li:nth-of-type(3)"
+    );
+    assert_eq!(
+        XPathAlternative::xpath_to_css("//*[2][@class]").unwrap(),
+        "*:nth-child(2)[class]"
+    );
+    assert_eq!(
+        XPathAlternative::xpath_to_css("//a[starts-with(@href, 'https:') and not(@rel)]").unwrap(),
+        "a[href^='https:']:not([rel])"
+    );
+    assert_eq!(
+        XPathAlternative::xpath_to_css("//div[@id='main content']").unwrap(),
+        "div[id='main content']"
+    );
+    assert_eq!(
+        XPathAlternative::xpath_to_css("/body").unwrap(),
+        "body:root"
+    );
+
+    for xpath in [
+        "//p/text()",
+        "//div[@class='a' or @class='b']",
+        "//li[@class][1]",
+        "//a | //img",
+        "//div/..",
+        "//div/following-sibling::p",
+        "//p[contains(text(), 'x')]",
+        "count(//p)",
+        "div",
+        "//my.elem",
+        "//a[@data.x]",
+        "//DIV",
+        "//a[@HREF]",
+    ] {
+        let error = XPathAlternative::xpath_to_css(xpath).unwrap_err();
+        assert!(
+            error.to_string().contains("no exact CSS equivalent"),
+            "{}: {}",
+            xpath,
+            error
+        );
+    }
+}
+
+#[test]
+fn test_evaluate_xpath() {
+    let html = r#"
+        <html>
+        <body>
+            <ul id="menu">
+                <li><a href="/one">One</a></li>
+                <li class="active"><a href="/two">Two</a></li>
+            </ul>
+            <!-- footer -->
+        </body>
+        </html>
+    "#;
+    let extractor = ElementExtractor::new(html);
+
+    let links = extractor.evaluate_xpath("//li[@class='active']/a").unwrap();
+    let XPathResult::Nodes(nodes) = links else {
+        panic!("expected nodes, got {:?}", links);
+    };
+    assert_eq!(nodes.len(), 1);
+    assert_eq!(nodes[0]["type"], "element");
+    assert_eq!(nodes[0]["tag"], "a");
+    assert_eq!(nodes[0]["attributes"]["href"], "/two");
+    assert_eq!(nodes[0]["text"], "Two");
+
+    let hrefs = extractor.evaluate_xpath("//ul/li/a/@href").unwrap();
+    assert_eq!(
+        hrefs.to_json()["nodes"],
+        json!([
+            {"type": "attribute", "name": "href", "value": "/one"},
+            {"type": "attribute", "name": "href", "value": "/two"}
+        ])
+    );
+
+    assert_eq!(
+        extractor.evaluate_xpath("count(//li)").unwrap(),
+        XPathResult::Number(2.0)
+    );
+    assert_eq!(
+        extractor.evaluate_xpath("string(//li[last()])").unwrap(),
+        XPathResult::String("Two".to_string())
+    );
+    assert_eq!(
+        extractor
+            .evaluate_xpath("boolean(//ul[@id='menu'])")
+            .unwrap(),
+        XPathResult::Boolean(true)
+    );
+    assert_eq!(
+        extractor
+            .evaluate_xpath("normalize-space(//comment())")
+            .unwrap(),
+        XPathResult::String("footer".to_string())
+    );
+    assert!(extractor.evaluate_xpath("//li[").is_err());
 }
 
 #[test]
@@ -493,3 +800,2510 @@ fn test_extract_tables_financial_data() {
     let msft_row = rows[2].as_array().unwrap();
     assert_eq!(msft_row[0], "Microsoft Corporation");
 }
+
+#[test]
+fn test_crawl_scope() {
+    let seed = Url::parse("https://www.example.com/docs/").unwrap();
+    let same = Url::parse("https://example.com/blog").unwrap();
+    let sub = Url::parse("https://api.example.com/").unwrap();
+    let other = Url::parse("https://example.org/").unwrap();
+
+    assert!(CrawlScope::SameDomain.allows(&seed, &same));
+    assert!(!CrawlScope::SameDomain.allows(&seed, &sub));
+    assert!(CrawlScope::Subdomains.allows(&seed, &sub));
+    assert!(!CrawlScope::Subdomains.allows(&seed, &other));
+    assert!(CrawlScope::Any.allows(&seed, &other));
+}
+
+#[test]
+fn test_crawl_url_filters() {
+    let seed = Url::parse("https://example.com/").unwrap();
+    let options = CrawlOptions {
+        include_patterns: vec![Regex::new("/docs/").unwrap()],
+        exclude_patterns: vec![Regex::new(r"\.pdf$").unwrap()],
+        ..Default::default()
+    };
+
+    assert!(options.should_visit(&seed, &seed));
+    assert!(options.should_visit(&seed, &Url::parse("https://example.com/docs/a").unwrap()));
+    assert!(!options.should_visit(&seed, &Url::parse("https://example.com/blog/a").unwrap()));
+    assert!(!options.should_visit(
+        &seed,
+        &Url::parse("https://example.com/docs/a.pdf").unwrap()
+    ));
+    assert!(!options.should_visit(&seed, &Url::parse("mailto:a@example.com").unwrap()));
+}
+
+#[test]
+fn test_extract_page_links_resolves_urls() {
+    let html = r##"
+        <a href="/about">About</a>
+        <a href="team.html#top">Team</a>
+        <a href="team.html">Team again</a>
+        <a href="https://other.com/">Other</a>
+    "##;
+    let base = Url::parse("https://example.com/company/index.html").unwrap();
+    let links: Vec<String> = extract_page_links(html, &base)
+        .iter()
+        .map(|u| u.to_string())
+        .collect();
+
+    assert_eq!(
+        links,
+        vec![
+            "https://example.com/about",
+            "https://example.com/company/team.html",
+            "https://other.com/",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_crawl_follows_links() {
+    let (base, _) = serve_pages(vec![
+        (
+            "/",
+            200,
+            "text/html",
+            r#"<html><head><title>Home</title></head><body>
+                <p>Welcome home.</p>
+                <a href="/a">A</a> <a href="/b">B</a> <a href="/missing">Missing</a>
+                <a href="https://elsewhere.invalid/">Elsewhere</a>
+            </body></html>"#
+                .to_string(),
+        ),
+        (
+            "/a",
+            200,
+            "text/html",
+            r#"<html><head><title>Page A</title></head><body>
+                <a href="/deep">Deep</a></body></html>"#
+                .to_string(),
+        ),
+        (
+            "/b",
+            200,
+            "text/html",
+            "<html><head><title>Page B</title></head><body>B</body></html>".to_string(),
+        ),
+        (
+            "/deep",
+            200,
+            "text/html",
+            "<html><head><title>Deep</title></head><body>Deep</body></html>".to_string(),
+        ),
+    ])
+    .await;
+
+    let options = CrawlOptions {
+        max_depth: 1,
+        ..Default::default()
+    };
+    let mut session = ScrapingSession::new(&test_config()).unwrap();
+    let report = Crawler::new(options)
+        .run(&mut session, &format!("{}/", base))
+        .await
+        .unwrap();
+
+    let urls: Vec<&str> = report.pages.iter().map(|p| p.url.as_str()).collect();
+    assert_eq!(
+        urls,
+        vec![
+            format!("{}/", base),
+            format!("{}/a", base),
+            format!("{}/b", base),
+            format!("{}/missing", base),
+        ]
+    );
+    assert_eq!(report.pages[0].title.as_deref(), Some("Home"));
+    assert_eq!(report.pages[0].depth, 0);
+    assert_eq!(report.pages[1].title.as_deref(), Some("Page A"));
+    assert_eq!(report.pages[3].status, Some(404));
+    assert_eq!(report.pages_failed, 1);
+
+    // The page limit stops the crawl and reports what is left
+    let options = CrawlOptions {
+        max_pages: 2,
+        ..Default::default()
+    };
+    let report = Crawler::new(options)
+        .run(&mut session, &format!("{}/", base))
+        .await
+        .unwrap();
+    assert_eq!(report.pages_crawled, 2);
+    assert_eq!(report.pages_remaining, 3);
+}
+
+#[tokio::test]
+async fn test_session_follows_crawler_config() {
+    let (base, requests) = serve_pages(vec![(
+        "/",
+        200,
+        "text/html",
+        "<html><body>Hello</body></html>".to_string(),
+    )])
+    .await;
+
+    let config = CrawlerConfig {
+        user_agent: "test-agent/2.0".to_string(),
+        delay: Duration::from_millis(300),
+        ..Default::default()
+    };
+    let mut session = ScrapingSession::new(&config).unwrap();
+
+    let started = Instant::now();
+    session.fetch_page(&format!("{}/", base)).await.unwrap();
+    session.fetch_page(&format!("{}/", base)).await.unwrap();
+    assert!(started.elapsed() >= config.delay);
+
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    for request in requests.iter() {
+        assert!(request
+            .lines()
+            .any(|line| line.eq_ignore_ascii_case("user-agent: test-agent/2.0")));
+    }
+}
+
+#[tokio::test]
+async fn test_crawl_uses_configured_depth() {
+    let (base, _) = serve_pages(vec![
+        (
+            "/",
+            200,
+            "text/html",
+            r#"<html><body><a href="/a">A</a></body></html>"#.to_string(),
+        ),
+        (
+            "/a",
+            200,
+            "text/html",
+            r#"<html><body><a href="/b">B</a></body></html>"#.to_string(),
+        ),
+    ])
+    .await;
+
+    let config = CrawlerConfig {
+        max_depth: 0,
+        ..test_config()
+    };
+    let args = HashMap::from([("url".to_string(), json!(format!("{}/", base)))]);
+    let options = crate::server::crawl_options_from_args(&args, &config).unwrap();
+    assert_eq!(options.max_depth, 0);
+    assert_eq!(options.scope, CrawlScope::SameDomain);
+
+    let mut session = ScrapingSession::new(&config).unwrap();
+    let report = Crawler::new(options)
+        .run(&mut session, &format!("{}/", base))
+        .await
+        .unwrap();
+    assert_eq!(report.pages_crawled, 1);
+    assert_eq!(report.pages_remaining, 0);
+
+    // Tool arguments still win over the server defaults
+    let config = CrawlerConfig {
+        subdomains: true,
+        ..config
+    };
+    let args = HashMap::from([("max_depth".to_string(), json!(3))]);
+    let options = crate::server::crawl_options_from_args(&args, &config).unwrap();
+    assert_eq!(options.max_depth, 3);
+    assert_eq!(options.scope, CrawlScope::Subdomains);
+}
+
+#[test]
+fn test_page_cache_limits() {
+    let cache = PageCache::new(CacheOptions {
+        max_entries: 2,
+        max_bytes: 10,
+        ..Default::default()
+    });
+    let entry = |body: &str| CacheEntry::new(body.to_string(), None, None);
+
+    cache.insert("a", entry("aaa"));
+    cache.insert("b", entry("bbb"));
+    assert!(matches!(cache.lookup("a"), Lookup::Fresh(entry) if entry.body == "aaa"));
+
+    // "b" is the least recently used entry
+    cache.insert("c", entry("ccc"));
+    assert!(matches!(cache.lookup("b"), Lookup::Missing));
+    assert!(matches!(cache.lookup("a"), Lookup::Fresh(_)));
+
+    // Making room for a large page evicts more than one entry
+    cache.insert("d", entry("dddddddd"));
+    assert_eq!(cache.stats().entries, 1);
+    assert_eq!(cache.stats().bytes, 8);
+
+    // Pages over the size limit are never cached
+    cache.insert("e", entry("eeeeeeeeeeee"));
+    assert!(matches!(cache.lookup("e"), Lookup::Missing));
+
+    assert_eq!(cache.clear(), 1);
+    assert_eq!(cache.stats().entries, 0);
+}
+
+#[tokio::test]
+async fn test_session_uses_page_cache() {
+    let (base, requests) = serve_pages(vec![(
+        "/",
+        200,
+        "text/html",
+        "<html><body>Cached</body></html>".to_string(),
+    )])
+    .await;
+    let url = format!("{}/", base);
+    let missing = format!("{}/missing", base);
+
+    let cache = Arc::new(PageCache::new(CacheOptions::default()));
+    let mut session = ScrapingSession::new(&test_config())
+        .unwrap()
+        .with_cache(cache.clone());
+
+    let page = session.fetch(&url).await.unwrap();
+    assert_eq!(page.cache_status, CacheStatus::Miss);
+    let page = session.fetch(&url).await.unwrap();
+    assert_eq!(page.cache_status, CacheStatus::Hit);
+    assert!(page.html.contains("Cached"));
+
+    // Other sessions share the cache
+    let mut other = ScrapingSession::new(&test_config())
+        .unwrap()
+        .with_cache(cache.clone());
+    assert_eq!(
+        other.fetch(&url).await.unwrap().cache_status,
+        CacheStatus::Hit
+    );
+    assert_eq!(requests.lock().unwrap().len(), 1);
+
+    // Error pages are not cached
+    assert_eq!(
+        session.fetch(&missing).await.unwrap().cache_status,
+        CacheStatus::Miss
+    );
+    assert_eq!(
+        session.fetch(&missing).await.unwrap().cache_status,
+        CacheStatus::Miss
+    );
+    assert_eq!(requests.lock().unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn test_session_revalidates_expired_pages() {
+    let (base, requests) = serve_pages(vec![(
+        "/",
+        200,
+        "text/html",
+        "<html><body>Revalidated</body></html>".to_string(),
+    )])
+    .await;
+    let url = format!("{}/", base);
+
+    let cache = Arc::new(PageCache::new(CacheOptions {
+        ttl: Duration::ZERO,
+        ..Default::default()
+    }));
+    let mut session = ScrapingSession::new(&test_config())
+        .unwrap()
+        .with_cache(cache);
+
+    assert_eq!(
+        session.fetch(&url).await.unwrap().cache_status,
+        CacheStatus::Miss
+    );
+    let page = session.fetch(&url).await.unwrap();
+    assert_eq!(page.cache_status, CacheStatus::Revalidated);
+    assert!(page.cache_status.is_cached());
+    assert!(page.html.contains("Revalidated"));
+
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert!(requests[1].to_ascii_lowercase().contains("if-none-match:"));
+}
+
+#[tokio::test]
+async fn test_tools_accept_raw_html() {
+    let state = CrawlerState::new(test_config());
+    let html = r#"<html><body>
+        <a href="/about">About</a>
+        <form action="login" method="post"><input name="user"></form>
+    </body></html>"#;
+
+    let args = HashMap::from([
+        ("html".to_string(), json!(html)),
+        (
+            "base_url".to_string(),
+            json!("https://example.com/account/"),
+        ),
+    ]);
+    let page = page_from_args(&state, &args).await.unwrap();
+    assert!(page.meta().is_none());
+
+    let extractor = page.extractor();
+    let links = extractor.extract_links().unwrap();
+    assert_eq!(links[0]["absolute_url"], "https://example.com/about");
+    let forms = extractor.extract_forms().unwrap();
+    assert_eq!(forms[0]["action"], "https://example.com/account/login");
+
+    // Without a base URL links are left as they are
+    let args = HashMap::from([("html".to_string(), json!(html))]);
+    let links = page_from_args(&state, &args)
+        .await
+        .unwrap()
+        .extractor()
+        .extract_links()
+        .unwrap();
+    assert_eq!(links[0]["absolute_url"], "/about");
+
+    let args = HashMap::from([
+        ("html".to_string(), json!(html)),
+        ("url".to_string(), json!("https://example.com/")),
+    ]);
+    assert!(page_from_args(&state, &args).await.is_err());
+    assert!(page_from_args(&state, &HashMap::new()).await.is_err());
+}
+
+#[test]
+fn test_form_data() {
+    let html = r#"
+        <form action="/search" method="get">
+            <input type="text" name="q" value="rust">
+            <input type="hidden" name="lang" value="en">
+            <input type="checkbox" name="exact" checked>
+            <input type="checkbox" name="safe" value="1">
+            <input type="radio" name="sort" value="new">
+            <input type="radio" name="sort" value="top" checked>
+            <select name="page"><option value="1">1</option><option value="2" selected>2</option></select>
+            <textarea name="note">hi</textarea>
+            <input type="submit" name="go" value="Go">
+        </form>
+    "#;
+    let forms = ElementExtractor::new(html).extract_forms().unwrap();
+
+    let data = FormSubmitter::form_data(&forms[0], &serde_json::Map::new());
+    let pairs: Vec<(&str, &str)> = data.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+    assert_eq!(
+        pairs,
+        vec![
+            ("q", "rust"),
+            ("lang", "en"),
+            ("exact", "on"),
+            ("sort", "top"),
+            ("page", "2"),
+            ("note", "hi"),
+        ]
+    );
+
+    let values = json!({"q": "mcp", "sort": "new", "safe": ["1"], "extra": 5});
+    let data = FormSubmitter::form_data(&forms[0], values.as_object().unwrap());
+    let pairs: Vec<(&str, &str)> = data.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+    assert_eq!(
+        pairs,
+        vec![
+            ("q", "mcp"),
+            ("lang", "en"),
+            ("exact", "on"),
+            ("safe", "1"),
+            ("sort", "new"),
+            ("page", "2"),
+            ("note", "hi"),
+            ("extra", "5"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_named_session_keeps_cookies() {
+    let (base, requests) = serve_pages(vec![
+        (
+            "/login",
+            200,
+            "text/html\r\nSet-Cookie: sid=abc; Path=/",
+            r#"<form action="welcome" method="post">
+                <input name="user"><input type="hidden" name="token" value="t1">
+                <input type="submit" name="go" value="Go">
+            </form>"#
+                .to_string(),
+        ),
+        (
+            "/welcome",
+            200,
+            "text/html",
+            "<html><head><title>Welcome</title></head></html>".to_string(),
+        ),
+    ])
+    .await;
+
+    let state = CrawlerState::new(test_config());
+    let session = ScrapingSession::new(&state.config)
+        .unwrap()
+        .with_base_url(Url::parse(&base).unwrap())
+        .with_headers(&HashMap::from([("X-Test".to_string(), "yes".to_string())]))
+        .unwrap();
+    let id = state.sessions.open(Some("shop"), session).unwrap();
+    assert_eq!(id, "shop");
+    assert!(state
+        .sessions
+        .open(Some("shop"), ScrapingSession::new(&state.config).unwrap())
+        .is_err());
+
+    // Relative URLs resolve against the session's base URL
+    let args = HashMap::from([
+        ("url".to_string(), json!("/login")),
+        ("session_id".to_string(), json!("shop")),
+    ]);
+    let page = page_from_args(&state, &args).await.unwrap();
+    assert_eq!(page.cache_status, Some(CacheStatus::Bypass));
+    let forms = page.extractor().extract_forms().unwrap();
+    assert_eq!(forms[0]["action"], format!("{}/welcome", base));
+
+    let values = json!({"user": "ana"});
+    let data = FormSubmitter::form_data(&forms[0], values.as_object().unwrap());
+    let session = state.session_for(Some("shop")).unwrap();
+    let response = session
+        .lock()
+        .await
+        .submit_form(forms[0]["action"].as_str().unwrap(), "POST", &data)
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        session.lock().await.base_url().map(|u| u.to_string()),
+        Some(format!("{}/welcome", base))
+    );
+
+    let requests = requests.lock().unwrap();
+    let submitted = requests.last().unwrap().to_ascii_lowercase();
+    assert!(submitted.starts_with("post /welcome "));
+    assert!(submitted.contains("cookie: sid=abc"));
+    assert!(submitted.contains("x-test: yes"));
+    assert!(submitted.ends_with("user=ana&token=t1"));
+
+    assert!(state.sessions.close("shop"));
+    assert!(state.session_for(Some("shop")).is_err());
+}
+
+#[test]
+fn test_session_limits() {
+    let config = test_config();
+    let new_session = || ScrapingSession::new(&config).unwrap();
+
+    let store = SessionStore::with_limits(2, Duration::from_secs(3600));
+    store.open(Some("a"), new_session()).unwrap();
+    store.open(Some("b"), new_session()).unwrap();
+    let error = store.open(Some("c"), new_session()).unwrap_err();
+    assert!(error.to_string().contains("Too many open sessions"));
+    assert!(store.close("a"));
+    store.open(Some("c"), new_session()).unwrap();
+
+    // Idle sessions are closed, unless a call still holds them
+    let store = SessionStore::with_limits(2, Duration::from_millis(100));
+    store.open(Some("idle"), new_session()).unwrap();
+    store.open(Some("busy"), new_session()).unwrap();
+    let busy = store.get("busy").unwrap();
+    std::thread::sleep(Duration::from_millis(150));
+    assert_eq!(store.ids(), vec!["busy"]);
+    assert!(store.get("idle").is_err());
+    drop(busy);
+    assert!(store.ids().is_empty());
+}
+
+#[test]
+fn test_links_resolve_against_page_url() {
+    let html = r##"
+        <html><head><base href="/docs/v2/"></head>
+        <body>
+            <a href="intro.html">Intro</a>
+            <a href="//cdn.example.net/file.zip">Download</a>
+            <a href="#install">Install</a>
+            <a href="https://www.example.com/blog">Blog</a>
+            <a href="https://other.org/">Other</a>
+            <a href="mailto:team@example.com">Mail</a>
+            <a href="tel:+123">Call</a>
+        </body></html>
+    "##;
+    let page_url = Url::parse("https://example.com/guide/index.html").unwrap();
+    let extractor = ElementExtractor::new(html).with_page_url(Some(page_url.clone()));
+    assert_eq!(
+        extractor.base_url().map(|u| u.as_str()),
+        Some("https://example.com/docs/v2/")
+    );
+
+    let links = extractor.extract_links().unwrap();
+    let resolved: Vec<(&str, &str)> = links
+        .iter()
+        .map(|l| {
+            (
+                l["absolute_url"].as_str().unwrap(),
+                l["kind"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        resolved,
+        vec![
+            ("https://example.com/docs/v2/intro.html", "internal"),
+            ("https://cdn.example.net/file.zip", "external"),
+            ("https://example.com/docs/v2/#install", "anchor"),
+            ("https://www.example.com/blog", "internal"),
+            ("https://other.org/", "external"),
+            ("mailto:team@example.com", "mailto"),
+            ("tel:+123", "other"),
+        ]
+    );
+
+    // Crawled links honour <base href> as well
+    let links: Vec<String> = extract_page_links(html, &page_url)
+        .iter()
+        .map(|u| u.to_string())
+        .collect();
+    assert_eq!(links[0], "https://example.com/docs/v2/intro.html");
+}
+
+#[test]
+fn test_parse_srcset() {
+    assert_eq!(
+        parse_srcset("small.jpg 480w, large.jpg 1080w"),
+        vec![
+            ("small.jpg".to_string(), Some("480w".to_string())),
+            ("large.jpg".to_string(), Some("1080w".to_string())),
+        ]
+    );
+    assert_eq!(
+        parse_srcset(" a.png, b.png 2x ,, "),
+        vec![
+            ("a.png".to_string(), None),
+            ("b.png".to_string(), Some("2x".to_string())),
+        ]
+    );
+    // Commas inside URLs are kept
+    assert_eq!(
+        parse_srcset("img.php?w=1,h=2 1x"),
+        vec![("img.php?w=1,h=2".to_string(), Some("1x".to_string()))]
+    );
+    assert!(parse_srcset("").is_empty());
+
+    let html = r#"<img srcset="/a.jpg 1x, //cdn.example.com/b.jpg 2x" sizes="50vw" alt="A">"#;
+    let page_url = Url::parse("https://example.com/page").unwrap();
+    let images = ElementExtractor::new(html)
+        .with_page_url(Some(page_url))
+        .extract_images()
+        .unwrap();
+    assert_eq!(images.len(), 1);
+    assert_eq!(images[0]["absolute_url"], serde_json::Value::Null);
+    assert_eq!(images[0]["srcset"][0]["url"], "https://example.com/a.jpg");
+    assert_eq!(
+        images[0]["srcset"][1]["url"],
+        "https://cdn.example.com/b.jpg"
+    );
+    assert_eq!(images[0]["srcset"][1]["descriptor"], "2x");
+    assert_eq!(images[0]["sizes"], "50vw");
+}
+
+#[test]
+fn test_extract_markdown() {
+    let html = r#"
+        <html>
+        <head><title>Guide</title><base href="https://example.com/docs/"><style>p {}</style></head>
+        <body>
+            <h1>Getting  started</h1>
+            <p>Read the <a href="install">install guide</a> or <em>skip</em> to
+               <strong>usage</strong>. Use <code>cargo run</code>.<br>Second line.</p>
+            <img src="/logo.png" alt="Logo">
+            <ul>
+                <li>One</li>
+                <li>Two
+                    <ol start="3"><li>Nested</li></ol>
+                </li>
+            </ul>
+            <blockquote><p>Quoted</p></blockquote>
+            <pre><code class="language-rust">fn main() {
+    println!("hi");
+}</code></pre>
+            <table>
+                <thead><tr><th>Name</th><th>Size</th></tr></thead>
+                <tbody><tr><td>a|b</td><td>1</td></tr></tbody>
+            </table>
+            <script>ignored()</script>
+        </body>
+        </html>
+    "#;
+    let extractor = ElementExtractor::new(html);
+
+    let markdown = extractor
+        .extract_markdown(&MarkdownOptions::default())
+        .unwrap();
+    assert_eq!(
+        markdown,
+        [
+            "# Getting started",
+            "Read the [install guide](https://example.com/docs/install) or *skip* to \
+             **usage**. Use `cargo run`.  \nSecond line.",
+            "- One\n- Two\n  3. Nested",
+            "> Quoted",
+            "```rust\nfn main() {\n    println!(\"hi\");\n}\n```",
+            "| Name | Size |\n| --- | --- |\n| a\\|b | 1 |",
+        ]
+        .join("\n\n")
+    );
+
+    let with_images = extractor
+        .extract_markdown(&MarkdownOptions {
+            include_images: true,
+        })
+        .unwrap();
+    assert!(with_images.contains("![Logo](https://example.com/logo.png)"));
+}
+
+#[test]
+fn test_chunk_markdown_by_heading() {
+    let markdown = [
+        "# Intro",
+        "Short intro.",
+        "## Details",
+        "word ".repeat(60).trim_end(),
+        "more ".repeat(60).trim_end(),
+        "## Code",
+        "```\n# not a heading\n\nstill code\n```",
+    ]
+    .join("\n\n");
+    let options = |max_tokens| ChunkOptions {
+        max_tokens,
+        ..ChunkOptions::default()
+    };
+
+    let chunks = chunk_text(&markdown, &options(80));
+    assert!(chunks.iter().all(|chunk| chunk.tokens <= 80));
+    assert_eq!(chunks[0].heading.as_deref(), Some("Intro"));
+    assert_eq!(chunks[1].heading.as_deref(), Some("Details"));
+    assert!(chunks[1].text.starts_with("## Details"));
+    assert!(chunks[1].text.ends_with("word"));
+    // The rest of the section keeps its heading
+    assert!(chunks[2].text.starts_with("more"));
+    assert_eq!(chunks[2].heading.as_deref(), Some("Details"));
+    let code = chunks.last().unwrap();
+    assert_eq!(code.heading.as_deref(), Some("Code"));
+    assert!(code.text.contains("# not a heading\n\nstill code"));
+    assert_eq!(
+        chunks.iter().map(|chunk| chunk.index).collect::<Vec<_>>(),
+        (0..chunks.len()).collect::<Vec<_>>()
+    );
+
+    // Everything fits in one chunk with a large budget
+    let chunks = chunk_text(&markdown, &options(10_000));
+    assert_eq!(chunks.len(), 1);
+    assert_eq!(chunks[0].text, markdown);
+    assert_eq!(chunks[0].tokens, estimate_tokens(&chunks[0].text));
+}
+
+#[test]
+fn test_chunk_by_sentence_with_overlap() {
+    let text = "First sentence here. Second one follows! Is this the third? \
+                Yes, e.g. the fourth.\n\nA new paragraph starts.";
+
+    let options = ChunkOptions {
+        strategy: ChunkStrategy::Sentence,
+        max_tokens: 12,
+        overlap_tokens: 0,
+    };
+    let chunks = chunk_text(text, &options);
+    let texts: Vec<&str> = chunks.iter().map(|chunk| chunk.text.as_str()).collect();
+    assert_eq!(
+        texts,
+        [
+            "First sentence here. Second one follows!",
+            "Is this the third? Yes, e.g. the fourth.",
+            "A new paragraph starts.",
+        ]
+    );
+
+    let chunks = chunk_text(
+        text,
+        &ChunkOptions {
+            overlap_tokens: 6,
+            ..options.clone()
+        },
+    );
+    assert_eq!(chunks[1].text, "Second one follows! Is this the third?");
+    assert!(chunks.iter().all(|chunk| chunk.tokens <= 12));
+    assert!(chunks
+        .last()
+        .unwrap()
+        .text
+        .ends_with("A new paragraph starts."));
+
+    // Paragraphs stay whole when they fit
+    let chunks = chunk_text(
+        text,
+        &ChunkOptions {
+            strategy: ChunkStrategy::Paragraph,
+            max_tokens: 25,
+            overlap_tokens: 0,
+        },
+    );
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(chunks[1].text, "A new paragraph starts.");
+}
+
+#[test]
+fn test_chunk_store_cursors() {
+    let store = ChunkStore::new(1);
+    let chunks = chunk_text(
+        "one two three four five six seven eight",
+        &ChunkOptions {
+            max_tokens: 4,
+            ..ChunkOptions::default()
+        },
+    );
+    assert_eq!(chunks.len(), 3);
+
+    let first = store.store(chunks.clone()).unwrap();
+    assert_eq!(first.chunk, chunks[0]);
+    assert_eq!(first.total_chunks, 3);
+    let second = store.get(first.next_cursor.as_deref().unwrap()).unwrap();
+    assert_eq!(second.chunk, chunks[1]);
+    let third = store.get(second.next_cursor.as_deref().unwrap()).unwrap();
+    assert_eq!(third.chunk, chunks[2]);
+    assert_eq!(third.next_cursor, None);
+
+    // Cursors of evicted documents expire
+    let cursor = second.next_cursor.unwrap();
+    store.store(chunks).unwrap();
+    assert!(store.get(&cursor).is_err());
+    assert!(store.get("nonsense").is_err());
+    assert!(store.store(vec![]).is_none());
+}
+
+#[test]
+fn test_robots_txt_rules() {
+    let robots = RobotsTxt::parse(
+        "# comment\n\
+         User-agent: *\n\
+         Disallow: /private\n\
+         Allow: /private/open$\n\
+         Disallow: /*.pdf$\n\
+         Crawl-delay: 2\n\
+         \n\
+         User-agent: OtherBot\n\
+         User-agent: MCP-Crawl\n\
+         Disallow: /search?q=\n\
+         Crawl-delay: 0.5\n\
+         \n\
+         Sitemap: https://example.com/sitemap.xml\n",
+    );
+    let url = |path: &str| Url::parse(&format!("https://example.com{}", path)).unwrap();
+
+    // Generic crawlers get the `*` group
+    assert!(robots.is_allowed("SomeBot/1.0", &url("/")));
+    assert!(!robots.is_allowed("SomeBot/1.0", &url("/private/page")));
+    assert!(robots.is_allowed("SomeBot/1.0", &url("/private/open")));
+    assert!(!robots.is_allowed("SomeBot/1.0", &url("/private/open/more")));
+    assert!(!robots.is_allowed("SomeBot/1.0", &url("/docs/file.pdf")));
+    assert!(robots.is_allowed("SomeBot/1.0", &url("/docs/file.pdf?download=1")));
+    assert_eq!(
+        robots.crawl_delay("SomeBot/1.0"),
+        Some(Duration::from_secs(2))
+    );
+
+    // A group naming our product token replaces the `*` group
+    assert!(robots.is_allowed("mcp-crawl/0.1.0", &url("/private/page")));
+    assert!(!robots.is_allowed("mcp-crawl/0.1.0", &url("/search?q=rust")));
+    assert!(robots.is_allowed("mcp-crawl/0.1.0", &url("/search")));
+    assert_eq!(
+        robots.crawl_delay("mcp-crawl/0.1.0"),
+        Some(Duration::from_millis(500))
+    );
+
+    assert_eq!(robots.sitemaps(), ["https://example.com/sitemap.xml"]);
+    assert!(RobotsTxt::parse("").is_allowed("SomeBot", &url("/anything")));
+    assert!(!RobotsTxt::disallow_all().is_allowed("SomeBot", &url("/")));
+}
+
+#[test]
+fn test_config_delay_secs() {
+    let config = CrawlerConfig::default().with_delay_secs(0.25).unwrap();
+    assert_eq!(config.delay, Duration::from_millis(250));
+    let config = CrawlerConfig::default().with_delay_secs(-1.0).unwrap();
+    assert_eq!(config.delay, Duration::ZERO);
+    assert!(CrawlerConfig::default()
+        .with_delay_secs(f64::INFINITY)
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_session_honours_robots_txt() {
+    let (base, requests) = serve_pages(vec![
+        (
+            "/robots.txt",
+            200,
+            "text/plain",
+            "User-agent: *\nDisallow: /private\nCrawl-delay: 0.3\n".to_string(),
+        ),
+        ("/", 200, "text/html", "<p>Home</p>".to_string()),
+        ("/other", 200, "text/html", "<p>Other</p>".to_string()),
+        ("/private", 200, "text/html", "<p>Secret</p>".to_string()),
+        (
+            "/go-private",
+            302,
+            "text/html\r\nLocation: /private",
+            String::new(),
+        ),
+    ])
+    .await;
+
+    // Sessions of one server share the robots.txt rules and host delay
+    let state = CrawlerState::new(test_config());
+    let error = state
+        .session()
+        .unwrap()
+        .fetch(&format!("{}/private", base))
+        .await
+        .unwrap_err();
+    assert!(error.downcast_ref::<RobotsBlocked>().is_some());
+    assert!(error.to_string().contains("robots.txt"));
+
+    let started = Instant::now();
+    let mut first = state.session().unwrap();
+    let mut second = state.session().unwrap();
+    first.fetch(&format!("{}/", base)).await.unwrap();
+    second.fetch(&format!("{}/other", base)).await.unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(300));
+    assert_eq!(requests.lock().unwrap().len(), 2);
+
+    // Every redirect hop is checked against robots.txt
+    let error = first
+        .fetch(&format!("{}/go-private", base))
+        .await
+        .unwrap_err();
+    assert!(error.downcast_ref::<RobotsBlocked>().is_some());
+    assert!(error.to_string().contains("/private"));
+    let requests = requests.lock().unwrap().clone();
+    assert!(requests[2].starts_with("GET /go-private "));
+    assert_eq!(requests.len(), 3);
+
+    // Robots.txt can be ignored
+    let config = CrawlerConfig {
+        respect_robots: false,
+        ..test_config()
+    };
+    let mut session = ScrapingSession::new(&config).unwrap();
+    let html = session
+        .fetch_page(&format!("{}/private", base))
+        .await
+        .unwrap();
+    assert!(html.contains("Secret"));
+
+    // A host whose robots.txt cannot be fetched is treated as disallowed
+    let closed = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let error = state
+        .session()
+        .unwrap()
+        .fetch(&format!("http://{}/", closed))
+        .await
+        .unwrap_err();
+    assert!(error.downcast_ref::<RobotsBlocked>().is_some());
+}
+
+#[tokio::test]
+async fn test_fetch_returns_response_details() {
+    let (base, _) = serve_pages(vec![
+        ("/old", 301, "text/html\r\nLocation: /new", String::new()),
+        (
+            "/new",
+            200,
+            "text/html; charset=ISO-8859-1",
+            "<p>café</p>".to_string(),
+        ),
+    ])
+    .await;
+
+    let mut session = ScrapingSession::new(&test_config()).unwrap();
+    let page = session.fetch(&format!("{}/old", base)).await.unwrap();
+    assert_eq!(page.url.as_str(), format!("{}/new", base));
+    assert_eq!(session.base_url(), Some(&page.url));
+    assert_eq!(page.response.status, 200);
+    assert_eq!(page.response.content_type.as_deref(), Some("text/html"));
+    assert_eq!(page.response.charset.as_deref(), Some("windows-1252"));
+    assert!(page.response.headers.contains_key("etag"));
+    assert_eq!(page.response.size, "<p>café</p>".len());
+    // The UTF-8 bytes of the body are decoded as the declared Latin-1
+    assert_eq!(page.html, "<p>cafÃ©</p>");
+
+    // Tools report error statuses as errors, and the response in meta
+    let state = CrawlerState::new(test_config());
+    let args = HashMap::from([("url".to_string(), json!(format!("{}/missing", base)))]);
+    let error = page_from_args(&state, &args).await.err().unwrap();
+    assert_eq!(error.downcast_ref::<HttpError>().unwrap().status, 404);
+    assert!(error.to_string().starts_with("HTTP 404 Not Found"));
+
+    let args = HashMap::from([("url".to_string(), json!(format!("{}/new", base)))]);
+    let meta = page_from_args(&state, &args).await.unwrap().meta().unwrap();
+    assert_eq!(meta["response"]["status"], 200);
+    assert_eq!(meta["response"]["url"], format!("{}/new", base));
+
+    // Bodies past the limit are not read into memory
+    let config = CrawlerConfig {
+        max_body_bytes: 8,
+        ..test_config()
+    };
+    let mut session = ScrapingSession::new(&config).unwrap();
+    let error = session.fetch(&format!("{}/new", base)).await.unwrap_err();
+    assert_eq!(error.downcast_ref::<BodyTooLarge>().unwrap().limit, 8);
+}
+
+#[tokio::test]
+async fn test_fetch_survives_malformed_pdfs() {
+    let (base, _) = serve_pages(vec![(
+        "/report.pdf",
+        200,
+        "application/pdf",
+        "<html>Not a PDF after all</html>".to_string(),
+    )])
+    .await;
+
+    let mut session = ScrapingSession::new(&test_config()).unwrap();
+    let page = session
+        .fetch(&format!("{}/report.pdf", base))
+        .await
+        .unwrap();
+    assert_eq!(page.response.kind, ContentKind::Pdf);
+    assert_eq!(page.html, "");
+}
+
+#[tokio::test]
+async fn test_fetch_retries_busy_servers() {
+    let (base, requests) = serve_pages(vec![
+        ("/busy", 503, "text/html\r\nRetry-After: 0", String::new()),
+        (
+            "/limited",
+            429,
+            "text/html\r\nRetry-After: 1",
+            String::new(),
+        ),
+    ])
+    .await;
+
+    let config = CrawlerConfig {
+        max_retries: 2,
+        ..test_config()
+    };
+    let mut session = ScrapingSession::new(&config).unwrap();
+    let page = session.fetch(&format!("{}/busy", base)).await.unwrap();
+    assert_eq!(page.response.status, 503);
+    assert_eq!(requests.lock().unwrap().len(), 3);
+    let error = session
+        .fetch_page(&format!("{}/busy", base))
+        .await
+        .unwrap_err();
+    assert_eq!(error.downcast_ref::<HttpError>().unwrap().status, 503);
+
+    // Retry-After is honoured
+    requests.lock().unwrap().clear();
+    let config = CrawlerConfig {
+        max_retries: 1,
+        ..test_config()
+    };
+    let mut session = ScrapingSession::new(&config).unwrap();
+    let started = Instant::now();
+    let page = session.fetch(&format!("{}/limited", base)).await.unwrap();
+    assert_eq!(page.response.status, 429);
+    assert!(started.elapsed() >= Duration::from_secs(1));
+    assert_eq!(requests.lock().unwrap().len(), 2);
+
+    let backoff = Duration::from_millis(100);
+    let retry_after = |value: &str| {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RETRY_AFTER, value.parse().unwrap());
+        headers
+    };
+    let empty = HeaderMap::new();
+    assert_eq!(
+        retry_delay(StatusCode::BAD_GATEWAY, &empty, 2, backoff),
+        Some(Duration::from_millis(400))
+    );
+    assert_eq!(retry_delay(StatusCode::NOT_FOUND, &empty, 0, backoff), None);
+    assert_eq!(
+        retry_delay(
+            StatusCode::TOO_MANY_REQUESTS,
+            &retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            0,
+            backoff
+        ),
+        Some(Duration::ZERO)
+    );
+    // Waits longer than a minute are not worth it
+    assert_eq!(
+        retry_delay(
+            StatusCode::SERVICE_UNAVAILABLE,
+            &retry_after("3600"),
+            0,
+            backoff
+        ),
+        None
+    );
+}
+
+#[test]
+fn test_content_kind_and_charset_detection() {
+    let html = b"<!doctype html><p>Hi</p>";
+    assert_eq!(
+        ContentKind::detect(Some("text/html"), html),
+        ContentKind::Html
+    );
+    assert_eq!(ContentKind::detect(None, html), ContentKind::Html);
+    assert_eq!(
+        ContentKind::detect(Some("application/ld+json"), b"{}"),
+        ContentKind::Json
+    );
+    assert_eq!(
+        ContentKind::detect(Some("application/xml"), b"<?xml version=\"1.0\"?><rss>"),
+        ContentKind::Feed
+    );
+    assert_eq!(
+        ContentKind::detect(Some("application/xml"), b"<?xml version=\"1.0\"?><note/>"),
+        ContentKind::Text
+    );
+    assert_eq!(
+        ContentKind::detect(Some("text/plain"), b"plain"),
+        ContentKind::Text
+    );
+    assert_eq!(
+        ContentKind::detect(Some("application/octet-stream"), b"%PDF-1.7"),
+        ContentKind::Pdf
+    );
+    assert_eq!(
+        ContentKind::detect(Some("image/png"), b"\x89PNG"),
+        ContentKind::Binary
+    );
+
+    // <meta charset> and the XML declaration are used without a header charset
+    let latin1 = b"<html><head><meta charset=\"iso-8859-1\"></head><p>caf\xe9</p>";
+    let (text, encoding) = decode_body(latin1, None, ContentKind::Html);
+    assert!(text.contains("café"));
+    assert_eq!(encoding, "windows-1252");
+    let http_equiv = b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset=Shift_JIS\">";
+    assert_eq!(
+        decode_body(http_equiv, None, ContentKind::Html).1,
+        "Shift_JIS"
+    );
+    let xml = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><rss>\xe9</rss>";
+    assert!(decode_body(xml, None, ContentKind::Feed).0.contains('é'));
+
+    // The header beats <meta>, and a BOM beats both
+    assert_eq!(
+        decode_body(latin1, Some("utf-8"), ContentKind::Html).1,
+        "UTF-8"
+    );
+    let bom = "\u{feff}<p>café</p>".as_bytes();
+    let (text, encoding) = decode_body(bom, Some("iso-8859-1"), ContentKind::Html);
+    assert_eq!(text, "<p>café</p>");
+    assert_eq!(encoding, "UTF-8");
+}
+
+#[test]
+fn test_parse_feeds() {
+    let rss = r#"<?xml version="1.0"?>
+        <rss version="2.0" xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <channel>
+            <title>News</title>
+            <link>https://example.com/</link>
+            <description>Latest news</description>
+            <item>
+              <title>First</title>
+              <link>/posts/1</link>
+              <guid>post-1</guid>
+              <pubDate>Mon, 06 Sep 2021 16:45:00 GMT</pubDate>
+              <dc:creator>Ana</dc:creator>
+              <description><![CDATA[<p>Hello <b>world</b></p>]]></description>
+            </item>
+            <item><title>Second</title></item>
+          </channel>
+        </rss>"#;
+    let base = Url::parse("https://example.com/feed.xml").unwrap();
+    let feed = feed::parse(rss, Some(&base)).unwrap();
+    assert_eq!(feed.format, "rss");
+    assert_eq!(feed.title.as_deref(), Some("News"));
+    assert_eq!(feed.items.len(), 2);
+    let item = &feed.items[0];
+    assert_eq!(item.link.as_deref(), Some("https://example.com/posts/1"));
+    assert_eq!(item.id.as_deref(), Some("post-1"));
+    assert_eq!(item.author.as_deref(), Some("Ana"));
+    assert_eq!(item.summary.as_deref(), Some("Hello world"));
+    assert_eq!(
+        item.published.as_deref(),
+        Some("Mon, 06 Sep 2021 16:45:00 GMT")
+    );
+
+    let atom = r#"<feed xmlns="http://www.w3.org/2005/Atom">
+          <title>Blog</title>
+          <link rel="self" href="https://example.com/atom.xml"/>
+          <link href="https://example.com/"/>
+          <entry>
+            <title>Entry</title>
+            <link rel="alternate" href="https://example.com/entry"/>
+            <id>urn:uuid:1</id>
+            <updated>2024-01-02T03:04:05Z</updated>
+            <author><name>Bo</name></author>
+            <summary>Short</summary>
+          </entry>
+        </feed>"#;
+    let feed = feed::parse(atom, None).unwrap();
+    assert_eq!(feed.format, "atom");
+    assert_eq!(feed.link.as_deref(), Some("https://example.com/"));
+    let entry = &feed.items[0];
+    assert_eq!(entry.link.as_deref(), Some("https://example.com/entry"));
+    assert_eq!(entry.author.as_deref(), Some("Bo"));
+    assert_eq!(entry.updated.as_deref(), Some("2024-01-02T03:04:05Z"));
+
+    assert!(feed::parse("<html></html>", None).is_err());
+}
+
+#[tokio::test]
+async fn test_fetch_dispatches_on_content_type() {
+    let (base, _) = serve_pages(vec![
+        (
+            "/api",
+            200,
+            "application/json",
+            r#"{"ok": true}"#.to_string(),
+        ),
+        (
+            "/notes.txt",
+            200,
+            "text/plain",
+            "<b>not markup</b>".to_string(),
+        ),
+        (
+            "/feed",
+            200,
+            "application/rss+xml",
+            "<rss><channel><title>T</title></channel></rss>".to_string(),
+        ),
+        ("/logo.png", 200, "image/png", "\u{1}PNG".to_string()),
+    ])
+    .await;
+
+    let mut session = ScrapingSession::new(&test_config()).unwrap();
+    let page = session.fetch(&format!("{}/api", base)).await.unwrap();
+    assert_eq!(page.response.kind, ContentKind::Json);
+    let page = session.fetch(&format!("{}/notes.txt", base)).await.unwrap();
+    assert_eq!(page.response.kind, ContentKind::Text);
+    assert_eq!(page.html, "<b>not markup</b>");
+    let page = session.fetch(&format!("{}/feed", base)).await.unwrap();
+    assert_eq!(page.response.kind, ContentKind::Feed);
+
+    let page = session.fetch(&format!("{}/logo.png", base)).await.unwrap();
+    assert_eq!(page.response.kind, ContentKind::Binary);
+    assert!(page.html.is_empty());
+    assert_eq!(page.response.charset, None);
+    assert_eq!(page.response.size, 4);
+
+    // Extraction tools refuse binary content rather than parse it as HTML
+    let state = CrawlerState::new(test_config());
+    let args = HashMap::from([("url".to_string(), json!(format!("{}/logo.png", base)))]);
+    let error = page_from_args(&state, &args).await.err().unwrap();
+    assert!(error.to_string().contains("image/png content has no text"));
+}
+
+#[test]
+fn test_parse_sitemaps() {
+    let source = Url::parse("https://example.com/sitemap.xml").unwrap();
+    let urlset = r#"<?xml version="1.0" encoding="UTF-8"?>
+        <urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+          <url>
+            <loc>https://example.com/</loc>
+            <lastmod>2024-05-01</lastmod>
+            <changefreq>daily</changefreq>
+            <priority>0.8</priority>
+          </url>
+          <url><loc> https://example.com/about </loc></url>
+          <url><lastmod>2024-05-01</lastmod></url>
+        </urlset>"#;
+    let Sitemap::Urls(urls) = sitemap::parse(urlset, &source).unwrap() else {
+        panic!("expected a urlset");
+    };
+    assert_eq!(urls.len(), 2);
+    assert_eq!(urls[0].loc, "https://example.com/");
+    assert_eq!(urls[0].lastmod.as_deref(), Some("2024-05-01"));
+    assert_eq!(urls[0].changefreq.as_deref(), Some("daily"));
+    assert_eq!(urls[0].priority, Some(0.8));
+    assert_eq!(urls[0].sitemap, "https://example.com/sitemap.xml");
+    assert_eq!(urls[1].loc, "https://example.com/about");
+
+    let index = r#"<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+          <sitemap><loc>/posts.xml.gz</loc><lastmod>2024-01-01T10:00+02:00</lastmod></sitemap>
+        </sitemapindex>"#;
+    let Sitemap::Index(children) = sitemap::parse(index, &source).unwrap() else {
+        panic!("expected an index");
+    };
+    assert_eq!(children[0].0.as_str(), "https://example.com/posts.xml.gz");
+
+    let text = "https://example.com/a\n\nnot a url\nhttps://example.com/b\n";
+    let Sitemap::Urls(urls) = sitemap::parse(text, &source).unwrap() else {
+        panic!("expected a URL list");
+    };
+    assert_eq!(urls.len(), 2);
+    assert!(sitemap::parse("<html></html>", &source).is_err());
+
+    let midnight = sitemap::parse_lastmod("2024-01-01").unwrap();
+    assert_eq!(midnight.to_rfc3339(), "2024-01-01T00:00:00+00:00");
+    assert_eq!(
+        sitemap::parse_lastmod("2024-01-01T10:00+02:00").unwrap(),
+        midnight + chrono::Duration::hours(8)
+    );
+    assert!(sitemap::parse_lastmod("2024-01-01T08:00:00Z").is_some());
+    assert!(sitemap::parse_lastmod("yesterday").is_none());
+
+    // Gzipped sitemaps are inflated before being parsed
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(urlset.as_bytes()).unwrap();
+    let gzipped = encoder.finish().unwrap();
+    assert!(is_gzip(Some("application/x-gzip"), &gzipped));
+    assert!(!is_gzip(Some("text/xml"), &gzipped));
+    assert_eq!(gunzip(&gzipped).unwrap(), urlset.as_bytes());
+    assert_eq!(
+        ContentKind::detect(None, &gunzip(&gzipped).unwrap()),
+        ContentKind::Text
+    );
+}
+
+#[tokio::test]
+async fn test_sitemap_reader_follows_indexes() {
+    let (base, _) = serve_pages(vec![
+        (
+            "/robots.txt",
+            200,
+            "text/plain",
+            "User-agent: *\nAllow: /\nSitemap: /index.xml\n".to_string(),
+        ),
+        (
+            "/index.xml",
+            200,
+            "application/xml",
+            "<sitemapindex>\
+               <sitemap><loc>/pages.xml</loc></sitemap>\
+               <sitemap><loc>/old.xml</loc><lastmod>2019-01-01</lastmod></sitemap>\
+               <sitemap><loc>/missing.xml</loc></sitemap>\
+               <sitemap><loc>/index.xml</loc></sitemap>\
+             </sitemapindex>"
+                .to_string(),
+        ),
+        (
+            "/pages.xml",
+            200,
+            "application/xml",
+            "<urlset>\
+               <url><loc>/docs/a</loc><lastmod>2024-03-01</lastmod></url>\
+               <url><loc>/docs/b</loc><lastmod>2023-03-01</lastmod></url>\
+               <url><loc>/blog/c</loc><lastmod>2024-03-01</lastmod></url>\
+               <url><loc>/docs/d</loc></url>\
+               <url><loc>/docs/a</loc><lastmod>2024-03-01</lastmod></url>\
+             </urlset>"
+                .to_string(),
+        ),
+        (
+            "/old.xml",
+            200,
+            "application/xml",
+            "<urlset><url><loc>/old</loc><lastmod>2019-01-01</lastmod></url></urlset>".to_string(),
+        ),
+    ])
+    .await;
+    let site = Url::parse(&format!("{}/", base)).unwrap();
+    let mut session = ScrapingSession::new(&test_config()).unwrap();
+
+    let report = SitemapReader::new(SitemapOptions::default())
+        .run(&mut session, &site)
+        .await
+        .unwrap();
+    assert_eq!(report.sitemaps.len(), 4);
+    assert_eq!(report.total_urls, 5);
+    assert!(!report.truncated);
+    assert_eq!(report.errors.len(), 1);
+    assert_eq!(report.errors[0].sitemap, format!("{}/missing.xml", base));
+    assert!(report.errors[0].error.contains("404"));
+
+    // Filters skip old sitemaps of the index as well as URLs
+    let options = SitemapOptions {
+        include_patterns: vec![Regex::new("/docs/").unwrap()],
+        modified_since: sitemap::parse_lastmod("2024-01-01"),
+        ..Default::default()
+    };
+    let report = SitemapReader::new(options)
+        .run(&mut session, &site)
+        .await
+        .unwrap();
+    assert!(!report.sitemaps.contains(&format!("{}/old.xml", base)));
+    let locs: Vec<&str> = report.urls.iter().map(|u| u.loc.as_str()).collect();
+    assert_eq!(locs, [format!("{}/docs/a", base)]);
+    assert_eq!(report.urls[0].sitemap, format!("{}/pages.xml", base));
+
+    // A sitemap URL is read directly, within the limits
+    let options = SitemapOptions {
+        max_urls: 2,
+        ..Default::default()
+    };
+    let report = SitemapReader::new(options)
+        .run(&mut session, &site.join("/pages.xml").unwrap())
+        .await
+        .unwrap();
+    assert_eq!(report.sitemaps, [format!("{}/pages.xml", base)]);
+    assert_eq!(report.total_urls, 2);
+    assert!(report.truncated);
+
+    // robots.txt is not a text sitemap, so its site's sitemaps are read
+    let report = SitemapReader::new(SitemapOptions::default())
+        .run(&mut session, &site.join("/robots.txt").unwrap())
+        .await
+        .unwrap();
+    assert_eq!(report.sitemaps[0], format!("{}/index.xml", base));
+    assert_eq!(report.total_urls, 5);
+}
+
+#[test]
+fn test_extract_schema() {
+    let html = r#"
+        <html><body>
+            <div class="product" data-stock="yes">
+                <h2>  Blue
+                    Widget </h2>
+                <span class="price" data-value="1,299.50">$1,299.50</span>
+                <span class="reviews">Rated by 42 people</span>
+                <time datetime="2024-03-05">March 5, 2024</time>
+                <a href="/products/blue">Details</a>
+                <ul><li class="tag">sale</li><li class="tag">new</li></ul>
+                <ul class="variants">
+                    <li data-sku="B-1">Small</li>
+                    <li data-sku="B-2">Large</li>
+                </ul>
+            </div>
+            <div class="product">
+                <h2>Red Widget</h2>
+                <span class="price" data-value="n/a">Sold out</span>
+                <time datetime="05/03/2024">soon</time>
+                <a href="https://other.org/red">Details</a>
+            </div>
+        </body></html>
+    "#;
+    let schema = ExtractionSchema::from_json(&json!({
+        "items": "div.product",
+        "fields": {
+            "name": "h2",
+            "price": {"selector": ".price", "attr": "data-value", "type": "number"},
+            "reviews": {"selector": ".reviews", "regex": "(\\d+) people", "type": "integer"},
+            "released": {"selector": "time", "attr": "datetime", "type": "date"},
+            "url": {"selector": "a", "attr": "href", "resolve": true},
+            "in_stock": {"attr": "data-stock", "type": "boolean"},
+            "tags": {"selector": "li.tag", "all": true},
+            "currency": {"selector": ".currency", "default": "USD"},
+            "variants": {
+                "selector": "ul.variants",
+                "items": "li",
+                "fields": {"sku": {"attr": "data-sku"}, "size": {}}
+            }
+        }
+    }))
+    .unwrap();
+
+    let page_url = Url::parse("https://shop.example.com/catalog").unwrap();
+    let records = ElementExtractor::new(html)
+        .with_page_url(Some(page_url))
+        .extract_schema(&schema);
+    assert_eq!(
+        records,
+        vec![
+            json!({
+                "name": "Blue Widget",
+                "price": 1299.5,
+                "reviews": 42,
+                "released": "2024-03-05",
+                "url": "https://shop.example.com/products/blue",
+                "in_stock": true,
+                "tags": ["sale", "new"],
+                "currency": "USD",
+                "variants": [
+                    {"sku": "B-1", "size": "Small"},
+                    {"sku": "B-2", "size": "Large"}
+                ]
+            }),
+            json!({
+                "name": "Red Widget",
+                "price": null,
+                "reviews": null,
+                "released": null,
+                "url": "https://other.org/red",
+                "in_stock": false,
+                "tags": [],
+                "currency": "USD",
+                "variants": []
+            }),
+        ]
+    );
+
+    // Without items the page is one record; dates can have a custom format
+    let schema = ExtractionSchema::from_json(&json!({
+        "fields": {
+            "title": "h2",
+            "first_release": {
+                "selector": ".product:nth-of-type(2) time",
+                "attr": "datetime",
+                "type": "date",
+                "format": "%d/%m/%Y"
+            }
+        }
+    }))
+    .unwrap();
+    assert_eq!(
+        ElementExtractor::new(html).extract_schema(&schema),
+        vec![json!({"title": "Blue Widget", "first_release": "2024-03-05"})]
+    );
+
+    // Numbers are read up to the first character that cannot continue them
+    let numbers = r#"<html><body>
+        <p>10-20 items</p><p>Sale -15%</p><p>item-5</p><p>.5 kg</p>
+        <p>1.234,56 EUR</p><p>Costs 7.</p><p>1.2.3</p>
+    </body></html>"#;
+    let schema = ExtractionSchema::from_json(&json!({
+        "items": "p",
+        "fields": {"n": {"type": "number"}}
+    }))
+    .unwrap();
+    let values: Vec<serde_json::Value> = ElementExtractor::new(numbers)
+        .extract_schema(&schema)
+        .into_iter()
+        .map(|record| record["n"].clone())
+        .collect();
+    assert_eq!(
+        values,
+        [
+            json!(10.0),
+            json!(-15.0),
+            json!(5.0),
+            json!(0.5),
+            json!(null),
+            json!(7.0),
+            json!(null)
+        ]
+    );
+
+    // Invalid specs name the offending field
+    let error =
+        |spec: serde_json::Value| ExtractionSchema::from_json(&spec).unwrap_err().to_string();
+    assert!(error(json!({"items": "div"})).starts_with("schema.fields"));
+    assert!(error(json!({"fields": {"price": {"type": "money"}}}))
+        .starts_with("schema.fields.price.type"));
+    assert!(error(json!({"fields": {"name": "h2[["}})).starts_with("schema.fields.name"));
+    assert!(
+        error(json!({"fields": {"v": {"fields": {"x": {"regex": "("}}}}}))
+            .starts_with("schema.fields.v.fields.x.regex")
+    );
+}
+
+#[tokio::test]
+async fn test_scrape_paginated() {
+    let list = |items: &[&str], next: &str| {
+        let items: String = items
+            .iter()
+            .map(|item| {
+                format!(
+                    r#"<li class="item"><a href="/items/{0}">{0}</a></li>"#,
+                    item
+                )
+            })
+            .collect();
+        format!(
+            "<html><head>{}</head><body><ul>{}</ul></body></html>",
+            next, items
+        )
+    };
+    let (base, _) = serve_pages(vec![
+        (
+            "/",
+            200,
+            "text/html",
+            list(&["a", "b"], r#"<link rel="next" href="/p2">"#),
+        ),
+        (
+            "/p2",
+            200,
+            "text/html",
+            list(
+                &["b", "c"],
+                r#"<a class="next" rel="next" href="/p3#top">Next</a>"#,
+            ),
+        ),
+        (
+            "/p3",
+            200,
+            "text/html",
+            list(&["d"], r#"<a class="next" href="/p2">Back</a>"#),
+        ),
+        ("/list/1", 200, "text/html", list(&["a", "b"], "")),
+        ("/list/2", 200, "text/html", list(&["c"], "")),
+        ("/list/3", 200, "text/html", list(&[], "")),
+        ("/repeat/1", 200, "text/html", list(&["a", "b"], "")),
+        ("/repeat/2", 200, "text/html", list(&["a", "b"], "")),
+    ])
+    .await;
+    let start = Url::parse(&base).unwrap();
+    let mut session = ScrapingSession::new(&test_config()).unwrap();
+
+    // rel=next links, deduplicated by a schema field
+    let options = pagination_options_from_args(&HashMap::from([
+        (
+            "schema".to_string(),
+            json!({"items": "li.item", "fields": {
+                "name": "a",
+                "url": {"selector": "a", "attr": "href", "resolve": true}
+            }}),
+        ),
+        ("dedupe_by".to_string(), json!("url")),
+    ]))
+    .unwrap();
+    let report = Paginator::new(options)
+        .run(&mut session, start.clone())
+        .await
+        .unwrap();
+    assert_eq!(report.stopped, StopReason::NoNextPage);
+    let pages: Vec<(usize, usize)> = report
+        .pages
+        .iter()
+        .map(|p| (p.records, p.duplicates))
+        .collect();
+    assert_eq!(pages, [(2, 0), (2, 1), (1, 0)]);
+    assert_eq!(report.pages[2].url, format!("{}/p3", base));
+    let records: Vec<(usize, &str)> = report
+        .records
+        .iter()
+        .map(|r| (r.page, r.data["name"].as_str().unwrap()))
+        .collect();
+    assert_eq!(records, [(1, "a"), (1, "b"), (2, "c"), (3, "d")]);
+    assert_eq!(report.records[2].url, format!("{}/p2", base));
+    assert_eq!(report.records[2].data["url"], format!("{}/items/c", base));
+    assert_eq!((report.total_records, report.duplicates), (4, 1));
+
+    // A next-page selector leading back to a visited page stops there
+    let options = pagination_options_from_args(&HashMap::from([
+        ("selector".to_string(), json!("li.item a")),
+        ("next_selector".to_string(), json!("a.next")),
+    ]))
+    .unwrap();
+    let report = Paginator::new(options)
+        .run(&mut session, start.join("/p2").unwrap())
+        .await
+        .unwrap();
+    assert_eq!(report.stopped, StopReason::Cycle);
+    assert_eq!(report.pages.len(), 2);
+    assert_eq!(report.records[0].data["text"], "b");
+    assert_eq!(report.total_records, 3);
+
+    // URL templates stop at the first empty page, or at max_pages
+    let template = format!("{}/list/{{page}}", base);
+    let args = HashMap::from([
+        ("selector".to_string(), json!("li.item")),
+        ("url_template".to_string(), json!(template)),
+    ]);
+    let paginator = Paginator::new(pagination_options_from_args(&args).unwrap());
+    let first = paginator.first_url(None).unwrap();
+    assert_eq!(first.as_str(), format!("{}/list/1", base));
+    let report = paginator.run(&mut session, first).await.unwrap();
+    assert_eq!(report.stopped, StopReason::EmptyPage);
+    assert_eq!((report.pages.len(), report.total_records), (3, 3));
+
+    // Sites repeating their last page past the end stop there too
+    let repeat = HashMap::from([
+        ("selector".to_string(), json!("li.item")),
+        (
+            "url_template".to_string(),
+            json!(format!("{}/repeat/{{page}}", base)),
+        ),
+    ]);
+    let paginator = Paginator::new(pagination_options_from_args(&repeat).unwrap());
+    let report = paginator
+        .run(&mut session, paginator.first_url(None).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(report.stopped, StopReason::EmptyPage);
+    assert_eq!((report.pages.len(), report.duplicates), (2, 2));
+
+    let mut args = args;
+    args.insert("max_pages".to_string(), json!(1));
+    args.insert("start_page".to_string(), json!(2));
+    let paginator = Paginator::new(pagination_options_from_args(&args).unwrap());
+    let report = paginator
+        .run(&mut session, paginator.first_url(None).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(report.stopped, StopReason::MaxPages);
+    assert_eq!(report.records[0].url, format!("{}/list/2", base));
+
+    // Missing pages end the walk with an error
+    let options = pagination_options_from_args(&HashMap::from([
+        ("selector".to_string(), json!("li")),
+        ("next_selector".to_string(), json!("a")),
+    ]))
+    .unwrap();
+    let report = Paginator::new(options)
+        .run(&mut session, start.join("/missing").unwrap())
+        .await
+        .unwrap();
+    assert_eq!(report.stopped, StopReason::Error);
+    assert_eq!(report.pages[0].status, Some(404));
+    assert!(report.pages[0].error.as_ref().unwrap().contains("404"));
+
+    let error = |args: serde_json::Value| {
+        let args: HashMap<String, serde_json::Value> = serde_json::from_value(args).unwrap();
+        pagination_options_from_args(&args).unwrap_err().to_string()
+    };
+    assert!(error(json!({"url": "https://example.com/"})).contains("selector or schema"));
+    assert!(
+        error(json!({"selector": "li", "url_template": "https://example.com/list"}))
+            .contains("{page}")
+    );
+    assert!(error(json!({"selector": "li", "schema": {"fields": {}}})).contains("not both"));
+}
+
+#[test]
+fn test_extract_metadata_details() {
+    let html = r#"
+        <html lang="en-GB">
+        <head>
+            <title>
+                Release notes
+            </title>
+            <meta name="author" content="Ada Lovelace">
+            <meta property="article:published_time" content="2024-03-01T09:00:00Z">
+            <meta property="article:modified_time" content="2024-03-02T10:30:00Z">
+            <meta name="robots" content="noindex, follow">
+            <meta name="viewport" content="width=device-width, initial-scale=1">
+            <meta property="og:type" content="article">
+            <meta property="og:url" content="/news/release">
+            <meta property="og:image" content="/img/cover.png">
+            <meta property="og:image:width" content="1200">
+            <meta property="og:image" content="/img/second.png">
+            <meta name="twitter:card" content="summary_large_image">
+            <meta name="twitter:site" content="@example">
+            <meta name="twitter:image" content="img/card.png">
+            <link rel="canonical" href="/news/release">
+            <link rel="alternate" hreflang="de" href="/de/news/release">
+            <link rel="alternate" hreflang="x-default" href="https://example.com/news/release">
+            <link rel="alternate" type="application/rss+xml" title="News" href="/feed.xml">
+            <link rel="alternate" type="application/atom+xml" href="/atom.xml">
+            <link rel="alternate" type="text/html" href="/print">
+            <link rel="shortcut icon" href="/favicon.ico">
+            <link rel="apple-touch-icon" sizes="180x180" href="/apple.png">
+        </head>
+        <body><p>Notes</p></body>
+        </html>
+    "#;
+    let page_url = Url::parse("https://example.com/news/release?ref=home").unwrap();
+    let metadata = ElementExtractor::new(html)
+        .with_page_url(Some(page_url))
+        .extract_metadata();
+
+    assert_eq!(metadata["title"], "Release notes");
+    assert_eq!(metadata["description"], serde_json::Value::Null);
+    assert_eq!(metadata["author"], "Ada Lovelace");
+    assert_eq!(metadata["published"], "2024-03-01T09:00:00Z");
+    assert_eq!(metadata["modified"], "2024-03-02T10:30:00Z");
+    assert_eq!(metadata["language"], "en-GB");
+    assert_eq!(metadata["robots"], "noindex, follow");
+    assert_eq!(metadata["viewport"], "width=device-width, initial-scale=1");
+    assert_eq!(metadata["canonical"], "https://example.com/news/release");
+    assert_eq!(
+        metadata["open_graph"],
+        json!({
+            "type": "article",
+            "url": "https://example.com/news/release",
+            "image": "https://example.com/img/cover.png",
+            "image:width": "1200"
+        })
+    );
+    assert_eq!(
+        metadata["twitter"],
+        json!({
+            "card": "summary_large_image",
+            "site": "@example",
+            "image": "https://example.com/news/img/card.png"
+        })
+    );
+    assert_eq!(
+        metadata["alternates"],
+        json!([
+            {"hreflang": "de", "href": "https://example.com/de/news/release"},
+            {"hreflang": "x-default", "href": "https://example.com/news/release"}
+        ])
+    );
+    assert_eq!(
+        metadata["feeds"],
+        json!([
+            {"type": "application/rss+xml", "title": "News", "href": "https://example.com/feed.xml"},
+            {"type": "application/atom+xml", "title": null, "href": "https://example.com/atom.xml"}
+        ])
+    );
+    assert_eq!(metadata["favicon"], "https://example.com/favicon.ico");
+    assert_eq!(metadata["icons"].as_array().unwrap().len(), 2);
+    assert_eq!(metadata["icons"][1]["rel"], "apple-touch-icon");
+    assert_eq!(metadata["icons"][1]["sizes"], "180x180");
+
+    // Pages without metadata keep the same keys
+    let empty = ElementExtractor::new("<p>Hi</p>").extract_metadata();
+    let keys: Vec<&String> = empty.as_object().unwrap().keys().collect();
+    let full_keys: Vec<&String> = metadata.as_object().unwrap().keys().collect();
+    assert_eq!(keys, full_keys);
+    assert_eq!(empty["open_graph"], json!({}));
+    assert_eq!(empty["feeds"], json!([]));
+}
+
+#[test]
+fn test_structured_data_formats() {
+    let html = r##"
+        <html><head>
+            <script type="application/ld+json">
+            {
+                "@context": "https://schema.org",
+                "@graph": [
+                    {"@type": "WebPage", "@id": "#page", "name": "Catalog",
+                     "mainEntity": {"@type": "Product", "name": "Lamp"}},
+                    {"@type": "Organization", "name": "Acme"}
+                ]
+            }
+            </script>
+            <script type="application/ld+json">not json</script>
+        </head><body>
+            <div itemscope itemtype="https://schema.org/Product" itemid="/products/widget"
+                 itemref="extra">
+                <h1 itemprop="name">  Blue
+                    Widget </h1>
+                <img itemprop="image" src="/img/widget.png">
+                <a itemprop="url" href="widget">Link</a>
+                <span itemprop="color">blue</span> <span itemprop="color">navy</span>
+                <div itemprop="offers" itemscope itemtype="http://schema.org/Offer">
+                    <meta itemprop="priceCurrency" content="EUR">
+                    <data itemprop="price" value="19.99">19,99 €</data>
+                    <time itemprop="validFrom" datetime="2024-05-01">May</time>
+                </div>
+                <div itemscope><span itemprop="name">Unrelated</span></div>
+            </div>
+            <p id="extra"><span itemprop="brand">Acme</span></p>
+
+            <div vocab="https://schema.org/" typeof="Article" resource="#post">
+                <h2 property="headline">Launch day</h2>
+                <a property="url" href="/blog/launch">Read</a>
+                <div property="author" typeof="Person">
+                    <span property="name">Ada</span>
+                </div>
+                <time property="datePublished" datetime="2024-05-02">2 May</time>
+                <meta property="schema:keywords" content="launch">
+            </div>
+        </body></html>
+    "##;
+    let page_url = Url::parse("https://shop.example.com/catalog/").unwrap();
+    let extractor = ElementExtractor::new(html).with_page_url(Some(page_url));
+    let data = extractor.extract_structured_data().unwrap();
+    let formats: Vec<&str> = data.iter().map(|d| d["type"].as_str().unwrap()).collect();
+    assert_eq!(
+        formats,
+        ["json-ld", "json-ld", "microdata", "microdata", "rdfa"]
+    );
+
+    // @graph nodes become items of their own, keeping the context
+    assert_eq!(data[0]["data"]["@type"], "WebPage");
+    assert_eq!(data[0]["data"]["@context"], "https://schema.org");
+    assert_eq!(data[1]["data"]["name"], "Acme");
+
+    assert_eq!(
+        data[2]["data"],
+        json!({
+            "@context": "https://schema.org",
+            "@type": "Product",
+            "@id": "https://shop.example.com/products/widget",
+            "name": "Blue Widget",
+            "image": "https://shop.example.com/img/widget.png",
+            "url": "https://shop.example.com/catalog/widget",
+            "color": ["blue", "navy"],
+            "offers": {
+                "@type": "Offer",
+                "priceCurrency": "EUR",
+                "price": "19.99",
+                "validFrom": "2024-05-01"
+            },
+            "brand": "Acme"
+        })
+    );
+    // An untyped itemscope without itemprop is an item of its own
+    assert_eq!(data[3]["data"]["name"], "Unrelated");
+
+    assert_eq!(
+        data[4]["data"],
+        json!({
+            "@context": "https://schema.org/",
+            "@type": "Article",
+            "@id": "https://shop.example.com/catalog/#post",
+            "headline": "Launch day",
+            "url": "https://shop.example.com/blog/launch",
+            "author": {"@type": "Person", "name": "Ada"},
+            "datePublished": "2024-05-02",
+            "keywords": "launch"
+        })
+    );
+
+    // Type filters look into nested items and ignore the vocabulary prefix
+    let types = |types: &[&str]| -> Vec<serde_json::Value> {
+        let types: Vec<String> = types.iter().map(|t| t.to_string()).collect();
+        extractor
+            .extract_structured_data_of_types(&types)
+            .unwrap()
+            .into_iter()
+            .map(|d| d["data"].clone())
+            .collect()
+    };
+    let products = types(&["Product"]);
+    assert_eq!(products.len(), 2);
+    assert_eq!(products[0], json!({"@type": "Product", "name": "Lamp"}));
+    assert_eq!(products[1]["name"], "Blue Widget");
+    assert_eq!(types(&["schema:Offer"])[0]["price"], "19.99");
+    let people = types(&["https://schema.org/Person", "article"]);
+    assert_eq!(people.len(), 1);
+    assert_eq!(people[0]["headline"], "Launch day");
+    assert!(types(&["Recipe"]).is_empty());
+}
+
+#[test]
+fn test_extract_tables_expands_spans() {
+    let html = r#"
+        <table>
+            <caption> Quarterly   sales </caption>
+            <thead>
+                <tr><th rowspan="2">Region</th><th colspan="2">Sales</th><th rowspan="2">Growth</th><th rowspan="2">Updated</th></tr>
+                <tr><th>Q1</th><th>Q2</th></tr>
+            </thead>
+            <tbody>
+                <tr><td rowspan="2">North</td><td>$1,200.50</td><td>$900</td><td>+4.5%</td><td>2024-04-01</td></tr>
+                <tr><td>-$30</td><td>1,000 USD</td><td>-1%</td><td>March 3, 2024</td></tr>
+                <tr><td>South, East</td><td colspan="2">n/a</td><td></td><td>2024-04-02</td></tr>
+                <tr><td>Nested</td><td><table><tr><td>inner</td></tr></table></td><td>1</td><td>2%</td><td></td></tr>
+            </tbody>
+        </table>
+    "#;
+    let extractor = ElementExtractor::new(html);
+    let tables = extractor.extract_tables().unwrap();
+    // The nested table is a table of its own, without headers
+    assert_eq!(tables.len(), 2);
+    assert_eq!(tables[1]["headers"], json!(["inner"]));
+
+    let table = &tables[0];
+    assert_eq!(table["caption"], "Quarterly sales");
+    assert_eq!(
+        table["headers"],
+        json!(["Region", "Sales / Q1", "Sales / Q2", "Growth", "Updated"])
+    );
+    assert_eq!(
+        table["rows"],
+        json!([
+            ["North", "$1,200.50", "$900", "+4.5%", "2024-04-01"],
+            ["North", "-$30", "1,000 USD", "-1%", "March 3, 2024"],
+            ["South, East", "n/a", "n/a", "", "2024-04-02"],
+            ["Nested", "inner", "1", "2%", ""]
+        ])
+    );
+
+    // Columns with cells of mixed types stay strings; empty cells are null
+    let options = TableOptions {
+        format: TableFormat::Records,
+        infer_types: true,
+    };
+    let tables = extractor.extract_tables_with(&options).unwrap();
+    let table = &tables[0];
+    assert_eq!(
+        table["column_types"],
+        json!(["string", "string", "string", "percentage", "date"])
+    );
+    assert_eq!(
+        table["records"][1],
+        json!({
+            "Region": "North",
+            "Sales / Q1": "-$30",
+            "Sales / Q2": "1,000 USD",
+            "Growth": -1.0,
+            "Updated": "2024-03-03"
+        })
+    );
+    assert_eq!(table["records"][2]["Growth"], serde_json::Value::Null);
+    assert_eq!(table["records"][3]["Updated"], serde_json::Value::Null);
+
+    let html = r#"
+        <table>
+            <tr><th>Item</th><th>Price</th><th>Qty</th><th></th></tr>
+            <tr><td>Tea | green</td><td>€4.50</td><td>2</td><td>x</td></tr>
+            <tr><td>Coffee "dark"</td><td>€12</td><td>1,000</td><td>y</td></tr>
+        </table>
+    "#;
+    let options = TableOptions {
+        format: TableFormat::Records,
+        infer_types: true,
+    };
+    let table = &ElementExtractor::new(html)
+        .extract_tables_with(&options)
+        .unwrap()[0];
+    assert_eq!(
+        table["column_types"],
+        json!(["string", "currency", "integer", "string"])
+    );
+    assert_eq!(
+        table["records"][1],
+        json!({"Item": "Coffee \"dark\"", "Price": 12.0, "Qty": 1000, "column_4": "y"})
+    );
+
+    let csv = TableOptions {
+        format: TableFormat::Csv,
+        infer_types: false,
+    };
+    assert_eq!(
+        ElementExtractor::new(html)
+            .extract_tables_with(&csv)
+            .unwrap()[0]["csv"],
+        "Item,Price,Qty,\r\nTea | green,€4.50,2,x\r\n\"Coffee \"\"dark\"\"\",€12,\"1,000\",y\r\n"
+    );
+    let markdown = TableOptions {
+        format: TableFormat::Markdown,
+        infer_types: false,
+    };
+    assert_eq!(
+        ElementExtractor::new(html)
+            .extract_tables_with(&markdown)
+            .unwrap()[0]["markdown"],
+        "| Item | Price | Qty |  |\n| --- | --- | --- | --- |\n\
+         | Tea \\| green | €4.50 | 2 | x |\n| Coffee \"dark\" | €12 | 1,000 | y |"
+    );
+    assert!(TableFormat::parse("xml").is_err());
+}
+
+#[tokio::test]
+async fn test_monitor() {
+    let page = |updated: &str, items: &[&str], ad: &str| {
+        let items: String = items.iter().map(|i| format!("<li>{}</li>", i)).collect();
+        format!(
+            r#"<html><body><h1>Prices</h1><p class="updated">Updated {}</p>
+            <ul id="list">{}</ul><div class="ad">{}</div>
+            <script>var now = Date.now();</script></body></html>"#,
+            updated, items, ad
+        )
+    };
+    let base = serve_versions(vec![
+        page("5 minutes ago", &["Apple $1", "Pear $2"], "Buy shoes"),
+        page("2024-03-05 10:42", &["Apple $1", "Pear $2"], "Buy hats"),
+        page("just now", &["Apple $1", "Plum $3"], "Buy socks"),
+        page("just now", &["Apple $1", "Plum $3"], "Buy socks"),
+    ])
+    .await;
+    let url = Url::parse(&format!("{}/prices", base)).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let store = MonitorStore::new(Some(dir.path().to_path_buf()));
+    let mut session = ScrapingSession::new(&test_config()).unwrap();
+    let options = MonitorOptions::default();
+
+    let created = store.check(&mut session, &url, &options).await.unwrap();
+    assert_eq!(created.status, CheckStatus::Created);
+    assert_eq!(created.previous_check, None);
+    assert_eq!(created.monitor.checks, 0);
+
+    // Only the timestamp and the ad differ
+    let unchanged = store.check(&mut session, &url, &options).await.unwrap();
+    assert_eq!(unchanged.status, CheckStatus::Unchanged);
+    assert_eq!(unchanged.previous_check, Some(created.monitor.last_checked));
+    assert!(unchanged.diff.is_empty());
+    assert_eq!(unchanged.monitor.last_changed, None);
+
+    let changed = store.check(&mut session, &url, &options).await.unwrap();
+    assert_eq!(changed.status, CheckStatus::Changed);
+    assert_eq!(changed.added, vec!["Plum $3"]);
+    assert_eq!(changed.removed, vec!["Pear $2"]);
+    assert!(
+        changed.diff.contains("\n-- Pear $2\n+- Plum $3\n"),
+        "{}",
+        changed.diff
+    );
+    assert!(!changed.diff.contains("Buy"));
+    assert_eq!(
+        changed.monitor.last_changed,
+        Some(changed.monitor.last_checked)
+    );
+    assert_eq!((changed.monitor.checks, changed.monitor.changes), (2, 1));
+
+    // Monitors are kept in the directory, and a selector makes a new one
+    let store = MonitorStore::new(Some(dir.path().to_path_buf()));
+    assert_eq!(store.list().unwrap(), vec![changed.monitor.clone()]);
+    let list = MonitorOptions {
+        selector: Some("#list".to_string()),
+        ..Default::default()
+    };
+    let scoped = store.check(&mut session, &url, &list).await.unwrap();
+    assert_eq!(scoped.status, CheckStatus::Created);
+    assert_ne!(scoped.monitor.id, changed.monitor.id);
+    assert_eq!(store.list().unwrap().len(), 2);
+    assert!(store.remove(&scoped.monitor.id).unwrap());
+    assert!(!store.remove(&scoped.monitor.id).unwrap());
+
+    let missing = MonitorOptions {
+        selector: Some("#missing".to_string()),
+        ..Default::default()
+    };
+    let error = store.check(&mut session, &url, &missing).await.unwrap_err();
+    assert!(error.to_string().contains("No element matches #missing"));
+    assert_eq!(store.list().unwrap().len(), 1);
+
+    // Each monitor has a file of its own, and a failed save leaves the
+    // monitor as it was
+    let file = dir.path().join(format!("{}.json", changed.monitor.id));
+    assert!(file.is_file());
+    std::fs::create_dir(file.with_extension("json.tmp")).unwrap();
+    assert!(store.check(&mut session, &url, &options).await.is_err());
+    assert_eq!(store.list().unwrap(), vec![changed.monitor.clone()]);
+    std::fs::remove_dir(file.with_extension("json.tmp")).unwrap();
+    assert!(store.remove(&changed.monitor.id).unwrap());
+    assert!(!file.exists());
+
+    let args: HashMap<String, serde_json::Value> = serde_json::from_value(json!({
+        "url": url.as_str(),
+        "ignore_ads": false,
+        "ignore_patterns": ["("]
+    }))
+    .unwrap();
+    assert!(monitor_options_from_args(&args).is_err());
+}
+
+#[tokio::test]
+async fn test_monitor_reports_version_bumps() {
+    let page = |version: &str, ratio: &str, updated: &str| {
+        format!(
+            "<html><body><p>Release {}</p><p>Video in {}</p><p>Updated {}</p></body></html>",
+            version, ratio, updated
+        )
+    };
+    let base = serve_versions(vec![
+        page("2.10.15", "16:9", "05.03.2024 10:42"),
+        page("2.10.15", "16:9", "06.03.2024 9:05pm"),
+        page("2.10.16", "21:9", "07/03/24 23:59"),
+    ])
+    .await;
+    let url = Url::parse(&format!("{}/releases", base)).unwrap();
+    let store = MonitorStore::new(None);
+    let mut session = ScrapingSession::new(&test_config()).unwrap();
+    let options = MonitorOptions::default();
+
+    store.check(&mut session, &url, &options).await.unwrap();
+    let unchanged = store.check(&mut session, &url, &options).await.unwrap();
+    assert_eq!(unchanged.status, CheckStatus::Unchanged);
+    let changed = store.check(&mut session, &url, &options).await.unwrap();
+    assert_eq!(changed.status, CheckStatus::Changed);
+    assert_eq!(changed.added, vec!["Release 2.10.16", "Video in 21:9"]);
+}
+
+#[tokio::test]
+async fn test_archive_and_replay() {
+    let (base, requests) = serve_pages(vec![
+        (
+            "/",
+            200,
+            "text/html",
+            r#"<html><head><title>Home</title></head><body><a href="/about">About</a></body></html>"#
+                .to_string(),
+        ),
+        (
+            "/about",
+            200,
+            "text/html",
+            "<html><head><title>About</title></head><body><p>We crawl.</p></body></html>"
+                .to_string(),
+        ),
+    ])
+    .await;
+    let config = test_config();
+    let dir = tempfile::tempdir().unwrap();
+    let archive = Arc::new(Archive::new(dir.path().to_path_buf(), &config.user_agent));
+    let record = ArchiveMode::Record(archive.clone());
+    let mut session = ScrapingSession::new(&config)
+        .unwrap()
+        .with_politeness(Arc::new(
+            Politeness::new(&config).with_archive(record.clone()),
+        ))
+        .with_archive(record);
+
+    session.set_job_id(Some("job-1".to_string()));
+    let recorded = Crawler::new(CrawlOptions::default())
+        .run(&mut session, &base)
+        .await
+        .unwrap();
+    assert_eq!(recorded.pages_crawled, 2);
+    session.set_job_id(None);
+    let about = format!("{}/about", base);
+    session.fetch(&about).await.unwrap();
+
+    // robots.txt, the two crawled pages and the last fetch
+    let exchanges = archive.exchanges(None).unwrap();
+    assert_eq!(exchanges.len(), 4);
+    assert_eq!(exchanges[0].url, format!("{}/robots.txt", base));
+    assert_eq!(exchanges[0].status, 404);
+    let page = &exchanges[2];
+    assert_eq!(
+        (page.method.as_str(), page.url.as_str()),
+        ("GET", about.as_str())
+    );
+    assert_eq!(page.job_id.as_deref(), Some("job-1"));
+    assert!(page
+        .request_headers
+        .contains(&("user-agent".to_string(), config.user_agent.clone())));
+    assert!(page
+        .response_headers
+        .contains(&("content-type".to_string(), "text/html".to_string())));
+    assert!(String::from_utf8_lossy(&page.body).contains("We crawl."));
+    assert_eq!(archive.exchanges(Some("job-1")).unwrap().len(), 2);
+
+    let files: Vec<_> = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .collect();
+    assert_eq!(files.len(), 1);
+    let warc = std::fs::read_to_string(&files[0]).unwrap();
+    assert!(warc.starts_with("WARC/1.1\r\nWARC-Type: warcinfo\r\n"));
+    assert_eq!(warc.matches("WARC-Type: request\r\n").count(), 4);
+    assert_eq!(warc.matches("WARC-Type: response\r\n").count(), 4);
+    assert_eq!(warc.matches("X-Crawl-Job-ID: job-1\r\n").count(), 4);
+    assert!(warc.contains("\r\n\r\nGET /about HTTP/1.1\r\nhost: 127.0.0.1:"));
+
+    let jsonl = archive.export(Some("job-1"), ExportFormat::Jsonl).unwrap();
+    assert_eq!(jsonl.exchanges, 2);
+    let lines: Vec<serde_json::Value> = std::fs::read_to_string(&jsonl.path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines[1]["url"], about);
+    assert_eq!(lines[1]["status"], 200);
+    assert_eq!(lines[1]["response_headers"]["content-type"], "text/html");
+    assert_eq!(lines[1]["body_encoding"], "text");
+    let export = archive.export(Some("job-1"), ExportFormat::Warc).unwrap();
+    assert!(export.path.starts_with(dir.path().join("exports")));
+    assert!(archive.export(Some("job-2"), ExportFormat::Warc).is_err());
+
+    // Replaying the export gives the same crawl without a single request
+    let served = requests.lock().unwrap().len();
+    let replay = Replay::open(&export.path).unwrap();
+    assert_eq!(replay.len(), 2);
+    let mut offline = ScrapingSession::new(&config)
+        .unwrap()
+        .with_archive(ArchiveMode::Replay(Arc::new(replay)));
+    let replayed = Crawler::new(CrawlOptions::default())
+        .run(&mut offline, &base)
+        .await
+        .unwrap();
+    let titles = |report: &CrawlReport| -> Vec<Option<String>> {
+        report.pages.iter().map(|p| p.title.clone()).collect()
+    };
+    assert_eq!(titles(&replayed), titles(&recorded));
+    let page = offline.fetch(&about).await.unwrap();
+    assert_eq!(page.response.status, 200);
+    assert_eq!(page.response.content_type.as_deref(), Some("text/html"));
+    assert!(page.html.contains("We crawl."));
+    let error = offline
+        .fetch(&format!("{}/missing", base))
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("not in the replayed archive"));
+    assert_eq!(requests.lock().unwrap().len(), served);
+
+    // A directory replays every WARC file in it, exports aside
+    assert_eq!(Replay::open(dir.path()).unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn test_archive_records_redirect_hops_and_revalidations() {
+    let (base, _) = serve_pages(vec![
+        ("/old", 301, "text/html\r\nLocation: /new", String::new()),
+        ("/new", 200, "text/html", "<p>Moved here</p>".to_string()),
+    ])
+    .await;
+    let old = format!("{}/old", base);
+    let new = format!("{}/new", base);
+    let config = CrawlerConfig {
+        cache: CacheOptions {
+            ttl: Duration::ZERO,
+            ..Default::default()
+        },
+        ..test_config()
+    };
+    let dir = tempfile::tempdir().unwrap();
+    let archive = Arc::new(Archive::new(dir.path().to_path_buf(), &config.user_agent));
+    let state =
+        CrawlerState::new(config.clone()).with_archive(ArchiveMode::Record(archive.clone()));
+
+    let mut session = state.session().unwrap();
+    assert_eq!(session.fetch(&old).await.unwrap().url.as_str(), new);
+    let page = session.fetch(&old).await.unwrap();
+    assert_eq!(page.cache_status, CacheStatus::Revalidated);
+
+    // Every hop is an exchange of its own, revalidation included
+    let exchanges = archive.exchanges(None).unwrap();
+    let hops: Vec<(&str, u16)> = exchanges
+        .iter()
+        .map(|e| (e.url.trim_start_matches(&base), e.status))
+        .collect();
+    assert_eq!(
+        hops,
+        [
+            ("/robots.txt", 404),
+            ("/old", 301),
+            ("/new", 200),
+            ("/old", 301),
+            ("/new", 304)
+        ]
+    );
+    assert!(exchanges.iter().all(|e| e.url == e.response_url));
+
+    // The 304 does not hide the page it revalidated
+    let replay = Replay::open(dir.path()).unwrap();
+    let offline = CrawlerState::new(config).with_archive(ArchiveMode::Replay(Arc::new(replay)));
+    let page = offline.session().unwrap().fetch(&old).await.unwrap();
+    assert_eq!(page.response.status, 200);
+    assert_eq!(page.url.as_str(), new);
+    assert!(page.html.contains("Moved here"));
+
+    // A corrupt Content-Length is an error, not an overflow
+    let corrupt = dir.path().join("corrupt.warc");
+    std::fs::write(
+        &corrupt,
+        format!("WARC/1.1\r\nContent-Length: {}\r\n\r\n", usize::MAX),
+    )
+    .unwrap();
+    let error = Replay::open(&corrupt).err().unwrap();
+    assert!(format!("{:#}", error).contains("Truncated record"));
+}
+
+#[tokio::test]
+async fn test_check_links() {
+    let slow = serve_hanging().await;
+    let docs = format!(
+        r##"<html><body><h1 id="intro">Docs</h1>
+        <a href="/guide">Guide</a> <a href="/guide#install">Install</a>
+        <a href="/guide#setup">Setup</a> <a href="/old">Old</a> <a href="/gone">Gone</a>
+        <a href="/loop">Loop</a> <a href="#intro">Intro</a> <a href="#top">Top</a>
+        <a href="mailto:docs@example.com">Mail</a> <a href="{}/api">API</a></body></html>"##,
+        slow
+    );
+    let (base, requests) = serve_pages(vec![
+        ("/docs", 200, "text/html", docs),
+        (
+            "/guide",
+            200,
+            "text/html",
+            r#"<h2 id="install">Install</h2><a href="/docs">Docs</a><a href="/guide/faq">FAQ</a>"#
+                .to_string(),
+        ),
+        ("/old", 301, "text/html\r\nLocation: /guide", String::new()),
+        ("/loop", 302, "text/html\r\nLocation: /loop", String::new()),
+    ])
+    .await;
+    let start = Url::parse(&format!("{}/docs", base)).unwrap();
+    let session = ScrapingSession::new(&test_config()).unwrap();
+    let options = LinkCheckOptions {
+        timeout: Duration::from_millis(300),
+        ..Default::default()
+    };
+
+    let report = LinkChecker::new(options.clone())
+        .run(&session, &start)
+        .await
+        .unwrap();
+    assert_eq!(report.pages_checked, 1);
+    assert_eq!(report.urls_checked, 6);
+    let page = &report.pages[0];
+    assert_eq!((page.http_status, page.links_found), (Some(200), 9));
+    let summary = &report.summary;
+    assert_eq!((summary.ok, summary.redirected, summary.broken), (4, 1, 1));
+    assert_eq!(
+        (summary.missing_anchor, summary.timeout, summary.error),
+        (1, 1, 1)
+    );
+
+    let link = |path: &str| {
+        let url = if path.starts_with("http") {
+            path.to_string()
+        } else {
+            format!("{}{}", base, path)
+        };
+        page.links.iter().find(|l| l.url == url).unwrap()
+    };
+    let setup = link("/guide#setup");
+    assert_eq!(setup.status, LinkStatus::MissingAnchor);
+    assert_eq!(setup.http_status, Some(200));
+    assert_eq!(setup.text.as_deref(), Some("Setup"));
+    let old = link("/old");
+    assert_eq!(old.status, LinkStatus::Redirected);
+    assert_eq!(old.redirects.len(), 1);
+    assert_eq!(
+        (old.redirects[0].url.as_str(), old.redirects[0].status),
+        (format!("{}/old", base).as_str(), 301)
+    );
+    assert_eq!(old.final_url, Some(format!("{}/guide", base)));
+    assert_eq!(link("/gone").status, LinkStatus::Broken);
+    assert_eq!(link("/gone").http_status, Some(404));
+    let looping = link("/loop");
+    assert_eq!(looping.status, LinkStatus::Error);
+    assert!(looping.error.as_ref().unwrap().starts_with("Redirect loop"));
+    assert_eq!(link(&format!("{}/api", slow)).status, LinkStatus::Timeout);
+    assert_eq!(page.links.len(), 5);
+
+    // HEAD is enough without an anchor to check, and GET is tried when it fails
+    let methods = |path: &str| -> Vec<String> {
+        requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|r| r.split_whitespace().nth(1) == Some(path))
+            .map(|r| r.split_whitespace().next().unwrap().to_string())
+            .collect()
+    };
+    assert_eq!(methods("/old"), vec!["HEAD"]);
+    assert_eq!(methods("/gone"), vec!["HEAD", "GET"]);
+    // /guide is read for its anchors, and reached from /old with HEAD
+    let mut guide = methods("/guide");
+    guide.sort();
+    assert_eq!(guide, vec!["GET", "HEAD"]);
+
+    // A crawl checks the links of every page it reaches
+    let options = LinkCheckOptions {
+        check_external: false,
+        crawl: CrawlOptions {
+            max_pages: 2,
+            ..options.crawl
+        },
+        ..options
+    };
+    let report = LinkChecker::new(options)
+        .run(&session, &start)
+        .await
+        .unwrap();
+    assert_eq!(report.pages_checked, 2);
+    assert_eq!(report.summary.timeout, 0);
+    let guide = &report.pages[1];
+    assert_eq!(guide.url, format!("{}/guide", base));
+    assert_eq!(guide.links_found, 2);
+    assert_eq!(guide.links.len(), 1);
+    assert_eq!(guide.links[0].url, format!("{}/guide/faq", base));
+    assert_eq!(guide.links[0].status, LinkStatus::Broken);
+
+    let args: HashMap<String, serde_json::Value> = serde_json::from_value(json!({
+        "url": start.as_str(),
+        "timeout_seconds": 2.5,
+        "concurrency": 100
+    }))
+    .unwrap();
+    let options = link_check_options_from_args(&args, &test_config()).unwrap();
+    assert_eq!(options.crawl.max_pages, 1);
+    assert_eq!(options.timeout, Duration::from_millis(2500));
+    assert_eq!(options.concurrency, 32);
+    assert!(options.check_anchors && options.check_external && !options.include_ok);
+}
+
+#[tokio::test]
+async fn test_crawl_jobs() {
+    let page = |links: &[&str]| {
+        let links: String = links
+            .iter()
+            .map(|l| format!(r#"<a href="{}">{}</a>"#, l, l))
+            .collect();
+        format!("<html><body>{}</body></html>", links)
+    };
+    let (base, _) = serve_pages(vec![
+        ("/", 200, "text/html", page(&["/a", "/b"])),
+        ("/a", 200, "text/html", page(&["/"])),
+        ("/b", 200, "text/html", page(&[])),
+    ])
+    .await;
+    let notifications = Arc::new(Mutex::new(Vec::new()));
+    let sent = notifications.clone();
+    let notifier: Notifier = Arc::new(move |method, params| {
+        sent.lock().unwrap().push((method, params));
+        Box::pin(async {})
+    });
+    let jobs = CrawlJobs::default();
+    let session = || Arc::new(ScrapingSession::new(&test_config()).unwrap().into());
+    let options = CrawlOptions {
+        extract_content: false,
+        ..Default::default()
+    };
+
+    let seed = Url::parse(&format!("{}/", base)).unwrap();
+    let reporter = ProgressReporter::new(json!("crawl-1"), notifier);
+    let started = jobs.start(session(), seed, options.clone(), Some(reporter));
+    assert_eq!(started.state, JobState::Running);
+    assert_eq!(started.pages_done, 0);
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let status = loop {
+        let status = jobs.status(&started.job_id).unwrap();
+        if status.state != JobState::Running || Instant::now() > deadline {
+            break status;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    };
+    assert_eq!(status.state, JobState::Completed);
+    assert_eq!(
+        (status.pages_done, status.pages_failed, status.pages_queued),
+        (3, 0, 0)
+    );
+    assert!(status.finished.is_some());
+
+    let results = jobs.results(&started.job_id, 0, 2).unwrap();
+    assert_eq!(results.pages.len(), 2);
+    assert_eq!(results.pages[0].url, format!("{}/", base));
+    assert_eq!(results.next_offset, Some(2));
+    let results = jobs.results(&started.job_id, 2, 2).unwrap();
+    assert_eq!(results.pages[0].url, format!("{}/b", base));
+    assert_eq!(results.next_offset, None);
+
+    // One notification per page, then one when the crawl ends
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let notifications = notifications.lock().unwrap().clone();
+    let progress: Vec<(u64, u64)> = notifications
+        .iter()
+        .map(|(method, params)| {
+            assert_eq!(method, "notifications/progress");
+            assert_eq!(params["progressToken"], "crawl-1");
+            (
+                params["progress"].as_u64().unwrap(),
+                params["total"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(progress, vec![(1, 3), (2, 3), (3, 3), (3, 3)]);
+    assert_eq!(notifications[0].1["message"], format!("Crawled {}/", base));
+    assert_eq!(notifications[3].1["message"], "Crawl completed, 3 pages");
+
+    // Cancelling stops a crawl stuck on a page and releases its session
+    let hanging = Url::parse(&serve_hanging().await).unwrap();
+    let shared = session();
+    let job = jobs.start(shared.clone(), hanging, options, None);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(jobs.status(&job.job_id).unwrap().state, JobState::Running);
+    let cancelled = jobs.cancel(&job.job_id).unwrap();
+    assert_eq!(cancelled.state, JobState::Cancelled);
+    assert_eq!(cancelled.pages_done, 0);
+    assert!(cancelled.finished.is_some());
+    let released = tokio::time::timeout(Duration::from_secs(1), shared.lock()).await;
+    assert!(released.is_ok());
+    assert_eq!(
+        jobs.cancel(&job.job_id).unwrap().finished,
+        cancelled.finished
+    );
+    assert_eq!(
+        jobs.cancel(&started.job_id).unwrap().state,
+        JobState::Completed
+    );
+
+    let error = jobs.status("missing").unwrap_err();
+    assert_eq!(error.to_string(), "Unknown crawl job: missing");
+}
@@ -0,0 +1,493 @@
+use anyhow::Result;
+use scraper::{Html, Node};
+use serde_json::{json, Map, Value};
+use sxd_document::dom::{Document, Element};
+use sxd_document::Package;
+use sxd_xpath::nodeset::Node as XNode;
+use sxd_xpath::{Context, Factory, Value as XValue};
+
+/// Result of an XPath 1.0 expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum XPathResult {
+    /// Matched nodes in document order, as JSON objects.
+    Nodes(Vec<Value>),
+    String(String),
+    Number(f64),
+    Boolean(bool),
+}
+
+impl XPathResult {
+    pub fn to_json(&self) -> Value {
+        match self {
+            XPathResult::Nodes(nodes) => json!({
+                "type": "nodes",
+                "count": nodes.len(),
+                "nodes": nodes,
+            }),
+            XPathResult::String(value) => json!({"type": "string", "value": value}),
+            // NaN and infinities have no JSON representation and become null
+            XPathResult::Number(value) => json!({"type": "number", "value": value}),
+            XPathResult::Boolean(value) => json!({"type": "boolean", "value": value}),
+        }
+    }
+}
+
+/// Evaluate an XPath 1.0 expression against a parsed HTML document, with
+/// the document root as context node.
+///
+/// Elements and attributes are matched by local name without a namespace,
+/// so `//div/@class` works on HTML as expected.
+pub fn evaluate(document: &Html, expression: &str) -> Result<XPathResult> {
+    let xpath = Factory::new()
+        .build(expression)
+        .map_err(|e| anyhow::anyhow!("Invalid XPath expression {:?}: {}", expression, e))?
+        .ok_or(anyhow::anyhow!("Empty XPath expression"))?;
+
+    let package = Package::new();
+    let xml = package.as_document();
+    copy_document(document, xml);
+
+    let value = xpath
+        .evaluate(&Context::new(), xml.root())
+        .map_err(|e| anyhow::anyhow!("Failed to evaluate XPath {:?}: {}", expression, e))?;
+
+    Ok(match value {
+        XValue::Nodeset(nodes) => {
+            XPathResult::Nodes(nodes.document_order().iter().map(node_to_json).collect())
+        }
+        XValue::String(value) => XPathResult::String(value),
+        XValue::Number(value) => XPathResult::Number(value),
+        XValue::Boolean(value) => XPathResult::Boolean(value),
+    })
+}
+
+/// Rebuild the HTML tree as an XML document XPath can walk.
+fn copy_document<'d>(document: &Html, xml: Document<'d>) {
+    let root = xml.root();
+    for child in document.tree.root().children() {
+        match child.value() {
+            Node::Element(element) => {
+                let xml_element = xml.create_element(element.name());
+                root.append_child(xml_element);
+                copy_children(child, xml, xml_element);
+            }
+            Node::Comment(comment) => root.append_child(xml.create_comment(comment)),
+            _ => {}
+        }
+    }
+}
+
+fn copy_children<'d>(node: ego_tree::NodeRef<'_, Node>, xml: Document<'d>, parent: Element<'d>) {
+    if let Node::Element(element) = node.value() {
+        for (name, value) in element.attrs() {
+            parent.set_attribute_value(name, value);
+        }
+    }
+    for child in node.children() {
+        match child.value() {
+            Node::Element(element) => {
+                let xml_element = xml.create_element(element.name());
+                parent.append_child(xml_element);
+                copy_children(child, xml, xml_element);
+            }
+            Node::Text(text) => parent.append_child(xml.create_text(text)),
+            Node::Comment(comment) => parent.append_child(xml.create_comment(comment)),
+            _ => {}
+        }
+    }
+}
+
+fn node_to_json(node: &XNode) -> Value {
+    match node {
+        XNode::Element(element) => {
+            let attributes: Map<String, Value> = element
+                .attributes()
+                .iter()
+                .map(|a| (a.name().local_part().to_string(), json!(a.value())))
+                .collect();
+            json!({
+                "type": "element",
+                "tag": element.name().local_part(),
+                "attributes": attributes,
+                "text": collapse_whitespace(&node.string_value()),
+            })
+        }
+        XNode::Attribute(attribute) => json!({
+            "type": "attribute",
+            "name": attribute.name().local_part(),
+            "value": attribute.value(),
+        }),
+        XNode::Text(text) => json!({"type": "text", "text": text.text()}),
+        XNode::Comment(comment) => json!({"type": "comment", "text": comment.text()}),
+        XNode::Root(_) => json!({
+            "type": "document",
+            "text": collapse_whitespace(&node.string_value()),
+        }),
+        _ => json!({"type": "other", "text": node.string_value()}),
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Translate an XPath expression to an equivalent CSS selector.
+///
+/// Only location paths whose meaning CSS can express exactly are accepted:
+/// `child` and `descendant` steps on element names, attribute tests
+/// (`@a`, `@a='v'`, `@a!='v'`, `contains(@a, 'v')`, `starts-with(@a, 'v')`),
+/// `not(...)` and `and` of those, and a leading position (`[n]`,
+/// `[last()]`). Element and attribute names must be lower case, since CSS
+/// matches HTML names in any case. Anything else is an error rather than
+/// an approximation.
+pub fn to_css(xpath: &str) -> Result<String> {
+    let mut parser = Parser {
+        input: xpath.trim(),
+        pos: 0,
+    };
+    let css = parser.location_path()?;
+    if !parser.at_end() {
+        return parser.unsupported("trailing input");
+    }
+    Ok(css)
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn rest(&self) -> &str {
+        &self.input[self.pos..]
+    }
+
+    fn at_end(&self) -> bool {
+        self.rest().is_empty()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let trimmed = self.rest().trim_start();
+        self.pos = self.input.len() - trimmed.len();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<()> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            self.unsupported(&format!("expected `{}`", token))
+        }
+    }
+
+    fn unsupported<T>(&self, reason: &str) -> Result<T> {
+        anyhow::bail!(
+            "XPath {:?} has no exact CSS equivalent ({} at position {}); use xpath_query instead",
+            self.input,
+            reason,
+            self.pos
+        )
+    }
+
+    fn name(&mut self) -> Option<String> {
+        self.skip_whitespace();
+        let len = self
+            .rest()
+            .find(|c: char| !(c.is_alphanumeric() || matches!(c, '-' | '_' | '.')))
+            .unwrap_or(self.rest().len());
+        if len == 0 || self.rest().starts_with(['-', '.']) {
+            return None;
+        }
+        let name = self.rest()[..len].to_string();
+        self.pos += len;
+        Some(name)
+    }
+
+    fn location_path(&mut self) -> Result<String> {
+        let mut css = String::new();
+        let mut first = true;
+        loop {
+            self.skip_whitespace();
+            if self.at_end() {
+                break;
+            }
+            let descendant = if self.eat("//") {
+                true
+            } else if self.eat("/") {
+                false
+            } else {
+                return self.unsupported("expected an absolute path starting with `/`");
+            };
+
+            let (step, explicit_descendant) = self.step()?;
+            if !first {
+                css.push_str(if descendant || explicit_descendant {
+                    " "
+                } else {
+                    " > "
+                });
+            }
+            let root_step = first && !descendant && !explicit_descendant;
+            css.push_str(&step.to_css(root_step));
+            first = false;
+        }
+        if first {
+            return self.unsupported("empty path");
+        }
+        Ok(css)
+    }
+
+    /// Parse one step; returns it and whether it used the `descendant` axis.
+    fn step(&mut self) -> Result<(Step, bool)> {
+        let start = self.pos;
+        let mut explicit_descendant = false;
+        if let Some(word) = self.name() {
+            if self.rest().starts_with("::") {
+                self.pos += 2;
+                match word.as_str() {
+                    "child" => {}
+                    "descendant" => explicit_descendant = true,
+                    _ => return self.unsupported(&format!("axis `{}`", word)),
+                }
+            } else {
+                self.pos = start;
+            }
+        }
+
+        self.skip_whitespace();
+        let name = if self.eat("*") {
+            None
+        } else {
+            match self.name() {
+                Some(_) if self.rest().starts_with(':') => {
+                    return self.unsupported("namespace prefixes");
+                }
+                Some(name) if self.rest().trim_start().starts_with('(') => {
+                    return self.unsupported(&format!("node test `{}()`", name));
+                }
+                // `my.elem` would read as element `my` with class `elem`
+                Some(name) if !is_css_identifier(&name) => {
+                    return self.unsupported(&format!("element name `{}`", name));
+                }
+                // CSS matches HTML element names in any case, XPath only
+                // the lower-case names the parser gives them
+                Some(name) if name.bytes().any(|b| b.is_ascii_uppercase()) => {
+                    return self.unsupported(&format!("upper-case element name `{}`", name));
+                }
+                Some(name) => Some(name),
+                None => return self.unsupported("expected an element name"),
+            }
+        };
+
+        let mut step = Step {
+            name,
+            position: None,
+            filters: vec![],
+        };
+        while self.eat("[") {
+            self.skip_whitespace();
+            let position = if let Some(digits) = self.number() {
+                Some(Position::Nth(digits))
+            } else if self.eat("last()") {
+                Some(Position::Last)
+            } else {
+                None
+            };
+
+            match position {
+                Some(position) => {
+                    // A position only counts siblings when nothing filtered them first
+                    if step.position.is_some() || !step.filters.is_empty() || explicit_descendant {
+                        return self.unsupported("position after other predicates");
+                    }
+                    step.position = Some(position);
+                }
+                None => {
+                    let filters = self.and_expression()?;
+                    step.filters.extend(filters);
+                }
+            }
+            self.expect("]")?;
+        }
+        Ok((step, explicit_descendant))
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        self.skip_whitespace();
+        let len = self
+            .rest()
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(self.rest().len());
+        if len == 0 {
+            return None;
+        }
+        let after = self.rest()[len..].trim_start();
+        if !after.starts_with(']') {
+            return None;
+        }
+        let n = self.rest()[..len].parse().ok()?;
+        self.pos += len;
+        Some(n)
+    }
+
+    fn and_expression(&mut self) -> Result<Vec<String>> {
+        let mut filters = vec![self.filter()?];
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with("and ") || self.rest().starts_with("and(") {
+                self.pos += 3;
+                filters.push(self.filter()?);
+            } else if self.rest().starts_with("or ") {
+                return self.unsupported("`or`");
+            } else {
+                return Ok(filters);
+            }
+        }
+    }
+
+    /// A single attribute test, as a CSS simple selector.
+    fn filter(&mut self) -> Result<String> {
+        self.skip_whitespace();
+        if self.eat("not(") {
+            let inner = self.filter()?;
+            self.expect(")")?;
+            // `:not()` takes a single simple selector
+            if inner.starts_with(':') || inner.contains("]:") {
+                return self.unsupported("nested negation");
+            }
+            return Ok(format!(":not({})", inner));
+        }
+        for (function, operator) in [("contains(", "*="), ("starts-with(", "^=")] {
+            if self.eat(function) {
+                let attribute = self.attribute()?;
+                self.expect(",")?;
+                let value = self.literal()?;
+                self.expect(")")?;
+                // Every string contains the empty string
+                return Ok(if value.is_empty() {
+                    format!("[{}]", attribute)
+                } else {
+                    format!("[{}{}{}]", attribute, operator, quote(&value))
+                });
+            }
+        }
+        if self.rest().starts_with('@') {
+            let attribute = self.attribute()?;
+            if self.eat("!=") {
+                let value = self.literal()?;
+                return Ok(format!(
+                    "[{}]:not([{}={}])",
+                    attribute,
+                    attribute,
+                    quote(&value)
+                ));
+            }
+            if self.eat("=") {
+                let value = self.literal()?;
+                if attribute == "id" && is_css_identifier(&value) {
+                    return Ok(format!("#{}", value));
+                }
+                return Ok(format!("[{}={}]", attribute, quote(&value)));
+            }
+            return Ok(format!("[{}]", attribute));
+        }
+        self.unsupported("only attribute tests can be translated")
+    }
+
+    fn attribute(&mut self) -> Result<String> {
+        self.expect("@")?;
+        match self.name() {
+            Some(_) if self.rest().starts_with(':') => self.unsupported("namespace prefixes"),
+            Some(name) if !is_css_identifier(&name) => {
+                self.unsupported(&format!("attribute name `{}`", name))
+            }
+            Some(name) if name.bytes().any(|b| b.is_ascii_uppercase()) => {
+                self.unsupported(&format!("upper-case attribute name `{}`", name))
+            }
+            Some(name) => Ok(name),
+            None => self.unsupported("expected an attribute name"),
+        }
+    }
+
+    fn literal(&mut self) -> Result<String> {
+        self.skip_whitespace();
+        let Some(quote) = self
+            .rest()
+            .chars()
+            .next()
+            .filter(|c| *c == '\'' || *c == '"')
+        else {
+            return self.unsupported("expected a string literal");
+        };
+        let Some(end) = self.rest()[1..].find(quote) else {
+            return self.unsupported("unterminated string literal");
+        };
+        let value = self.rest()[1..1 + end].to_string();
+        self.pos += end + 2;
+        Ok(value)
+    }
+}
+
+enum Position {
+    Nth(usize),
+    Last,
+}
+
+struct Step {
+    /// Element name, `None` for `*`.
+    name: Option<String>,
+    position: Option<Position>,
+    filters: Vec<String>,
+}
+
+impl Step {
+    fn to_css(&self, root: bool) -> String {
+        let mut css = self.name.clone().unwrap_or_else(|| "*".to_string());
+        // `html` is always the root of a parsed HTML document
+        if root && self.name.as_deref() != Some("html") {
+            css.push_str(":root");
+        }
+        // Positions count siblings of the same name, or all elements for `*`
+        let of_type = if self.name.is_some() {
+            "of-type"
+        } else {
+            "child"
+        };
+        match self.position {
+            Some(Position::Nth(1)) => css.push_str(&format!(":first-{}", of_type)),
+            Some(Position::Nth(n)) => css.push_str(&format!(":nth-{}({})", of_type, n)),
+            Some(Position::Last) => css.push_str(&format!(":last-{}", of_type)),
+            None => {}
+        }
+        for filter in &self.filters {
+            css.push_str(filter);
+        }
+        css
+    }
+}
+
+fn quote(value: &str) -> String {
+    if value.contains('\'') {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        format!("'{}'", value.replace('\\', "\\\\"))
+    }
+}
+
+fn is_css_identifier(value: &str) -> bool {
+    let mut chars = value.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
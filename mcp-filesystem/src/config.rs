@@ -0,0 +1,236 @@
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Default upper bound for files read or written through the server (10 MiB).
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Prefix for environment variables that override config file settings.
+pub const ENV_PREFIX: &str = "MCP_FS_";
+
+/// Settings for the filesystem server.
+///
+/// Values are layered in the following order, later sources winning:
+/// built-in defaults, the TOML config file, `MCP_FS_*` environment
+/// variables and finally command line flags.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilesystemConfig {
+    /// Working directory of the server. Defaults to the process cwd.
+    pub directory: Option<PathBuf>,
+    /// Directories the server may touch. Defaults to `directory` when empty.
+    pub allowed_roots: Vec<PathBuf>,
+    /// Reject every operation that modifies the filesystem.
+    pub read_only: bool,
+    /// Tracing level: trace, debug, info, warn or error.
+    pub log_level: String,
+    /// Maximum size in bytes of a file that can be read or written.
+    pub max_file_size: u64,
+    /// Directory holding the templates used by the `scaffold` tool.
+    pub templates_dir: Option<PathBuf>,
+}
+
+impl Default for FilesystemConfig {
+    fn default() -> Self {
+        Self {
+            directory: None,
+            allowed_roots: vec![],
+            read_only: false,
+            log_level: "info".to_string(),
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            templates_dir: None,
+        }
+    }
+}
+
+impl FilesystemConfig {
+    /// Load settings from a TOML file. Missing keys keep their defaults.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {:?}", path))?;
+        Self::from_toml(&content).with_context(|| format!("Invalid config file {:?}", path))
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self> {
+        Ok(toml::from_str(content)?)
+    }
+
+    /// Apply `MCP_FS_*` overrides from the process environment.
+    pub fn apply_env(&mut self) -> Result<()> {
+        self.apply_vars(|key| std::env::var(format!("{}{}", ENV_PREFIX, key)).ok())
+    }
+
+    /// Apply overrides from `lookup`, which is given variable names without
+    /// the `MCP_FS_` prefix. `ALLOWED_ROOTS` uses the platform path list
+    /// separator (`:` on Unix, `;` on Windows).
+    pub fn apply_vars(&mut self, lookup: impl Fn(&str) -> Option<String>) -> Result<()> {
+        if let Some(directory) = lookup("DIRECTORY") {
+            self.directory = Some(PathBuf::from(directory));
+        }
+        if let Some(roots) = lookup("ALLOWED_ROOTS") {
+            self.allowed_roots = std::env::split_paths(&roots)
+                .filter(|p| !p.as_os_str().is_empty())
+                .collect();
+        }
+        if let Some(read_only) = lookup("READ_ONLY") {
+            self.read_only = parse_bool(&read_only)
+                .with_context(|| format!("Invalid {}READ_ONLY value", ENV_PREFIX))?;
+        }
+        if let Some(log_level) = lookup("LOG_LEVEL") {
+            self.log_level = log_level;
+        }
+        if let Some(max_file_size) = lookup("MAX_FILE_SIZE") {
+            self.max_file_size = max_file_size
+                .trim()
+                .parse()
+                .with_context(|| format!("Invalid {}MAX_FILE_SIZE value", ENV_PREFIX))?;
+        }
+        if let Some(templates_dir) = lookup("TEMPLATES_DIR") {
+            self.templates_dir = Some(PathBuf::from(templates_dir));
+        }
+        Ok(())
+    }
+
+    /// Check the settings and resolve every directory to an absolute,
+    /// canonical path. Called once at startup, before the server is built.
+    pub fn validate(mut self) -> Result<Self> {
+        self.tracing_level()?;
+        if self.max_file_size == 0 {
+            anyhow::bail!("max_file_size must be greater than zero");
+        }
+
+        let directory = match self.directory.take() {
+            Some(directory) => directory,
+            None => std::env::current_dir()?,
+        };
+        let directory = canonical_dir(&directory, "directory")?;
+
+        let mut roots = Vec::with_capacity(self.allowed_roots.len());
+        for root in &self.allowed_roots {
+            // Relative roots are taken relative to the working directory
+            roots.push(canonical_dir(&directory.join(root), "allowed root")?);
+        }
+        if roots.is_empty() {
+            roots.push(directory.clone());
+        }
+
+        if let Some(templates_dir) = self.templates_dir.take() {
+            self.templates_dir = Some(canonical_dir(
+                &directory.join(templates_dir),
+                "templates directory",
+            )?);
+        }
+
+        self.directory = Some(directory);
+        self.allowed_roots = roots;
+        Ok(self)
+    }
+
+    pub fn tracing_level(&self) -> Result<tracing::Level> {
+        tracing::Level::from_str(self.log_level.trim())
+            .map_err(|_| anyhow::anyhow!("Invalid log level: {}", self.log_level))
+    }
+
+    /// Working directory of the server. Only meaningful after [`validate`](Self::validate).
+    pub fn working_directory(&self) -> &Path {
+        self.directory.as_deref().unwrap_or_else(|| Path::new("."))
+    }
+
+    /// Ensure `path` lies inside one of the allowed roots and return the
+    /// path tools must operate on: its parent resolved, but not its final
+    /// component, so deleting or moving a symlink acts on the link itself.
+    ///
+    /// The path does not need to exist: its existing prefixes are
+    /// canonicalized so that symlinks cannot be used to escape a root.
+    /// Both the link and what it points to must be inside a root.
+    pub fn check_allowed(&self, path: &Path) -> Result<PathBuf> {
+        let resolved = canonicalize_lenient(path)?;
+        let location = match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => canonicalize_lenient(parent)?.join(name),
+            _ => resolved.clone(),
+        };
+        let inside = |path: &Path| self.allowed_roots.iter().any(|root| path.starts_with(root));
+        if inside(&resolved) && inside(&location) {
+            Ok(location)
+        } else {
+            Err(anyhow::anyhow!(
+                "Access denied: {:?} is outside the allowed directories",
+                path
+            ))
+        }
+    }
+
+    pub fn check_writable(&self) -> Result<()> {
+        if self.read_only {
+            anyhow::bail!("Server is running in read-only mode");
+        }
+        Ok(())
+    }
+
+    pub fn check_size(&self, size: u64) -> Result<()> {
+        if size > self.max_file_size {
+            anyhow::bail!(
+                "File size {} bytes exceeds the limit of {} bytes",
+                size,
+                self.max_file_size
+            );
+        }
+        Ok(())
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" | "" => Ok(false),
+        other => Err(anyhow::anyhow!("Expected a boolean, got {:?}", other)),
+    }
+}
+
+fn canonical_dir(path: &Path, what: &str) -> Result<PathBuf> {
+    let canonical = path
+        .canonicalize()
+        .with_context(|| format!("Invalid {} {:?}", what, path))?;
+    if !canonical.is_dir() {
+        anyhow::bail!("Invalid {} {:?}: not a directory", what, path);
+    }
+    Ok(canonical)
+}
+
+/// Resolve `path` one component at a time, canonicalizing each prefix that
+/// exists so that `..` is applied after symlinks are followed, as the OS
+/// does. Components past the deepest existing one are appended as they are.
+pub(crate) fn canonicalize_lenient(path: &Path) -> Result<PathBuf> {
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()?.join(path)
+    };
+
+    let mut resolved = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => resolved.push(component.as_os_str()),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::Normal(name) => {
+                resolved.push(name);
+                match resolved.canonicalize() {
+                    Ok(canonical) => resolved = canonical,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                        // A dangling symlink would be followed when writing
+                        if resolved.symlink_metadata().is_ok() {
+                            anyhow::bail!("Cannot resolve symlink {:?}", resolved);
+                        }
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+    }
+    Ok(resolved)
+}
@@ -1,4 +1,8 @@
+mod config;
+mod paths;
 mod server;
+mod templates;
+pub use config::{FilesystemConfig, DEFAULT_MAX_FILE_SIZE};
 pub use server::build;
 #[cfg(test)]
-mod tests;
\ No newline at end of file
+mod tests;
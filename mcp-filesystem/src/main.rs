@@ -1,20 +1,92 @@
+use std::path::PathBuf;
+
 use anyhow::Result;
 use async_mcp::transport::ServerStdioTransport;
-use mcp_filesystem::build;
+use clap::Parser;
+use mcp_filesystem::{build, FilesystemConfig};
+
+#[derive(Parser)]
+#[command(name = "mcp-filesystem")]
+#[command(about = "MCP server for filesystem operations")]
+#[command(version)]
+struct Cli {
+    /// Path to a TOML config file
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    /// Working directory of the server (defaults to the current directory)
+    #[arg(short, long)]
+    directory: Option<PathBuf>,
+
+    /// Directory the server is allowed to access; can be repeated
+    #[arg(long = "allowed-root", value_name = "DIR")]
+    allowed_roots: Vec<PathBuf>,
+
+    /// Reject all operations that modify the filesystem
+    #[arg(long)]
+    read_only: bool,
+
+    /// Log level: trace, debug, info, warn or error
+    #[arg(long)]
+    log_level: Option<String>,
+
+    /// Maximum size in bytes of files that can be read or written
+    #[arg(long)]
+    max_file_size: Option<u64>,
+
+    /// Directory containing templates for the scaffold tool
+    #[arg(long)]
+    templates_dir: Option<PathBuf>,
+}
+
+impl Cli {
+    fn into_config(self) -> Result<FilesystemConfig> {
+        let mut config = match &self.config {
+            Some(path) => FilesystemConfig::from_file(path)?,
+            None => FilesystemConfig::default(),
+        };
+        config.apply_env()?;
+
+        if let Some(directory) = self.directory {
+            config.directory = Some(directory);
+        }
+        if !self.allowed_roots.is_empty() {
+            config.allowed_roots = self.allowed_roots;
+        }
+        if self.read_only {
+            config.read_only = true;
+        }
+        if let Some(log_level) = self.log_level {
+            config.log_level = log_level;
+        }
+        if let Some(max_file_size) = self.max_file_size {
+            config.max_file_size = max_file_size;
+        }
+        if let Some(templates_dir) = self.templates_dir {
+            config.templates_dir = Some(templates_dir);
+        }
+
+        config.validate()
+    }
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let config = Cli::parse().into_config()?;
+
     tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::INFO)
+        .with_max_level(config.tracing_level()?)
         // needs to be stderr due to stdio transport
         .with_writer(std::io::stderr)
         .init();
 
-    let server = build(ServerStdioTransport)?;
+    tracing::info!("Working directory: {:?}", config.working_directory());
+
+    let server = build(ServerStdioTransport, config)?;
     let server_handle = tokio::spawn(async move { server.listen().await });
 
     server_handle
         .await?
         .map_err(|e| anyhow::anyhow!("Server error: {:#?}", e))?;
     Ok(())
-}
\ No newline at end of file
+}
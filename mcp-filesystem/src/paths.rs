@@ -0,0 +1,118 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+/// Resolve a user supplied path against the server working directory.
+///
+/// Environment variables (`$VAR` and `${VAR}`) are expanded first, then a
+/// leading `~` or `~user`. Unset variables are left as they are and `$$`
+/// stands for a literal `$`. Whatever is still relative is joined onto
+/// `workspace`, so the result never depends on the process cwd.
+pub fn resolve_path(path: &str, workspace: &Path) -> Result<PathBuf> {
+    let expanded = expand_env_vars(path)?;
+    let path = expand_tilde(&expanded)?;
+    if path.is_absolute() {
+        Ok(path)
+    } else {
+        Ok(workspace.join(path))
+    }
+}
+
+/// Format `path` for tool output: relative to `workspace` unless `absolute`
+/// is requested or the path lies outside of it.
+pub fn display_path(path: &Path, workspace: &Path, absolute: bool) -> String {
+    if !absolute {
+        if let Ok(relative) = path.strip_prefix(workspace) {
+            if relative.as_os_str().is_empty() {
+                return ".".to_string();
+            }
+            return relative.to_string_lossy().to_string();
+        }
+    }
+    path.to_string_lossy().to_string()
+}
+
+fn expand_env_vars(path: &str) -> Result<String> {
+    let mut expanded = String::with_capacity(path.len());
+    let mut chars = path.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        if c != '$' {
+            expanded.push(c);
+            continue;
+        }
+
+        if path[i + 1..].starts_with('$') {
+            // `$$` stands for a literal `$`
+            chars.next();
+            expanded.push('$');
+            continue;
+        }
+
+        // The name, and where the whole reference ends
+        let (name, end) = if path[i + 1..].starts_with('{') {
+            let len = path[i + 2..]
+                .find('}')
+                .ok_or(anyhow::anyhow!("Unterminated variable in path: {}", path))?;
+            (&path[i + 2..i + 2 + len], i + 3 + len)
+        } else {
+            let len = path[i + 1..]
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(path.len() - i - 1);
+            (&path[i + 1..i + 1 + len], i + 1 + len)
+        };
+        while chars.next_if(|(j, _)| *j < end).is_some() {}
+
+        match std::env::var(name) {
+            Ok(value) if !name.is_empty() => expanded.push_str(&value),
+            // A lone `$` and unset variables are more likely part of a
+            // file name, so they are kept as is
+            _ => expanded.push_str(&path[i..end]),
+        }
+    }
+
+    Ok(expanded)
+}
+
+fn expand_tilde(path: &str) -> Result<PathBuf> {
+    let Some(rest) = path.strip_prefix('~') else {
+        return Ok(PathBuf::from(path));
+    };
+    let (user, rest) = match rest.find(['/', '\\']) {
+        Some(index) => (
+            &rest[..index],
+            rest[index + 1..].trim_start_matches(['/', '\\']),
+        ),
+        None => (rest, ""),
+    };
+
+    let home = if user.is_empty() {
+        home::home_dir().ok_or(anyhow::anyhow!("Could not determine home directory"))?
+    } else {
+        user_home_dir(user)?
+    };
+    Ok(home.join(rest))
+}
+
+#[cfg(unix)]
+fn user_home_dir(user: &str) -> Result<PathBuf> {
+    let passwd = std::fs::read_to_string("/etc/passwd").unwrap_or_default();
+    passwd
+        .lines()
+        .map(|line| line.split(':').collect::<Vec<_>>())
+        .find(|fields| fields.len() > 5 && fields[0] == user)
+        .map(|fields| PathBuf::from(fields[5]))
+        .ok_or(anyhow::anyhow!("Unknown user: {}", user))
+}
+
+#[cfg(not(unix))]
+fn user_home_dir(user: &str) -> Result<PathBuf> {
+    // Other users' profiles live next to ours
+    let home = home::home_dir().ok_or(anyhow::anyhow!("Could not determine home directory"))?;
+    let dir = home
+        .parent()
+        .map(|parent| parent.join(user))
+        .filter(|dir| dir.is_dir())
+        .ok_or(anyhow::anyhow!("Unknown user: {}", user))?;
+    Ok(dir)
+}
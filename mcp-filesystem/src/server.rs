@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use anyhow::Result;
 use async_mcp::server::{Server, ServerBuilder};
@@ -11,7 +12,11 @@ use async_mcp::types::{
 use serde_json::json;
 use tracing::info;
 
-pub fn build<T: Transport>(transport: T) -> Result<Server<T>> {
+use crate::config::FilesystemConfig;
+use crate::paths::{display_path, resolve_path};
+use crate::templates::Template;
+
+pub fn build<T: Transport>(transport: T, config: FilesystemConfig) -> Result<Server<T>> {
     let mut server = Server::builder(transport)
         .capabilities(ServerCapabilities {
             tools: Some(json!({})),
@@ -27,14 +32,21 @@ pub fn build<T: Transport>(transport: T) -> Result<Server<T>> {
             })
         });
 
-    register_tools(&mut server)?;
+    info!(
+        "Allowed roots: {:?}, read-only: {}",
+        config.allowed_roots, config.read_only
+    );
+    register_tools(&mut server, Arc::new(config))?;
     
     let server = server.build();
     info!("MCP Filesystem server initialized");
     Ok(server)
 }
 
-fn register_tools<T: Transport>(server: &mut ServerBuilder<T>) -> Result<()> {
+fn register_tools<T: Transport>(
+    server: &mut ServerBuilder<T>,
+    config: Arc<FilesystemConfig>,
+) -> Result<()> {
     // Read File Tool
     let read_file_tool = Tool {
         name: "read_file".to_string(),
@@ -61,12 +73,15 @@ fn register_tools<T: Transport>(server: &mut ServerBuilder<T>) -> Result<()> {
         })),
     };
 
-    server.register_tool(read_file_tool, |req: CallToolRequest| {
+    let tool_config = config.clone();
+    server.register_tool(read_file_tool, move |req: CallToolRequest| {
+        let config = tool_config.clone();
         Box::pin(async move {
             let args = req.arguments.unwrap_or_default();
             let result: Result<CallToolResponse, anyhow::Error> = async {
-                let path = get_path(&args)?;
+                let path = get_path(&args, &config)?;
                 info!("Reading file: {:?}", path);
+                config.check_size(std::fs::metadata(&path)?.len())?;
                 let content = std::fs::read_to_string(path)?;
                 
                 Ok(CallToolResponse {
@@ -121,14 +136,19 @@ fn register_tools<T: Transport>(server: &mut ServerBuilder<T>) -> Result<()> {
         })),
     };
 
-    server.register_tool(write_file_tool, |req: CallToolRequest| {
+    let tool_config = config.clone();
+    server.register_tool(write_file_tool, move |req: CallToolRequest| {
+        let config = tool_config.clone();
         Box::pin(async move {
             let args = req.arguments.unwrap_or_default();
             let result: Result<CallToolResponse, anyhow::Error> = async {
-                let path = get_path(&args)?;
-                let content = args["content"]
-                    .as_str()
+                config.check_writable()?;
+                let path = get_path(&args, &config)?;
+                let content = args
+                    .get("content")
+                    .and_then(|v| v.as_str())
                     .ok_or(anyhow::anyhow!("Missing content parameter"))?;
+                config.check_size(content.len() as u64)?;
                 info!("Writing file: {:?}", path);
                 
                 // Create parent directories if they don't exist
@@ -189,11 +209,13 @@ fn register_tools<T: Transport>(server: &mut ServerBuilder<T>) -> Result<()> {
         })),
     };
 
-    server.register_tool(list_directory_tool, |req: CallToolRequest| {
+    let tool_config = config.clone();
+    server.register_tool(list_directory_tool, move |req: CallToolRequest| {
+        let config = tool_config.clone();
         Box::pin(async move {
             let args = req.arguments.unwrap_or_default();
             let result: Result<CallToolResponse, anyhow::Error> = async {
-                let path = get_path(&args)?;
+                let path = get_path(&args, &config)?;
                 info!("Listing directory: {:?}", path);
                 let entries = std::fs::read_dir(path)?;
                 let mut text = String::new();
@@ -259,11 +281,14 @@ fn register_tools<T: Transport>(server: &mut ServerBuilder<T>) -> Result<()> {
         })),
     };
 
-    server.register_tool(create_directory_tool, |req: CallToolRequest| {
+    let tool_config = config.clone();
+    server.register_tool(create_directory_tool, move |req: CallToolRequest| {
+        let config = tool_config.clone();
         Box::pin(async move {
             let args = req.arguments.unwrap_or_default();
             let result: Result<CallToolResponse, anyhow::Error> = async {
-                let path = get_path(&args)?;
+                config.check_writable()?;
+                let path = get_path(&args, &config)?;
                 info!("Creating directory: {:?}", path);
                 std::fs::create_dir_all(path)?;
                 
@@ -317,17 +342,16 @@ fn register_tools<T: Transport>(server: &mut ServerBuilder<T>) -> Result<()> {
         })),
     };
 
-    server.register_tool(delete_file_tool, |req: CallToolRequest| {
+    let tool_config = config.clone();
+    server.register_tool(delete_file_tool, move |req: CallToolRequest| {
+        let config = tool_config.clone();
         Box::pin(async move {
             let args = req.arguments.unwrap_or_default();
             let result: Result<CallToolResponse, anyhow::Error> = async {
-                let path = get_path(&args)?;
+                config.check_writable()?;
+                let path = get_path(&args, &config)?;
                 info!("Deleting file: {:?}", path);
-                if path.is_dir() {
-                    std::fs::remove_dir_all(path)?;
-                } else {
-                    std::fs::remove_file(path)?;
-                }
+                delete_path(&path)?;
                 
                 Ok(CallToolResponse {
                     content: vec![ToolResponseContent::Text { 
@@ -384,20 +408,17 @@ fn register_tools<T: Transport>(server: &mut ServerBuilder<T>) -> Result<()> {
         })),
     };
 
-    server.register_tool(move_file_tool, |req: CallToolRequest| {
+    let tool_config = config.clone();
+    server.register_tool(move_file_tool, move |req: CallToolRequest| {
+        let config = tool_config.clone();
         Box::pin(async move {
             let args = req.arguments.unwrap_or_default();
             let result: Result<CallToolResponse, anyhow::Error> = async {
-                let from_path = get_path_from_key(&args, "from")?;
-                let to_path = get_path_from_key(&args, "to")?;
+                config.check_writable()?;
+                let from_path = get_path_from_key(&args, "from", &config)?;
+                let to_path = get_path_from_key(&args, "to", &config)?;
                 info!("Moving file from {:?} to {:?}", from_path, to_path);
-                
-                // Create parent directories if they don't exist
-                if let Some(parent) = to_path.parent() {
-                    std::fs::create_dir_all(parent)?;
-                }
-                
-                std::fs::rename(from_path, to_path)?;
+                move_path(&from_path, &to_path)?;
                 
                 Ok(CallToolResponse {
                     content: vec![ToolResponseContent::Text { 
@@ -429,8 +450,9 @@ fn register_tools<T: Transport>(server: &mut ServerBuilder<T>) -> Result<()> {
         name: "search_files".to_string(),
         description: Some("Recursively search for files and directories matching a pattern. \
             Searches through all subdirectories from the starting path. The search \
-            is case-insensitive and matches partial names. Returns full paths to all \
-            matching items. Great for finding files when you don't know their exact location.".to_string()),
+            is case-insensitive and matches partial names. Returns paths relative to the \
+            working directory (or absolute paths on request) for all matching items. \
+            Great for finding files when you don't know their exact location.".to_string()),
         input_schema: json!({
             "type": "object",
             "properties": {
@@ -441,6 +463,11 @@ fn register_tools<T: Transport>(server: &mut ServerBuilder<T>) -> Result<()> {
                 "pattern": {
                     "type": "string",
                     "description": "Search pattern to match against file and directory names"
+                },
+                "absolute": {
+                    "type": "boolean",
+                    "description": "Return absolute paths instead of paths relative to the working directory",
+                    "default": false
                 }
             },
             "required": ["path", "pattern"],
@@ -454,17 +481,25 @@ fn register_tools<T: Transport>(server: &mut ServerBuilder<T>) -> Result<()> {
         })),
     };
 
-    server.register_tool(search_files_tool, |req: CallToolRequest| {
+    let tool_config = config.clone();
+    server.register_tool(search_files_tool, move |req: CallToolRequest| {
+        let config = tool_config.clone();
         Box::pin(async move {
             let args = req.arguments.unwrap_or_default();
             let result: Result<CallToolResponse, anyhow::Error> = async {
-                let path = get_path(&args)?;
-                let pattern = args["pattern"]
-                    .as_str()
+                let path = get_path(&args, &config)?;
+                let pattern = args
+                    .get("pattern")
+                    .and_then(|v| v.as_str())
                     .ok_or(anyhow::anyhow!("Missing pattern parameter"))?;
                 info!("Searching files in {:?} with pattern: {}", path, pattern);
                 let mut matches = Vec::new();
                 search_directory(&path, pattern, &mut matches)?;
+                let absolute = get_absolute_flag(&args);
+                let matches: Vec<String> = matches
+                    .iter()
+                    .map(|m| display_path(m, config.working_directory(), absolute))
+                    .collect();
                 
                 Ok(CallToolResponse {
                     content: vec![ToolResponseContent::Text {
@@ -504,6 +539,11 @@ fn register_tools<T: Transport>(server: &mut ServerBuilder<T>) -> Result<()> {
                 "path": {
                     "type": "string",
                     "description": "Path to the file or directory to get info about"
+                },
+                "absolute": {
+                    "type": "boolean",
+                    "description": "Report an absolute path instead of a path relative to the working directory",
+                    "default": false
                 }
             },
             "required": ["path"],
@@ -517,11 +557,13 @@ fn register_tools<T: Transport>(server: &mut ServerBuilder<T>) -> Result<()> {
         })),
     };
 
-    server.register_tool(get_file_info_tool, |req: CallToolRequest| {
+    let tool_config = config.clone();
+    server.register_tool(get_file_info_tool, move |req: CallToolRequest| {
+        let config = tool_config.clone();
         Box::pin(async move {
             let args = req.arguments.unwrap_or_default();
             let result: Result<CallToolResponse, anyhow::Error> = async {
-                let path = get_path(&args)?;
+                let path = get_path(&args, &config)?;
                 info!("Getting file info for: {:?}", path);
                 let metadata = std::fs::metadata(&path)?;
                 let file_type = if metadata.is_file() {
@@ -533,7 +575,7 @@ fn register_tools<T: Transport>(server: &mut ServerBuilder<T>) -> Result<()> {
                 };
                 
                 let info = json!({
-                    "path": path.to_string_lossy(),
+                    "path": display_path(&path, config.working_directory(), get_absolute_flag(&args)),
                     "type": file_type,
                     "size": metadata.len(),
                     "modified": metadata.modified().ok().and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok().map(|d| d.as_secs())),
@@ -566,10 +608,241 @@ fn register_tools<T: Transport>(server: &mut ServerBuilder<T>) -> Result<()> {
         })
     });
 
+    // List Templates Tool
+    let list_templates_tool = Tool {
+        name: "list_templates".to_string(),
+        description: Some("List the templates available to the scaffold tool, with their \
+            descriptions and the variables each template expects. Templates that cannot \
+            be loaded are listed with an error.".to_string()),
+        input_schema: json!({
+            "type": "object",
+            "properties": {},
+            "additionalProperties": false
+        }),
+        output_schema: Some(json!({
+            "type": "array",
+            "items": {
+                "type": "object",
+                "properties": {
+                    "name": {"type": "string"},
+                    "description": {"type": "string"},
+                    "variables": {"type": "array", "items": {"type": "object"}},
+                    "error": {
+                        "type": "string",
+                        "description": "Why the template could not be loaded"
+                    }
+                }
+            }
+        })),
+    };
+
+    let tool_config = config.clone();
+    server.register_tool(list_templates_tool, move |_req: CallToolRequest| {
+        let config = tool_config.clone();
+        Box::pin(async move {
+            let result: Result<CallToolResponse, anyhow::Error> = async {
+                let templates_dir = get_templates_dir(&config)?;
+                info!("Listing templates in {:?}", templates_dir);
+                let listing = Template::list(templates_dir)?;
+                let mut templates = Vec::new();
+                for template in &listing.templates {
+                    let describe = || -> Result<serde_json::Value> {
+                        let variables: Vec<serde_json::Value> = template
+                            .variables()?
+                            .into_iter()
+                            .map(|v| json!({
+                                "name": v.name,
+                                "description": v.description,
+                                "default": v.default,
+                                "required": v.default.is_none(),
+                            }))
+                            .collect();
+                        Ok(json!({
+                            "name": template.name,
+                            "description": template.manifest.description,
+                            "files": template.files()?.len(),
+                            "variables": variables,
+                        }))
+                    };
+                    // One unreadable template should not hide the others
+                    templates.push(describe().unwrap_or_else(|e| json!({
+                        "name": template.name,
+                        "error": format!("{:#}", e),
+                    })));
+                }
+                for (name, error) in listing.invalid {
+                    templates.push(json!({"name": name, "error": error}));
+                }
+
+                Ok(CallToolResponse {
+                    content: vec![ToolResponseContent::Text {
+                        text: serde_json::to_string_pretty(&templates)?,
+                    }],
+                    is_error: None,
+                    meta: None,
+                })
+            }.await;
+
+            match result {
+                Ok(response) => Ok(response),
+                Err(e) => {
+                    info!("Error listing templates: {:#?}", e);
+                    Ok(CallToolResponse {
+                        content: vec![ToolResponseContent::Text {
+                            text: format!("Error listing templates: {}", e),
+                        }],
+                        is_error: Some(true),
+                        meta: None,
+                    })
+                }
+            }
+        })
+    });
+
+    // Scaffold Tool
+    let scaffold_tool = Tool {
+        name: "scaffold".to_string(),
+        description: Some("Create files from a directory template. Every {{variable}} \
+            placeholder in file names and contents is replaced with the given value. \
+            Fails without writing anything if a target file already exists, unless \
+            overwrite is set. Use list_templates to see the available templates.".to_string()),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "template": {
+                    "type": "string",
+                    "description": "Name of the template to instantiate"
+                },
+                "destination": {
+                    "type": "string",
+                    "description": "Directory to create the files in"
+                },
+                "variables": {
+                    "type": "object",
+                    "description": "Values for the template variables",
+                    "additionalProperties": {"type": "string"}
+                },
+                "overwrite": {
+                    "type": "boolean",
+                    "description": "Replace files that already exist",
+                    "default": false
+                },
+                "absolute": {
+                    "type": "boolean",
+                    "description": "Return absolute paths instead of paths relative to the working directory",
+                    "default": false
+                }
+            },
+            "required": ["template", "destination"],
+            "additionalProperties": false
+        }),
+        output_schema: Some(json!({
+            "type": "object",
+            "properties": {
+                "template": {"type": "string"},
+                "created": {"type": "array", "items": {"type": "string"}}
+            }
+        })),
+    };
+
+    let tool_config = config.clone();
+    server.register_tool(scaffold_tool, move |req: CallToolRequest| {
+        let config = tool_config.clone();
+        Box::pin(async move {
+            let args = req.arguments.unwrap_or_default();
+            let result: Result<CallToolResponse, anyhow::Error> = async {
+                config.check_writable()?;
+                let templates_dir = get_templates_dir(&config)?;
+                let name = args
+                    .get("template")
+                    .and_then(|v| v.as_str())
+                    .ok_or(anyhow::anyhow!("Missing template parameter"))?;
+                let destination = get_path_from_key(&args, "destination", &config)?;
+                let overwrite = args
+                    .get("overwrite")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+
+                let mut values = HashMap::new();
+                if let Some(variables) = args.get("variables").and_then(|v| v.as_object()) {
+                    for (key, value) in variables {
+                        let value = match value {
+                            serde_json::Value::String(s) => s.clone(),
+                            other => other.to_string(),
+                        };
+                        values.insert(key.clone(), value);
+                    }
+                }
+
+                info!("Scaffolding template {} into {:?}", name, destination);
+                let template = Template::load(templates_dir, name)?;
+                let created = template.scaffold(&destination, &values, overwrite)?;
+
+                let absolute = get_absolute_flag(&args);
+                let created: Vec<String> = created
+                    .iter()
+                    .map(|p| display_path(p, config.working_directory(), absolute))
+                    .collect();
+                let response = json!({
+                    "template": template.name,
+                    "destination": display_path(&destination, config.working_directory(), absolute),
+                    "created": created,
+                });
+
+                Ok(CallToolResponse {
+                    content: vec![ToolResponseContent::Text {
+                        text: serde_json::to_string_pretty(&response)?,
+                    }],
+                    is_error: None,
+                    meta: None,
+                })
+            }.await;
+
+            match result {
+                Ok(response) => Ok(response),
+                Err(e) => {
+                    info!("Error scaffolding template: {:#?}", e);
+                    Ok(CallToolResponse {
+                        content: vec![ToolResponseContent::Text {
+                            text: format!("Error scaffolding template: {}", e),
+                        }],
+                        is_error: Some(true),
+                        meta: None,
+                    })
+                }
+            }
+        })
+    });
+
     Ok(())
 }
 
-fn search_directory(dir: &Path, pattern: &str, matches: &mut Vec<String>) -> Result<()> {
+/// Delete a file or directory tree. A symlink is removed itself, never the
+/// directory it points to.
+pub(crate) fn delete_path(path: &Path) -> Result<()> {
+    if std::fs::symlink_metadata(path)?.is_dir() {
+        std::fs::remove_dir_all(path)?;
+    } else {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Move `from` to `to`, creating the parent directories of `to`. A symlink
+/// is moved itself rather than its target.
+pub(crate) fn move_path(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::rename(from, to)?;
+    Ok(())
+}
+
+pub(crate) fn search_directory(
+    dir: &Path,
+    pattern: &str,
+    matches: &mut Vec<PathBuf>,
+) -> Result<()> {
     for entry in std::fs::read_dir(dir)? {
         let entry = entry?;
         let path = entry.path();
@@ -581,38 +854,44 @@ fn search_directory(dir: &Path, pattern: &str, matches: &mut Vec<String>) -> Res
 
         // Check if the current file/directory matches the pattern
         if name.contains(&pattern.to_lowercase()) {
-            matches.push(path.to_string_lossy().to_string());
+            matches.push(path.clone());
         }
 
-        // Recursively search subdirectories
-        if path.is_dir() {
+        // Recursively search subdirectories. Symlinked ones are skipped, as
+        // they may lead outside the allowed roots or back to a parent
+        if entry.file_type()?.is_dir() {
             search_directory(&path, pattern, matches)?;
         }
     }
     Ok(())
 }
 
-fn get_path(args: &HashMap<String, serde_json::Value>) -> Result<PathBuf> {
-    let path = args["path"]
-        .as_str()
-        .ok_or(anyhow::anyhow!("Missing path parameter"))?;
-    resolve_path(path)
+fn get_path(args: &HashMap<String, serde_json::Value>, config: &FilesystemConfig) -> Result<PathBuf> {
+    get_path_from_key(args, "path", config)
 }
 
-fn get_path_from_key(args: &HashMap<String, serde_json::Value>, key: &str) -> Result<PathBuf> {
-    let path = args[key]
-        .as_str()
+fn get_path_from_key(
+    args: &HashMap<String, serde_json::Value>,
+    key: &str,
+    config: &FilesystemConfig,
+) -> Result<PathBuf> {
+    let path = args
+        .get(key)
+        .and_then(|v| v.as_str())
         .ok_or(anyhow::anyhow!("Missing {} parameter", key))?;
-    resolve_path(path)
+    let path = resolve_path(path, config.working_directory())?;
+    config.check_allowed(&path)
 }
 
-fn resolve_path(path: &str) -> Result<PathBuf> {
-    if path.starts_with('~') {
-        let home = home::home_dir().ok_or(anyhow::anyhow!("Could not determine home directory"))?;
-        // Strip the ~ and join with home path
-        let path = home.join(path.strip_prefix("~/").unwrap_or_default());
-        Ok(path)
-    } else {
-        Ok(PathBuf::from(path))
-    }
-}
\ No newline at end of file
+fn get_absolute_flag(args: &HashMap<String, serde_json::Value>) -> bool {
+    args.get("absolute")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+fn get_templates_dir(config: &FilesystemConfig) -> Result<&Path> {
+    config
+        .templates_dir
+        .as_deref()
+        .ok_or(anyhow::anyhow!("No templates directory configured (use --templates-dir)"))
+}
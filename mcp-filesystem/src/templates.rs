@@ -0,0 +1,266 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::config::canonicalize_lenient;
+
+/// Optional file in a template directory describing the template.
+pub const MANIFEST_FILE: &str = "template.toml";
+
+/// Contents of `template.toml`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TemplateManifest {
+    pub description: Option<String>,
+    pub variables: Vec<TemplateVariable>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateVariable {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub default: Option<String>,
+}
+
+/// A directory template: every file below `root` is copied to the
+/// destination with `{{variable}}` placeholders substituted in both file
+/// names and contents.
+#[derive(Debug, Clone)]
+pub struct Template {
+    pub name: String,
+    pub root: PathBuf,
+    pub manifest: TemplateManifest,
+}
+
+/// The templates of a templates directory.
+#[derive(Debug, Default)]
+pub struct TemplateListing {
+    pub templates: Vec<Template>,
+    /// Directories that could not be loaded as templates, with the reason.
+    pub invalid: Vec<(String, String)>,
+}
+
+impl Template {
+    pub fn load(templates_dir: &Path, name: &str) -> Result<Self> {
+        if name.is_empty() || name.contains(['/', '\\']) || name == "." || name == ".." {
+            anyhow::bail!("Invalid template name: {:?}", name);
+        }
+        let root = templates_dir.join(name);
+        if !root.is_dir() {
+            anyhow::bail!("Template not found: {}", name);
+        }
+
+        let manifest_path = root.join(MANIFEST_FILE);
+        let manifest = if manifest_path.is_file() {
+            let content = std::fs::read_to_string(&manifest_path)?;
+            toml::from_str(&content)
+                .with_context(|| format!("Invalid manifest for template {}", name))?
+        } else {
+            TemplateManifest::default()
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            root,
+            manifest,
+        })
+    }
+
+    /// All templates in `templates_dir`, sorted by name. A template that
+    /// fails to load is skipped and reported instead of failing the rest.
+    pub fn list(templates_dir: &Path) -> Result<TemplateListing> {
+        let mut listing = TemplateListing::default();
+        for entry in std::fs::read_dir(templates_dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                match Self::load(templates_dir, &name) {
+                    Ok(template) => listing.templates.push(template),
+                    Err(e) => listing.invalid.push((name, format!("{:#}", e))),
+                }
+            }
+        }
+        listing.templates.sort_by(|a, b| a.name.cmp(&b.name));
+        listing.invalid.sort();
+        Ok(listing)
+    }
+
+    /// Variables declared in the manifest followed by any other
+    /// placeholders found in file names or contents.
+    pub fn variables(&self) -> Result<Vec<TemplateVariable>> {
+        let mut variables = self.manifest.variables.clone();
+        let mut found = BTreeSet::new();
+        for file in self.files()? {
+            collect_placeholders(&file.to_string_lossy(), &mut found);
+            if let Ok(content) = std::fs::read_to_string(self.root.join(&file)) {
+                collect_placeholders(&content, &mut found);
+            }
+        }
+        for name in found {
+            if !variables.iter().any(|v| v.name == name) {
+                variables.push(TemplateVariable {
+                    name,
+                    description: None,
+                    default: None,
+                });
+            }
+        }
+        Ok(variables)
+    }
+
+    /// Files of the template relative to its root, excluding the manifest.
+    pub fn files(&self) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        collect_files(&self.root, &self.root, &mut files)?;
+        files.retain(|f| f != Path::new(MANIFEST_FILE));
+        files.sort();
+        Ok(files)
+    }
+
+    /// Instantiate the template below `destination`.
+    ///
+    /// Nothing is written when a target file already exists, unless
+    /// `overwrite` is set. Returns the paths of the created files.
+    pub fn scaffold(
+        &self,
+        destination: &Path,
+        values: &HashMap<String, String>,
+        overwrite: bool,
+    ) -> Result<Vec<PathBuf>> {
+        let values = self.resolve_values(values)?;
+        let destination = canonicalize_lenient(destination)?;
+
+        // Render everything before touching the filesystem
+        let mut planned = Vec::new();
+        for file in self.files()? {
+            let relative = PathBuf::from(render(&file.to_string_lossy(), &values)?);
+            if relative.is_absolute()
+                || relative
+                    .components()
+                    .any(|c| matches!(c, std::path::Component::ParentDir))
+            {
+                anyhow::bail!("Rendered path {:?} escapes the destination", relative);
+            }
+            // Symlinks already below the destination must not lead out of it
+            let path = canonicalize_lenient(&destination.join(&relative))?;
+            if !path.starts_with(&destination) {
+                anyhow::bail!("Rendered path {:?} escapes the destination", relative);
+            }
+            let bytes = std::fs::read(self.root.join(&file))?;
+            let content = match String::from_utf8(bytes) {
+                Ok(text) => render(&text, &values)?.into_bytes(),
+                // Binary files are copied verbatim
+                Err(e) => e.into_bytes(),
+            };
+            planned.push((path, content));
+        }
+        planned.sort_by(|a, b| a.0.cmp(&b.0));
+
+        if !overwrite {
+            let conflicts: Vec<String> = planned
+                .iter()
+                .filter(|(path, _)| path.exists())
+                .map(|(path, _)| path.to_string_lossy().to_string())
+                .collect();
+            if !conflicts.is_empty() {
+                anyhow::bail!(
+                    "Refusing to overwrite existing files: {}",
+                    conflicts.join(", ")
+                );
+            }
+        }
+
+        let mut created = Vec::with_capacity(planned.len());
+        for (path, content) in planned {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&path, content)?;
+            created.push(path);
+        }
+        Ok(created)
+    }
+
+    /// Merge supplied values over manifest defaults and make sure every
+    /// placeholder has a value.
+    fn resolve_values(&self, values: &HashMap<String, String>) -> Result<HashMap<String, String>> {
+        let mut resolved = HashMap::new();
+        let mut missing = Vec::new();
+        for variable in self.variables()? {
+            match values.get(&variable.name).or(variable.default.as_ref()) {
+                Some(value) => {
+                    resolved.insert(variable.name, value.clone());
+                }
+                None => missing.push(variable.name),
+            }
+        }
+        if !missing.is_empty() {
+            anyhow::bail!("Missing template variables: {}", missing.join(", "));
+        }
+        Ok(resolved)
+    }
+}
+
+/// Replace `{{name}}` placeholders (whitespace inside the braces is
+/// allowed) with their values. `\{{` stands for a literal `{{`, for files
+/// that use the same syntax themselves.
+pub fn render(input: &str, values: &HashMap<String, String>) -> Result<String> {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        if let Some(before) = rest[..start].strip_suffix('\\') {
+            output.push_str(before);
+            output.push_str("{{");
+            rest = after;
+            continue;
+        }
+        output.push_str(&rest[..start]);
+        let end = after
+            .find("}}")
+            .ok_or(anyhow::anyhow!("Unterminated placeholder in template"))?;
+        let name = after[..end].trim();
+        let value = values
+            .get(name)
+            .ok_or(anyhow::anyhow!("Unknown template variable: {}", name))?;
+        output.push_str(value);
+        rest = &after[end + 2..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+fn collect_placeholders(input: &str, found: &mut BTreeSet<String>) {
+    let mut rest = input;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        if rest[..start].ends_with('\\') {
+            rest = after;
+            continue;
+        }
+        let Some(end) = after.find("}}") else {
+            break;
+        };
+        let name = after[..end].trim();
+        if !name.is_empty() {
+            found.insert(name.to_string());
+        }
+        rest = &after[end + 2..];
+    }
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            files.push(relative.to_path_buf());
+        }
+    }
+    Ok(())
+}
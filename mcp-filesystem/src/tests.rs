@@ -1,9 +1,15 @@
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
     use std::fs;
     use std::path::PathBuf;
     use tempfile::TempDir;
 
+    use crate::paths::{display_path, resolve_path};
+    use crate::server::{delete_path, move_path, search_directory};
+    use crate::templates::{render, Template};
+    use crate::{FilesystemConfig, DEFAULT_MAX_FILE_SIZE};
+
     #[test]
     fn test_path_operations() {
         let path = PathBuf::from("/tmp/test");
@@ -37,4 +43,377 @@ mod tests {
         assert!(test_dir.exists());
         assert!(test_dir.is_dir());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_config_from_toml() {
+        let config = FilesystemConfig::from_toml(
+            r#"
+            directory = "/srv/project"
+            allowed_roots = ["/srv/project", "/tmp"]
+            read_only = true
+            log_level = "debug"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.directory, Some(PathBuf::from("/srv/project")));
+        assert_eq!(config.allowed_roots.len(), 2);
+        assert!(config.read_only);
+        assert_eq!(config.log_level, "debug");
+        assert_eq!(config.max_file_size, DEFAULT_MAX_FILE_SIZE);
+
+        assert!(FilesystemConfig::from_toml("unknown_key = 1").is_err());
+    }
+
+    #[test]
+    fn test_config_env_overrides() {
+        let vars: HashMap<&str, &str> = [
+            ("READ_ONLY", "true"),
+            ("LOG_LEVEL", "warn"),
+            ("MAX_FILE_SIZE", "1024"),
+        ]
+        .into_iter()
+        .collect();
+
+        let mut config = FilesystemConfig::default();
+        config
+            .apply_vars(|key| vars.get(key).map(|v| v.to_string()))
+            .unwrap();
+        assert!(config.read_only);
+        assert_eq!(config.log_level, "warn");
+        assert_eq!(config.max_file_size, 1024);
+
+        let mut config = FilesystemConfig::default();
+        let result =
+            config.apply_vars(|key| (key == "MAX_FILE_SIZE").then(|| "lots".to_string()));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("docs")).unwrap();
+
+        let config = FilesystemConfig {
+            directory: Some(temp_dir.path().to_path_buf()),
+            allowed_roots: vec![PathBuf::from("docs")],
+            ..Default::default()
+        }
+        .validate()
+        .unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        assert_eq!(config.working_directory(), root);
+        assert_eq!(config.allowed_roots, vec![root.join("docs")]);
+
+        let missing = FilesystemConfig {
+            directory: Some(temp_dir.path().join("missing")),
+            ..Default::default()
+        };
+        assert!(missing.validate().is_err());
+
+        let bad_level = FilesystemConfig {
+            directory: Some(temp_dir.path().to_path_buf()),
+            log_level: "loud".to_string(),
+            ..Default::default()
+        };
+        assert!(bad_level.validate().is_err());
+    }
+
+    #[test]
+    fn test_config_path_checks() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = FilesystemConfig {
+            directory: Some(temp_dir.path().to_path_buf()),
+            read_only: true,
+            max_file_size: 10,
+            ..Default::default()
+        }
+        .validate()
+        .unwrap();
+
+        assert!(config
+            .check_allowed(&temp_dir.path().join("new/file.txt"))
+            .is_ok());
+        assert!(config
+            .check_allowed(&temp_dir.path().join("a/../../escape.txt"))
+            .is_err());
+        assert!(config.check_allowed(&PathBuf::from("/")).is_err());
+
+        assert!(config.check_writable().is_err());
+        assert!(config.check_size(10).is_ok());
+        assert!(config.check_size(11).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_config_symlink_parent_escape() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        let outside = temp_dir.path().join("outside");
+        std::fs::create_dir_all(root.join("docs")).unwrap();
+        std::fs::create_dir_all(outside.join("inner")).unwrap();
+        std::fs::write(outside.join("secret"), "s").unwrap();
+        std::os::unix::fs::symlink(outside.join("inner"), root.join("link")).unwrap();
+        std::os::unix::fs::symlink(outside.join("gone"), root.join("dangling")).unwrap();
+        let config = FilesystemConfig {
+            directory: Some(root.clone()),
+            ..Default::default()
+        }
+        .validate()
+        .unwrap();
+        let root = root.canonicalize().unwrap();
+
+        // The OS resolves `link/..` to the parent of the link target
+        assert!(config.check_allowed(&root.join("link/../secret")).is_err());
+        assert!(config.check_allowed(&root.join("link/file")).is_err());
+        assert!(config.check_allowed(&root.join("dangling")).is_err());
+        assert_eq!(
+            config.check_allowed(&root.join("docs/../new.txt")).unwrap(),
+            root.join("new.txt")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks_are_deleted_and_moved_themselves() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join("target/sub")).unwrap();
+        fs::write(root.join("target/sub/file.txt"), "keep").unwrap();
+        std::os::unix::fs::symlink(root.join("target"), root.join("link")).unwrap();
+        std::os::unix::fs::symlink(root.join("target"), root.join("other")).unwrap();
+        let config = FilesystemConfig {
+            directory: Some(root.clone()),
+            ..Default::default()
+        }
+        .validate()
+        .unwrap();
+
+        let link = config.check_allowed(&root.join("link")).unwrap();
+        assert_eq!(link, root.join("link"));
+        delete_path(&link).unwrap();
+        assert!(fs::symlink_metadata(root.join("link")).is_err());
+        assert!(root.join("target/sub/file.txt").is_file());
+
+        let from = config.check_allowed(&root.join("other")).unwrap();
+        let to = config.check_allowed(&root.join("moved/other")).unwrap();
+        move_path(&from, &to).unwrap();
+        assert_eq!(fs::read_link(&to).unwrap(), root.join("target"));
+        assert!(root.join("target/sub/file.txt").is_file());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_search_skips_symlinked_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        let outside = temp_dir.path().join("outside");
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(root.join("docs/notes.txt"), "").unwrap();
+        fs::write(outside.join("notes-secret.txt"), "").unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("docs/escape")).unwrap();
+        std::os::unix::fs::symlink(&root, root.join("docs/loop")).unwrap();
+
+        let mut matches = Vec::new();
+        search_directory(&root, "notes", &mut matches).unwrap();
+        assert_eq!(matches, vec![root.join("docs/notes.txt")]);
+    }
+
+    #[test]
+    fn test_resolve_path_against_workspace() {
+        let workspace = PathBuf::from("/srv/project");
+
+        assert_eq!(
+            resolve_path("src/main.rs", &workspace).unwrap(),
+            PathBuf::from("/srv/project/src/main.rs")
+        );
+        assert_eq!(
+            resolve_path("/etc/hosts", &workspace).unwrap(),
+            PathBuf::from("/etc/hosts")
+        );
+
+        let home = home::home_dir().unwrap();
+        assert_eq!(
+            resolve_path("~/notes.txt", &workspace).unwrap(),
+            home.join("notes.txt")
+        );
+        assert_eq!(resolve_path("~", &workspace).unwrap(), home);
+        assert!(resolve_path("~no-such-user-xyz/file", &workspace).is_err());
+    }
+
+    #[test]
+    fn test_resolve_path_env_vars() {
+        let workspace = PathBuf::from("/srv/project");
+        std::env::set_var("MCP_FS_TEST_DIR", "/data");
+
+        assert_eq!(
+            resolve_path("$MCP_FS_TEST_DIR/in.csv", &workspace).unwrap(),
+            PathBuf::from("/data/in.csv")
+        );
+        assert_eq!(
+            resolve_path("${MCP_FS_TEST_DIR}_old/in.csv", &workspace).unwrap(),
+            PathBuf::from("/data_old/in.csv")
+        );
+        assert_eq!(
+            resolve_path("cost$", &workspace).unwrap(),
+            PathBuf::from("/srv/project/cost$")
+        );
+        // File names may contain `$`: unset variables stay literal and `$$`
+        // escapes a set one
+        assert_eq!(
+            resolve_path("price$5.txt", &workspace).unwrap(),
+            PathBuf::from("/srv/project/price$5.txt")
+        );
+        assert_eq!(
+            resolve_path("$MCP_FS_TEST_UNSET_VAR/${MCP_FS_TEST_UNSET_VAR}", &workspace).unwrap(),
+            PathBuf::from("/srv/project/$MCP_FS_TEST_UNSET_VAR/${MCP_FS_TEST_UNSET_VAR}")
+        );
+        assert_eq!(
+            resolve_path("build$$MCP_FS_TEST_DIR/a$$b", &workspace).unwrap(),
+            PathBuf::from("/srv/project/build$MCP_FS_TEST_DIR/a$b")
+        );
+        assert!(resolve_path("${MCP_FS_TEST_DIR", &workspace).is_err());
+    }
+
+    #[test]
+    fn test_display_path() {
+        let workspace = PathBuf::from("/srv/project");
+
+        assert_eq!(
+            display_path(&workspace.join("src/lib.rs"), &workspace, false),
+            "src/lib.rs"
+        );
+        assert_eq!(
+            display_path(&workspace.join("src/lib.rs"), &workspace, true),
+            "/srv/project/src/lib.rs"
+        );
+        assert_eq!(display_path(&workspace, &workspace, false), ".");
+        assert_eq!(
+            display_path(&PathBuf::from("/etc/hosts"), &workspace, false),
+            "/etc/hosts"
+        );
+    }
+
+    fn write_template(templates_dir: &std::path::Path) {
+        let root = templates_dir.join("module");
+        fs::create_dir_all(root.join("src/{{name}}")).unwrap();
+        fs::write(
+            root.join("template.toml"),
+            r#"
+            description = "Rust module"
+
+            [[variables]]
+            name = "name"
+            description = "Module name"
+
+            [[variables]]
+            name = "author"
+            default = "anonymous"
+            "#,
+        )
+        .unwrap();
+        fs::write(
+            root.join("src/{{name}}/mod.rs"),
+            "//! {{ name }} by {{author}}\npub fn {{name}}() {}\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("{{name}}.md"),
+            "# {{name}} ({{version}})\n\\{{ not_a_variable }}\n",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_template_variables() {
+        let temp_dir = TempDir::new().unwrap();
+        write_template(temp_dir.path());
+
+        // A broken manifest only affects its own template
+        fs::create_dir_all(temp_dir.path().join("broken")).unwrap();
+        fs::write(
+            temp_dir.path().join("broken/template.toml"),
+            "variables = 1",
+        )
+        .unwrap();
+        let listing = Template::list(temp_dir.path()).unwrap();
+        assert_eq!(listing.invalid.len(), 1);
+        assert_eq!(listing.invalid[0].0, "broken");
+        let templates = listing.templates;
+        assert_eq!(templates.len(), 1);
+        assert_eq!(templates[0].name, "module");
+        assert_eq!(
+            templates[0].manifest.description.as_deref(),
+            Some("Rust module")
+        );
+
+        let names: Vec<String> = templates[0]
+            .variables()
+            .unwrap()
+            .into_iter()
+            .map(|v| v.name)
+            .collect();
+        assert_eq!(names, vec!["name", "author", "version"]);
+
+        assert!(Template::load(temp_dir.path(), "../module").is_err());
+        assert!(Template::load(temp_dir.path(), "missing").is_err());
+    }
+
+    #[test]
+    fn test_scaffold_template() {
+        let temp_dir = TempDir::new().unwrap();
+        let templates_dir = temp_dir.path().join("templates");
+        let destination = temp_dir.path().join("out");
+        write_template(&templates_dir);
+        let template = Template::load(&templates_dir, "module").unwrap();
+
+        let mut values = HashMap::new();
+        values.insert("name".to_string(), "parser".to_string());
+        assert!(template.scaffold(&destination, &values, false).is_err());
+        assert!(!destination.exists());
+
+        values.insert("version".to_string(), "0.1".to_string());
+        let created = template.scaffold(&destination, &values, false).unwrap();
+        assert_eq!(
+            created,
+            vec![
+                destination.join("parser.md"),
+                destination.join("src/parser/mod.rs")
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(destination.join("src/parser/mod.rs")).unwrap(),
+            "//! parser by anonymous\npub fn parser() {}\n"
+        );
+        assert_eq!(
+            fs::read_to_string(destination.join("parser.md")).unwrap(),
+            "# parser (0.1)\n{{ not_a_variable }}\n"
+        );
+
+        // Existing files are only replaced on request
+        let err = template.scaffold(&destination, &values, false).unwrap_err();
+        assert!(err.to_string().contains("parser.md"));
+        assert!(template.scaffold(&destination, &values, true).is_ok());
+    }
+
+    #[test]
+    fn test_render_placeholders() {
+        let mut values = HashMap::new();
+        values.insert("name".to_string(), "demo".to_string());
+
+        assert_eq!(render("{{name}}-{{ name }}", &values).unwrap(), "demo-demo");
+        assert!(render("{{other}}", &values).is_err());
+        assert!(render("{{name", &values).is_err());
+        assert_eq!(
+            render("${{ secrets.TOKEN }} \\{{name}} {{name}}", &values)
+                .unwrap_err()
+                .to_string(),
+            "Unknown template variable: secrets.TOKEN"
+        );
+        assert_eq!(
+            render("$\\{{ secrets.TOKEN }} \\{{name}} {{name}}", &values).unwrap(),
+            "${{ secrets.TOKEN }} {{name}} demo"
+        );
+    }
+}